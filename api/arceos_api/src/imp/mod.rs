@@ -50,5 +50,12 @@ pub use self::stdio::*;
 pub use self::task::*;
 pub use self::time::*;
 
-pub use axhal::misc::terminate as ax_terminate;
+pub fn ax_terminate() -> ! {
+    #[cfg(feature = "fs")]
+    if let Err(e) = axfs::api::sync() {
+        axlog::warn!("failed to sync filesystems: {:?}", e);
+    }
+    axhal::misc::terminate()
+}
+
 pub use axio::PollState as AxPollState;
@@ -192,6 +192,7 @@ pub mod fs {
         pub type AxFilePerm;
         pub type AxDirEntry;
         pub type AxSeekFrom;
+        pub type AxFsStat;
         #[cfg(feature = "myfs")]
         pub type AxDisk;
         #[cfg(feature = "myfs")]
@@ -255,6 +256,9 @@ pub mod fs {
         ///
         /// It will delete the original file if `old` already exists.
         pub fn ax_rename(old: &str, new: &str) -> AxResult;
+        /// Returns the statistics of the filesystem that the path is in, e.g.,
+        /// its capacity and free space.
+        pub fn ax_statfs(path: &str) -> AxResult<AxFsStat>;
 
         /// Returns the current working directory.
         pub fn ax_current_dir() -> AxResult<alloc::string::String>;
@@ -272,6 +276,7 @@ pub mod net {
         @cfg "net";
         pub type AxTcpSocketHandle;
         pub type AxUdpSocketHandle;
+        pub type AxIcmpSocketHandle;
     }
 
     define_api! {
@@ -346,6 +351,24 @@ pub mod net {
         /// Returns whether the UDP socket is readable or writable.
         pub fn ax_udp_poll(socket: &AxUdpSocketHandle) -> AxResult<AxPollState>;
 
+        // ICMP socket
+
+        /// Creates a new ICMP socket for echo messages.
+        pub fn ax_icmp_socket() -> AxIcmpSocketHandle;
+        /// Moves this ICMP socket into or out of nonblocking mode.
+        pub fn ax_icmp_set_nonblocking(socket: &AxIcmpSocketHandle, nonblocking: bool) -> AxResult;
+        /// Sets the timeout of receiving echo replies on the ICMP socket. `None`
+        /// means blocking indefinitely.
+        pub fn ax_icmp_set_read_timeout(socket: &AxIcmpSocketHandle, timeout: Option<core::time::Duration>) -> AxResult;
+        /// Sends an echo request with the given sequence number and data to the
+        /// given address.
+        pub fn ax_icmp_send_echo_request(socket: &AxIcmpSocketHandle, addr: IpAddr, seq_no: u16, data: &[u8]) -> AxResult;
+        /// Receives an echo reply on the ICMP socket. On success, returns the
+        /// length of the data, the origin and the sequence number.
+        pub fn ax_icmp_recv_echo_reply(socket: &AxIcmpSocketHandle, buf: &mut [u8]) -> AxResult<(usize, IpAddr, u16)>;
+        /// Returns whether the ICMP socket is readable or writable.
+        pub fn ax_icmp_poll(socket: &AxIcmpSocketHandle) -> AxResult<AxPollState>;
+
         // Miscellaneous
 
         /// Resolves the host name to a list of IP addresses.
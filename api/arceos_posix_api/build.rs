@@ -53,6 +53,9 @@ typedef struct {{
             "clockid_t",
             "rlimit",
             "aibuf",
+            "flock",
+            "statfs",
+            "inotify_event",
         ];
         let allow_vars = [
             "CLOCK_.*",
@@ -60,8 +63,18 @@ typedef struct {{
             "AF_.*",
             "SOCK_.*",
             "IPPROTO_.*",
+            "SOL_.*",
+            "SO_.*",
             "FD_.*",
             "F_.*",
+            "LOCK_.*",
+            "AT_.*",
+            "UTIME_.*",
+            "IN_.*",
+            "PROT_.*",
+            "MAP_.*",
+            "MS_.*",
+            "MREMAP_.*",
             "_SC_.*",
             "EPOLL_CTL_.*",
             "EPOLL.*",
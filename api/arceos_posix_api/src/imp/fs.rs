@@ -1,9 +1,11 @@
+use alloc::format;
 use alloc::string::{String, ToString};
 use alloc::sync::Arc;
-use core::ffi::{c_char, c_int};
+use core::ffi::{c_char, c_int, c_long, c_ulong, c_void};
+use core::time::Duration;
 
 use axerrno::{LinuxError, LinuxResult};
-use axfs::fops::OpenOptions;
+use axfs::fops::{DirEntry, FileAttr, FsStat, LockError, LockType, OpenOptions, RecordLock};
 use axio::{PollState, SeekFrom};
 use axsync::Mutex;
 
@@ -57,21 +59,7 @@ impl FileLike for File {
     }
 
     fn stat(&self) -> LinuxResult<ctypes::stat> {
-        let metadata = self.inner.lock().get_attr()?;
-        let ty = metadata.file_type() as u8;
-        let perm = metadata.perm().bits() as u32;
-        let st_mode = ((ty as u32) << 12) | perm;
-        Ok(ctypes::stat {
-            st_ino: 1,
-            st_nlink: 1,
-            st_mode,
-            st_uid: 1000,
-            st_gid: 1000,
-            st_size: metadata.size() as _,
-            st_blocks: metadata.blocks() as _,
-            st_blksize: 512,
-            ..Default::default()
-        })
+        Ok(stat_from_attr(&self.inner.lock().get_attr()?))
     }
 
     fn into_any(self: Arc<Self>) -> Arc<dyn core::any::Any + Send + Sync> {
@@ -90,6 +78,29 @@ impl FileLike for File {
     }
 }
 
+/// Convert file attributes to `struct stat`.
+fn stat_from_attr(metadata: &FileAttr) -> ctypes::stat {
+    let ty = metadata.file_type() as u8;
+    let perm = metadata.perm().bits() as u32;
+    let st_mode = ((ty as u32) << 12) | perm;
+    let times = metadata.times();
+    ctypes::stat {
+        st_ino: metadata.ino(),
+        st_nlink: metadata.nlink() as _,
+        st_mode,
+        st_uid: 1000,
+        st_gid: 1000,
+        st_size: metadata.size() as _,
+        st_blocks: metadata.blocks() as _,
+        st_blksize: 512,
+        st_atim: times.accessed.into(),
+        st_mtim: times.modified.into(),
+        // no change time is kept, and the last modification is the closest
+        st_ctim: times.modified.into(),
+        ..Default::default()
+    }
+}
+
 /// Convert open flags to [`OpenOptions`].
 fn flags_to_options(flags: c_int, _mode: ctypes::mode_t) -> OpenOptions {
     let flags = flags as u32;
@@ -222,11 +233,75 @@ pub fn sys_lseek(fd: c_int, offset: ctypes::off_t, whence: c_int) -> ctypes::off
             2 => SeekFrom::End(offset as _),
             _ => return Err(LinuxError::EINVAL),
         };
+        if let Ok(dir) = Directory::from_fd(fd) {
+            // the offset of a directory is the index of the next entry
+            let mut dir = dir.inner.lock();
+            let off = match pos {
+                SeekFrom::Start(off) => off,
+                SeekFrom::Current(0) => dir.entry_idx() as u64,
+                _ => return Err(LinuxError::EINVAL),
+            };
+            dir.set_entry_idx(off as usize);
+            return Ok(off);
+        }
         let off = File::from_fd(fd)?.inner.lock().seek(pos)?;
         Ok(off)
     })
 }
 
+/// Size of the header of `struct linux_dirent64`, before the name.
+const DIRENT64_HEADER_SIZE: usize = 19;
+
+/// Read entries of the directory `fd` into `dirp` as `struct linux_dirent64`
+/// records, whose total size is at most `count`.
+///
+/// Return the number of bytes read, 0 at the end of the directory, or
+/// `EINVAL` if the buffer is too small for the next entry.
+pub fn sys_getdents64(fd: c_int, dirp: *mut c_void, count: usize) -> ctypes::ssize_t {
+    debug!("sys_getdents64 <= {} {:#x} {}", fd, dirp as usize, count);
+    syscall_body!(sys_getdents64, {
+        if dirp.is_null() {
+            return Err(LinuxError::EFAULT);
+        }
+        let buf = unsafe { core::slice::from_raw_parts_mut(dirp as *mut u8, count) };
+        let dir = Directory::from_fd(fd).map_err(|_| LinuxError::ENOTDIR)?;
+        let mut dir = dir.inner.lock();
+        let mut entries = [const { DirEntry::default() }; 16];
+        let mut written = 0;
+        loop {
+            let start_idx = dir.entry_idx();
+            let n = dir.read_dir(&mut entries)?;
+            if n == 0 {
+                break;
+            }
+            for (i, entry) in entries[..n].iter().enumerate() {
+                let name = entry.name_as_bytes();
+                let reclen = (DIRENT64_HEADER_SIZE + name.len() + 1).next_multiple_of(8);
+                if written + reclen > buf.len() {
+                    // leave the rest for the next call
+                    dir.set_entry_idx(start_idx + i);
+                    return if written == 0 {
+                        Err(LinuxError::EINVAL)
+                    } else {
+                        Ok(written as ctypes::ssize_t)
+                    };
+                }
+                let next_idx = (start_idx + i + 1) as i64;
+                let record = &mut buf[written..written + reclen];
+                let ino = dir.entry_ino(&String::from_utf8_lossy(name));
+                record[..8].copy_from_slice(&ino.to_ne_bytes()); // d_ino
+                record[8..16].copy_from_slice(&next_idx.to_ne_bytes()); // d_off
+                record[16..18].copy_from_slice(&(reclen as u16).to_ne_bytes());
+                record[18] = entry.entry_type() as u8; // same as `DT_*`
+                record[DIRENT64_HEADER_SIZE..][..name.len()].copy_from_slice(name);
+                record[DIRENT64_HEADER_SIZE + name.len()..].fill(0);
+                written += reclen;
+            }
+        }
+        Ok(written as ctypes::ssize_t)
+    })
+}
+
 /// Get the file metadata by `path` and write into `buf`.
 ///
 /// Return 0 if success.
@@ -310,6 +385,538 @@ pub fn sys_rename(old: *const c_char, new: *const c_char) -> c_int {
     })
 }
 
+/// Create a hard link `new` to the file `old`.
+///
+/// Return 0 if success.
+pub fn sys_link(old: *const c_char, new: *const c_char) -> c_int {
+    syscall_body!(sys_link, {
+        let old_path = char_ptr_to_str(old)?;
+        let new_path = char_ptr_to_str(new)?;
+        debug!("sys_link <= old: {:?}, new: {:?}", old_path, new_path);
+        axfs::api::hard_link(old_path, new_path)?;
+        Ok(0)
+    })
+}
+
+/// Remove the link `path` to a file, which is deleted if it was the last link.
+///
+/// Return 0 if success.
+pub fn sys_unlink(path: *const c_char) -> c_int {
+    syscall_body!(sys_unlink, {
+        let path = char_ptr_to_str(path)?;
+        debug!("sys_unlink <= {:?}", path);
+        axfs::api::remove_file(path)?;
+        Ok(0)
+    })
+}
+
+/// Resolve `path` relative to the directory `dirfd`, or to the current
+/// directory if `dirfd` is `AT_FDCWD`, as the `*at` syscalls do.
+fn path_at(dirfd: c_int, path: *const c_char) -> LinuxResult<String> {
+    let path = char_ptr_to_str(path)?;
+    if path.is_empty() {
+        return Err(LinuxError::ENOENT);
+    }
+    if path.starts_with('/') || dirfd == AT_FDCWD as c_int {
+        return Ok(path.into());
+    }
+    let dir = match Directory::from_fd(dirfd) {
+        Err(LinuxError::EINVAL) => return Err(LinuxError::ENOTDIR),
+        dir => dir?,
+    };
+    Ok(format!("{}/{}", dir.inner.lock().path(), path))
+}
+
+/// Returns the absolute path of the file or directory opened as `fd`.
+fn fd_path(fd: c_int) -> LinuxResult<String> {
+    if let Ok(file) = File::from_fd(fd) {
+        Ok(file.inner.lock().path().into())
+    } else {
+        Ok(Directory::from_fd(fd)?.inner.lock().path().into())
+    }
+}
+
+/// Create the directory `path` relative to the directory `dirfd`.
+///
+/// `mode` is ignored for now. Return 0 if success.
+pub fn sys_mkdirat(dirfd: c_int, path: *const c_char, mode: ctypes::mode_t) -> c_int {
+    syscall_body!(sys_mkdirat, {
+        let path = path_at(dirfd, path)?;
+        debug!("sys_mkdirat <= {:?} {:#o}", path, mode);
+        axfs::api::create_dir(&path)?;
+        Ok(0)
+    })
+}
+
+/// Remove the file, or the empty directory if `flags` has `AT_REMOVEDIR`,
+/// at `path` relative to the directory `dirfd`.
+///
+/// Return 0 if success.
+pub fn sys_unlinkat(dirfd: c_int, path: *const c_char, flags: c_int) -> c_int {
+    syscall_body!(sys_unlinkat, {
+        let path = path_at(dirfd, path)?;
+        debug!("sys_unlinkat <= {:?} {:#x}", path, flags);
+        match flags as u32 {
+            0 => axfs::api::remove_file(&path)?,
+            ctypes::AT_REMOVEDIR => axfs::api::remove_dir(&path)?,
+            _ => return Err(LinuxError::EINVAL),
+        }
+        Ok(0)
+    })
+}
+
+/// Remove the empty directory `path`.
+///
+/// Return 0 if success.
+pub fn sys_rmdir(path: *const c_char) -> c_int {
+    syscall_body!(sys_rmdir, {
+        let path = char_ptr_to_str(path)?;
+        debug!("sys_rmdir <= {:?}", path);
+        axfs::api::remove_dir(path)?;
+        Ok(0)
+    })
+}
+
+/// Change the permissions of the file `fd` to `mode`.
+///
+/// Return 0 if success.
+pub fn sys_fchmod(fd: c_int, mode: ctypes::mode_t) -> c_int {
+    debug!("sys_fchmod <= {} {:#o}", fd, mode);
+    syscall_body!(sys_fchmod, {
+        let mode = (mode & 0o777) as u16;
+        if let Ok(file) = File::from_fd(fd) {
+            file.inner.lock().set_permissions(mode)?;
+        } else {
+            Directory::from_fd(fd)?.inner.lock().set_permissions(mode)?;
+        }
+        Ok(0)
+    })
+}
+
+/// Truncate or extend the file `fd` to `length` bytes.
+///
+/// Return 0 if success.
+pub fn sys_ftruncate(fd: c_int, length: ctypes::off_t) -> c_int {
+    debug!("sys_ftruncate <= {} {}", fd, length);
+    syscall_body!(sys_ftruncate, {
+        if length < 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        File::from_fd(fd)?.inner.lock().truncate(length as u64)?;
+        Ok(0)
+    })
+}
+
+/// Write the data of the file `fd` back to the storage device.
+///
+/// Return 0 if success.
+pub fn sys_fsync(fd: c_int) -> c_int {
+    debug!("sys_fsync <= {}", fd);
+    syscall_body!(sys_fsync, {
+        if let Ok(file) = File::from_fd(fd) {
+            file.inner.lock().fsync()?;
+        } else {
+            // nothing to write back for directories
+            Directory::from_fd(fd)?;
+        }
+        Ok(0)
+    })
+}
+
+/// Converts a timestamp of `utimensat` to the time to set, which is `None`
+/// for `UTIME_OMIT`.
+fn utime_of(ts: &ctypes::timespec, now: Duration) -> LinuxResult<Option<Duration>> {
+    if ts.tv_nsec == ctypes::UTIME_NOW as c_long {
+        Ok(Some(now))
+    } else if ts.tv_nsec == ctypes::UTIME_OMIT as c_long {
+        Ok(None)
+    } else if ts.tv_sec < 0 || !(0..1_000_000_000).contains(&ts.tv_nsec) {
+        Err(LinuxError::EINVAL)
+    } else {
+        Ok(Some((*ts).into()))
+    }
+}
+
+/// Set the access and modification times of the file at `path` relative to
+/// the directory `dirfd`, or of the file `dirfd` itself if `path` is null.
+///
+/// `times` points to the access and modification times, or is null to set
+/// both to the current time. `flags` may have `AT_SYMLINK_NOFOLLOW`, but the
+/// times of a symbolic link itself cannot be set, which fails with
+/// `EOPNOTSUPP`.
+///
+/// Return 0 if success.
+pub fn sys_utimensat(
+    dirfd: c_int,
+    path: *const c_char,
+    times: *const ctypes::timespec,
+    flags: c_int,
+) -> c_int {
+    syscall_body!(sys_utimensat, {
+        let nofollow = match flags as u32 {
+            0 => false,
+            ctypes::AT_SYMLINK_NOFOLLOW if !path.is_null() => true,
+            _ => return Err(LinuxError::EINVAL),
+        };
+        let now = axhal::time::wall_time();
+        let (accessed, modified) = if times.is_null() {
+            (Some(now), Some(now))
+        } else {
+            let times = unsafe { core::slice::from_raw_parts(times, 2) };
+            (utime_of(&times[0], now)?, utime_of(&times[1], now)?)
+        };
+        if path.is_null() {
+            // the opened file itself, even if it has been renamed or removed
+            debug!("sys_utimensat <= {} {:#x}", dirfd, flags);
+            if let Ok(file) = File::from_fd(dirfd) {
+                file.inner.lock().set_times(accessed, modified)?;
+            } else {
+                Directory::from_fd(dirfd)?
+                    .inner
+                    .lock()
+                    .set_times(accessed, modified)?;
+            }
+        } else {
+            let path = path_at(dirfd, path)?;
+            debug!("sys_utimensat <= {:?} {:#x}", path, flags);
+            if nofollow && axfs::api::is_symlink(&path)? {
+                return Err(LinuxError::EOPNOTSUPP);
+            }
+            axfs::api::set_times(&path, accessed, modified)?;
+        }
+        Ok(0)
+    })
+}
+
+/// Read data from the file `fd` at `offset`, without changing its position.
+///
+/// Return the read size if success.
+pub fn sys_pread64(
+    fd: c_int,
+    buf: *mut c_void,
+    count: usize,
+    offset: ctypes::off_t,
+) -> ctypes::ssize_t {
+    debug!(
+        "sys_pread64 <= {} {:#x} {} {}",
+        fd, buf as usize, count, offset
+    );
+    syscall_body!(sys_pread64, {
+        if buf.is_null() {
+            return Err(LinuxError::EFAULT);
+        } else if offset < 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        let dst = unsafe { core::slice::from_raw_parts_mut(buf as *mut u8, count) };
+        let read = File::from_fd(fd)?
+            .inner
+            .lock()
+            .read_at(offset as u64, dst)?;
+        Ok(read as ctypes::ssize_t)
+    })
+}
+
+/// Write data to the file `fd` at `offset`, without changing its position.
+///
+/// Return the written size if success.
+pub fn sys_pwrite64(
+    fd: c_int,
+    buf: *const c_void,
+    count: usize,
+    offset: ctypes::off_t,
+) -> ctypes::ssize_t {
+    debug!(
+        "sys_pwrite64 <= {} {:#x} {} {}",
+        fd, buf as usize, count, offset
+    );
+    syscall_body!(sys_pwrite64, {
+        if buf.is_null() {
+            return Err(LinuxError::EFAULT);
+        } else if offset < 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        let src = unsafe { core::slice::from_raw_parts(buf as *const u8, count) };
+        let written = File::from_fd(fd)?
+            .inner
+            .lock()
+            .write_at(offset as u64, src)?;
+        Ok(written as ctypes::ssize_t)
+    })
+}
+
+/// Create a symbolic link at `path` relative to the directory `dirfd`, which
+/// contains `target`.
+///
+/// Return 0 if success.
+pub fn sys_symlinkat(target: *const c_char, dirfd: c_int, path: *const c_char) -> c_int {
+    syscall_body!(sys_symlinkat, {
+        let target = char_ptr_to_str(target)?;
+        let path = path_at(dirfd, path)?;
+        debug!("sys_symlinkat <= {:?} -> {:?}", path, target);
+        axfs::api::create_symlink(target, &path)?;
+        Ok(0)
+    })
+}
+
+/// Read the target of the symbolic link at `path` relative to the directory
+/// `dirfd` into `buf`, which is not null-terminated.
+///
+/// Return the length of the target if success, which is truncated to `size`.
+pub fn sys_readlinkat(
+    dirfd: c_int,
+    path: *const c_char,
+    buf: *mut c_char,
+    size: usize,
+) -> ctypes::ssize_t {
+    syscall_body!(sys_readlinkat, {
+        let path = path_at(dirfd, path)?;
+        debug!("sys_readlinkat <= {:?} {:#x} {}", path, buf as usize, size);
+        if buf.is_null() {
+            return Err(LinuxError::EFAULT);
+        }
+        let dst = unsafe { core::slice::from_raw_parts_mut(buf as *mut u8, size) };
+        let len = axfs::api::read_link(&path, dst)?;
+        Ok(len as ctypes::ssize_t)
+    })
+}
+
+/// Check whether the file at `path` relative to the directory `dirfd` exists,
+/// and can be accessed as `mode`, a mask of `R_OK`, `W_OK` and `X_OK`, by its
+/// owner, as all tasks are.
+///
+/// `flags` is ignored for now. Return 0 if success, or `EACCES` if access is
+/// denied.
+pub fn sys_faccessat(dirfd: c_int, path: *const c_char, mode: c_int, flags: c_int) -> c_int {
+    syscall_body!(sys_faccessat, {
+        let path = path_at(dirfd, path)?;
+        debug!("sys_faccessat <= {:?} {:#o} {:#x}", path, mode, flags);
+        let mode = mode as u32;
+        if mode & !(ctypes::R_OK | ctypes::W_OK | ctypes::X_OK) != 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        let perm = axfs::api::metadata(&path)?.permissions();
+        if (mode & ctypes::R_OK != 0 && !perm.owner_readable())
+            || (mode & ctypes::W_OK != 0 && !perm.owner_writable())
+            || (mode & ctypes::X_OK != 0 && !perm.owner_executable())
+        {
+            return Err(LinuxError::EACCES);
+        }
+        Ok(0)
+    })
+}
+
+/// Change the current directory to `path`.
+///
+/// Return 0 if success.
+pub fn sys_chdir(path: *const c_char) -> c_int {
+    syscall_body!(sys_chdir, {
+        let path = char_ptr_to_str(path)?;
+        debug!("sys_chdir <= {:?}", path);
+        axfs::api::set_current_dir(path)?;
+        Ok(0)
+    })
+}
+
+/// Returns the owner of the record locks taken by the current task, which is
+/// its ID returned by `getpid`.
+fn lock_owner() -> u64 {
+    super::task::sys_getpid() as u64
+}
+
+/// Apply or remove an advisory lock on the open file `fd`.
+///
+/// Return 0 if success, or `EWOULDBLOCK` if `LOCK_NB` is given and the file
+/// is locked by others.
+pub fn sys_flock(fd: c_int, operation: c_int) -> c_int {
+    debug!("sys_flock <= {} {:#x}", fd, operation);
+    syscall_body!(sys_flock, {
+        let wait = operation & ctypes::LOCK_NB as c_int == 0;
+        let ty = match (operation & !(ctypes::LOCK_NB as c_int)) as u32 {
+            ctypes::LOCK_SH => LockType::Shared,
+            ctypes::LOCK_EX => LockType::Exclusive,
+            ctypes::LOCK_UN => LockType::Unlocked,
+            _ => return Err(LinuxError::EINVAL),
+        };
+        // wait without holding the file, which others may use to unlock
+        let handle = File::from_fd(fd)?.inner.lock().lock_handle();
+        handle.flock(ty, wait)?;
+        Ok(0)
+    })
+}
+
+/// Handle the record lock commands `F_GETLK`, `F_SETLK` and `F_SETLKW` of
+/// `fcntl` on the file `fd`, with `lock` pointing to a `struct flock`.
+pub(crate) fn fcntl_lock(fd: c_int, cmd: u32, lock: *mut ctypes::flock) -> LinuxResult<c_int> {
+    if lock.is_null() {
+        return Err(LinuxError::EFAULT);
+    }
+    let flock = unsafe { &mut *lock };
+    let ty = match flock.l_type as u32 {
+        ctypes::F_RDLCK => LockType::Shared,
+        ctypes::F_WRLCK => LockType::Exclusive,
+        ctypes::F_UNLCK => LockType::Unlocked,
+        _ => return Err(LinuxError::EINVAL),
+    };
+    let file = File::from_fd(fd)?;
+    let (handle, base) = {
+        let mut inner = file.inner.lock();
+        let base = match flock.l_whence {
+            0 => 0,
+            1 => inner.seek(SeekFrom::Current(0))?,
+            2 => inner.get_attr()?.size(),
+            _ => return Err(LinuxError::EINVAL),
+        };
+        (inner.lock_handle(), base)
+    };
+    let start = base
+        .checked_add_signed(flock.l_start)
+        .ok_or(LinuxError::EINVAL)?;
+    // a negative length locks the bytes before the start
+    let (start, end) = match flock.l_len {
+        0 => (start, u64::MAX),
+        len if len > 0 => (start, start.saturating_add(len as u64)),
+        len => (
+            start.checked_add_signed(len).ok_or(LinuxError::EINVAL)?,
+            start,
+        ),
+    };
+    let lock = RecordLock {
+        ty,
+        start,
+        end,
+        owner: lock_owner(),
+    };
+    match cmd {
+        ctypes::F_GETLK => match handle.test(&lock) {
+            Some(other) => {
+                flock.l_type = match other.ty {
+                    LockType::Shared => ctypes::F_RDLCK,
+                    _ => ctypes::F_WRLCK,
+                } as _;
+                flock.l_whence = 0;
+                flock.l_start = other.start as _;
+                flock.l_len = match other.end {
+                    u64::MAX => 0,
+                    end => (end - other.start) as _,
+                };
+                flock.l_pid = other.owner as _;
+            }
+            None => flock.l_type = ctypes::F_UNLCK as _,
+        },
+        _ => match handle.set(lock, cmd == ctypes::F_SETLKW) {
+            Ok(()) => {}
+            Err(LockError::Deadlock) => return Err(LinuxError::EDEADLK),
+            Err(LockError::Other(e)) => return Err(e.into()),
+        },
+    }
+    Ok(0)
+}
+
+/// Remove the record locks of the current task on `f`, if it is a file, as
+/// closing any descriptor of a file releases them.
+pub(crate) fn release_record_locks(f: &Arc<dyn FileLike>) {
+    if let Ok(file) = f.clone().into_any().downcast::<File>() {
+        file.inner.lock().lock_handle().release(lock_owner());
+    }
+}
+
+/// Mount the filesystem of type `fstype` on the directory `target`.
+///
+/// `source` may be null for virtual filesystems. `data` may be null, or
+/// point to the comma-separated mount options, e.g., `size=1m` of tmpfs.
+/// `flags` is ignored for now.
+///
+/// Return 0 if success.
+pub fn sys_mount(
+    source: *const c_char,
+    target: *const c_char,
+    fstype: *const c_char,
+    flags: c_ulong,
+    data: *const c_void,
+) -> c_int {
+    syscall_body!(sys_mount, {
+        let source = if source.is_null() {
+            ""
+        } else {
+            char_ptr_to_str(source)?
+        };
+        let target = char_ptr_to_str(target)?;
+        let fstype = char_ptr_to_str(fstype)?;
+        let options = if data.is_null() {
+            ""
+        } else {
+            char_ptr_to_str(data as *const c_char)?
+        };
+        debug!(
+            "sys_mount <= source: {:?}, target: {:?}, fstype: {:?}, flags: {:#x}, options: {:?}",
+            source, target, fstype, flags, options
+        );
+        axfs::api::mount_with_options(source, target, fstype, options)?;
+        Ok(0)
+    })
+}
+
+/// Convert filesystem statistics to `struct statfs`.
+fn statfs_from(stat: &FsStat) -> ctypes::statfs {
+    ctypes::statfs {
+        f_type: stat.fs_type as _,
+        f_bsize: stat.block_size as _,
+        f_blocks: stat.blocks,
+        f_bfree: stat.blocks_free,
+        f_bavail: stat.blocks_available,
+        f_files: stat.files,
+        f_ffree: stat.files_free,
+        f_namelen: stat.name_max as _,
+        f_frsize: stat.block_size as _,
+        ..Default::default()
+    }
+}
+
+/// Get the statistics of the filesystem that `path` is in, and write into
+/// `buf`.
+///
+/// Return 0 if success, or `ENOSYS` if the filesystem does not report them.
+pub fn sys_statfs(path: *const c_char, buf: *mut ctypes::statfs) -> c_int {
+    syscall_body!(sys_statfs, {
+        let path = char_ptr_to_str(path)?;
+        debug!("sys_statfs <= {:?} {:#x}", path, buf as usize);
+        if buf.is_null() {
+            return Err(LinuxError::EFAULT);
+        }
+        let stat = axfs::api::statfs(path)?;
+        unsafe { *buf = statfs_from(&stat) };
+        Ok(0)
+    })
+}
+
+/// Get the statistics of the filesystem that the file `fd` is in, and write
+/// into `buf`.
+///
+/// Return 0 if success.
+pub fn sys_fstatfs(fd: c_int, buf: *mut ctypes::statfs) -> c_int {
+    syscall_body!(sys_fstatfs, {
+        debug!("sys_fstatfs <= {} {:#x}", fd, buf as usize);
+        if buf.is_null() {
+            return Err(LinuxError::EFAULT);
+        }
+        let stat = axfs::api::statfs(&fd_path(fd)?)?;
+        unsafe { *buf = statfs_from(&stat) };
+        Ok(0)
+    })
+}
+
+/// Unmount the filesystem mounted on the directory `target`.
+///
+/// Return `EBUSY` if there are still files opened in it.
+pub fn sys_umount2(target: *const c_char, flags: c_int) -> c_int {
+    syscall_body!(sys_umount2, {
+        let target = char_ptr_to_str(target)?;
+        debug!("sys_umount2 <= target: {:?}, flags: {:#x}", target, flags);
+        axfs::api::umount(target)?;
+        Ok(0)
+    })
+}
+
 /// Directory wrapper for `axfs::fops::Directory`.
 pub struct Directory {
     inner: Mutex<axfs::fops::Directory>,
@@ -352,7 +959,7 @@ impl FileLike for Directory {
     }
 
     fn stat(&self) -> LinuxResult<ctypes::stat> {
-        Err(LinuxError::EBADF)
+        Ok(stat_from_attr(&self.inner.lock().get_attr()?))
     }
 
     fn into_any(self: Arc<Self>) -> Arc<dyn core::any::Any + Send + Sync> {
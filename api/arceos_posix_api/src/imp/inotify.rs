@@ -0,0 +1,150 @@
+use alloc::sync::Arc;
+use core::ffi::{c_char, c_int};
+use core::mem::size_of;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use axerrno::{LinuxError, LinuxResult};
+use axfs::fops::{EventMask, Watcher};
+use axio::PollState;
+
+use super::fd_ops::{FileLike, add_file_like, get_file_like};
+use crate::{ctypes, utils::char_ptr_to_str};
+
+/// An inotify instance, whose events are read as `struct inotify_event`s.
+pub struct Inotify {
+    watcher: Watcher,
+    nonblocking: AtomicBool,
+}
+
+impl Inotify {
+    fn from_fd(fd: c_int) -> LinuxResult<Arc<Self>> {
+        get_file_like(fd)?
+            .into_any()
+            .downcast::<Self>()
+            .map_err(|_| LinuxError::EINVAL)
+    }
+}
+
+impl FileLike for Inotify {
+    /// Reads as many events as fit in `buf`, each followed by its name
+    /// padded with NULs to the alignment of `struct inotify_event`.
+    ///
+    /// Returns `EINVAL` if the next event does not fit.
+    fn read(&self, buf: &mut [u8]) -> LinuxResult<usize> {
+        const HEADER_SIZE: usize = size_of::<ctypes::inotify_event>();
+        let wait = !self.nonblocking.load(Ordering::Relaxed);
+        let mut len = 0;
+        self.watcher.read_events(wait, |event| {
+            let name_len = if event.name.is_empty() {
+                0
+            } else {
+                (event.name.len() + 1).next_multiple_of(HEADER_SIZE)
+            };
+            let Some(rest) = buf.get_mut(len..len + HEADER_SIZE + name_len) else {
+                return false;
+            };
+            let header = ctypes::inotify_event {
+                wd: event.wd,
+                mask: event.mask.bits(),
+                cookie: event.cookie,
+                len: name_len as u32,
+                ..Default::default()
+            };
+            // SAFETY: `inotify_event` is plain data of `HEADER_SIZE` bytes
+            let header = unsafe {
+                core::slice::from_raw_parts(&header as *const _ as *const u8, HEADER_SIZE)
+            };
+            let (head, name) = rest.split_at_mut(HEADER_SIZE);
+            head.copy_from_slice(header);
+            name.fill(0);
+            name[..event.name.len()].copy_from_slice(event.name.as_bytes());
+            len += HEADER_SIZE + name_len;
+            true
+        })?;
+        if len == 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        Ok(len)
+    }
+
+    fn write(&self, _buf: &[u8]) -> LinuxResult<usize> {
+        Err(LinuxError::EBADF)
+    }
+
+    fn stat(&self) -> LinuxResult<ctypes::stat> {
+        Ok(ctypes::stat {
+            st_ino: 1,
+            st_nlink: 1,
+            st_mode: 0o600, // rw-------, an anonymous inode
+            st_uid: 1000,
+            st_gid: 1000,
+            st_blksize: 4096,
+            ..Default::default()
+        })
+    }
+
+    fn into_any(self: Arc<Self>) -> Arc<dyn core::any::Any + Send + Sync> {
+        self
+    }
+
+    fn poll(&self) -> LinuxResult<PollState> {
+        Ok(PollState {
+            readable: self.watcher.has_events(),
+            writable: false,
+        })
+    }
+
+    fn set_nonblocking(&self, nonblocking: bool) -> LinuxResult {
+        self.nonblocking.store(nonblocking, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+/// Create an inotify instance, with `IN_NONBLOCK` and `IN_CLOEXEC` in
+/// `flags`.
+///
+/// Return its file descriptor.
+pub fn sys_inotify_init1(flags: c_int) -> c_int {
+    debug!("sys_inotify_init1 <= {:#x}", flags);
+    syscall_body!(sys_inotify_init1, {
+        let flags = flags as u32;
+        if flags & !(ctypes::O_NONBLOCK | ctypes::O_CLOEXEC) != 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        add_file_like(Arc::new(Inotify {
+            watcher: Watcher::new(),
+            nonblocking: AtomicBool::new(flags & ctypes::O_NONBLOCK != 0),
+        }))
+    })
+}
+
+/// Watch the file or directory at `path` for the events in `mask` by the
+/// inotify instance `fd`.
+///
+/// Return the watch descriptor. The events of opening, closing without
+/// writing and reading files are not reported.
+pub fn sys_inotify_add_watch(fd: c_int, path: *const c_char, mask: u32) -> c_int {
+    syscall_body!(sys_inotify_add_watch, {
+        let path = char_ptr_to_str(path)?;
+        debug!("sys_inotify_add_watch <= {} {:?} {:#x}", fd, path, mask);
+        if mask & ctypes::IN_ALL_EVENTS == 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        let inotify = Inotify::from_fd(fd)?;
+        let wd = inotify
+            .watcher
+            .add_watch(path, EventMask::from_bits_truncate(mask))?;
+        Ok(wd)
+    })
+}
+
+/// Remove the watch `wd` from the inotify instance `fd`.
+///
+/// Return 0 if success.
+pub fn sys_inotify_rm_watch(fd: c_int, wd: c_int) -> c_int {
+    debug!("sys_inotify_rm_watch <= {} {}", fd, wd);
+    syscall_body!(sys_inotify_rm_watch, {
+        Inotify::from_fd(fd)?.watcher.remove_watch(wd)?;
+        Ok(0)
+    })
+}
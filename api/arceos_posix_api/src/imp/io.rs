@@ -7,26 +7,51 @@ use crate::imp::fd_ops::get_file_like;
 #[cfg(not(feature = "fd"))]
 use axio::prelude::*;
 
+fn read_impl(fd: c_int, buf: *mut c_void, count: usize) -> LinuxResult<ctypes::ssize_t> {
+    if buf.is_null() {
+        return Err(LinuxError::EFAULT);
+    }
+    let dst = unsafe { core::slice::from_raw_parts_mut(buf as *mut u8, count) };
+    #[cfg(feature = "fd")]
+    {
+        Ok(get_file_like(fd)?.read(dst)? as ctypes::ssize_t)
+    }
+    #[cfg(not(feature = "fd"))]
+    match fd {
+        0 => Ok(super::stdio::stdin().read(dst)? as ctypes::ssize_t),
+        1 | 2 => Err(LinuxError::EPERM),
+        _ => Err(LinuxError::EBADF),
+    }
+}
+
 /// Read data from the file indicated by `fd`.
 ///
 /// Return the read size if success.
 pub fn sys_read(fd: c_int, buf: *mut c_void, count: usize) -> ctypes::ssize_t {
     debug!("sys_read <= {} {:#x} {}", fd, buf as usize, count);
-    syscall_body!(sys_read, {
-        if buf.is_null() {
-            return Err(LinuxError::EFAULT);
-        }
-        let dst = unsafe { core::slice::from_raw_parts_mut(buf as *mut u8, count) };
-        #[cfg(feature = "fd")]
-        {
-            Ok(get_file_like(fd)?.read(dst)? as ctypes::ssize_t)
+    syscall_body!(sys_read, read_impl(fd, buf, count))
+}
+
+/// Read into a vector.
+pub unsafe fn sys_readv(fd: c_int, iov: *const ctypes::iovec, iocnt: c_int) -> ctypes::ssize_t {
+    debug!("sys_readv <= fd: {}", fd);
+    syscall_body!(sys_readv, {
+        if !(0..=1024).contains(&iocnt) {
+            return Err(LinuxError::EINVAL);
         }
-        #[cfg(not(feature = "fd"))]
-        match fd {
-            0 => Ok(super::stdio::stdin().read(dst)? as ctypes::ssize_t),
-            1 | 2 => Err(LinuxError::EPERM),
-            _ => Err(LinuxError::EBADF),
+
+        let iovs = unsafe { core::slice::from_raw_parts(iov, iocnt as usize) };
+        let mut ret = 0;
+        for iov in iovs.iter() {
+            let result = read_impl(fd, iov.iov_base, iov.iov_len)?;
+            ret += result;
+
+            if result < iov.iov_len as isize {
+                break;
+            }
         }
+
+        Ok(ret)
     })
 }
 
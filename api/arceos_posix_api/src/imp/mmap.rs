@@ -0,0 +1,399 @@
+//! Memory mappings in the kernel address space, which is shared by all tasks.
+//!
+//! Mappings are placed in the upper half of the kernel address space, away
+//! from the linear mappings of the physical memory, and only that part can be
+//! changed by `mmap`, `munmap`, `mremap` and `mprotect`. Pages are allocated, and read
+//! from the mapped file, when they are accessed for the first time.
+//!
+//! The address space is locked with IRQs disabled, so the mapped files are
+//! never read or written with the lock held, as the file system may sleep.
+//! Pages of shared file mappings are written back only if they are changed,
+//! by `msync` or when they are unmapped.
+
+use alloc::vec::Vec;
+use core::ffi::{c_int, c_void};
+
+use axerrno::{LinuxError, LinuxResult};
+use axhal::mem::{MemoryAddr, PAGE_SIZE_4K, VirtAddr};
+use axhal::paging::{MappingFlags, PageSize};
+use axhal::trap::{PAGE_FAULT, register_trap_handler};
+use axmm::{AddrSpace, FilePage};
+use memory_addr::VirtAddrRange;
+
+use crate::ctypes;
+
+/// Returns the part of the kernel address space for memory mappings.
+fn mmap_range(aspace: &AddrSpace) -> VirtAddrRange {
+    VirtAddrRange::new(aspace.base() + aspace.size() / 2, aspace.end())
+}
+
+/// Checks that the range of `len` bytes from `addr` is aligned and can be
+/// changed by memory mapping calls. Returns the range rounded up to pages.
+fn check_range(aspace: &AddrSpace, addr: *mut c_void, len: usize) -> LinuxResult<VirtAddrRange> {
+    let start = VirtAddr::from(addr as usize);
+    if !start.is_aligned_4k() || len == 0 {
+        return Err(LinuxError::EINVAL);
+    }
+    let end = len
+        .checked_next_multiple_of(PAGE_SIZE_4K)
+        .and_then(|len| start.as_usize().checked_add(len))
+        .ok_or(LinuxError::EINVAL)?;
+    let range = VirtAddrRange::new(start, end.into());
+    if !mmap_range(aspace).contains_range(range) {
+        return Err(LinuxError::EINVAL);
+    }
+    Ok(range)
+}
+
+fn prot_to_flags(prot: c_int) -> MappingFlags {
+    let prot = prot as u32;
+    let mut flags = MappingFlags::empty();
+    if prot & ctypes::PROT_READ != 0 {
+        flags |= MappingFlags::READ;
+    }
+    if prot & ctypes::PROT_WRITE != 0 {
+        flags |= MappingFlags::WRITE;
+    }
+    if prot & ctypes::PROT_EXEC != 0 {
+        flags |= MappingFlags::EXECUTE;
+    }
+    flags
+}
+
+/// Writes back the dirty `pages` taken from shared file mappings.
+///
+/// All the pages are written even if some fail, and the first error is
+/// returned.
+fn write_back(pages: Vec<FilePage>) -> LinuxResult {
+    let mut result = Ok(());
+    for page in pages {
+        if let Err(e) = page.write_back() {
+            warn!("failed to write back page at {:#x}: {:?}", page.offset(), e);
+            result = result.and(Err(e.into()));
+        }
+    }
+    result
+}
+
+/// Handles page faults in the mappings, by allocating the pages, or reading
+/// them from the mapped file.
+#[register_trap_handler(PAGE_FAULT)]
+fn handle_page_fault(vaddr: VirtAddr, access_flags: MappingFlags, is_user: bool) -> bool {
+    if is_user {
+        return false;
+    }
+    loop {
+        let mut aspace = axmm::kernel_aspace().lock();
+        let Some((file, offset)) = aspace.file_page_to_read(vaddr, access_flags) else {
+            return aspace.handle_page_fault(vaddr, access_flags);
+        };
+        drop(aspace);
+
+        let page = match FilePage::read(file, offset) {
+            Ok(page) => page,
+            Err(e) => {
+                warn!("failed to read page at {:#x}: {:?}", vaddr, e);
+                return false;
+            }
+        };
+        // the mapping may be changed while the page is read, then try again
+        let mut aspace = axmm::kernel_aspace().lock();
+        if let Some(handled) = aspace.map_file_page(vaddr, access_flags, page) {
+            return handled;
+        }
+    }
+}
+
+#[cfg(feature = "fs")]
+mod file {
+    use core::ffi::c_int;
+
+    use axerrno::{AxResult, LinuxError, LinuxResult};
+    use axmm::MappedFile;
+
+    use crate::imp::{fd_ops::get_file_like, fs::File};
+
+    /// A file mapped into memory.
+    ///
+    /// It is the file opened again, so that the mapping is read and written
+    /// back without locking the opened file of `fd`, which may be reading
+    /// into or writing from the mapping itself.
+    pub struct FileMapping(axfs::fops::File);
+
+    impl FileMapping {
+        /// Gets the file of `fd` to be mapped, which must be opened for
+        /// reading, and also for writing if `write_shared` is true.
+        pub fn from_fd(fd: c_int, write_shared: bool) -> LinuxResult<Self> {
+            let file = get_file_like(fd)?
+                .into_any()
+                .downcast::<File>()
+                .map_err(|_| LinuxError::ENODEV)?;
+            let inner = file.inner().lock();
+            if !inner.is_readable() || (write_shared && !inner.is_writable()) {
+                return Err(LinuxError::EACCES);
+            }
+            Ok(Self(inner.reopen()?))
+        }
+
+        /// Returns whether changes to the mapping can be written back.
+        pub fn is_writable(&self) -> bool {
+            self.0.is_writable()
+        }
+    }
+
+    impl MappedFile for FileMapping {
+        fn read_at(&self, offset: u64, buf: &mut [u8]) -> AxResult<usize> {
+            let file = &self.0;
+            let mut read = 0;
+            while read < buf.len() {
+                match file.read_at(offset + read as u64, &mut buf[read..])? {
+                    0 => break,
+                    n => read += n,
+                }
+            }
+            Ok(read)
+        }
+
+        fn write_back(&self, offset: u64, buf: &[u8]) -> AxResult {
+            let file = &self.0;
+            let size = file.get_attr()?.size();
+            let len = buf.len().min(size.saturating_sub(offset) as usize);
+            let mut written = 0;
+            while written < len {
+                written += file.write_at(offset + written as u64, &buf[written..len])?;
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Map `len` bytes of the file `fd` from `off`, or anonymous memory if
+/// `MAP_ANONYMOUS` is in `flags`, with the protection `prot`.
+///
+/// With `MAP_SHARED`, changes to a file mapping are written back to the file
+/// by `msync`, or when the pages are unmapped. With `MAP_PRIVATE`, they are
+/// not. The mapping is placed at `addr` if `MAP_FIXED` is in `flags`,
+/// replacing the mappings there, or near it otherwise.
+///
+/// Return the start address of the mapping.
+pub fn sys_mmap(
+    addr: *mut c_void,
+    len: usize,
+    prot: c_int,
+    flags: c_int,
+    fd: c_int,
+    off: ctypes::off_t,
+) -> *mut c_void {
+    debug!(
+        "sys_mmap <= {:#x} {:#x} {:#x} {:#x} {} {:#x}",
+        addr as usize, len, prot, flags, fd, off
+    );
+    syscall_body!(sys_mmap, {
+        let flags = flags as u32;
+        let shared = match flags & ctypes::MAP_TYPE {
+            ctypes::MAP_SHARED | ctypes::MAP_SHARED_VALIDATE => true,
+            ctypes::MAP_PRIVATE => false,
+            _ => return Err(LinuxError::EINVAL),
+        };
+        if len == 0 || off < 0 || !(off as usize).is_aligned_4k() {
+            return Err(LinuxError::EINVAL);
+        }
+        let len = len
+            .checked_next_multiple_of(PAGE_SIZE_4K)
+            .ok_or(LinuxError::ENOMEM)?;
+        let map_flags = prot_to_flags(prot);
+
+        #[cfg(feature = "fs")]
+        let file = if flags & ctypes::MAP_ANONYMOUS == 0 {
+            let write_shared = shared && map_flags.contains(MappingFlags::WRITE);
+            Some(file::FileMapping::from_fd(fd, write_shared)?)
+        } else {
+            None
+        };
+        #[cfg(not(feature = "fs"))]
+        if flags & ctypes::MAP_ANONYMOUS == 0 {
+            return Err(LinuxError::ENODEV);
+        }
+
+        let mut aspace = axmm::kernel_aspace().lock();
+        let mut dirty = Vec::new();
+        let start = if flags & ctypes::MAP_FIXED != 0 {
+            let range = check_range(&aspace, addr, len)?;
+            dirty = aspace.take_dirty_pages(range.start, range.size())?;
+            aspace.unmap(range.start, range.size())?;
+            range.start
+        } else {
+            let limit = mmap_range(&aspace);
+            let hint = VirtAddr::from(addr as usize).align_down_4k();
+            aspace
+                .find_free_area(hint, len, limit, PageSize::Size4K)
+                .or_else(|| aspace.find_free_area(limit.start, len, limit, PageSize::Size4K))
+                .ok_or(LinuxError::ENOMEM)?
+        };
+
+        #[cfg(feature = "fs")]
+        if let Some(file) = file {
+            // a read-only file is never changed by the mapping
+            let shared = shared && file.is_writable();
+            let file = alloc::sync::Arc::new(file);
+            aspace.map_file(start, len, map_flags, file, off as u64, shared)?;
+        } else {
+            aspace.map_alloc(start, len, map_flags, false, PageSize::Size4K)?;
+        }
+        #[cfg(not(feature = "fs"))]
+        aspace.map_alloc(start, len, map_flags, false, PageSize::Size4K)?;
+        drop(aspace);
+
+        // the replaced mappings are gone, even if their pages are not written
+        write_back(dirty).ok();
+        Ok(start.as_mut_ptr() as *mut c_void)
+    })
+}
+
+/// Remove the mappings in the `len` bytes from `addr`, writing back the
+/// changes to shared file mappings.
+///
+/// Return 0 if success.
+pub fn sys_munmap(addr: *mut c_void, len: usize) -> c_int {
+    debug!("sys_munmap <= {:#x} {:#x}", addr as usize, len);
+    syscall_body!(sys_munmap, {
+        let mut aspace = axmm::kernel_aspace().lock();
+        let range = check_range(&aspace, addr, len)?;
+        let dirty = aspace.take_dirty_pages(range.start, range.size())?;
+        aspace.unmap(range.start, range.size())?;
+        drop(aspace);
+
+        write_back(dirty)?;
+        Ok(0)
+    })
+}
+
+/// Resize the mapping of `old_size` bytes at `old_addr` to `new_size` bytes.
+///
+/// The mapping is extended in place if the pages after it are free, or moved
+/// with its pages otherwise if `MREMAP_MAYMOVE` is in `flags`. It is moved to
+/// `new_addr` if `MREMAP_FIXED` is also in `flags`, replacing the mappings
+/// there. The changes to the pages of shared file mappings removed by
+/// shrinking are written back. `MREMAP_DONTUNMAP` is not supported.
+///
+/// Return the new start address of the mapping.
+pub fn sys_mremap(
+    old_addr: *mut c_void,
+    old_size: usize,
+    new_size: usize,
+    flags: c_int,
+    new_addr: *mut c_void,
+) -> *mut c_void {
+    debug!(
+        "sys_mremap <= {:#x} {:#x} {:#x} {:#x} {:#x}",
+        old_addr as usize, old_size, new_size, flags, new_addr as usize
+    );
+    syscall_body!(sys_mremap, {
+        let flags = flags as u32;
+        let may_move = flags & ctypes::MREMAP_MAYMOVE != 0;
+        let fixed = flags & ctypes::MREMAP_FIXED != 0;
+        if flags & !(ctypes::MREMAP_MAYMOVE | ctypes::MREMAP_FIXED) != 0
+            || (fixed && !may_move)
+            || new_size == 0
+        {
+            return Err(LinuxError::EINVAL);
+        }
+        let new_len = new_size
+            .checked_next_multiple_of(PAGE_SIZE_4K)
+            .ok_or(LinuxError::ENOMEM)?;
+
+        let mut aspace = axmm::kernel_aspace().lock();
+        let old = check_range(&aspace, old_addr, old_size)?;
+        if !aspace.check_region_access(old, MappingFlags::empty()) {
+            return Err(LinuxError::EFAULT);
+        }
+        let fixed_range = if fixed {
+            let range = check_range(&aspace, new_addr, new_len)?;
+            if range.overlaps(old) {
+                return Err(LinuxError::EINVAL);
+            }
+            Some(range)
+        } else {
+            None
+        };
+
+        let mut dirty = Vec::new();
+        if new_len < old.size() {
+            let tail = old.start + new_len;
+            dirty = aspace.take_dirty_pages(tail, old.end - tail)?;
+            aspace.unmap(tail, old.end - tail)?;
+        }
+        let len = new_len.min(old.size());
+
+        let limit = mmap_range(&aspace);
+        let start = if let Some(range) = fixed_range {
+            dirty.extend(aspace.take_dirty_pages(range.start, range.size())?);
+            aspace.unmap(range.start, range.size())?;
+            range.start
+        } else if new_len == len
+            || aspace.find_free_area(old.end, new_len - len, limit, PageSize::Size4K)
+                == Some(old.end)
+        {
+            old.start
+        } else if may_move {
+            aspace
+                .find_free_area(old.start, new_len, limit, PageSize::Size4K)
+                .or_else(|| aspace.find_free_area(limit.start, new_len, limit, PageSize::Size4K))
+                .ok_or(LinuxError::ENOMEM)?
+        } else {
+            return Err(LinuxError::ENOMEM);
+        };
+        if start != old.start || new_len != len {
+            aspace.remap(old.start, len, start, new_len)?;
+        }
+        drop(aspace);
+
+        // the removed mappings are gone, even if their pages are not written
+        write_back(dirty).ok();
+        Ok(start.as_mut_ptr() as *mut c_void)
+    })
+}
+
+/// Write back the changes to shared file mappings in the `len` bytes from
+/// `addr`. Only the pages changed since they were last written back are
+/// written.
+///
+/// The pages are always written before returning, so `MS_ASYNC` behaves like
+/// `MS_SYNC`, and `MS_INVALIDATE` has nothing to do, as the mappings of a
+/// file do not share pages.
+///
+/// Return 0 if success.
+pub fn sys_msync(addr: *mut c_void, len: usize, flags: c_int) -> c_int {
+    debug!("sys_msync <= {:#x} {:#x} {:#x}", addr as usize, len, flags);
+    syscall_body!(sys_msync, {
+        let flags = flags as u32;
+        let sync = ctypes::MS_ASYNC | ctypes::MS_SYNC;
+        if flags & !(sync | ctypes::MS_INVALIDATE) != 0 || flags & sync == sync {
+            return Err(LinuxError::EINVAL);
+        }
+        let mut aspace = axmm::kernel_aspace().lock();
+        let range = check_range(&aspace, addr, len)?;
+        let dirty = aspace.take_dirty_pages(range.start, range.size())?;
+        drop(aspace);
+
+        write_back(dirty)?;
+        Ok(0)
+    })
+}
+
+/// Change the protection of the mappings in the `len` bytes from `addr` to
+/// `prot`.
+///
+/// Return 0 if success, or `ENOMEM` if part of the range is not mapped.
+pub fn sys_mprotect(addr: *mut c_void, len: usize, prot: c_int) -> c_int {
+    debug!(
+        "sys_mprotect <= {:#x} {:#x} {:#x}",
+        addr as usize, len, prot
+    );
+    syscall_body!(sys_mprotect, {
+        let mut aspace = axmm::kernel_aspace().lock();
+        let range = check_range(&aspace, addr, len)?;
+        aspace.protect(range.start, range.size(), prot_to_flags(prot))?;
+        Ok(0)
+    })
+}
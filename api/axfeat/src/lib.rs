@@ -22,7 +22,10 @@
 //! - Upperlayer stacks (fs, net, display)
 //!     - `fs`: Enable file system support.
 //!     - `myfs`: Allow users to define their custom filesystems to override the default.
+//!     - `overlayfs`: Keep the root filesystem read-only, with changes kept in memory.
+//!     - `initramfs`: Use a ramfs unpacked from a cpio or tar archive as the root filesystem.
 //!     - `net`: Enable networking support.
+//!     - `dhcp`: Configure the NIC by DHCP.
 //!     - `display`: Enable graphics support.
 //! - Device drivers
 //!     - `bus-mmio`: Use device tree to probe all MMIO devices.
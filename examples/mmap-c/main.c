@@ -0,0 +1,152 @@
+// Tests private and shared file mappings.
+//
+// Run with `make A=examples/mmap-c BLK=y run`, after making the disk image
+// by `make disk_img`.
+
+#include <assert.h>
+#include <errno.h>
+#include <fcntl.h>
+#include <stdio.h>
+#include <string.h>
+#include <sys/mman.h>
+#include <unistd.h>
+
+#define PAGE_SIZE 4096
+#define FILE_SIZE (2 * PAGE_SIZE)
+
+static const char *path = "/mmap.txt";
+
+static int create_file(char c)
+{
+    char buf[FILE_SIZE];
+    int fd = open(path, O_RDWR | O_CREAT | O_TRUNC, 0644);
+    assert(fd >= 0);
+    memset(buf, c, sizeof(buf));
+    assert(write(fd, buf, sizeof(buf)) == sizeof(buf));
+    return fd;
+}
+
+static char file_byte(int fd, off_t off)
+{
+    char c = 0;
+    assert(pread(fd, &c, 1, off) == 1);
+    return c;
+}
+
+static void test_private_mapping(void)
+{
+    int fd = create_file('a');
+    char *p = mmap(NULL, FILE_SIZE, PROT_READ | PROT_WRITE, MAP_PRIVATE, fd, 0);
+    assert(p != MAP_FAILED);
+    assert(p[0] == 'a' && p[PAGE_SIZE] == 'a');
+
+    // changes are never written back
+    p[0] = 'x';
+    assert(msync(p, FILE_SIZE, MS_SYNC) == 0);
+    assert(munmap(p, FILE_SIZE) == 0);
+    assert(file_byte(fd, 0) == 'a');
+
+    close(fd);
+    puts("test_private_mapping() OK!");
+}
+
+static void test_shared_mapping(void)
+{
+    int fd = create_file('a');
+    char *p = mmap(NULL, FILE_SIZE, PROT_READ | PROT_WRITE, MAP_SHARED, fd, 0);
+    assert(p != MAP_FAILED);
+    assert(p[0] == 'a');
+
+    // only the changed pages are written back, so the first page read by the
+    // mapping does not overwrite the file
+    assert(pwrite(fd, "b", 1, 1) == 1);
+    p[PAGE_SIZE] = 'y';
+    assert(msync(p, FILE_SIZE, MS_SYNC) == 0);
+    assert(file_byte(fd, 1) == 'b');
+    assert(file_byte(fd, PAGE_SIZE) == 'y');
+
+    // the whole page is written back again after it is changed again
+    assert(pwrite(fd, "c", 1, PAGE_SIZE + 1) == 1);
+    p[PAGE_SIZE] = 'z';
+    assert(munmap(p, FILE_SIZE) == 0);
+    assert(file_byte(fd, PAGE_SIZE) == 'z');
+    assert(file_byte(fd, PAGE_SIZE + 1) == 'a');
+
+    assert(msync(p, FILE_SIZE, MS_SYNC | MS_ASYNC) == -1 && errno == EINVAL);
+    close(fd);
+    puts("test_shared_mapping() OK!");
+}
+
+static void test_io_through_mapping(void)
+{
+    int fd = create_file('a');
+    char *p = mmap(NULL, FILE_SIZE, PROT_READ | PROT_WRITE, MAP_SHARED, fd, 0);
+    assert(p != MAP_FAILED);
+
+    // the pages of the mapping are read from the file while the same file
+    // is read into them or written from them
+    assert(pwrite(fd, "bbbb", 4, PAGE_SIZE) == 4);
+    assert(read(fd, p, 4) == 4);
+    assert(memcmp(p, "aaaa", 4) == 0);
+    assert(lseek(fd, 0, SEEK_SET) == 0);
+    assert(write(fd, p + PAGE_SIZE, 4) == 4);
+    assert(file_byte(fd, 0) == 'b');
+
+    assert(munmap(p, FILE_SIZE) == 0);
+    close(fd);
+    puts("test_io_through_mapping() OK!");
+}
+
+static void test_remap(void)
+{
+    // make a hole after the first page, to extend the mapping in place
+    char *p = mmap(NULL, 3 * PAGE_SIZE, PROT_READ | PROT_WRITE, MAP_PRIVATE | MAP_ANONYMOUS, -1, 0);
+    assert(p != MAP_FAILED);
+    assert(munmap(p + PAGE_SIZE, 2 * PAGE_SIZE) == 0);
+    p[0] = 'a';
+    assert(mremap(p, PAGE_SIZE, 2 * PAGE_SIZE, 0) == p);
+    assert(p[0] == 'a' && p[PAGE_SIZE] == 0);
+
+    // the mapping is moved with its pages only if it may be
+    char *q = mmap(p + 2 * PAGE_SIZE, PAGE_SIZE, PROT_READ | PROT_WRITE,
+                   MAP_PRIVATE | MAP_ANONYMOUS | MAP_FIXED, -1, 0);
+    assert(q == p + 2 * PAGE_SIZE);
+    assert(mremap(p, 2 * PAGE_SIZE, 3 * PAGE_SIZE, 0) == MAP_FAILED && errno == ENOMEM);
+    char *r = mremap(p, 2 * PAGE_SIZE, 3 * PAGE_SIZE, MREMAP_MAYMOVE);
+    assert(r != MAP_FAILED && r != p);
+    assert(r[0] == 'a' && r[2 * PAGE_SIZE] == 0);
+    assert(mremap(r, 3 * PAGE_SIZE, PAGE_SIZE, 0) == r);
+    assert(mremap(r, PAGE_SIZE, PAGE_SIZE, MREMAP_FIXED, q) == MAP_FAILED && errno == EINVAL);
+    assert(munmap(r, PAGE_SIZE) == 0);
+    assert(munmap(q, PAGE_SIZE) == 0);
+
+    // the changes to a shared file mapping are moved with it, and written
+    // back when it shrinks
+    int fd = create_file('a');
+    p = mmap(NULL, FILE_SIZE, PROT_READ | PROT_WRITE, MAP_SHARED, fd, 0);
+    assert(p != MAP_FAILED);
+    p[0] = 'x';
+    p[PAGE_SIZE] = 'y';
+    q = mmap(NULL, FILE_SIZE, PROT_NONE, MAP_PRIVATE | MAP_ANONYMOUS, -1, 0);
+    assert(q != MAP_FAILED);
+    assert(mremap(p, FILE_SIZE, FILE_SIZE, MREMAP_MAYMOVE | MREMAP_FIXED, q) == q);
+    assert(mremap(q, FILE_SIZE, PAGE_SIZE, 0) == q);
+    assert(file_byte(fd, PAGE_SIZE) == 'y');
+    assert(q[0] == 'x' && file_byte(fd, 0) == 'a');
+    assert(munmap(q, PAGE_SIZE) == 0);
+    assert(file_byte(fd, 0) == 'x');
+
+    close(fd);
+    puts("test_remap() OK!");
+}
+
+int main()
+{
+    test_private_mapping();
+    test_shared_mapping();
+    test_io_through_mapping();
+    test_remap();
+    unlink(path);
+    puts("All mmap tests passed!");
+    return 0;
+}
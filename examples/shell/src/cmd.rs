@@ -26,6 +26,8 @@ const CMD_TABLE: &[(&str, CmdHandler)] = &[
     ("help", do_help),
     ("ls", do_ls),
     ("mkdir", do_mkdir),
+    #[cfg(all(feature = "axstd", feature = "net"))]
+    ("ping", do_ping),
     ("pwd", do_pwd),
     ("rm", do_rm),
     ("uname", do_uname),
@@ -240,6 +242,107 @@ fn do_cd(mut args: &str) {
     }
 }
 
+#[cfg(all(feature = "axstd", feature = "net"))]
+fn do_ping(args: &str) {
+    use std::net::{IcmpSocket, IpAddr, ToSocketAddrs};
+    use std::time::{Duration, Instant};
+
+    const DATA_LEN: usize = 56;
+    const INTERVAL: Duration = Duration::from_secs(1);
+
+    let mut count = 4;
+    let mut host = None;
+    let mut args = args.split_whitespace();
+    while let Some(arg) = args.next() {
+        if arg == "-c" {
+            match args.next().and_then(|n| n.parse().ok()) {
+                Some(n) if n > 0 => count = n,
+                _ => {
+                    print_err!("ping", "invalid count");
+                    return;
+                }
+            }
+        } else {
+            host = Some(arg);
+        }
+    }
+    let Some(host) = host else {
+        print_err!("ping", "missing host operand");
+        return;
+    };
+
+    let addr = match host.parse::<IpAddr>() {
+        Ok(addr) => addr,
+        Err(_) => match (host, 0).to_socket_addrs().map(|mut addrs| addrs.next()) {
+            Ok(Some(addr)) => addr.ip(),
+            Ok(None) => {
+                print_err!("ping", host, "unknown host");
+                return;
+            }
+            Err(e) => {
+                print_err!("ping", host, e);
+                return;
+            }
+        },
+    };
+
+    let socket = match IcmpSocket::new() {
+        Ok(socket) => socket,
+        Err(e) => {
+            print_err!("ping", e);
+            return;
+        }
+    };
+    if let Err(e) = socket.set_read_timeout(Some(INTERVAL)) {
+        print_err!("ping", e);
+        return;
+    }
+
+    println!("PING {} ({}): {} data bytes", host, addr, DATA_LEN);
+    let data: Vec<u8> = (0..DATA_LEN as u8).collect();
+    let mut buf = [0; DATA_LEN];
+    let mut received = 0;
+    for seq_no in 0..count {
+        let start = Instant::now();
+        if let Err(e) = socket.send_echo_request(addr, seq_no, &data) {
+            print_err!("ping", host, e);
+            return;
+        }
+        loop {
+            match socket.recv_echo_reply(&mut buf) {
+                Ok((len, from, seq)) if seq == seq_no => {
+                    let rtt = start.elapsed().as_micros();
+                    println!(
+                        "{} bytes from {}: icmp_seq={} time={}.{:03} ms",
+                        len + 8,
+                        from,
+                        seq,
+                        rtt / 1000,
+                        rtt % 1000
+                    );
+                    received += 1;
+                }
+                // a late reply of the previous request
+                Ok(_) => continue,
+                Err(io::Error::TimedOut) => println!("Request timeout for icmp_seq={}", seq_no),
+                Err(e) => print_err!("ping", host, e),
+            }
+            break;
+        }
+        if seq_no + 1 < count {
+            std::thread::sleep(INTERVAL.saturating_sub(start.elapsed()));
+        }
+    }
+
+    println!("--- {} ping statistics ---", host);
+    println!(
+        "{} packets transmitted, {} received, {}% packet loss",
+        count,
+        received,
+        (count - received) as u32 * 100 / count as u32
+    );
+}
+
 fn do_pwd(_args: &str) {
     let pwd = std::env::current_dir().unwrap();
     println!("{}", path_to_str(&pwd));
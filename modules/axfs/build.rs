@@ -0,0 +1,18 @@
+use std::path::PathBuf;
+
+fn main() {
+    // the initramfs archive to embed, empty if not given
+    println!("cargo:rerun-if-env-changed=AX_INITRAMFS");
+    let archive = match std::env::var("AX_INITRAMFS") {
+        Ok(path) if !path.is_empty() => {
+            println!("cargo:rerun-if-changed={path}");
+            PathBuf::from(path)
+        }
+        _ => {
+            let path = PathBuf::from(std::env::var("OUT_DIR").unwrap()).join("empty.cpio");
+            std::fs::write(&path, []).unwrap();
+            path
+        }
+    };
+    println!("cargo:rustc-env=AX_INITRAMFS_PATH={}", archive.display());
+}
@@ -1,6 +1,6 @@
 use alloc::vec::Vec;
 use axio::{Result, SeekFrom, default_read_to_end, prelude::*};
-use core::fmt;
+use core::{fmt, time::Duration};
 
 use crate::fops;
 
@@ -111,6 +111,34 @@ impl Metadata {
     pub const fn blocks(&self) -> u64 {
         self.0.blocks()
     }
+
+    /// Returns the last access time of the file, since the Unix epoch.
+    ///
+    /// It is zero if the filesystem does not keep it, and so are the other
+    /// timestamps.
+    pub const fn accessed(&self) -> Duration {
+        self.0.times().accessed
+    }
+
+    /// Returns the last modification time of the file, since the Unix epoch.
+    pub const fn modified(&self) -> Duration {
+        self.0.times().modified
+    }
+
+    /// Returns the creation time of the file, since the Unix epoch.
+    pub const fn created(&self) -> Duration {
+        self.0.times().created
+    }
+
+    /// Returns the number of hard links to the file.
+    pub const fn nlink(&self) -> u64 {
+        self.0.nlink()
+    }
+
+    /// Returns the inode number of the file.
+    pub const fn ino(&self) -> u64 {
+        self.0.ino()
+    }
 }
 
 impl fmt::Debug for Metadata {
@@ -120,6 +148,7 @@ impl fmt::Debug for Metadata {
             .field("is_dir", &self.is_dir())
             .field("is_file", &self.is_file())
             .field("permissions", &self.permissions())
+            .field("modified", &self.modified())
             .finish_non_exhaustive()
     }
 }
@@ -5,8 +5,14 @@ mod file;
 
 pub use self::dir::{DirBuilder, DirEntry, ReadDir};
 pub use self::file::{File, FileType, Metadata, OpenOptions, Permissions};
+pub use crate::cache::CacheStats;
+pub use crate::fops::FsStat;
+pub use crate::tunable::Tunable;
 
-use alloc::{string::String, vec::Vec};
+use alloc::{string::String, sync::Arc, vec::Vec};
+use core::time::Duration;
+
+use axfs_vfs::VfsOps;
 use axio::{self as io, prelude::*};
 
 /// Returns an iterator over the entries within a directory.
@@ -56,7 +62,7 @@ pub fn write<C: AsRef<[u8]>>(path: &str, contents: C) -> io::Result<()> {
 /// Given a path, query the file system to get information about a file,
 /// directory, etc.
 pub fn metadata(path: &str) -> io::Result<Metadata> {
-    crate::root::lookup(None, path)?.get_attr().map(Metadata)
+    crate::root::path_attr(path).map(Metadata)
 }
 
 /// Creates a new, empty directory at the provided path.
@@ -72,12 +78,12 @@ pub fn create_dir_all(path: &str) -> io::Result<()> {
 
 /// Removes an empty directory.
 pub fn remove_dir(path: &str) -> io::Result<()> {
-    crate::root::remove_dir(None, path)
+    crate::root::remove_dir(None, path, &crate::root::absolute_path(path)?)
 }
 
 /// Removes a file from the filesystem.
 pub fn remove_file(path: &str) -> io::Result<()> {
-    crate::root::remove_file(None, path)
+    crate::root::remove_file(path)
 }
 
 /// Rename a file or directory to a new name.
@@ -93,6 +99,14 @@ pub fn absolute_path_exists(path: &str) -> bool {
     crate::root::lookup(None, path).is_ok()
 }
 
+/// Creates a hard link `new` to the file `old`.
+///
+/// Both paths must be in the same mounted fs, which must support hard links,
+/// e.g., ext4.
+pub fn hard_link(old: &str, new: &str) -> io::Result<()> {
+    crate::root::create_link(old, new)
+}
+
 /// Create a symbolic link.
 ///
 /// Creates a symbolic link named `new` which contains the string `old`.
@@ -113,9 +127,7 @@ pub fn read_link(path: &str, buf: &mut [u8]) -> io::Result<usize> {
 /// This is similar to `metadata()` but for symbolic links, it returns the metadata
 /// of the link itself rather than the file it points to.
 pub fn symlink_metadata(path: &str) -> io::Result<Metadata> {
-    let node = crate::root::lookup(None, path)?;
-    let attr = node.get_attr()?;
-    Ok(Metadata(attr))
+    crate::root::path_attr(path).map(Metadata)
 }
 
 /// Check if a path is a symbolic link.
@@ -131,3 +143,104 @@ pub fn is_symlink(path: &str) -> io::Result<bool> {
 pub fn set_permissions(path: &str, mode: u16) -> io::Result<()> {
     crate::root::set_perm(path, mode)
 }
+
+/// Sets the access and modification times of the file at `path`.
+///
+/// Times that are `None` are left unchanged. Filesystems that do not keep
+/// timestamps ignore them.
+pub fn set_times(
+    path: &str,
+    accessed: Option<Duration>,
+    modified: Option<Duration>,
+) -> io::Result<()> {
+    crate::root::set_times(path, accessed, modified)
+}
+
+/// Returns the statistics of the mounted filesystem that `path` is in, e.g.,
+/// its capacity and free space.
+///
+/// Data in the page cache is not counted until it is written back.
+pub fn statfs(path: &str) -> io::Result<FsStat> {
+    crate::root::statfs(path)
+}
+
+/// Mounts a new filesystem of type `fstype` on the directory `target`.
+///
+/// The directory is created if it does not exist. Mounting on an existing
+/// mount point hides the filesystem mounted there until this one is
+/// unmounted.
+///
+/// Disk filesystems (`vfat` and `ext4`) are read from the block device
+/// `source`, e.g. `/dev/vdb`. For virtual filesystems (`devtmpfs`, `tmpfs`,
+/// `proc` and `sysfs`), `source` is ignored.
+pub fn mount(source: &str, target: &str, fstype: &str) -> io::Result<()> {
+    mount_with_options(source, target, fstype, "")
+}
+
+/// Mounts a new filesystem like [`mount`], with the comma-separated mount
+/// `options`.
+///
+/// Only tmpfs takes options for now, which are `size=<bytes>` and
+/// `nr_inodes=<number>` to limit its size and number of files, e.g.,
+/// `size=64m,nr_inodes=1k`. Writes beyond the limits fail with
+/// [`StorageFull`](io::Error::StorageFull).
+pub fn mount_with_options(
+    source: &str,
+    target: &str,
+    fstype: &str,
+    options: &str,
+) -> io::Result<()> {
+    let fs = crate::mounts::new_fs(source, fstype, options)?;
+    crate::root::mount(source, target, fstype, fs)
+}
+
+/// Mounts the filesystem `fs` on the directory `target`.
+///
+/// See [`mount`] for more details.
+pub fn mount_fs(target: &str, fs: Arc<dyn VfsOps>) -> io::Result<()> {
+    crate::root::mount("none", target, "none", Arc::new(crate::fs::ForeignFs(fs)))
+}
+
+/// Unmounts the filesystem mounted on `target`.
+///
+/// Returns [`ResourceBusy`](io::Error::ResourceBusy) if there are still files
+/// or directories opened in it, or other filesystems mounted under it.
+pub fn umount(target: &str) -> io::Result<()> {
+    crate::root::umount(target)
+}
+
+/// Writes all buffered data of files and block devices back to the disks,
+/// like `sync(2)`. It is called before the system shuts down.
+pub fn sync() -> io::Result<()> {
+    let cached = crate::cache::write_back_all();
+    crate::dev::flush_all().and(cached)
+}
+
+/// Returns the statistics of the page cache.
+pub fn page_cache_stats() -> CacheStats {
+    crate::cache::stats()
+}
+
+/// Registers `tunable` as the file `/sys/<path>`, e.g., `kernel/log_level`.
+///
+/// The file appears in every sysfs, including ones mounted later. Returns
+/// [`AlreadyExists`](io::Error::AlreadyExists) if `path` is taken by another
+/// tunable.
+pub fn register_tunable(path: &'static str, tunable: Tunable) -> io::Result<()> {
+    crate::tunable::register(path, tunable)
+}
+
+/// Returns the name of the I/O scheduler of the block device `device`, e.g.,
+/// `vda`.
+pub fn io_scheduler(device: &str) -> io::Result<&'static str> {
+    Ok(crate::dev::scheduler(device)?.name())
+}
+
+/// Sets the I/O scheduler of the block device `device` to `noop` or
+/// `elevator`.
+pub fn set_io_scheduler(device: &str, scheduler: &str) -> io::Result<()> {
+    match crate::dev::Scheduler::from_name(scheduler) {
+        Some(scheduler) => crate::dev::set_scheduler(device, scheduler),
+        None => axerrno::ax_err!(InvalidInput, "unknown I/O scheduler"),
+    }
+}
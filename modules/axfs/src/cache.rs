@@ -0,0 +1,641 @@
+//! Page cache of file data.
+//!
+//! Data of regular files on disk filesystems is cached in pages of
+//! [`PAGE_SIZE`] bytes, shared by all opened instances of a file. Writes are
+//! buffered in the cache, and written back when the file is synced or closed,
+//! or when the pages are evicted. Pages are evicted in LRU order when the
+//! cache holds more than `axconfig::PAGE_CACHE_PAGES` pages or the system
+//! runs short of memory. Sequential reads make the cache read ahead in a
+//! growing window.
+//!
+//! Files are identified by their [`NodeKey`]s, so a file reached by different
+//! paths or hard links shares its pages if its filesystem has inode numbers.
+//! The namespace operations in [`crate::root`] tell the cache about removed
+//! files, and about renamed and (un)mounted paths, which change the identities
+//! of files identified by paths.
+//!
+//! The lock of the cache is never held during I/O:
+//!
+//! - Missing pages are read without it, and only inserted if the file has not
+//!   been written back or truncated in the meantime, as they may be stale.
+//! - Dirty pages are written back from a copy. They stay dirty, and thus
+//!   cached, until the write is done, and a lock of each file keeps its
+//!   write-backs in order.
+
+use alloc::{boxed::Box, collections::BTreeMap, sync::Arc, vec, vec::Vec};
+use axerrno::{AxResult, ax_err};
+use axfs_vfs::VfsNodeRef;
+use axsync::Mutex;
+use core::ops::Range;
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use crate::root::NodeKey;
+
+/// Size of a cached page.
+pub const PAGE_SIZE: usize = 4096;
+/// Maximum number of cached pages.
+const MAX_PAGES: usize = axconfig::PAGE_CACHE_PAGES;
+/// Initial size of the readahead window, in pages.
+const MIN_READAHEAD: u64 = 4;
+/// Maximum size of the readahead window, in pages, which is also the most
+/// pages read from the disk at a time.
+const MAX_READAHEAD: u64 = 32;
+
+type FileId = u64;
+type PageKey = (FileId, u64);
+
+struct Page {
+    data: Box<[u8]>,
+    dirty: bool,
+    /// Incremented on every write, to tell whether the page is written again
+    /// while it is being written back.
+    version: u64,
+    /// Position in the LRU list.
+    stamp: u64,
+}
+
+struct CachedFile {
+    key: NodeKey,
+    node: VfsNodeRef,
+    /// Serializes write-backs and truncations of the file.
+    io_lock: Arc<Mutex<()>>,
+    /// Size of the file, including data not written back yet.
+    size: u64,
+    /// Size of the file on the disk.
+    disk_size: u64,
+    /// Incremented whenever the data on the disk changes.
+    generation: u64,
+    /// Number of opened instances.
+    open_count: usize,
+    /// Whether the file can still be reached by its key, i.e., it has not
+    /// been forgotten since it was opened.
+    linked: bool,
+}
+
+/// Statistics of the page cache.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct CacheStats {
+    /// Number of cached pages.
+    pub pages: usize,
+    /// Number of cached pages not written back yet.
+    pub dirty: usize,
+    /// Number of page lookups served by the cache.
+    pub hits: u64,
+    /// Number of page lookups that read the disk.
+    pub misses: u64,
+    /// Number of pages read ahead.
+    pub readahead: u64,
+    /// Number of pages evicted.
+    pub evictions: u64,
+    /// Number of pages written back.
+    pub writebacks: u64,
+}
+
+struct PageCache {
+    files: BTreeMap<FileId, CachedFile>,
+    keys: BTreeMap<NodeKey, FileId>,
+    pages: BTreeMap<PageKey, Page>,
+    /// Keys of pages, ordered by the time they were last used.
+    lru: BTreeMap<u64, PageKey>,
+    next_id: FileId,
+    next_stamp: u64,
+    stats: CacheStats,
+}
+
+static PAGE_CACHE: Mutex<PageCache> = Mutex::new(PageCache::new());
+
+/// Whether the system is short of memory, i.e., less than 1/16 of the pages
+/// are free.
+fn memory_pressure() -> bool {
+    let allocator = axalloc::global_allocator();
+    let available = allocator.available_pages();
+    let total = available + allocator.used_pages();
+    available < total / 16
+}
+
+fn page_range(id: FileId, from: u64) -> core::ops::RangeInclusive<PageKey> {
+    (id, from)..=(id, u64::MAX)
+}
+
+/// Reads the page at `index` of `node`, whose size on the disk is
+/// `disk_size`.
+fn read_page(node: &VfsNodeRef, index: u64, disk_size: u64) -> AxResult<Box<[u8]>> {
+    let offset = index * PAGE_SIZE as u64;
+    let mut data = vec![0u8; PAGE_SIZE].into_boxed_slice();
+    let mut pos = 0;
+    while pos < PAGE_SIZE && offset + (pos as u64) < disk_size {
+        match node.read_at(offset + pos as u64, &mut data[pos..])? {
+            0 => break,
+            n => pos += n,
+        }
+    }
+    Ok(data)
+}
+
+/// Writes the page at `index` back to `node`, whose size is `size`. Returns
+/// the end of the data written.
+fn write_page(node: &VfsNodeRef, index: u64, data: &[u8], size: u64) -> AxResult<u64> {
+    let offset = index * PAGE_SIZE as u64;
+    if offset >= size {
+        return Ok(0); // truncated
+    }
+    let len = PAGE_SIZE.min((size - offset) as usize);
+    let mut pos = 0;
+    while pos < len {
+        match node.write_at(offset + pos as u64, &data[pos..len])? {
+            0 => return ax_err!(WriteZero),
+            n => pos += n,
+        }
+    }
+    Ok(offset + len as u64)
+}
+
+impl PageCache {
+    const fn new() -> Self {
+        Self {
+            files: BTreeMap::new(),
+            keys: BTreeMap::new(),
+            pages: BTreeMap::new(),
+            lru: BTreeMap::new(),
+            next_id: 0,
+            next_stamp: 0,
+            stats: CacheStats {
+                pages: 0,
+                dirty: 0,
+                hits: 0,
+                misses: 0,
+                readahead: 0,
+                evictions: 0,
+                writebacks: 0,
+            },
+        }
+    }
+
+    fn insert_page(&mut self, key: PageKey, data: Box<[u8]>) {
+        self.next_stamp += 1;
+        self.lru.insert(self.next_stamp, key);
+        let page = Page {
+            data,
+            dirty: false,
+            version: 0,
+            stamp: self.next_stamp,
+        };
+        if let Some(old) = self.pages.insert(key, page) {
+            self.lru.remove(&old.stamp);
+        }
+    }
+
+    /// Returns the page at `key` if it is cached, and marks it as the most
+    /// recently used.
+    fn touch_page(&mut self, key: PageKey) -> Option<&mut Page> {
+        let page = self.pages.get_mut(&key)?;
+        self.next_stamp += 1;
+        self.lru.remove(&page.stamp);
+        page.stamp = self.next_stamp;
+        self.lru.insert(page.stamp, key);
+        Some(page)
+    }
+
+    /// Drops the pages of file `id` from `index` on, without writing them
+    /// back.
+    fn remove_pages(&mut self, id: FileId, index: u64) {
+        let keys: Vec<_> = self
+            .pages
+            .range(page_range(id, index))
+            .map(|(k, _)| *k)
+            .collect();
+        for key in keys {
+            let page = self.pages.remove(&key).unwrap();
+            self.lru.remove(&page.stamp);
+        }
+    }
+
+    /// Forgets file `id` if it is not opened, and either has no cached pages
+    /// or cannot be reached by its key anymore.
+    fn release_if_unused(&mut self, id: FileId) {
+        let Some(file) = self.files.get(&id) else {
+            return;
+        };
+        if file.open_count > 0 {
+            return;
+        }
+        if file.linked && self.pages.range(page_range(id, 0)).next().is_some() {
+            return;
+        }
+        self.remove_pages(id, 0);
+        let file = self.files.remove(&id).unwrap();
+        if self.keys.get(&file.key) == Some(&id) {
+            self.keys.remove(&file.key);
+        }
+    }
+
+    /// Evicts clean pages in LRU order until the cache is within its capacity
+    /// and the system is not short of memory.
+    ///
+    /// Returns the file of the first dirty page on the way, which has to be
+    /// written back before it is evicted.
+    fn evict(&mut self) -> Option<FileId> {
+        while let Some((&stamp, &key)) = self.lru.first_key_value() {
+            if self.pages.len() <= MAX_PAGES && !memory_pressure() {
+                break;
+            }
+            if self.pages[&key].dirty {
+                return Some(key.0);
+            }
+            self.lru.remove(&stamp);
+            self.pages.remove(&key);
+            self.stats.evictions += 1;
+            self.release_if_unused(key.0);
+        }
+        None
+    }
+}
+
+/// Reads the pages of file `id` in `indices` that are not cached into the
+/// cache, either on a miss or for `readahead`.
+///
+/// Nothing is inserted if the data on the disk changes while reading, in
+/// which case the caller looks up the pages again.
+fn load_pages(id: FileId, indices: Range<u64>, readahead: bool) -> AxResult {
+    let (node, disk_size, generation, missing) = {
+        let cache = PAGE_CACHE.lock();
+        let Some(file) = cache.files.get(&id) else {
+            return Ok(());
+        };
+        let missing: Vec<_> = indices
+            .filter(|&index| !cache.pages.contains_key(&(id, index)))
+            .collect();
+        (file.node.clone(), file.disk_size, file.generation, missing)
+    };
+    let mut loaded = Vec::with_capacity(missing.len());
+    for index in missing {
+        loaded.push((index, read_page(&node, index, disk_size)?));
+    }
+
+    let mut cache = PAGE_CACHE.lock();
+    if cache
+        .files
+        .get(&id)
+        .is_none_or(|f| f.generation != generation)
+    {
+        return Ok(());
+    }
+    for (index, data) in loaded {
+        if cache.pages.contains_key(&(id, index)) {
+            continue; // written meanwhile
+        }
+        cache.insert_page((id, index), data);
+        if readahead {
+            cache.stats.readahead += 1;
+        } else {
+            cache.stats.misses += 1;
+        }
+    }
+    Ok(())
+}
+
+/// Writes the dirty pages of file `id` back to the disk.
+fn write_back_file(id: FileId) -> AxResult {
+    let (node, io_lock) = {
+        let cache = PAGE_CACHE.lock();
+        let Some(file) = cache.files.get(&id) else {
+            return Ok(());
+        };
+        (file.node.clone(), file.io_lock.clone())
+    };
+    let _io = io_lock.lock();
+    let (size, dirty) = {
+        let cache = PAGE_CACHE.lock();
+        let Some(file) = cache.files.get(&id) else {
+            return Ok(());
+        };
+        let dirty: Vec<_> = cache
+            .pages
+            .range(page_range(id, 0))
+            .filter(|(_, page)| page.dirty)
+            .map(|(&(_, index), page)| (index, page.version, page.data.clone()))
+            .collect();
+        (file.size, dirty)
+    };
+    if dirty.is_empty() {
+        return Ok(());
+    }
+
+    let mut result = Ok(());
+    let mut written = Vec::with_capacity(dirty.len());
+    let mut end = 0;
+    for (index, version, data) in dirty {
+        match write_page(&node, index, &data, size) {
+            Ok(page_end) => {
+                written.push((index, version));
+                end = end.max(page_end);
+            }
+            Err(e) => {
+                result = Err(e);
+                break;
+            }
+        }
+    }
+
+    let mut cache = PAGE_CACHE.lock();
+    if let Some(file) = cache.files.get_mut(&id) {
+        file.disk_size = file.disk_size.max(end);
+        file.generation += 1;
+    }
+    for (index, version) in written {
+        // the page stays dirty if it is written again meanwhile
+        let page = cache.pages.get_mut(&(id, index));
+        if let Some(page) = page.filter(|page| page.version == version) {
+            page.dirty = false;
+        }
+        cache.stats.writebacks += 1;
+    }
+    result
+}
+
+/// Evicts the least recently used pages until the cache is within its
+/// capacity and the system is not short of memory.
+fn shrink() {
+    loop {
+        let Some(id) = PAGE_CACHE.lock().evict() else {
+            return;
+        };
+        if let Err(e) = write_back_file(id) {
+            warn!("failed to write back evicted pages: {:?}", e);
+            return;
+        }
+    }
+}
+
+/// An opened instance of a cached file.
+///
+/// The dirty pages of the file are written back when it is dropped.
+pub struct CacheHandle {
+    id: FileId,
+    /// Offset where the next sequential read starts.
+    next_read: AtomicU64,
+    /// Size of the readahead window in pages, zero for random reads.
+    window: AtomicU64,
+}
+
+/// Opens the file identified by `key` in the cache, whose node is `node`.
+pub fn open(key: NodeKey, node: &VfsNodeRef) -> AxResult<CacheHandle> {
+    let size = node.get_attr()?.size();
+    let mut cache = PAGE_CACHE.lock();
+    let id = match cache.keys.get(&key) {
+        Some(&id) => id,
+        None => {
+            let id = cache.next_id;
+            cache.next_id += 1;
+            cache.keys.insert(key.clone(), id);
+            cache.files.insert(
+                id,
+                CachedFile {
+                    key,
+                    node: node.clone(),
+                    io_lock: Arc::new(Mutex::new(())),
+                    size,
+                    disk_size: size,
+                    generation: 0,
+                    open_count: 0,
+                    linked: true,
+                },
+            );
+            id
+        }
+    };
+    cache.files.get_mut(&id).unwrap().open_count += 1;
+    Ok(CacheHandle {
+        id,
+        next_read: AtomicU64::new(0),
+        window: AtomicU64::new(0),
+    })
+}
+
+/// Returns the size of the file identified by `key` if it is cached, which
+/// may differ from the size on the disk.
+pub fn file_size(key: &NodeKey) -> Option<u64> {
+    let cache = PAGE_CACHE.lock();
+    let id = cache.keys.get(key)?;
+    Some(cache.files[id].size)
+}
+
+/// Writes the dirty pages of the file identified by `key` back to the disk,
+/// if it is cached.
+pub fn write_back(key: &NodeKey) -> AxResult {
+    let id = PAGE_CACHE.lock().keys.get(key).copied();
+    id.map_or(Ok(()), write_back_file)
+}
+
+/// Writes the dirty pages of all cached files back to the disk.
+pub fn write_back_all() -> AxResult {
+    let ids: Vec<_> = PAGE_CACHE.lock().files.keys().copied().collect();
+    let mut result = Ok(());
+    for id in ids {
+        if let Err(e) = write_back_file(id) {
+            result = Err(e);
+        }
+    }
+    result
+}
+
+/// Forgets the files whose keys match `pred`, so that they are looked up
+/// again when reopened.
+///
+/// Their dirty pages are written back, unless `discard` is set, e.g., when
+/// they are removed. Files still opened keep their cached pages until closed.
+pub fn forget(pred: impl Fn(&NodeKey) -> bool, discard: bool) -> AxResult {
+    let ids: Vec<_> = {
+        let mut cache = PAGE_CACHE.lock();
+        let ids: Vec<_> = cache
+            .keys
+            .iter()
+            .filter(|(key, _)| pred(key))
+            .map(|(_, &id)| id)
+            .collect();
+        cache.keys.retain(|key, _| !pred(key));
+        for id in &ids {
+            cache.files.get_mut(id).unwrap().linked = false;
+        }
+        ids
+    };
+    let mut result = Ok(());
+    for id in ids {
+        if discard {
+            let mut cache = PAGE_CACHE.lock();
+            for (_, page) in cache.pages.range_mut(page_range(id, 0)) {
+                page.dirty = false;
+            }
+        } else if let Err(e) = write_back_file(id) {
+            result = result.and(Err(e));
+        }
+        PAGE_CACHE.lock().release_if_unused(id);
+    }
+    result
+}
+
+/// Returns the statistics of the page cache.
+pub fn stats() -> CacheStats {
+    let cache = PAGE_CACHE.lock();
+    CacheStats {
+        pages: cache.pages.len(),
+        dirty: cache.pages.values().filter(|p| p.dirty).count(),
+        ..cache.stats
+    }
+}
+
+impl CacheHandle {
+    /// Returns the size of the file, including data not written back yet.
+    pub fn size(&self) -> u64 {
+        PAGE_CACHE.lock().files[&self.id].size
+    }
+
+    /// Opens the cached file again, even if it has been forgotten, e.g., as it
+    /// was removed.
+    pub(crate) fn reopen(&self) -> Self {
+        let mut cache = PAGE_CACHE.lock();
+        cache.files.get_mut(&self.id).unwrap().open_count += 1;
+        Self {
+            id: self.id,
+            next_read: AtomicU64::new(0),
+            window: AtomicU64::new(0),
+        }
+    }
+
+    /// Updates the readahead state with a read of `offset..end`, and returns
+    /// the number of pages to read ahead.
+    fn update_readahead(&self, offset: u64, end: u64) -> u64 {
+        let sequential = self.next_read.swap(end, Ordering::Relaxed) == offset;
+        let window = if sequential {
+            (self.window.load(Ordering::Relaxed) * 2).clamp(MIN_READAHEAD, MAX_READAHEAD)
+        } else {
+            0
+        };
+        self.window.store(window, Ordering::Relaxed);
+        window
+    }
+
+    /// Reads the file at `offset` through the cache. Returns the number of
+    /// bytes read.
+    pub fn read_at(&self, offset: u64, buf: &mut [u8]) -> AxResult<usize> {
+        let size = self.size();
+        if offset >= size || buf.is_empty() {
+            return Ok(0);
+        }
+        let end = size.min(offset + buf.len() as u64);
+        let mut pos = offset;
+        while pos < end {
+            let mut cache = PAGE_CACHE.lock();
+            // copy from the cached pages, until a page is missing
+            while pos < end {
+                let page_off = (pos % PAGE_SIZE as u64) as usize;
+                let len = (PAGE_SIZE - page_off).min((end - pos) as usize);
+                let Some(page) = cache.touch_page((self.id, pos / PAGE_SIZE as u64)) else {
+                    break;
+                };
+                let start = (pos - offset) as usize;
+                buf[start..start + len].copy_from_slice(&page.data[page_off..page_off + len]);
+                cache.stats.hits += 1;
+                pos += len as u64;
+            }
+            drop(cache);
+            if pos < end {
+                let index = pos / PAGE_SIZE as u64;
+                let last = (end - 1) / PAGE_SIZE as u64;
+                load_pages(self.id, index..(last + 1).min(index + MAX_READAHEAD), false)?;
+            }
+        }
+
+        let window = self.update_readahead(offset, end);
+        if window > 0 && !memory_pressure() {
+            let index = end.div_ceil(PAGE_SIZE as u64);
+            let num_pages = self.size().div_ceil(PAGE_SIZE as u64);
+            // readahead is only a hint, errors are left to the next read
+            load_pages(self.id, index..num_pages.min(index + window), true).ok();
+        }
+        shrink();
+        Ok((end - offset) as usize)
+    }
+
+    /// Writes the file at `offset` into the cache. Returns the number of
+    /// bytes written.
+    pub fn write_at(&self, offset: u64, buf: &[u8]) -> AxResult<usize> {
+        let Some(end) = offset.checked_add(buf.len() as u64) else {
+            return ax_err!(InvalidInput);
+        };
+        let mut pos = offset;
+        while pos < end {
+            let mut cache = PAGE_CACHE.lock();
+            // write to the cached pages, until a page is missing
+            while pos < end {
+                let key = (self.id, pos / PAGE_SIZE as u64);
+                let page_off = (pos % PAGE_SIZE as u64) as usize;
+                let len = (PAGE_SIZE - page_off).min((end - pos) as usize);
+                if cache.pages.contains_key(&key) {
+                    cache.stats.hits += 1;
+                } else if len == PAGE_SIZE {
+                    // overwritten entirely, so it is not read
+                    cache.insert_page(key, vec![0u8; PAGE_SIZE].into_boxed_slice());
+                } else {
+                    break;
+                }
+                let page = cache.touch_page(key).unwrap();
+                let start = (pos - offset) as usize;
+                page.data[page_off..page_off + len].copy_from_slice(&buf[start..start + len]);
+                page.dirty = true;
+                page.version += 1;
+                pos += len as u64;
+                let file = cache.files.get_mut(&self.id).unwrap();
+                file.size = file.size.max(pos);
+            }
+            drop(cache);
+            if pos < end {
+                let index = pos / PAGE_SIZE as u64;
+                load_pages(self.id, index..index + 1, false)?;
+            }
+        }
+        shrink();
+        Ok(buf.len())
+    }
+
+    /// Truncates the file to `size`, both in the cache and on the disk.
+    pub fn truncate(&self, size: u64) -> AxResult {
+        let (node, io_lock) = {
+            let cache = PAGE_CACHE.lock();
+            let file = &cache.files[&self.id];
+            (file.node.clone(), file.io_lock.clone())
+        };
+        let _io = io_lock.lock();
+        node.truncate(size)?;
+
+        let mut cache = PAGE_CACHE.lock();
+        let file = cache.files.get_mut(&self.id).unwrap();
+        file.size = size;
+        file.disk_size = size;
+        file.generation += 1;
+        cache.remove_pages(self.id, size.div_ceil(PAGE_SIZE as u64));
+        // the page holding the new end of file, if it is not aligned
+        if let Some(page) = cache.pages.get_mut(&(self.id, size / PAGE_SIZE as u64)) {
+            page.data[(size % PAGE_SIZE as u64) as usize..].fill(0);
+        }
+        Ok(())
+    }
+
+    /// Writes the dirty pages of the file back, and syncs it on the disk.
+    pub fn sync(&self) -> AxResult {
+        write_back_file(self.id)?;
+        let node = PAGE_CACHE.lock().files[&self.id].node.clone();
+        node.fsync()
+    }
+}
+
+impl Drop for CacheHandle {
+    fn drop(&mut self) {
+        if let Err(e) = write_back_file(self.id) {
+            warn!("failed to write back a closed file: {:?}", e);
+        }
+        let mut cache = PAGE_CACHE.lock();
+        cache.files.get_mut(&self.id).unwrap().open_count -= 1;
+        cache.release_if_unused(self.id);
+    }
+}
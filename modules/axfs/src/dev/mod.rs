@@ -0,0 +1,376 @@
+mod queue;
+
+use alloc::{format, string::String, sync::Arc, vec::Vec};
+use axdriver::prelude::*;
+use axerrno::{AxError, AxResult, ax_err};
+use lazyinit::LazyInit;
+
+use crate::{partition, tunable::Tunable};
+
+pub use self::queue::{BlockQueue, Scheduler};
+
+pub(crate) const BLOCK_SIZE: usize = 512;
+
+/// A registered block device or partition.
+struct BlockDeviceEntry {
+    /// The device name, e.g., `vda` or `vda1`.
+    name: &'static str,
+    /// The partition UUID, if this is a partition.
+    part_uuid: Option<String>,
+    /// The blocks of the device or partition, with the cursor at 0.
+    disk: Disk,
+}
+
+impl BlockDeviceEntry {
+    const fn is_partition(&self) -> bool {
+        self.part_uuid.is_some()
+    }
+}
+
+/// Block devices found at boot, named `vda`, `vdb`, ... in order, each
+/// followed by its partitions `vda1`, `vda2`, ...
+static BLOCK_DEVICES: LazyInit<Vec<BlockDeviceEntry>> = LazyInit::new();
+
+/// A disk device with a cursor.
+///
+/// A disk covers either a whole block device or a range of its blocks (a
+/// partition). Positions are relative to the start of the range. All disks on
+/// a block device share its [`BlockQueue`].
+#[derive(Clone)]
+pub struct Disk {
+    block_id: u64,
+    offset: usize,
+    start_block: u64,
+    num_blocks: u64,
+    dev: Arc<BlockQueue>,
+}
+
+impl Disk {
+    /// Create a new disk.
+    pub fn new(dev: AxBlockDevice) -> Self {
+        let dev = Arc::new(BlockQueue::new(dev));
+        Self {
+            block_id: 0,
+            offset: 0,
+            start_block: 0,
+            num_blocks: dev.num_blocks(),
+            dev,
+        }
+    }
+
+    /// Create a new disk on `num_blocks` blocks of this disk, starting from
+    /// block `start_block`.
+    pub(crate) fn slice(&self, start_block: u64, num_blocks: u64) -> Self {
+        assert!(start_block + num_blocks <= self.num_blocks);
+        Self {
+            block_id: 0,
+            offset: 0,
+            start_block: self.start_block + start_block,
+            num_blocks,
+            dev: self.dev.clone(),
+        }
+    }
+
+    /// Get the number of blocks of the disk.
+    pub fn num_blocks(&self) -> u64 {
+        self.num_blocks
+    }
+
+    /// Get the size of the disk.
+    pub fn size(&self) -> u64 {
+        self.num_blocks * BLOCK_SIZE as u64
+    }
+
+    /// Checks that `buf` holds whole blocks from `block_id` within the disk.
+    fn check_blocks(&self, block_id: u64, buf: &[u8]) -> DevResult {
+        let num_blocks = (buf.len() / BLOCK_SIZE) as u64;
+        if buf.len() % BLOCK_SIZE != 0 || block_id + num_blocks > self.num_blocks {
+            return Err(DevError::InvalidParam);
+        }
+        Ok(())
+    }
+
+    /// Read blocks from the block `block_id` of the disk. The length of `buf`
+    /// must be a multiple of the block size.
+    pub(crate) fn read_block(&self, block_id: u64, buf: &mut [u8]) -> DevResult {
+        self.check_blocks(block_id, buf)?;
+        self.dev.read(self.start_block + block_id, buf)
+    }
+
+    /// Write blocks from the block `block_id` of the disk. The length of `buf`
+    /// must be a multiple of the block size.
+    pub(crate) fn write_block(&self, block_id: u64, buf: &[u8]) -> DevResult {
+        self.check_blocks(block_id, buf)?;
+        self.dev.write(self.start_block + block_id, buf)
+    }
+
+    /// Get the position of the cursor.
+    pub fn position(&self) -> u64 {
+        self.block_id * BLOCK_SIZE as u64 + self.offset as u64
+    }
+
+    /// Set the position of the cursor.
+    pub fn set_position(&mut self, pos: u64) {
+        self.block_id = pos / BLOCK_SIZE as u64;
+        self.offset = pos as usize % BLOCK_SIZE;
+    }
+
+    /// Returns the number of bytes from the cursor to the end of the disk,
+    /// up to `len`.
+    fn remaining(&self, len: usize) -> usize {
+        let remaining = self.size().saturating_sub(self.position());
+        (len as u64).min(remaining) as usize
+    }
+
+    /// Advances the cursor by `len` bytes.
+    fn advance(&mut self, len: usize) {
+        self.set_position(self.position() + len as u64);
+    }
+
+    /// Read from the cursor, returns the number of bytes read, which is less
+    /// than `buf.len()` only at the end of the disk.
+    ///
+    /// Whole blocks are read in one request.
+    pub fn read_data(&mut self, buf: &mut [u8]) -> DevResult<usize> {
+        let len = self.remaining(buf.len());
+        let mut pos = 0;
+        while pos < len {
+            let count = if self.offset == 0 && len - pos >= BLOCK_SIZE {
+                // whole blocks
+                let count = (len - pos) / BLOCK_SIZE * BLOCK_SIZE;
+                self.read_block(self.block_id, &mut buf[pos..pos + count])?;
+                count
+            } else {
+                // partial block
+                let mut data = [0u8; BLOCK_SIZE];
+                let start = self.offset;
+                let count = (len - pos).min(BLOCK_SIZE - start);
+                self.read_block(self.block_id, &mut data)?;
+                buf[pos..pos + count].copy_from_slice(&data[start..start + count]);
+                count
+            };
+            self.advance(count);
+            pos += count;
+        }
+        Ok(len)
+    }
+
+    /// Write from the cursor, returns the number of bytes written, which is
+    /// less than `buf.len()` only at the end of the disk.
+    ///
+    /// Whole blocks are written in one request, and partial blocks are read,
+    /// modified and written back.
+    pub fn write_data(&mut self, buf: &[u8]) -> DevResult<usize> {
+        let len = self.remaining(buf.len());
+        let mut pos = 0;
+        while pos < len {
+            let count = if self.offset == 0 && len - pos >= BLOCK_SIZE {
+                // whole blocks
+                let count = (len - pos) / BLOCK_SIZE * BLOCK_SIZE;
+                self.write_block(self.block_id, &buf[pos..pos + count])?;
+                count
+            } else {
+                // partial block
+                let mut data = [0u8; BLOCK_SIZE];
+                let start = self.offset;
+                let count = (len - pos).min(BLOCK_SIZE - start);
+                self.read_block(self.block_id, &mut data)?;
+                data[start..start + count].copy_from_slice(&buf[pos..pos + count]);
+                self.write_block(self.block_id, &data)?;
+                count
+            };
+            self.advance(count);
+            pos += count;
+        }
+        Ok(len)
+    }
+
+    /// Read within one block, returns the number of bytes read.
+    pub fn read_one(&mut self, buf: &mut [u8]) -> DevResult<usize> {
+        let len = buf.len().min(BLOCK_SIZE - self.offset);
+        self.read_data(&mut buf[..len])
+    }
+
+    /// Write within one block, returns the number of bytes written.
+    pub fn write_one(&mut self, buf: &[u8]) -> DevResult<usize> {
+        let len = buf.len().min(BLOCK_SIZE - self.offset);
+        self.write_data(&buf[..len])
+    }
+
+    /// Read a single block starting from the specified offset.
+    #[allow(unused)]
+    pub fn read_offset(&mut self, offset: usize) -> [u8; BLOCK_SIZE] {
+        let block_id = offset / BLOCK_SIZE;
+        let mut block_data = [0u8; BLOCK_SIZE];
+        self.read_block(block_id as u64, &mut block_data).unwrap();
+        block_data
+    }
+
+    /// Write single block starting from the specified offset.
+    #[allow(unused)]
+    pub fn write_offset(&mut self, offset: usize, buf: &[u8]) -> DevResult<usize> {
+        assert!(
+            buf.len() == BLOCK_SIZE,
+            "Buffer length must be equal to BLOCK_SIZE"
+        );
+        assert!(offset % BLOCK_SIZE == 0);
+        let block_id = offset / BLOCK_SIZE;
+        self.write_block(block_id as u64, buf).unwrap();
+        Ok(buf.len())
+    }
+
+    /// Flush the buffered data of the underlying device.
+    pub fn flush(&mut self) -> DevResult {
+        self.dev.flush()
+    }
+}
+
+/// Returns the name of the `idx`-th block device, i.e., `vda`, ..., `vdz`,
+/// `vdaa`, ...
+fn block_device_name(idx: usize) -> String {
+    let mut suffix = Vec::new();
+    let mut n = idx + 1;
+    while n > 0 {
+        n -= 1;
+        suffix.push(b'a' + (n % 26) as u8);
+        n /= 26;
+    }
+    suffix.reverse();
+    String::from("vd") + core::str::from_utf8(&suffix).unwrap()
+}
+
+/// Registers all block devices found at boot, along with their partitions.
+pub(crate) fn init_block_devices(devs: Vec<AxBlockDevice>) {
+    let mut entries = Vec::new();
+    for (i, dev) in devs.into_iter().enumerate() {
+        let name = block_device_name(i);
+        info!("  block device {}: {:?}", name, dev.device_name());
+        let disk = Disk::new(dev);
+        let parts = partition::parse(&disk);
+        entries.push(BlockDeviceEntry {
+            name: name.clone().leak(),
+            part_uuid: None,
+            disk: disk.clone(),
+        });
+        for part in parts {
+            let part_name = format!("{}{}", name, part.number);
+            info!(
+                "    partition {}: start {}, {} blocks, PARTUUID={}",
+                part_name, part.start_block, part.num_blocks, part.uuid
+            );
+            entries.push(BlockDeviceEntry {
+                name: part_name.leak(),
+                part_uuid: Some(part.uuid),
+                disk: disk.slice(part.start_block, part.num_blocks),
+            });
+        }
+    }
+    BLOCK_DEVICES.init_once(entries);
+
+    for e in BLOCK_DEVICES.iter().filter(|e| !e.is_partition()) {
+        let queue = &e.disk.dev;
+        let tunable = Tunable::choice(
+            &["noop", "elevator"],
+            move || queue.scheduler().name(),
+            move |name| queue.set_scheduler(Scheduler::from_name(name).unwrap()),
+        );
+        let path = format!("block/{}/queue/scheduler", e.name).leak();
+        if let Err(err) = crate::tunable::register(path, tunable) {
+            warn!("failed to register {}: {:?}", path, err);
+        }
+    }
+}
+
+/// Returns the names and disks of all registered block devices and
+/// partitions.
+pub(crate) fn block_devices() -> impl Iterator<Item = (&'static str, Disk)> {
+    BLOCK_DEVICES.iter().map(|e| (e.name, e.disk.clone()))
+}
+
+fn find_block_device(name: &str) -> Option<&'static BlockDeviceEntry> {
+    if let Some(uuid) = name.strip_prefix("PARTUUID=") {
+        BLOCK_DEVICES.iter().find(|e| {
+            e.part_uuid
+                .as_deref()
+                .is_some_and(|u| u.eq_ignore_ascii_case(uuid))
+        })
+    } else {
+        let name = name.strip_prefix("/dev/").unwrap_or(name);
+        BLOCK_DEVICES.iter().find(|e| e.name == name)
+    }
+}
+
+/// Opens the block device or partition with the given name, e.g., `vda`,
+/// `/dev/vda1`, or `PARTUUID=<uuid>`.
+pub(crate) fn open_block_device(name: &str) -> AxResult<Disk> {
+    match find_block_device(name) {
+        Some(e) => Ok(e.disk.clone()),
+        None => ax_err!(NotFound, "no such block device"),
+    }
+}
+
+/// Selects the disk holding the root filesystem, and returns it with its name.
+///
+/// `spec` is a device name like `/dev/vda2`, a partition number of the first
+/// device like `2`, or `PARTUUID=<uuid>`. The whole first device is used if
+/// `spec` is empty.
+#[cfg(any(not(feature = "initramfs"), feature = "myfs"))]
+pub(crate) fn root_device(spec: &str) -> AxResult<(&'static str, Disk)> {
+    let Some(first) = BLOCK_DEVICES.first() else {
+        return ax_err!(NotFound, "no block device found");
+    };
+    let entry = if spec.is_empty() {
+        Some(first)
+    } else if spec.bytes().all(|b| b.is_ascii_digit()) {
+        find_block_device(&format!("{}{}", first.name, spec))
+    } else {
+        find_block_device(spec)
+    };
+    match entry {
+        Some(e) => Ok((e.name, e.disk.clone())),
+        None => ax_err!(NotFound, "root device not found"),
+    }
+}
+
+/// Writes the buffered blocks of the block device or partition `name` back,
+/// if there is such a device. Other sources of filesystems are ignored.
+pub(crate) fn flush_block_device(name: &str) -> AxResult {
+    match find_block_device(name) {
+        Some(e) => e.disk.dev.flush().map_err(|_| AxError::Io),
+        None => Ok(()),
+    }
+}
+
+/// Writes the buffered blocks of all block devices back.
+pub(crate) fn flush_all() -> AxResult {
+    if !BLOCK_DEVICES.is_inited() {
+        return Ok(());
+    }
+    let mut result = Ok(());
+    for e in BLOCK_DEVICES.iter().filter(|e| !e.is_partition()) {
+        if let Err(err) = e.disk.dev.flush() {
+            warn!("failed to flush {}: {:?}", e.name, err);
+            result = Err(AxError::Io);
+        }
+    }
+    result
+}
+
+/// Returns the request queue of the whole block device `name`, e.g., `vda`.
+fn block_queue(name: &str) -> AxResult<&'static Arc<BlockQueue>> {
+    match find_block_device(name) {
+        Some(e) if !e.is_partition() => Ok(&e.disk.dev),
+        _ => ax_err!(NotFound, "no such block device"),
+    }
+}
+
+/// Returns the I/O scheduler of the block device `name`.
+pub(crate) fn scheduler(name: &str) -> AxResult<Scheduler> {
+    Ok(block_queue(name)?.scheduler())
+}
+
+/// Sets the I/O scheduler of the block device `name`.
+pub(crate) fn set_scheduler(name: &str, scheduler: Scheduler) -> AxResult {
+    block_queue(name)?.set_scheduler(scheduler);
+    Ok(())
+}
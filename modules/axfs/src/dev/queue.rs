@@ -0,0 +1,335 @@
+//! Block request queues.
+//!
+//! Every block device is accessed through a [`BlockQueue`], shared by the
+//! disks and partitions on it. The queue keeps recently used blocks in a
+//! small buffer cache, and buffers writes as dirty blocks until the device is
+//! flushed, e.g., by an unmount or [`crate::api::sync`], too many blocks are
+//! dirty, or the queue is dropped. Requests to the driver are ordered by
+//! the [`Scheduler`] of the queue, and adjacent blocks are merged into
+//! multi-block requests.
+
+use alloc::{boxed::Box, collections::BTreeMap, vec, vec::Vec};
+use axdriver::prelude::*;
+use axsync::Mutex;
+
+use super::BLOCK_SIZE;
+
+/// Maximum number of blocks in the buffer cache.
+const CACHE_BLOCKS: usize = 256;
+/// Number of dirty blocks that triggers a write-back.
+const DIRTY_LIMIT: usize = 128;
+/// Requests of more blocks than this bypass the buffer cache.
+const BYPASS_BLOCKS: usize = 64;
+/// Maximum number of blocks in one request to the driver.
+const MAX_REQUEST_BLOCKS: usize = 256;
+
+/// The policy to order requests to the driver.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scheduler {
+    /// Requests are dispatched in the order they are made, and only merged
+    /// with the previous one.
+    Noop,
+    /// Requests are sorted by block and dispatched in one sweep from the
+    /// current head position, wrapping around to the lowest block (C-LOOK).
+    Elevator,
+}
+
+impl Scheduler {
+    /// Returns the scheduler with the given name, `noop` or `elevator`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "noop" => Some(Self::Noop),
+            "elevator" => Some(Self::Elevator),
+            _ => None,
+        }
+    }
+
+    /// Returns the name of the scheduler.
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Noop => "noop",
+            Self::Elevator => "elevator",
+        }
+    }
+}
+
+/// A request to the driver for `num_blocks` blocks from `block_id`.
+#[derive(Clone, Copy)]
+struct Request {
+    block_id: u64,
+    num_blocks: usize,
+}
+
+impl Request {
+    const fn one(block_id: u64) -> Self {
+        Self {
+            block_id,
+            num_blocks: 1,
+        }
+    }
+
+    const fn end(&self) -> u64 {
+        self.block_id + self.num_blocks as u64
+    }
+}
+
+struct Buffer {
+    data: Box<[u8]>,
+    /// The order in which the block became dirty, or `None` if it is clean.
+    dirty_seq: Option<u64>,
+    /// Position in the LRU list.
+    stamp: u64,
+}
+
+struct QueueInner {
+    dev: AxBlockDevice,
+    scheduler: Scheduler,
+    buffers: BTreeMap<u64, Buffer>,
+    /// Blocks in the buffer cache, ordered by the time they were last used.
+    lru: BTreeMap<u64, u64>,
+    num_dirty: usize,
+    next_stamp: u64,
+    /// The block after the last request, where the elevator goes on from.
+    head: u64,
+}
+
+/// The request queue of a block device.
+pub struct BlockQueue {
+    inner: Mutex<QueueInner>,
+    num_blocks: u64,
+}
+
+impl QueueInner {
+    fn next_stamp(&mut self) -> u64 {
+        self.next_stamp += 1;
+        self.next_stamp
+    }
+
+    /// Orders `reqs` by the scheduler, and merges adjacent ones.
+    fn schedule(&self, mut reqs: Vec<Request>) -> Vec<Request> {
+        if self.scheduler == Scheduler::Elevator {
+            reqs.sort_unstable_by_key(|r| (r.block_id < self.head, r.block_id));
+        }
+        let mut merged: Vec<Request> = Vec::with_capacity(reqs.len());
+        for req in reqs {
+            match merged.last_mut() {
+                Some(last)
+                    if last.end() == req.block_id
+                        && last.num_blocks + req.num_blocks <= MAX_REQUEST_BLOCKS =>
+                {
+                    last.num_blocks += req.num_blocks;
+                }
+                _ => merged.push(req),
+            }
+        }
+        merged
+    }
+
+    /// Returns the cached data of `block_id`, and marks it as the most
+    /// recently used.
+    fn lookup(&mut self, block_id: u64) -> Option<&mut Buffer> {
+        let stamp = self.next_stamp();
+        let buf = self.buffers.get_mut(&block_id)?;
+        self.lru.remove(&buf.stamp);
+        buf.stamp = stamp;
+        self.lru.insert(stamp, block_id);
+        Some(buf)
+    }
+
+    /// Puts `data` of `block_id` into the buffer cache.
+    fn insert(&mut self, block_id: u64, data: &[u8], dirty: bool) {
+        let stamp = self.next_stamp();
+        let dirty_seq = dirty.then_some(stamp);
+        if let Some(buf) = self.lookup(block_id) {
+            buf.data.copy_from_slice(data);
+            if dirty && buf.dirty_seq.is_none() {
+                buf.dirty_seq = dirty_seq;
+                self.num_dirty += 1;
+            }
+            return;
+        }
+        let buf = Buffer {
+            data: data.into(),
+            dirty_seq,
+            stamp,
+        };
+        self.buffers.insert(block_id, buf);
+        self.lru.insert(stamp, block_id);
+        if dirty {
+            self.num_dirty += 1;
+        }
+    }
+
+    /// Drops the cached blocks in `block_id..block_id + num_blocks`, without
+    /// writing them back.
+    fn invalidate(&mut self, block_id: u64, num_blocks: usize) {
+        let ids: Vec<_> = self
+            .buffers
+            .range(block_id..block_id + num_blocks as u64)
+            .map(|(&id, _)| id)
+            .collect();
+        for id in ids {
+            let buf = self.buffers.remove(&id).unwrap();
+            self.lru.remove(&buf.stamp);
+            if buf.dirty_seq.is_some() {
+                self.num_dirty -= 1;
+            }
+        }
+    }
+
+    // The driver assumes a linear mapping between virtual and physical
+    // addresses, which is only present in the kernel address space, so buffers
+    // passed to it must be allocated by the kernel.
+
+    /// Reads `buf.len() / BLOCK_SIZE` blocks from `block_id` from the driver.
+    fn dispatch_read(&mut self, block_id: u64, buf: &mut [u8]) -> DevResult {
+        self.dev.read_block(block_id, buf)?;
+        self.head = block_id + (buf.len() / BLOCK_SIZE) as u64;
+        Ok(())
+    }
+
+    /// Writes `buf.len() / BLOCK_SIZE` blocks from `block_id` to the driver.
+    fn dispatch_write(&mut self, block_id: u64, buf: &[u8]) -> DevResult {
+        self.dev.write_block(block_id, buf)?;
+        self.head = block_id + (buf.len() / BLOCK_SIZE) as u64;
+        Ok(())
+    }
+
+    /// Writes all dirty blocks back to the device.
+    fn write_back(&mut self) -> DevResult {
+        let mut dirty: Vec<_> = self
+            .buffers
+            .iter()
+            .filter_map(|(&id, buf)| Some((buf.dirty_seq?, id)))
+            .collect();
+        dirty.sort_unstable();
+        let reqs = self.schedule(dirty.into_iter().map(|(_, id)| Request::one(id)).collect());
+        for req in reqs {
+            let mut data = Vec::with_capacity(req.num_blocks * BLOCK_SIZE);
+            for id in req.block_id..req.end() {
+                data.extend_from_slice(&self.buffers[&id].data);
+            }
+            self.dispatch_write(req.block_id, &data)?;
+            for id in req.block_id..req.end() {
+                self.buffers.get_mut(&id).unwrap().dirty_seq = None;
+            }
+            self.num_dirty -= req.num_blocks;
+        }
+        Ok(())
+    }
+
+    /// Evicts the least recently used blocks until the buffer cache is within
+    /// its capacity, and writes dirty blocks back if there are too many.
+    fn shrink(&mut self) -> DevResult {
+        if self.num_dirty > DIRTY_LIMIT {
+            self.write_back()?;
+        }
+        while self.buffers.len() > CACHE_BLOCKS {
+            let (&stamp, &id) = self.lru.first_key_value().unwrap();
+            if self.buffers[&id].dirty_seq.is_some() {
+                self.write_back()?;
+            }
+            self.lru.remove(&stamp);
+            self.buffers.remove(&id);
+        }
+        Ok(())
+    }
+}
+
+impl BlockQueue {
+    /// Creates a request queue of `dev`.
+    pub fn new(dev: AxBlockDevice) -> Self {
+        assert_eq!(BLOCK_SIZE, dev.block_size());
+        let num_blocks = dev.num_blocks();
+        Self {
+            inner: Mutex::new(QueueInner {
+                dev,
+                scheduler: Scheduler::Elevator,
+                buffers: BTreeMap::new(),
+                lru: BTreeMap::new(),
+                num_dirty: 0,
+                next_stamp: 0,
+                head: 0,
+            }),
+            num_blocks,
+        }
+    }
+
+    /// Returns the number of blocks of the device.
+    pub fn num_blocks(&self) -> u64 {
+        self.num_blocks
+    }
+
+    /// Returns the scheduler of the queue.
+    pub fn scheduler(&self) -> Scheduler {
+        self.inner.lock().scheduler
+    }
+
+    /// Sets the scheduler of the queue.
+    pub fn set_scheduler(&self, scheduler: Scheduler) {
+        self.inner.lock().scheduler = scheduler;
+    }
+
+    /// Reads blocks from `block_id` into `buf`, whose length is a multiple of
+    /// [`BLOCK_SIZE`].
+    pub fn read(&self, block_id: u64, buf: &mut [u8]) -> DevResult {
+        let num_blocks = buf.len() / BLOCK_SIZE;
+        let mut inner = self.inner.lock();
+        let mut missing = Vec::new();
+        for (i, chunk) in buf.chunks_exact_mut(BLOCK_SIZE).enumerate() {
+            match inner.lookup(block_id + i as u64) {
+                Some(cached) => chunk.copy_from_slice(&cached.data),
+                None => missing.push(Request::one(block_id + i as u64)),
+            }
+        }
+        for req in inner.schedule(missing) {
+            let mut data = vec![0u8; req.num_blocks * BLOCK_SIZE];
+            inner.dispatch_read(req.block_id, &mut data)?;
+            let start = (req.block_id - block_id) as usize * BLOCK_SIZE;
+            buf[start..start + data.len()].copy_from_slice(&data);
+            if num_blocks <= BYPASS_BLOCKS {
+                for (i, chunk) in data.chunks_exact(BLOCK_SIZE).enumerate() {
+                    inner.insert(req.block_id + i as u64, chunk, false);
+                }
+            }
+        }
+        inner.shrink()
+    }
+
+    /// Writes blocks from `block_id` from `buf`, whose length is a multiple of
+    /// [`BLOCK_SIZE`].
+    ///
+    /// Small writes are buffered until the queue is flushed, while large ones
+    /// are dispatched at once.
+    pub fn write(&self, block_id: u64, buf: &[u8]) -> DevResult {
+        let num_blocks = buf.len() / BLOCK_SIZE;
+        let mut inner = self.inner.lock();
+        if num_blocks > BYPASS_BLOCKS {
+            inner.invalidate(block_id, num_blocks);
+            for (i, data) in buf.chunks(MAX_REQUEST_BLOCKS * BLOCK_SIZE).enumerate() {
+                let id = block_id + (i * MAX_REQUEST_BLOCKS) as u64;
+                inner.dispatch_write(id, &data.to_vec())?;
+            }
+            return Ok(());
+        }
+        for (i, chunk) in buf.chunks_exact(BLOCK_SIZE).enumerate() {
+            inner.insert(block_id + i as u64, chunk, true);
+        }
+        inner.shrink()
+    }
+
+    /// Writes all dirty blocks back, and flushes the device.
+    pub fn flush(&self) -> DevResult {
+        let mut inner = self.inner.lock();
+        inner.write_back()?;
+        inner.dev.flush()
+    }
+}
+
+impl Drop for BlockQueue {
+    fn drop(&mut self) {
+        if let Err(e) = self.flush() {
+            warn!("failed to write back buffered blocks: {:?}", e);
+        }
+    }
+}
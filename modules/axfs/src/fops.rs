@@ -1,30 +1,158 @@
 //! Low-level filesystem operations.
 
+use alloc::{borrow::Cow, format, string::String};
 use axerrno::{AxError, AxResult, ax_err, ax_err_type};
-use axfs_vfs::{VfsError, VfsNodeRef};
+use axfs_vfs::{VfsError, VfsNodeAttr, VfsNodeRef};
 use axio::SeekFrom;
 use cap_access::{Cap, WithCap};
-use core::fmt;
+use core::{fmt, time::Duration};
 
+use crate::cache::{self, CacheHandle};
 #[cfg(feature = "myfs")]
 pub use crate::dev::Disk;
 #[cfg(feature = "myfs")]
 pub use crate::fs::myfs::MyFileSystemIf;
+pub use crate::lock::{LockError, LockHandle, LockType, RecordLock};
+use crate::notify;
+pub use crate::notify::{Event, EventMask, Watcher};
+use crate::root::{MountRef, NodeRelease};
 
 /// Alias of [`axfs_vfs::VfsNodeType`].
 pub type FileType = axfs_vfs::VfsNodeType;
 /// Alias of [`axfs_vfs::VfsDirEntry`].
 pub type DirEntry = axfs_vfs::VfsDirEntry;
-/// Alias of [`axfs_vfs::VfsNodeAttr`].
-pub type FileAttr = axfs_vfs::VfsNodeAttr;
 /// Alias of [`axfs_vfs::VfsNodePerm`].
 pub type FilePerm = axfs_vfs::VfsNodePerm;
 
+/// Timestamps of a file, as durations since the Unix epoch.
+///
+/// They are all zero if the filesystem does not keep them.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct FileTimes {
+    /// Time of the last access.
+    pub accessed: Duration,
+    /// Time of the last modification.
+    pub modified: Duration,
+    /// Time of the creation.
+    pub created: Duration,
+}
+
+/// Attributes of a file, i.e., [`axfs_vfs::VfsNodeAttr`] with timestamps, the
+/// number of hard links and the inode number.
+#[derive(Debug, Clone, Copy)]
+pub struct FileAttr {
+    attr: VfsNodeAttr,
+    times: FileTimes,
+    nlink: u64,
+    ino: u64,
+}
+
+impl FileAttr {
+    pub(crate) const fn new(attr: VfsNodeAttr, times: FileTimes, nlink: u64, ino: u64) -> Self {
+        Self {
+            attr,
+            times,
+            nlink,
+            ino,
+        }
+    }
+
+    /// Replaces the size, e.g., by the size in the page cache.
+    pub(crate) fn with_size(self, size: u64) -> Self {
+        let attr = VfsNodeAttr::new(
+            self.attr.perm(),
+            self.attr.file_type(),
+            size,
+            self.attr.blocks(),
+        );
+        Self::new(attr, self.times, self.nlink, self.ino)
+    }
+
+    /// Returns the permission of the file.
+    pub const fn perm(&self) -> FilePerm {
+        self.attr.perm()
+    }
+
+    /// Returns the type of the file.
+    pub const fn file_type(&self) -> FileType {
+        self.attr.file_type()
+    }
+
+    /// Whether the file is a directory.
+    pub const fn is_dir(&self) -> bool {
+        self.attr.is_dir()
+    }
+
+    /// Whether the file is a regular file.
+    pub const fn is_file(&self) -> bool {
+        self.attr.is_file()
+    }
+
+    /// Returns the size of the file.
+    pub const fn size(&self) -> u64 {
+        self.attr.size()
+    }
+
+    /// Returns the number of blocks the file occupies on the disk.
+    pub const fn blocks(&self) -> u64 {
+        self.attr.blocks()
+    }
+
+    /// Returns the timestamps of the file.
+    pub const fn times(&self) -> FileTimes {
+        self.times
+    }
+
+    /// Returns the number of hard links to the file.
+    pub const fn nlink(&self) -> u64 {
+        self.nlink
+    }
+
+    /// Returns the inode number of the file, which is unique in its
+    /// filesystem.
+    ///
+    /// Files in filesystems without inode numbers or persistent nodes get a
+    /// hash of their paths instead.
+    pub const fn ino(&self) -> u64 {
+        self.ino
+    }
+}
+
+/// Statistics of a mounted filesystem, like `struct statfs`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct FsStat {
+    /// Type of the filesystem, as the magic numbers of `statfs(2)`.
+    pub fs_type: u64,
+    /// Size of a block, in which the capacity is counted.
+    pub block_size: u64,
+    /// Total number of blocks.
+    pub blocks: u64,
+    /// Number of free blocks.
+    pub blocks_free: u64,
+    /// Number of free blocks that can be used by unprivileged users.
+    pub blocks_available: u64,
+    /// Total number of files, or inodes.
+    pub files: u64,
+    /// Number of free inodes.
+    pub files_free: u64,
+    /// Maximum length of file names.
+    pub name_max: u64,
+}
+
 /// An opened file object, with open permissions and a cursor.
+///
+/// Data of regular files on disk filesystems is accessed through the page
+/// cache.
 pub struct File {
     node: WithCap<VfsNodeRef>,
     is_append: bool,
     offset: u64,
+    mount: Option<MountRef>,
+    cache: Option<CacheHandle>,
+    lock: LockHandle,
+    /// Dropped after the node, to drop the state of the file if the node is
+    /// released then.
+    release: NodeRelease,
 }
 
 /// An opened directory object, with open permissions and a cursor for
@@ -32,6 +160,12 @@ pub struct File {
 pub struct Directory {
     node: WithCap<VfsNodeRef>,
     entry_idx: usize,
+    mount: Option<MountRef>,
+    /// The absolute path of the directory.
+    path: String,
+    /// Dropped after the node, to drop the state of the directory if the
+    /// node is released then.
+    release: NodeRelease,
 }
 
 /// Options and flags which can be used to configure how a file is opened.
@@ -151,7 +285,13 @@ impl File {
         self.node.access_or_err(cap, AxError::PermissionDenied)
     }
 
-    fn _open_at(dir: Option<&VfsNodeRef>, path: &str, opts: &OpenOptions) -> AxResult<Self> {
+    fn _open_at(
+        dir: Option<&VfsNodeRef>,
+        mount: Option<MountRef>,
+        path: &str,
+        abs_path: &str,
+        opts: &OpenOptions,
+    ) -> AxResult<Self> {
         debug!("open file: {} {:?}", path, opts);
         if !opts.is_valid() {
             return ax_err!(InvalidInput);
@@ -168,7 +308,11 @@ impl File {
                     node
                 }
                 // not exists, create new
-                Err(VfsError::NotFound) => crate::root::create_file(dir, path)?,
+                Err(VfsError::NotFound) => {
+                    let node = crate::root::create_file(dir, path)?;
+                    notify::created(abs_path, false);
+                    node
+                }
                 Err(e) => return Err(e),
             }
         } else {
@@ -186,25 +330,51 @@ impl File {
         }
 
         node.open()?;
-        if opts.truncate {
-            node.truncate(0)?;
-        }
-        Ok(Self {
+        let key = crate::root::node_key(mount.as_ref(), &node, abs_path);
+        let cache = if attr.is_file() && crate::root::page_cached(mount.as_ref(), &node) {
+            Some(cache::open(key.clone(), &node)?)
+        } else {
+            None
+        };
+        let release = NodeRelease::new(mount.as_ref(), &node);
+        let file = Self {
             node: WithCap::new(node, access_cap),
             is_append: opts.append,
             offset: 0,
-        })
+            mount,
+            cache,
+            lock: LockHandle::new(key, abs_path),
+            release,
+        };
+        if opts.truncate {
+            file.truncate(0)?;
+        }
+        Ok(file)
     }
 
     /// Opens a file at the path relative to the current directory. Returns a
     /// [`File`] object.
     pub fn open(path: &str, opts: &OpenOptions) -> AxResult<Self> {
-        Self::_open_at(None, path, opts)
+        let abs_path = crate::root::absolute_path(path)?;
+        Self::_open_at(None, crate::root::mount_of(path), path, &abs_path, opts)
+    }
+
+    /// Returns the size of the file, including data not written back yet.
+    fn size(&self) -> AxResult<u64> {
+        match &self.cache {
+            Some(cache) => Ok(cache.size()),
+            None => Ok(self.access_node(Cap::empty())?.get_attr()?.size()),
+        }
     }
 
     /// Truncates the file to the specified size.
     pub fn truncate(&self, size: u64) -> AxResult {
-        self.access_node(Cap::WRITE)?.truncate(size)?;
+        let node = self.access_node(Cap::WRITE)?;
+        match &self.cache {
+            Some(cache) => cache.truncate(size),
+            None => node.truncate(size),
+        }?;
+        notify::modified(self.lock.key(), self.path());
         Ok(())
     }
 
@@ -213,8 +383,7 @@ impl File {
     ///
     /// After the read, the cursor will be advanced by the number of bytes read.
     pub fn read(&mut self, buf: &mut [u8]) -> AxResult<usize> {
-        let node = self.access_node(Cap::READ)?;
-        let read_len = node.read_at(self.offset, buf)?;
+        let read_len = self.read_at(self.offset, buf)?;
         self.offset += read_len as u64;
         Ok(read_len)
     }
@@ -224,8 +393,10 @@ impl File {
     /// It does not update the file cursor.
     pub fn read_at(&self, offset: u64, buf: &mut [u8]) -> AxResult<usize> {
         let node = self.access_node(Cap::READ)?;
-        let read_len = node.read_at(offset, buf)?;
-        Ok(read_len)
+        match &self.cache {
+            Some(cache) => cache.read_at(offset, buf),
+            None => node.read_at(offset, buf),
+        }
     }
 
     /// Writes the file at the current position. Returns the number of bytes
@@ -235,12 +406,11 @@ impl File {
     /// written.
     pub fn write(&mut self, buf: &[u8]) -> AxResult<usize> {
         let offset = if self.is_append {
-            self.get_attr()?.size()
+            self.size()?
         } else {
             self.offset
         };
-        let node = self.access_node(Cap::WRITE)?;
-        let write_len = node.write_at(offset, buf)?;
+        let write_len = self.write_at(offset, buf)?;
         self.offset = offset + write_len as u64;
         Ok(write_len)
     }
@@ -251,25 +421,34 @@ impl File {
     /// It does not update the file cursor.
     pub fn write_at(&self, offset: u64, buf: &[u8]) -> AxResult<usize> {
         let node = self.access_node(Cap::WRITE)?;
-        let write_len = node.write_at(offset, buf)?;
-        Ok(write_len)
+        let len = match &self.cache {
+            Some(cache) => cache.write_at(offset, buf),
+            None => node.write_at(offset, buf),
+        }?;
+        if len > 0 {
+            notify::modified(self.lock.key(), self.path());
+        }
+        Ok(len)
     }
 
     /// Flushes the file, writes all buffered data to the underlying device.
     pub fn flush(&self) -> AxResult {
-        self.access_node(Cap::WRITE)?.fsync()?;
-        Ok(())
+        self.fsync()
     }
 
     /// Syncs the file, writes all buffered data to the underlying device.
     pub fn fsync(&self) -> AxResult {
-        self.access_node(Cap::WRITE)?.fsync()
+        let node = self.access_node(Cap::WRITE)?;
+        match &self.cache {
+            Some(cache) => cache.sync(),
+            None => node.fsync(),
+        }
     }
 
     /// Sets the cursor of the file to the specified offset. Returns the new
     /// position after the seek.
     pub fn seek(&mut self, pos: SeekFrom) -> AxResult<u64> {
-        let size = self.get_attr()?.size();
+        let size = self.size()?;
         let new_offset = match pos {
             SeekFrom::Start(pos) => Some(pos),
             SeekFrom::Current(off) => self.offset.checked_add_signed(off),
@@ -282,7 +461,25 @@ impl File {
 
     /// Gets the file attributes.
     pub fn get_attr(&self) -> AxResult<FileAttr> {
-        self.access_node(Cap::empty())?.get_attr()
+        let node = self.access_node(Cap::empty())?;
+        let attr = crate::root::node_attr(self.mount.as_ref(), node, self.lock.key())?;
+        match &self.cache {
+            Some(cache) => Ok(attr.with_size(cache.size())),
+            None => Ok(attr),
+        }
+    }
+
+    /// Sets the permissions of the file, like `fchmod`.
+    pub fn set_permissions(&self, mode: u16) -> AxResult {
+        let node = self.access_node(Cap::empty())?;
+        crate::root::set_node_perm(self.mount.as_ref(), node, self.path(), mode)
+    }
+
+    /// Sets the access and modification times of the file, leaving the ones
+    /// that are `None` unchanged, like `futimens`.
+    pub fn set_times(&self, accessed: Option<Duration>, modified: Option<Duration>) -> AxResult {
+        let node = self.access_node(Cap::empty())?;
+        crate::root::set_node_times(self.mount.as_ref(), node, self.path(), accessed, modified)
     }
 
     /// Handles ioctl system calls.
@@ -294,6 +491,49 @@ impl File {
     pub fn poll(&self) -> AxResult<axio::PollState> {
         self.access_node(Cap::empty())?.poll()
     }
+
+    /// Returns whether the file is opened for reading.
+    pub fn is_readable(&self) -> bool {
+        self.access_node(Cap::READ).is_ok()
+    }
+
+    /// Returns whether the file is opened for writing.
+    pub fn is_writable(&self) -> bool {
+        self.access_node(Cap::WRITE).is_ok()
+    }
+
+    /// Returns the handle to the advisory locks of the file.
+    pub fn lock_handle(&self) -> LockHandle {
+        self.lock.clone()
+    }
+
+    /// Returns the absolute path of the file when it was opened.
+    pub fn path(&self) -> &str {
+        self.lock.path()
+    }
+
+    /// Opens the file again, with the same access but its own position and
+    /// `flock` lock, even if it has been renamed or removed since.
+    ///
+    /// It can be used without sharing this opened file, e.g., by a memory
+    /// mapping of the file.
+    pub fn reopen(&self) -> AxResult<Self> {
+        let cap = [Cap::READ, Cap::WRITE, Cap::EXECUTE]
+            .into_iter()
+            .filter(|&cap| self.access_node(cap).is_ok())
+            .fold(Cap::empty(), |caps, cap| caps | cap);
+        let node = self.access_node(Cap::empty())?.clone();
+        node.open()?;
+        Ok(Self {
+            release: NodeRelease::new(self.mount.as_ref(), &node),
+            node: WithCap::new(node, cap),
+            is_append: self.is_append,
+            offset: 0,
+            mount: self.mount.clone(),
+            cache: self.cache.as_ref().map(CacheHandle::reopen),
+            lock: LockHandle::new(self.lock.key().clone(), self.lock.path()),
+        })
+    }
 }
 
 impl Directory {
@@ -301,7 +541,13 @@ impl Directory {
         self.node.access_or_err(cap, AxError::PermissionDenied)
     }
 
-    fn _open_dir_at(dir: Option<&VfsNodeRef>, path: &str, opts: &OpenOptions) -> AxResult<Self> {
+    fn _open_dir_at(
+        dir: Option<&VfsNodeRef>,
+        mount: Option<MountRef>,
+        path: &str,
+        abs_path: String,
+        opts: &OpenOptions,
+    ) -> AxResult<Self> {
         debug!("open dir: {}", path);
         if !opts.read {
             return ax_err!(InvalidInput);
@@ -322,64 +568,118 @@ impl Directory {
         }
 
         node.open()?;
+        let release = NodeRelease::new(mount.as_ref(), &node);
         Ok(Self {
             // Here we use `cap` as capability instead of `access_cap` to allow the user to manipulate the directory
             // without explicitly setting [`OpenOptions::execute`], but without requiring execute access even for
             // directories that don't have this permission.
             node: WithCap::new(node, cap),
             entry_idx: 0,
+            mount,
+            path: abs_path,
+            release,
         })
     }
 
-    fn access_at(&self, path: &str) -> AxResult<Option<&VfsNodeRef>> {
+    /// Returns the directory to resolve `path` from, and `path` relative to
+    /// it.
+    ///
+    /// Relative paths are resolved from this directory, unless they lead to
+    /// another filesystem, e.g., through a mount point under this directory,
+    /// in which case they are resolved by their absolute paths from the root
+    /// directory.
+    fn start_at<'a>(&self, path: &'a str) -> AxResult<(Option<&VfsNodeRef>, Cow<'a, str>)> {
+        if path.starts_with('/') {
+            return Ok((None, Cow::Borrowed(path)));
+        }
+        let dir = self.access_node(Cap::EXECUTE)?;
+        if MountRef::same_fs(self.mount_at(path).as_ref(), self.mount.as_ref()) {
+            Ok((Some(dir), Cow::Borrowed(path)))
+        } else {
+            Ok((None, Cow::Owned(self.path_at(path))))
+        }
+    }
+
+    fn mount_at(&self, path: &str) -> Option<MountRef> {
+        crate::root::mount_of(&self.path_at(path))
+    }
+
+    /// Returns the absolute path of `path` relative to this directory.
+    fn path_at(&self, path: &str) -> String {
         if path.starts_with('/') {
-            Ok(None)
+            axfs_vfs::path::canonicalize(path)
         } else {
-            Ok(Some(self.access_node(Cap::EXECUTE)?))
+            axfs_vfs::path::canonicalize(&format!("{}/{}", self.path, path))
         }
     }
 
     /// Opens a directory at the path relative to the current directory.
     /// Returns a [`Directory`] object.
     pub fn open_dir(path: &str, opts: &OpenOptions) -> AxResult<Self> {
-        Self::_open_dir_at(None, path, opts)
+        let abs_path = crate::root::absolute_path(path)?;
+        Self::_open_dir_at(None, crate::root::mount_of(path), path, abs_path, opts)
     }
 
     /// Opens a directory at the path relative to this directory. Returns a
     /// [`Directory`] object.
     pub fn open_dir_at(&self, path: &str, opts: &OpenOptions) -> AxResult<Self> {
-        Self::_open_dir_at(self.access_at(path)?, path, opts)
+        let abs_path = self.path_at(path);
+        let (dir, rel_path) = self.start_at(path)?;
+        Self::_open_dir_at(dir, self.mount_at(path), &rel_path, abs_path, opts)
     }
 
     /// Opens a file at the path relative to this directory. Returns a [`File`]
     /// object.
     pub fn open_file_at(&self, path: &str, opts: &OpenOptions) -> AxResult<File> {
-        File::_open_at(self.access_at(path)?, path, opts)
+        let abs_path = self.path_at(path);
+        let (dir, rel_path) = self.start_at(path)?;
+        File::_open_at(dir, self.mount_at(path), &rel_path, &abs_path, opts)
     }
 
     /// Creates an empty file at the path relative to this directory.
     pub fn create_file(&self, path: &str) -> AxResult<VfsNodeRef> {
-        crate::root::create_file(self.access_at(path)?, path)
+        let (dir, rel_path) = self.start_at(path)?;
+        let node = crate::root::create_file(dir, &rel_path)?;
+        notify::created(&self.path_at(path), false);
+        Ok(node)
     }
 
     /// Creates an empty directory at the path relative to this directory.
     pub fn create_dir(&self, path: &str) -> AxResult {
-        crate::root::create_dir(self.access_at(path)?, path)
+        let (dir, rel_path) = self.start_at(path)?;
+        crate::root::create_dir(dir, &rel_path)?;
+        // changes at absolute paths are reported by `root`
+        if dir.is_some() {
+            notify::created(&self.path_at(path), true);
+        }
+        Ok(())
     }
 
     /// Adds a node at the path relative to this directory.
     pub fn add_node(&self, path: &'static str, ty: VfsNodeRef) -> AxResult {
-        crate::root::add_node(self.access_at(path)?, path, ty)
+        let dir = if path.starts_with('/') {
+            None
+        } else {
+            Some(self.access_node(Cap::EXECUTE)?)
+        };
+        crate::root::add_node(dir, path, ty)
     }
 
     /// Removes a file at the path relative to this directory.
     pub fn remove_file(&self, path: &str) -> AxResult {
-        crate::root::remove_file(self.access_at(path)?, path)
+        match self.start_at(path)? {
+            (Some(dir), rel_path) => {
+                let abs_path = self.path_at(path);
+                crate::root::remove_file_at(dir, self.mount.as_ref(), &rel_path, &abs_path)
+            }
+            (None, abs_path) => crate::root::remove_file(&abs_path),
+        }
     }
 
     /// Removes a directory at the path relative to this directory.
     pub fn remove_dir(&self, path: &str) -> AxResult {
-        crate::root::remove_dir(self.access_at(path)?, path)
+        let (dir, rel_path) = self.start_at(path)?;
+        crate::root::remove_dir(dir, &rel_path, &self.path_at(path))
     }
 
     /// Reads directory entries starts from the current position into the
@@ -395,6 +695,49 @@ impl Directory {
         Ok(n)
     }
 
+    /// Returns the index of the next entry to be read by
+    /// [`read_dir`](Self::read_dir), like `telldir`.
+    pub fn entry_idx(&self) -> usize {
+        self.entry_idx
+    }
+
+    /// Sets the index of the next entry to be read, like `seekdir`.
+    pub fn set_entry_idx(&mut self, idx: usize) {
+        self.entry_idx = idx;
+    }
+
+    /// Gets the directory attributes.
+    pub fn get_attr(&self) -> AxResult<FileAttr> {
+        let node = self.access_node(Cap::empty())?;
+        let key = crate::root::node_key(self.mount.as_ref(), node, &self.path);
+        crate::root::node_attr(self.mount.as_ref(), node, &key)
+    }
+
+    /// Returns the inode number of the entry `name` of the directory, as
+    /// [`FileAttr::ino`] of the entry, without following symbolic links.
+    pub fn entry_ino(&self, name: &str) -> u64 {
+        let path = axfs_vfs::path::canonicalize(&format!("{}/{}", self.path, name));
+        crate::root::path_key(&path).ino()
+    }
+
+    /// Returns the absolute path of the directory when it was opened.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Sets the permissions of the directory, like `fchmod`.
+    pub fn set_permissions(&self, mode: u16) -> AxResult {
+        let node = self.access_node(Cap::empty())?;
+        crate::root::set_node_perm(self.mount.as_ref(), node, &self.path, mode)
+    }
+
+    /// Sets the access and modification times of the directory, leaving the
+    /// ones that are `None` unchanged, like `futimens`.
+    pub fn set_times(&self, accessed: Option<Duration>, modified: Option<Duration>) -> AxResult {
+        let node = self.access_node(Cap::empty())?;
+        crate::root::set_node_times(self.mount.as_ref(), node, &self.path, accessed, modified)
+    }
+
     /// Rename a file or directory to a new name.
     /// Delete the original file if `old` already exists.
     ///
@@ -406,6 +749,11 @@ impl Directory {
 
 impl Drop for File {
     fn drop(&mut self) {
+        drop(self.cache.take()); // write back before release
+        self.lock.release_flock();
+        if self.access_node(Cap::WRITE).is_ok() {
+            notify::closed_write(self.lock.key(), self.path());
+        }
         unsafe { self.node.access_unchecked().release().ok() };
     }
 }
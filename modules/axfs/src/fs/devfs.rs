@@ -0,0 +1,60 @@
+pub use axfs_devfs::*;
+use axfs_vfs::{VfsError, VfsNodeAttr, VfsNodeOps, VfsNodePerm, VfsNodeType, VfsResult};
+use axsync::Mutex;
+
+use crate::dev::Disk;
+
+/// `BlockDev` is a device file that exposes a whole block device.
+/// path: `/dev/vda`, `/dev/vdb`, ...
+pub struct BlockDev(Mutex<Disk>);
+
+impl BlockDev {
+    /// Creates a device file on the given disk.
+    pub(crate) fn new(disk: Disk) -> Self {
+        Self(Mutex::new(disk))
+    }
+}
+
+impl VfsNodeOps for BlockDev {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        let size = self.0.lock().size();
+        Ok(VfsNodeAttr::new(
+            VfsNodePerm::from_bits_truncate(0o660),
+            VfsNodeType::BlockDevice,
+            size,
+            size.div_ceil(512),
+        ))
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        let mut disk = self.0.lock();
+        let size = disk.size();
+        if offset >= size {
+            return Ok(0);
+        }
+        let len = buf.len().min((size - offset) as usize);
+        disk.set_position(offset);
+        disk.read_data(&mut buf[..len]).map_err(|_| VfsError::Io)
+    }
+
+    fn write_at(&self, offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        let mut disk = self.0.lock();
+        let size = disk.size();
+        if offset >= size {
+            return Err(VfsError::StorageFull);
+        }
+        let len = buf.len().min((size - offset) as usize);
+        disk.set_position(offset);
+        disk.write_data(&buf[..len]).map_err(|_| VfsError::Io)
+    }
+
+    fn fsync(&self) -> VfsResult {
+        self.0.lock().flush().map_err(|_| VfsError::Io)
+    }
+
+    fn truncate(&self, _size: u64) -> VfsResult {
+        Ok(())
+    }
+
+    axfs_vfs::impl_vfs_non_dir_default! {}
+}
@@ -1,22 +1,47 @@
 use alloc::sync::Arc;
 use core::cell::UnsafeCell;
+use core::time::Duration;
 
 use axfs_vfs::{VfsDirEntry, VfsError, VfsNodePerm, VfsResult};
 use axfs_vfs::{VfsNodeAttr, VfsNodeOps, VfsNodeRef, VfsNodeType, VfsOps};
 use axsync::Mutex;
-use fatfs::{Dir, File, LossyOemCpConverter, NullTimeProvider, Read, Seek, SeekFrom, Write};
+use fatfs::{Date, DateTime, Dir, DirEntry, File, LossyOemCpConverter, Time, TimeProvider};
+use fatfs::{Read, Seek, SeekFrom, Write};
 
 use crate::dev::Disk;
+use crate::fops::{FileTimes, FsStat};
 
 const BLOCK_SIZE: usize = 512;
+/// Magic number of FAT in `statfs(2)`.
+const MSDOS_SUPER_MAGIC: u64 = 0x4d44;
+const SECS_PER_DAY: u64 = 86400;
+
+type FatDir<'a, IO> = Dir<'a, IO, WallTimeProvider, LossyOemCpConverter>;
+type FatFile<'a, IO> = File<'a, IO, WallTimeProvider, LossyOemCpConverter>;
+type FatDirEntry<'a, IO> = DirEntry<'a, IO, WallTimeProvider, LossyOemCpConverter>;
 
 pub struct FatFileSystem {
-    inner: fatfs::FileSystem<Disk, NullTimeProvider, LossyOemCpConverter>,
+    inner: fatfs::FileSystem<Disk, WallTimeProvider, LossyOemCpConverter>,
     root_dir: UnsafeCell<Option<VfsNodeRef>>,
 }
 
-pub struct FileWrapper<'a, IO: IoTrait>(Mutex<File<'a, IO, NullTimeProvider, LossyOemCpConverter>>);
-pub struct DirWrapper<'a, IO: IoTrait>(Dir<'a, IO, NullTimeProvider, LossyOemCpConverter>);
+/// A [`TimeProvider`] that reads the wall clock, which comes from the RTC if
+/// the `rtc` feature of `axhal` is enabled.
+///
+/// FAT keeps local time in years 1980 to 2107, and we use UTC for it.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct WallTimeProvider;
+
+/// A file, with the timestamps of its directory entry.
+///
+/// The timestamps are read from the directory entry when the file is looked
+/// up, and kept up to date by the changes through this node. Those of the
+/// last modification are only written to the directory entry when the file
+/// is flushed.
+pub struct FileWrapper<'a, IO: IoTrait>(Mutex<FatFile<'a, IO>>, Mutex<FileTimes>);
+/// A directory, with the timestamps of its directory entry, which are all
+/// zero for the root directory.
+pub struct DirWrapper<'a, IO: IoTrait>(FatDir<'a, IO>, FileTimes);
 
 pub trait IoTrait: Read + Write + Seek {}
 
@@ -32,7 +57,7 @@ impl FatFileSystem {
     pub fn new(mut disk: Disk) -> Self {
         let opts = fatfs::FormatVolumeOptions::new();
         fatfs::format_volume(&mut disk, opts).expect("failed to format volume");
-        let inner = fatfs::FileSystem::new(disk, fatfs::FsOptions::new())
+        let inner = fatfs::FileSystem::new(disk, Self::options())
             .expect("failed to initialize FAT filesystem");
         Self {
             inner,
@@ -42,7 +67,7 @@ impl FatFileSystem {
 
     #[cfg(not(feature = "use-ramdisk"))]
     pub fn new(disk: Disk) -> Self {
-        let inner = fatfs::FileSystem::new(disk, fatfs::FsOptions::new())
+        let inner = fatfs::FileSystem::new(disk, Self::options())
             .expect("failed to initialize FAT filesystem");
         Self {
             inner,
@@ -50,27 +75,80 @@ impl FatFileSystem {
         }
     }
 
+    fn options() -> fatfs::FsOptions<WallTimeProvider, LossyOemCpConverter> {
+        fatfs::FsOptions::new().time_provider(WallTimeProvider)
+    }
+
     pub fn init(&'static self) {
         // must be called before later operations
-        unsafe { *self.root_dir.get() = Some(Self::new_dir(self.inner.root_dir())) }
+        let root_dir = Self::new_dir(self.inner.root_dir(), FileTimes::default());
+        unsafe { *self.root_dir.get() = Some(root_dir) }
+    }
+
+    /// Opens the existing FAT filesystem on `disk` for mounting at runtime.
+    pub fn open(disk: Disk) -> VfsResult<Arc<Self>> {
+        let inner = fatfs::FileSystem::new(disk, Self::options()).map_err(as_vfs_err)?;
+        let fs = Arc::new(Self {
+            inner,
+            root_dir: UnsafeCell::new(None),
+        });
+        // SAFETY: the root directory only borrows `inner`, which lives as long
+        // as the returned `Arc`.
+        let fs_ref: &'static Self = unsafe { &*Arc::as_ptr(&fs) };
+        fs_ref.init();
+        Ok(fs)
+    }
+
+    fn new_file<IO: IoTrait>(file: FatFile<'_, IO>, times: FileTimes) -> Arc<FileWrapper<'_, IO>> {
+        Arc::new(FileWrapper(Mutex::new(file), Mutex::new(times)))
     }
 
-    fn new_file<IO: IoTrait>(
-        file: File<'_, IO, NullTimeProvider, LossyOemCpConverter>,
-    ) -> Arc<FileWrapper<IO>> {
-        Arc::new(FileWrapper(Mutex::new(file)))
+    fn new_dir<IO: IoTrait>(dir: FatDir<'_, IO>, times: FileTimes) -> Arc<DirWrapper<'_, IO>> {
+        Arc::new(DirWrapper(dir, times))
+    }
+}
+
+impl<IO: IoTrait> FileWrapper<'_, IO> {
+    fn times(&self) -> FileTimes {
+        *self.1.lock()
+    }
+
+    fn touch(&self) {
+        let now = WallTimeProvider.get_current_date_time();
+        self.1.lock().modified = date_time_to_duration(now);
+    }
+
+    /// Writes the given timestamps to the directory entry.
+    fn set_times(&self, accessed: Option<Duration>, modified: Option<Duration>) -> VfsResult {
+        let mut file = self.0.lock();
+        let mut times = self.1.lock();
+        if let Some(accessed) = accessed {
+            let accessed = duration_to_date_time(accessed).date;
+            file.set_accessed(accessed);
+            times.accessed = date_to_duration(accessed);
+        }
+        if let Some(modified) = modified {
+            let modified = duration_to_date_time(modified);
+            file.set_modified(modified);
+            times.modified = date_time_to_duration(modified);
+        }
+        file.flush().map_err(as_vfs_err)
     }
+}
 
-    fn new_dir<IO: IoTrait>(
-        dir: Dir<'_, IO, NullTimeProvider, LossyOemCpConverter>,
-    ) -> Arc<DirWrapper<IO>> {
-        Arc::new(DirWrapper(dir))
+impl<IO: IoTrait> DirWrapper<'_, IO> {
+    fn times(&self) -> FileTimes {
+        self.1
     }
 }
 
-impl<IO: IoTrait> VfsNodeOps for FileWrapper<'static, IO> {
+impl<IO: IoTrait + 'static> VfsNodeOps for FileWrapper<'static, IO> {
     axfs_vfs::impl_vfs_non_dir_default! {}
 
+    fn as_any(&self) -> &dyn core::any::Any {
+        self
+    }
+
     fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
         let size = self.0.lock().seek(SeekFrom::End(0)).map_err(as_vfs_err)?;
         let blocks = (size + BLOCK_SIZE as u64 - 1) / BLOCK_SIZE as u64;
@@ -139,13 +217,16 @@ impl<IO: IoTrait> VfsNodeOps for FileWrapper<'static, IO> {
             total_written += written;
         }
 
+        self.touch();
         Ok(total_written)
     }
 
     fn truncate(&self, size: u64) -> VfsResult {
         let mut file = self.0.lock();
         file.seek(SeekFrom::Start(size)).map_err(as_vfs_err)?; // TODO: more efficient
-        file.truncate().map_err(as_vfs_err)
+        file.truncate().map_err(as_vfs_err)?;
+        self.touch();
+        Ok(())
     }
 
     fn fsync(&self) -> VfsResult {
@@ -154,9 +235,13 @@ impl<IO: IoTrait> VfsNodeOps for FileWrapper<'static, IO> {
     }
 }
 
-impl<IO: IoTrait> VfsNodeOps for DirWrapper<'static, IO> {
+impl<IO: IoTrait + 'static> VfsNodeOps for DirWrapper<'static, IO> {
     axfs_vfs::impl_vfs_dir_default! {}
 
+    fn as_any(&self) -> &dyn core::any::Any {
+        self
+    }
+
     fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
         // FAT fs doesn't support permissions, we just set everything to 755
         Ok(VfsNodeAttr::new(
@@ -168,9 +253,9 @@ impl<IO: IoTrait> VfsNodeOps for DirWrapper<'static, IO> {
     }
 
     fn parent(&self) -> Option<VfsNodeRef> {
-        self.0
-            .open_dir("..")
-            .map_or(None, |dir| Some(FatFileSystem::new_dir(dir)))
+        self.0.open_dir("..").map_or(None, |dir| {
+            Some(FatFileSystem::new_dir(dir, FileTimes::default()))
+        })
     }
 
     fn lookup(self: Arc<Self>, path: &str) -> VfsResult<VfsNodeRef> {
@@ -183,13 +268,22 @@ impl<IO: IoTrait> VfsNodeOps for DirWrapper<'static, IO> {
             return self.lookup(rest);
         }
 
-        // TODO: use `fatfs::Dir::find_entry`, but it's not public.
-        if let Ok(file) = self.0.open_file(path) {
-            Ok(FatFileSystem::new_file(file))
-        } else if let Ok(dir) = self.0.open_dir(path) {
-            Ok(FatFileSystem::new_dir(dir))
+        // `fatfs::Dir::find_entry` is not public, so we find the entry by
+        // ourselves, which gives its timestamps as well in one pass
+        let (dir, name) = match path.rsplit_once('/') {
+            Some((parent, name)) => (self.0.open_dir(parent).map_err(as_vfs_err)?, name),
+            None => (self.0.clone(), path),
+        };
+        let entry = dir
+            .iter()
+            .filter_map(Result::ok)
+            .find(|e| e.file_name().eq_ignore_ascii_case(name) || e.short_file_name() == name)
+            .ok_or(VfsError::NotFound)?;
+        let times = entry_times(&entry);
+        if entry.is_dir() {
+            Ok(FatFileSystem::new_dir(entry.to_dir(), times))
         } else {
-            Err(VfsError::NotFound)
+            Ok(FatFileSystem::new_file(entry.to_file(), times))
         }
     }
 
@@ -267,6 +361,134 @@ impl VfsOps for FatFileSystem {
     }
 }
 
+impl super::FileSystem for FatFileSystem {
+    fn node_times(&self, node: &dyn VfsNodeOps) -> Option<FileTimes> {
+        let node = node.as_any();
+        if let Some(file) = node.downcast_ref::<FileWrapper<'static, Disk>>() {
+            Some(file.times())
+        } else {
+            node.downcast_ref::<DirWrapper<'static, Disk>>()
+                .map(DirWrapper::times)
+        }
+    }
+
+    /// Sets the timestamps of files. Those of directories are kept, as
+    /// `fatfs` does not expose their directory entries.
+    fn set_node_times(
+        &self,
+        node: &dyn VfsNodeOps,
+        accessed: Option<Duration>,
+        modified: Option<Duration>,
+    ) -> VfsResult {
+        match node.as_any().downcast_ref::<FileWrapper<'static, Disk>>() {
+            Some(file) => file.set_times(accessed, modified),
+            None => Ok(()),
+        }
+    }
+
+    /// Reports clusters as blocks. FAT has no inode table, so the number of
+    /// files is reported as 0, like Linux does.
+    fn statfs(&self) -> VfsResult<FsStat> {
+        let stats = self.inner.stats().map_err(as_vfs_err)?;
+        let free = stats.free_clusters() as u64;
+        Ok(FsStat {
+            fs_type: MSDOS_SUPER_MAGIC,
+            block_size: stats.cluster_size() as u64,
+            blocks: stats.total_clusters() as u64,
+            blocks_free: free,
+            blocks_available: free,
+            files: 0,
+            files_free: 0,
+            name_max: 255,
+        })
+    }
+
+    fn page_cached(&self) -> bool {
+        true
+    }
+}
+
+impl TimeProvider for WallTimeProvider {
+    fn get_current_date(&self) -> Date {
+        self.get_current_date_time().date
+    }
+
+    fn get_current_date_time(&self) -> DateTime {
+        duration_to_date_time(axhal::time::wall_time())
+    }
+}
+
+/// Converts a time since the Unix epoch to a FAT timestamp, clamped to the
+/// range of FAT.
+fn duration_to_date_time(time: Duration) -> DateTime {
+    let (year, month, day) = civil_from_days(time.as_secs() / SECS_PER_DAY);
+    if year < 1980 {
+        return DateTime::new(Date::new(1980, 1, 1), Time::new(0, 0, 0, 0));
+    } else if year > 2107 {
+        return DateTime::new(Date::new(2107, 12, 31), Time::new(23, 59, 59, 999));
+    }
+    let secs = time.as_secs() % SECS_PER_DAY;
+    DateTime::new(
+        Date::new(year as u16, month, day),
+        Time::new(
+            (secs / 3600) as u16,
+            (secs / 60 % 60) as u16,
+            (secs % 60) as u16,
+            time.subsec_millis() as u16,
+        ),
+    )
+}
+
+/// Converts days since the Unix epoch to a `(year, month, day)` date.
+///
+/// See <http://howardhinnant.github.io/date_algorithms.html#civil_from_days>.
+fn civil_from_days(days: u64) -> (u64, u16, u16) {
+    let z = days + 719468;
+    let era = z / 146097;
+    let doe = z % 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as u64;
+    (year, month as u16, day as u16)
+}
+
+/// Converts a `(year, month, day)` date to days since the Unix epoch.
+///
+/// See <http://howardhinnant.github.io/date_algorithms.html#days_from_civil>.
+fn days_from_civil(year: u64, month: u64, day: u64) -> u64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year / 400;
+    let yoe = year % 400;
+    let mp = if month > 2 { month - 3 } else { month + 9 };
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+fn date_to_duration(date: Date) -> Duration {
+    let days = days_from_civil(date.year as u64, date.month as u64, date.day as u64);
+    Duration::from_secs(days * SECS_PER_DAY)
+}
+
+fn date_time_to_duration(dt: DateTime) -> Duration {
+    let secs = dt.time.hour as u64 * 3600 + dt.time.min as u64 * 60 + dt.time.sec as u64;
+    date_to_duration(dt.date)
+        + Duration::from_secs(secs)
+        + Duration::from_millis(dt.time.millis as u64)
+}
+
+/// Reads the timestamps from a directory entry.
+fn entry_times<IO: IoTrait>(entry: &FatDirEntry<'_, IO>) -> FileTimes {
+    FileTimes {
+        accessed: date_to_duration(entry.accessed()),
+        modified: date_time_to_duration(entry.modified()),
+        created: date_time_to_duration(entry.created()),
+    }
+}
+
 impl fatfs::IoBase for Disk {
     type Error = ();
 }
@@ -274,40 +496,17 @@ impl fatfs::IoBase for Disk {
 impl IoTrait for Disk {}
 
 impl Read for Disk {
-    fn read(&mut self, mut buf: &mut [u8]) -> Result<usize, Self::Error> {
-        let mut read_len = 0;
-        while !buf.is_empty() {
-            match self.read_one(buf) {
-                Ok(0) => break,
-                Ok(n) => {
-                    let tmp = buf;
-                    buf = &mut tmp[n..];
-                    read_len += n;
-                }
-                Err(_) => return Err(()),
-            }
-        }
-        Ok(read_len)
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        self.read_data(buf).map_err(|_| ())
     }
 }
 
 impl Write for Disk {
-    fn write(&mut self, mut buf: &[u8]) -> Result<usize, Self::Error> {
-        let mut write_len = 0;
-        while !buf.is_empty() {
-            match self.write_one(buf) {
-                Ok(0) => break,
-                Ok(n) => {
-                    buf = &buf[n..];
-                    write_len += n;
-                }
-                Err(_) => return Err(()),
-            }
-        }
-        Ok(write_len)
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        self.write_data(buf).map_err(|_| ())
     }
     fn flush(&mut self) -> Result<(), Self::Error> {
-        Ok(())
+        Disk::flush(self).map_err(|_| ())
     }
 }
 
@@ -350,12 +549,12 @@ impl Clone for FileWrapper<'static, Disk> {
     fn clone(&self) -> Self {
         let file = self.0.lock();
         let cloned_file = file.clone();
-        Self(Mutex::new(cloned_file))
+        Self(Mutex::new(cloned_file), Mutex::new(self.times()))
     }
 }
 
 pub struct FatFileSystemFromFile {
-    inner: fatfs::FileSystem<FileWrapper<'static, Disk>, NullTimeProvider, LossyOemCpConverter>,
+    inner: fatfs::FileSystem<FileWrapper<'static, Disk>, WallTimeProvider, LossyOemCpConverter>,
     root_dir: UnsafeCell<Option<VfsNodeRef>>,
 }
 
@@ -365,7 +564,7 @@ unsafe impl Send for FatFileSystemFromFile {}
 #[allow(unused)]
 impl FatFileSystemFromFile {
     pub fn new(file: FileWrapper<'static, Disk>) -> Self {
-        let inner = fatfs::FileSystem::new(file, fatfs::FsOptions::new())
+        let inner = fatfs::FileSystem::new(file, FatFileSystem::options())
             .expect("failed to initialize FAT filesystem");
         Self {
             inner,
@@ -375,7 +574,8 @@ impl FatFileSystemFromFile {
 
     pub fn init(&'static self) {
         // must be called before later operations
-        unsafe { *self.root_dir.get() = Some(FatFileSystem::new_dir(self.inner.root_dir())) }
+        let root_dir = FatFileSystem::new_dir(self.inner.root_dir(), FileTimes::default());
+        unsafe { *self.root_dir.get() = Some(root_dir) }
     }
 }
 
@@ -1,17 +1,34 @@
 use crate::alloc::string::String;
+use alloc::collections::VecDeque;
+use alloc::ffi::CString;
 use alloc::sync::Arc;
-use alloc::vec;
-use axerrno::AxError;
+use alloc::vec::Vec;
+use alloc::{format, vec};
+use axerrno::{AxError, ax_err};
 use axfs_vfs::{VfsDirEntry, VfsError, VfsNodePerm, VfsResult};
 use axfs_vfs::{VfsNodeAttr, VfsNodeOps, VfsNodeRef, VfsNodeType, VfsOps};
 use axsync::Mutex;
+use core::ffi::CStr;
+use core::time::Duration;
 use lwext4_rust::bindings::{
-    O_CREAT, O_RDONLY, O_RDWR, O_TRUNC, O_WRONLY, SEEK_CUR, SEEK_END, SEEK_SET,
+    self, O_CREAT, O_RDONLY, O_RDWR, O_TRUNC, O_WRONLY, SEEK_CUR, SEEK_END, SEEK_SET,
 };
 use lwext4_rust::{Ext4BlockWrapper, Ext4File, InodeTypes, KernelDevOp};
 
 use crate::dev::Disk;
+use crate::fops::{FileTimes, FsStat};
 pub const BLOCK_SIZE: usize = 512;
+/// Where [`Ext4BlockWrapper`] mounts the filesystem in the namespace of
+/// lwext4, which the paths of all nodes start with.
+const MOUNT_POINT: &str = "/";
+/// Magic number of ext2/3/4 in `statfs(2)`.
+const EXT4_SUPER_MAGIC: u64 = 0xef53;
+
+/// Converts an error number of lwext4 to an [`AxError`], or [`AxError::Io`]
+/// if it is unknown.
+fn ext4_err(errno: i32) -> AxError {
+    errno.try_into().unwrap_or(AxError::Io)
+}
 
 #[allow(dead_code)]
 pub struct Ext4FileSystem {
@@ -23,22 +40,25 @@ unsafe impl Sync for Ext4FileSystem {}
 unsafe impl Send for Ext4FileSystem {}
 
 impl Ext4FileSystem {
-    #[cfg(feature = "use-ramdisk")]
-    pub fn new(mut disk: Disk) -> Self {
-        unimplemented!()
-    }
-
-    #[cfg(not(feature = "use-ramdisk"))]
+    /// Opens the ext4 filesystem on `disk`, which may also be a ramdisk loaded
+    /// with an ext4 image.
+    ///
+    /// Unlike FAT, a blank ramdisk is not formatted, as lwext4 can only format
+    /// block devices it opens by itself.
     pub fn new(disk: Disk) -> Self {
         info!(
             "Got Disk size:{}, position:{}",
             disk.size(),
             disk.position()
         );
-        let inner =
-            Ext4BlockWrapper::<Disk>::new(disk).expect("failed to initialize EXT4 filesystem");
-        let root = Arc::new(FileWrapper::new("/", InodeTypes::EXT4_DE_DIR));
-        Self { inner, root }
+        Self::open(disk).expect("failed to initialize EXT4 filesystem")
+    }
+
+    /// Opens the existing ext4 filesystem on `disk`.
+    pub fn open(disk: Disk) -> VfsResult<Self> {
+        let inner = Ext4BlockWrapper::<Disk>::new(disk).map_err(ext4_err)?;
+        let root = Arc::new(FileWrapper::new(MOUNT_POINT, InodeTypes::EXT4_DE_DIR));
+        Ok(Self { inner, root })
     }
 }
 
@@ -53,6 +73,150 @@ impl VfsOps for Ext4FileSystem {
     }
 }
 
+impl super::FileSystem for Ext4FileSystem {
+    fn node_times(&self, node: &dyn VfsNodeOps) -> Option<FileTimes> {
+        let file = node.as_any().downcast_ref::<FileWrapper>()?;
+        let (_, inode) = file.raw_inode().ok()?;
+        let time = |secs: u32| Duration::from_secs(u32::from_le(secs) as u64);
+        Some(FileTimes {
+            accessed: time(inode.access_time),
+            modified: time(inode.modification_time),
+            created: time(inode.crtime),
+        })
+    }
+
+    fn set_node_times(
+        &self,
+        node: &dyn VfsNodeOps,
+        accessed: Option<Duration>,
+        modified: Option<Duration>,
+    ) -> VfsResult {
+        let file = node
+            .as_any()
+            .downcast_ref::<FileWrapper>()
+            .ok_or(AxError::Unsupported)?;
+        file.set_times(accessed, modified)
+    }
+
+    fn set_node_perm(&self, node: &dyn VfsNodeOps, perm: VfsNodePerm) -> VfsResult {
+        let file = node
+            .as_any()
+            .downcast_ref::<FileWrapper>()
+            .ok_or(AxError::Unsupported)?;
+        file.set_perm(perm)
+    }
+
+    fn node_nlink(&self, node: &dyn VfsNodeOps) -> Option<u64> {
+        let file = node.as_any().downcast_ref::<FileWrapper>()?;
+        let (_, inode) = file.raw_inode().ok()?;
+        Some(u16::from_le(inode.links_count) as u64)
+    }
+
+    fn node_ino(&self, node: &dyn VfsNodeOps) -> Option<u64> {
+        let file = node.as_any().downcast_ref::<FileWrapper>()?;
+        let (ino, _) = file.raw_inode().ok()?;
+        Some(ino as u64)
+    }
+
+    fn statfs(&self) -> VfsResult<FsStat> {
+        // the path of the root is where lwext4 mounted this filesystem
+        let root = self.root.as_any().downcast_ref::<FileWrapper>().unwrap();
+        let mount_point = root.0.lock().get_path();
+        // SAFETY: `ext4_mount_stats` is plain data, which is filled by lwext4
+        let mut stats: bindings::ext4_mount_stats = unsafe { core::mem::zeroed() };
+        match unsafe { bindings::ext4_mount_point_stats(mount_point.as_ptr(), &mut stats) } {
+            0 => {}
+            e => return Err(ext4_err(e)),
+        }
+        Ok(FsStat {
+            fs_type: EXT4_SUPER_MAGIC,
+            block_size: stats.block_size as u64,
+            blocks: stats.blocks_count,
+            blocks_free: stats.free_blocks_count,
+            blocks_available: stats.free_blocks_count,
+            files: stats.inodes_count as u64,
+            files_free: stats.free_inodes_count as u64,
+            name_max: 255,
+        })
+    }
+
+    fn page_cached(&self) -> bool {
+        true
+    }
+
+    fn link(&self, old_path: &str, new_path: &str) -> VfsResult {
+        let root = self.root.as_any().downcast_ref::<FileWrapper>().unwrap();
+        root.link(old_path, new_path)
+    }
+}
+
+/// Converts `path` to a C string for lwext4.
+fn c_path(path: &str) -> VfsResult<CString> {
+    CString::new(path).map_err(|_| AxError::InvalidInput)
+}
+
+/// Maximum number of symbolic links followed in a lookup, as in Linux.
+const MAX_SYMLINKS: usize = 40;
+
+/// Returns the absolute path of the components `comps`.
+fn join_path(comps: &[String]) -> String {
+    String::from("/") + &comps.join("/")
+}
+
+/// Returns the path of the entry `name` in the directory `dir`.
+fn child_of(dir: &str, name: &str) -> String {
+    format!("{}/{}", dir.trim_end_matches('/'), name)
+}
+
+/// Returns `path` as a string, as lwext4 takes paths of nodes.
+fn path_str(path: &CStr) -> VfsResult<&str> {
+    path.to_str().map_err(|_| AxError::InvalidData)
+}
+
+/// Reads the inode at `path`, and returns it with its number.
+fn raw_inode(path: &CStr) -> VfsResult<(u32, bindings::ext4_inode)> {
+    let mut ino = 0;
+    // SAFETY: `ext4_inode` is plain data, which is filled by lwext4
+    let mut inode: bindings::ext4_inode = unsafe { core::mem::zeroed() };
+    match unsafe { bindings::ext4_raw_inode_fill(path.as_ptr(), &mut ino, &mut inode) } {
+        0 => Ok((ino, inode)),
+        e => Err(ext4_err(e)),
+    }
+}
+
+/// Sets the change time of the inode at `path` to now.
+fn set_ctime_now(path: &CStr) -> VfsResult {
+    let now = axhal::time::wall_time().as_secs() as u32;
+    match unsafe { bindings::ext4_ctime_set(path.as_ptr(), now) } {
+        0 => Ok(()),
+        e => Err(ext4_err(e)),
+    }
+}
+
+/// Looks up the inode at `path` through the directory index of lwext4, and
+/// returns its type.
+fn inode_type(path: &str) -> VfsResult<InodeTypes> {
+    let (_, inode) = raw_inode(&c_path(path)?)?;
+    Ok(match u16::from_le(inode.mode) & 0xf000 {
+        0x1000 => InodeTypes::EXT4_DE_FIFO,
+        0x2000 => InodeTypes::EXT4_DE_CHRDEV,
+        0x4000 => InodeTypes::EXT4_DE_DIR,
+        0x6000 => InodeTypes::EXT4_DE_BLKDEV,
+        0xa000 => InodeTypes::EXT4_DE_SYMLINK,
+        0xc000 => InodeTypes::EXT4_DE_SOCK,
+        _ => InodeTypes::EXT4_DE_REG_FILE,
+    })
+}
+
+/// Reads the target of the symbolic link at `path`.
+fn read_link(path: &str) -> VfsResult<String> {
+    let mut buf = vec![0; 4096];
+    let mut link = Ext4File::new(path, InodeTypes::EXT4_DE_SYMLINK);
+    let len = link.readlink(path, &mut buf).map_err(ext4_err)?;
+    buf.truncate(len as usize);
+    String::from_utf8(buf).map_err(|_| AxError::InvalidData)
+}
+
 pub struct FileWrapper(Mutex<Ext4File>);
 
 unsafe impl Send for FileWrapper {}
@@ -66,31 +230,144 @@ impl FileWrapper {
         Self(Mutex::new(Ext4File::new(path, types)))
     }
 
-    fn path_deal_with(&self, path: &str) -> String {
-        if path.starts_with('/') {
-            warn!("path_deal_with: {}", path);
+    /// Returns the absolute path of this node in the filesystem.
+    fn path(&self) -> VfsResult<String> {
+        Ok(path_str(&self.0.lock().get_path())?.into())
+    }
+
+    /// Resolves `path` relative to this node, component by component.
+    ///
+    /// Each component is looked up by its inode in the directory resolved so
+    /// far, `..` goes to the parent of that directory, and symbolic links
+    /// are followed except in the last component. Absolute link targets are
+    /// resolved from the root of this filesystem.
+    ///
+    /// Returns the absolute path and the type of the node.
+    fn resolve(&self, path: &str) -> VfsResult<(String, InodeTypes)> {
+        let mut ty = self.0.lock().get_type();
+        let own = self.path()?;
+        let mut comps: Vec<String> = own
+            .split('/')
+            .filter(|c| !c.is_empty())
+            .map(String::from)
+            .collect();
+        let mut pending: VecDeque<String> = path.split('/').map(String::from).collect();
+        let mut links = 0;
+        while let Some(comp) = pending.pop_front() {
+            match comp.as_str() {
+                "" | "." => continue,
+                ".." => {
+                    if ty != InodeTypes::EXT4_DE_DIR {
+                        return Err(VfsError::NotADirectory);
+                    }
+                    comps.pop();
+                    continue;
+                }
+                _ => {}
+            }
+            if ty != InodeTypes::EXT4_DE_DIR {
+                return Err(VfsError::NotADirectory);
+            }
+            let entry = child_of(&join_path(&comps), &comp);
+            let entry_ty = inode_type(&entry)?;
+            if entry_ty == InodeTypes::EXT4_DE_SYMLINK && pending.iter().any(|c| !c.is_empty()) {
+                links += 1;
+                if links > MAX_SYMLINKS {
+                    return ax_err!(InvalidInput, "too many levels of symbolic links");
+                }
+                let target = read_link(&entry)?;
+                if target.starts_with('/') {
+                    comps.clear();
+                }
+                for c in target.split('/').rev() {
+                    pending.push_front(c.into());
+                }
+                continue;
+            }
+            comps.push(comp);
+            ty = entry_ty;
         }
-        let p = path.trim_matches('/'); // 首尾去除
-        if p.is_empty() || p == "." {
-            return String::new();
+        Ok((join_path(&comps), ty))
+    }
+
+    /// Returns the absolute path of the entry `path` relative to this node,
+    /// whose parent directory must exist. The entry itself need not exist.
+    ///
+    /// Returns an empty string if `path` refers to this node itself.
+    fn entry_path(&self, path: &str) -> VfsResult<String> {
+        let path = path.trim_end_matches('/');
+        let (parent, name) = path.rsplit_once('/').unwrap_or(("", path));
+        let fpath = match name {
+            "" | "." | ".." => self.resolve(path)?.0,
+            _ => {
+                let (dir, ty) = self.resolve(parent)?;
+                if ty != InodeTypes::EXT4_DE_DIR {
+                    return Err(VfsError::NotADirectory);
+                }
+                child_of(&dir, name)
+            }
+        };
+        if fpath == self.path()? {
+            return Ok(String::new());
         }
+        debug!("resolved full path: {}", fpath);
+        Ok(fpath)
+    }
 
-        if let Some(rest) = p.strip_prefix("./") {
-            //if starts with "./"
-            return self.path_deal_with(rest);
+    /// Reads the inode of this node, and returns it with its number.
+    fn raw_inode(&self) -> VfsResult<(u32, bindings::ext4_inode)> {
+        raw_inode(&self.0.lock().get_path())
+    }
+
+    /// Sets the access and modification times of this node, leaving the ones
+    /// that are `None` unchanged, and its change time to now.
+    ///
+    /// lwext4 keeps only the seconds of the times.
+    fn set_times(&self, accessed: Option<Duration>, modified: Option<Duration>) -> VfsResult {
+        let path = self.0.lock().get_path();
+        if let Some(time) = accessed {
+            match unsafe { bindings::ext4_atime_set(path.as_ptr(), time.as_secs() as u32) } {
+                0 => {}
+                e => return Err(ext4_err(e)),
+            }
         }
-        let rest_p = p.replace("//", "/");
-        if p != rest_p {
-            return self.path_deal_with(&rest_p);
+        if let Some(time) = modified {
+            match unsafe { bindings::ext4_mtime_set(path.as_ptr(), time.as_secs() as u32) } {
+                0 => {}
+                e => return Err(ext4_err(e)),
+            }
         }
+        set_ctime_now(&path)
+    }
 
-        //Todo ? ../
-        //注：lwext4创建文件必须提供文件path的绝对路径
-        let file = self.0.lock();
-        let path = file.get_path();
-        let fpath = String::from(path.to_str().unwrap().trim_end_matches('/')) + "/" + p;
-        info!("dealt with full path: {}", fpath.as_str());
-        fpath
+    /// Sets the permissions of this node, keeping its type, and its change
+    /// time to now.
+    fn set_perm(&self, perm: VfsNodePerm) -> VfsResult {
+        let path = self.0.lock().get_path();
+        match unsafe { bindings::ext4_mode_set(path.as_ptr(), perm.bits() as u32) } {
+            0 => {}
+            e => return Err(ext4_err(e)),
+        }
+        set_ctime_now(&path)
+    }
+
+    /// Creates a hard link `new_path` to the file `old_path`, both relative
+    /// to this directory, and increases the link count of the inode.
+    fn link(&self, old_path: &str, new_path: &str) -> VfsResult {
+        info!("link ext4fs: {} -> {}", new_path, old_path);
+        let (old_path, ty) = self.resolve(old_path)?;
+        if ty == InodeTypes::EXT4_DE_DIR {
+            return ax_err!(PermissionDenied, "cannot link directories");
+        }
+        let new_path = self.entry_path(new_path)?;
+        if new_path.is_empty() {
+            return ax_err!(AlreadyExists);
+        }
+        let (old_path, new_path) = (c_path(&old_path)?, c_path(&new_path)?);
+        match unsafe { bindings::ext4_flink(old_path.as_ptr(), new_path.as_ptr()) } {
+            0 => Ok(()),
+            e => Err(ext4_err(e)),
+        }
     }
 }
 
@@ -119,9 +396,8 @@ impl VfsNodeOps for FileWrapper {
 
         let size = if vtype == VfsNodeType::File {
             let path = file.get_path();
-            let path = path.to_str().unwrap();
-            file.file_open(path, O_RDONLY)
-                .map_err(|e| <i32 as TryInto<AxError>>::try_into(e).unwrap())?;
+            file.file_open(path_str(&path)?, O_RDONLY)
+                .map_err(ext4_err)?;
             let fsize = file.file_size();
             let _ = file.file_close();
             fsize
@@ -143,7 +419,7 @@ impl VfsNodeOps for FileWrapper {
 
     fn create(&self, path: &str, ty: VfsNodeType) -> VfsResult {
         info!("create {:?} on Ext4fs: {}", ty, path);
-        let fpath = self.path_deal_with(path);
+        let fpath = self.entry_path(path)?;
         let fpath = fpath.as_str();
         if fpath.is_empty() {
             return Ok(());
@@ -164,36 +440,36 @@ impl VfsNodeOps for FileWrapper {
             Ok(())
         } else {
             if types == InodeTypes::EXT4_DE_DIR {
-                file.dir_mk(fpath)
-                    .map(|_v| ())
-                    .map_err(|e| e.try_into().unwrap())
+                file.dir_mk(fpath).map(|_v| ()).map_err(ext4_err)
             } else {
                 file.file_open(fpath, O_WRONLY | O_CREAT | O_TRUNC)
-                    .expect("create file failed");
-                file.file_close()
-                    .map(|_v| ())
-                    .map_err(|e| e.try_into().unwrap())
+                    .map_err(ext4_err)?;
+                file.file_close().map(|_v| ()).map_err(ext4_err)
             }
         }
     }
 
     fn remove(&self, path: &str) -> VfsResult {
         info!("remove ext4fs: {}", path);
-        let fpath = self.path_deal_with(path);
+        let fpath = self.entry_path(path)?;
         let fpath = fpath.as_str();
 
         assert!(!fpath.is_empty()); // already check at `root.rs`
 
         let mut file = self.0.lock();
         if file.check_inode_exist(fpath, InodeTypes::EXT4_DE_DIR) {
-            // Recursive directory remove
-            file.dir_rm(fpath)
-                .map(|_v| ())
-                .map_err(|e| e.try_into().unwrap())
+            // `dir_rm` removes the directory recursively
+            let dir = Ext4File::new(fpath, InodeTypes::EXT4_DE_DIR);
+            let (names, _) = dir.lwext4_dir_entries().map_err(ext4_err)?;
+            if names
+                .iter()
+                .any(|n| n.as_slice() != b"." && n.as_slice() != b"..")
+            {
+                return Err(VfsError::DirectoryNotEmpty);
+            }
+            file.dir_rm(fpath).map(|_v| ()).map_err(ext4_err)
         } else {
-            file.file_remove(fpath)
-                .map(|_v| ())
-                .map_err(|e| e.try_into().unwrap())
+            file.file_remove(fpath).map(|_v| ()).map_err(ext4_err)
         }
     }
 
@@ -203,7 +479,7 @@ impl VfsNodeOps for FileWrapper {
         let file = self.0.lock();
         if file.get_type() == InodeTypes::EXT4_DE_DIR {
             let path = file.get_path();
-            let path = path.to_str().unwrap();
+            let path = path.to_str().ok()?;
             info!("Get the parent dir of {}", path);
             let path = path.trim_end_matches('/').trim_end_matches(|c| c != '/');
             if !path.is_empty() {
@@ -216,7 +492,7 @@ impl VfsNodeOps for FileWrapper {
     /// Read directory entries into `dirents`, starting from `start_idx`.
     fn read_dir(&self, start_idx: usize, dirents: &mut [VfsDirEntry]) -> VfsResult<usize> {
         let file = self.0.lock();
-        let (name, inode_type) = file.lwext4_dir_entries().unwrap();
+        let (name, inode_type) = file.lwext4_dir_entries().map_err(ext4_err)?;
 
         let mut name_iter = name.iter().skip(start_idx);
         let mut inode_type_iter = inode_type.iter().skip(start_idx);
@@ -238,8 +514,9 @@ impl VfsNodeOps for FileWrapper {
                         unreachable!()
                     };
 
-                    *out_entry =
-                        VfsDirEntry::new(core::str::from_utf8(iname.unwrap()).unwrap(), ty);
+                    let name =
+                        core::str::from_utf8(iname.unwrap()).map_err(|_| AxError::InvalidData)?;
+                    *out_entry = VfsDirEntry::new(name, ty);
                 }
                 _ => return Ok(i),
             }
@@ -253,55 +530,36 @@ impl VfsNodeOps for FileWrapper {
     fn lookup(self: Arc<Self>, path: &str) -> VfsResult<VfsNodeRef> {
         trace!("lookup ext4fs: {:?}, {}", self.0.lock().get_path(), path);
 
-        let fpath = self.path_deal_with(path);
-        let fpath = fpath.as_str();
-        if fpath.is_empty() {
+        let (fpath, ty) = self.resolve(path)?;
+        if fpath == self.path()? {
             return Ok(self.clone());
         }
-
-        /////////
-        let mut file = self.0.lock();
-        if file.check_inode_exist(fpath, InodeTypes::EXT4_DE_DIR) {
-            trace!("lookup new DIR FileWrapper");
-            Ok(Arc::new(Self::new(fpath, InodeTypes::EXT4_DE_DIR)))
-        } else if file.check_inode_exist(fpath, InodeTypes::EXT4_DE_REG_FILE) {
-            trace!("lookup new FILE FileWrapper");
-            Ok(Arc::new(Self::new(fpath, InodeTypes::EXT4_DE_REG_FILE)))
-        } else if file.check_inode_exist(fpath, InodeTypes::EXT4_DE_SYMLINK) {
-            trace!("lookup new SYMLINK FileWrapper");
-            Ok(Arc::new(Self::new(fpath, InodeTypes::EXT4_DE_SYMLINK)))
-        } else {
-            Err(VfsError::NotFound)
-        }
+        Ok(Arc::new(Self::new(&fpath, ty)))
     }
 
     fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
         let mut file = self.0.lock();
         let path = file.get_path();
-        let path = path.to_str().unwrap();
-        file.file_open(path, O_RDONLY)
-            .map_err(|e| <i32 as TryInto<AxError>>::try_into(e).unwrap())?;
+        file.file_open(path_str(&path)?, O_RDONLY)
+            .map_err(ext4_err)?;
 
-        file.file_seek(offset as i64, SEEK_SET)
-            .map_err(|e| <i32 as TryInto<AxError>>::try_into(e).unwrap())?;
+        file.file_seek(offset as i64, SEEK_SET).map_err(ext4_err)?;
         let r = file.file_read(buf);
 
         let _ = file.file_close();
-        r.map_err(|e| e.try_into().unwrap())
+        r.map_err(ext4_err)
     }
 
     fn write_at(&self, offset: u64, buf: &[u8]) -> VfsResult<usize> {
         let mut file = self.0.lock();
         let path = file.get_path();
-        let path = path.to_str().unwrap();
-        file.file_open(path, O_RDWR)
-            .map_err(|e| <i32 as TryInto<AxError>>::try_into(e).unwrap())?;
+        file.file_open(path_str(&path)?, O_RDWR).map_err(ext4_err)?;
 
         // Check if we need to extend the file first
         let current_size = file.file_size() as u64;
         if offset > current_size {
             file.file_seek(current_size as i64, SEEK_SET)
-                .map_err(|e| <i32 as TryInto<AxError>>::try_into(e).unwrap())?;
+                .map_err(ext4_err)?;
 
             let gap_size = offset - current_size;
             let zero_buf = vec![0u8; gap_size.min(4096) as usize];
@@ -311,7 +569,7 @@ impl VfsNodeOps for FileWrapper {
                 let write_size = remaining.min(4096);
                 let written = file
                     .file_write(&zero_buf[..write_size as usize])
-                    .map_err(|e| <i32 as TryInto<AxError>>::try_into(e).unwrap())?;
+                    .map_err(ext4_err)?;
                 if written == 0 {
                     break;
                 }
@@ -319,32 +577,34 @@ impl VfsNodeOps for FileWrapper {
             }
         }
 
-        file.file_seek(offset as i64, SEEK_SET)
-            .map_err(|e| <i32 as TryInto<AxError>>::try_into(e).unwrap())?;
+        file.file_seek(offset as i64, SEEK_SET).map_err(ext4_err)?;
         let r = file.file_write(buf);
 
         let _ = file.file_close();
-        r.map_err(|e| e.try_into().unwrap())
+        r.map_err(ext4_err)
     }
 
     fn truncate(&self, size: u64) -> VfsResult {
         let mut file = self.0.lock();
         let path = file.get_path();
-        let path = path.to_str().unwrap();
-        file.file_open(path, O_RDWR)
-            .map_err(|e| <i32 as TryInto<AxError>>::try_into(e).unwrap())?;
+        file.file_open(path_str(&path)?, O_RDWR).map_err(ext4_err)?;
 
         let t = file.file_truncate(size);
 
         let _ = file.file_close();
-        t.map(|_v| ()).map_err(|e| e.try_into().unwrap())
+        t.map(|_v| ()).map_err(ext4_err)
     }
 
     fn rename(&self, src_path: &str, dst_path: &str) -> VfsResult {
+        let src_path = self.entry_path(src_path)?;
+        let dst_path = self.entry_path(dst_path)?;
+        if src_path.is_empty() || dst_path.is_empty() {
+            return ax_err!(InvalidInput, "cannot rename a directory to itself");
+        }
         let mut file = self.0.lock();
-        file.file_rename(src_path, dst_path)
+        file.file_rename(&src_path, &dst_path)
             .map(|_v| ())
-            .map_err(|e| e.try_into().unwrap())
+            .map_err(ext4_err)
     }
 
     fn as_any(&self) -> &dyn core::any::Any {
@@ -352,17 +612,28 @@ impl VfsNodeOps for FileWrapper {
     }
 
     fn symlink(&self, target: &str, path: &str) -> VfsResult {
+        let fpath = self.entry_path(path)?;
+        if fpath.is_empty() {
+            return ax_err!(AlreadyExists);
+        }
         let mut file = self.0.lock();
-        file.create_symlink(target, path)
+        file.create_symlink(target, &fpath)
             .map(|_v| ())
-            .map_err(|e| e.try_into().unwrap())
+            .map_err(ext4_err)
     }
 
+    /// Reads the target of this symbolic link, or of the symbolic link at
+    /// `path` relative to this directory.
     fn readlink(&self, path: &str, buf: &mut [u8]) -> VfsResult<usize> {
+        let fpath = if self.is_symlink() {
+            self.path()?
+        } else {
+            self.resolve(path)?.0
+        };
         let mut file = self.0.lock();
-        file.readlink(path, buf)
+        file.readlink(&fpath, buf)
             .map(|len| len as usize)
-            .map_err(|e| e.try_into().unwrap())
+            .map_err(ext4_err)
     }
 
     fn is_symlink(&self) -> bool {
@@ -372,9 +643,7 @@ impl VfsNodeOps for FileWrapper {
 
     fn fsync(&self) -> VfsResult {
         let mut file = self.0.lock();
-        file.file_cache_flush()
-            .map(|_v| ())
-            .map_err(|e| e.try_into().unwrap())
+        file.file_cache_flush().map(|_v| ()).map_err(ext4_err)
     }
 }
 
@@ -391,40 +660,20 @@ impl KernelDevOp for Disk {
     //type DevType = Box<Disk>;
     type DevType = Disk;
 
-    fn read(dev: &mut Disk, mut buf: &mut [u8]) -> Result<usize, i32> {
+    fn read(dev: &mut Disk, buf: &mut [u8]) -> Result<usize, i32> {
         trace!("READ block device buf={}", buf.len());
-        let mut read_len = 0;
-        while !buf.is_empty() {
-            match dev.read_one(buf) {
-                Ok(0) => break,
-                Ok(n) => {
-                    let tmp = buf;
-                    buf = &mut tmp[n..];
-                    read_len += n;
-                }
-                Err(_e) => return Err(-1),
-            }
-        }
+        let read_len = dev.read_data(buf).map_err(|_| -1)?;
         trace!("READ rt len={}", read_len);
         Ok(read_len)
     }
-    fn write(dev: &mut Self::DevType, mut buf: &[u8]) -> Result<usize, i32> {
+    fn write(dev: &mut Self::DevType, buf: &[u8]) -> Result<usize, i32> {
         trace!("WRITE block device buf={}", buf.len());
-        let mut write_len = 0;
-        while !buf.is_empty() {
-            match dev.write_one(buf) {
-                Ok(0) => break,
-                Ok(n) => {
-                    buf = &buf[n..];
-                    write_len += n;
-                }
-                Err(_e) => return Err(-1),
-            }
-        }
+        let write_len = dev.write_data(buf).map_err(|_| -1)?;
         trace!("WRITE rt len={}", write_len);
         Ok(write_len)
     }
-    fn flush(_dev: &mut Self::DevType) -> Result<usize, i32> {
+    fn flush(dev: &mut Self::DevType) -> Result<usize, i32> {
+        dev.flush().map_err(|_| -1)?;
         Ok(0)
     }
     fn seek(dev: &mut Disk, off: i64, whence: i32) -> Result<i64, i32> {
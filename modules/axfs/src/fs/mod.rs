@@ -1,16 +1,190 @@
-cfg_if::cfg_if! {
-    if #[cfg(feature = "myfs")] {
-        pub mod myfs;
-    } else if #[cfg(feature = "lwext4_rs")] {
-        pub mod lwext4_rust;
-    } else if #[cfg(feature = "fatfs")] {
-        pub mod fatfs;
+use alloc::sync::Arc;
+use core::time::Duration;
+
+use axerrno::ax_err;
+use axfs_vfs::{VfsNodeOps, VfsNodePerm, VfsNodeRef, VfsOps, VfsResult};
+
+use crate::fops::{FileTimes, FsStat};
+
+#[cfg(feature = "myfs")]
+pub mod myfs;
+
+#[cfg(feature = "lwext4_rs")]
+pub mod lwext4_rust;
+
+#[cfg(feature = "fatfs")]
+pub mod fatfs;
+
+#[cfg(feature = "devfs")]
+pub mod devfs;
+
+#[cfg(any(
+    feature = "ramfs",
+    feature = "procfs",
+    feature = "sysfs",
+    feature = "overlayfs",
+    feature = "initramfs"
+))]
+pub mod ramfs;
+
+#[cfg(feature = "ramfs")]
+pub mod tmpfs;
+
+#[cfg(feature = "procfs")]
+pub mod procfs;
+
+#[cfg(feature = "overlayfs")]
+pub mod overlayfs;
+
+/// A mountable filesystem, with the operations that [`VfsOps`] lacks.
+///
+/// All operations have default implementations for filesystems that do not
+/// support them.
+pub(crate) trait FileSystem: VfsOps {
+    /// Returns the timestamps of `node`, which is a node of this filesystem.
+    fn node_times(&self, _node: &dyn VfsNodeOps) -> Option<FileTimes> {
+        None
+    }
+
+    /// Sets the access and modification times of `node`, which is a node of
+    /// this filesystem, leaving the ones that are `None` unchanged.
+    ///
+    /// Filesystems that do not keep timestamps ignore it.
+    fn set_node_times(
+        &self,
+        _node: &dyn VfsNodeOps,
+        _accessed: Option<Duration>,
+        _modified: Option<Duration>,
+    ) -> VfsResult {
+        Ok(())
+    }
+
+    /// Sets the permissions of `node`, which is a node of this filesystem.
+    fn set_node_perm(&self, _node: &dyn VfsNodeOps, _perm: VfsNodePerm) -> VfsResult {
+        ax_err!(Unsupported, "permissions cannot be changed")
+    }
+
+    /// Returns the number of hard links to `node`, which is a node of this
+    /// filesystem.
+    fn node_nlink(&self, _node: &dyn VfsNodeOps) -> Option<u64> {
+        None
+    }
+
+    /// Returns the inode number of `node`, which is a node of this filesystem.
+    ///
+    /// It identifies the file across all its paths and hard links, for as
+    /// long as the file exists. Without it, files are identified by the ids
+    /// of their [persistent nodes](Self::persistent_node), or by their paths.
+    fn node_ino(&self, _node: &dyn VfsNodeOps) -> Option<u64> {
+        None
+    }
+
+    /// Returns the node that represents the file of `node` for as long as the
+    /// file exists, for filesystems without inode numbers that keep a node
+    /// per file, e.g., ramfs.
+    ///
+    /// Such files are identified by ids allocated to those nodes, which are
+    /// never reused, rather than by their paths.
+    fn persistent_node(&self, _node: &VfsNodeRef) -> Option<VfsNodeRef> {
+        None
+    }
+
+    /// Returns the statistics of this filesystem, e.g., its capacity and free
+    /// space.
+    fn statfs(&self) -> VfsResult<FsStat> {
+        ax_err!(Unsupported, "statfs is not supported")
+    }
+
+    /// Whether data of regular files should be kept in the page cache, which
+    /// is the case for filesystems on block devices.
+    fn page_cached(&self) -> bool {
+        false
     }
 
+    /// Creates a hard link at `new_path` to the file at `old_path`, both
+    /// relative to the root of this filesystem.
+    ///
+    /// Filesystems without hard links refuse it with `EPERM`, like Linux.
+    fn link(&self, _old_path: &str, _new_path: &str) -> VfsResult {
+        ax_err!(PermissionDenied, "hard links are not supported")
+    }
+}
+
+/// A filesystem that only implements [`VfsOps`], e.g., provided by users.
+pub(crate) struct ForeignFs(pub Arc<dyn VfsOps>);
+
+impl VfsOps for ForeignFs {
+    fn mount(&self, path: &str, mount_point: VfsNodeRef) -> VfsResult {
+        self.0.mount(path, mount_point)
+    }
+
+    fn umount(&self) -> VfsResult {
+        self.0.umount()
+    }
+
+    fn root_dir(&self) -> VfsNodeRef {
+        self.0.root_dir()
+    }
+}
+
+impl FileSystem for ForeignFs {}
+
+/// Returns the address of `node`, which identifies it while it is alive.
+pub(crate) fn node_addr(node: &dyn VfsNodeOps) -> u64 {
+    node as *const dyn VfsNodeOps as *const () as usize as u64
+}
+
+/// Returns the statistics of a virtual filesystem of type `fs_type`, whose
+/// capacity is reported as 0, like Linux does for ramfs and procfs.
+#[cfg(any(
+    feature = "devfs",
+    feature = "ramfs",
+    feature = "procfs",
+    feature = "sysfs",
+    feature = "overlayfs",
+    feature = "initramfs"
+))]
+fn virtual_statfs(fs_type: u64) -> FsStat {
+    FsStat {
+        fs_type,
+        block_size: 4096,
+        name_max: 255,
+        ..Default::default()
+    }
 }
 
 #[cfg(feature = "devfs")]
-pub use axfs_devfs as devfs;
+impl FileSystem for devfs::DeviceFileSystem {
+    fn persistent_node(&self, node: &VfsNodeRef) -> Option<VfsNodeRef> {
+        Some(node.clone())
+    }
 
-#[cfg(feature = "ramfs")]
-pub mod ramfs;
+    /// Reports the type of tmpfs, which devtmpfs of Linux is.
+    fn statfs(&self) -> VfsResult<FsStat> {
+        Ok(virtual_statfs(0x0102_1994))
+    }
+}
+
+#[cfg(any(
+    feature = "ramfs",
+    feature = "procfs",
+    feature = "sysfs",
+    feature = "overlayfs",
+    feature = "initramfs"
+))]
+impl FileSystem for ramfs::RamFileSystem {
+    fn persistent_node(&self, node: &VfsNodeRef) -> Option<VfsNodeRef> {
+        Some(node.clone())
+    }
+
+    fn statfs(&self) -> VfsResult<FsStat> {
+        Ok(virtual_statfs(0x8584_58f6)) // RAMFS_MAGIC
+    }
+}
+
+#[cfg(feature = "procfs")]
+impl FileSystem for procfs::ProcFileSystem {
+    fn statfs(&self) -> VfsResult<FsStat> {
+        Ok(virtual_statfs(0x9fa0)) // PROC_SUPER_MAGIC
+    }
+}
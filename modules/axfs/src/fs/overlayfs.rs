@@ -0,0 +1,484 @@
+//! An overlay filesystem, which layers a writable upper filesystem over a
+//! read-only lower one.
+//!
+//! Paths are looked up in the upper filesystem first, and then in the lower
+//! one. A lower file is copied up to the upper filesystem when it is written.
+//! Removing a lower file or directory leaves a whiteout, an empty file named
+//! `.wh.<name>` in the upper directory that hides it. A directory created over
+//! a whiteout is opaque, marked by a `.wh..wh..opq` file in it, so that the
+//! lower directory of the same name stays hidden. Directory listings merge both
+//! layers, without the whiteouts and the entries they hide.
+//!
+//! The lower filesystem is never written. Renaming a directory that is merged
+//! with a lower one is not supported.
+
+use alloc::{collections::BTreeSet, format, string::String, sync::Arc, vec, vec::Vec};
+use axerrno::ax_err;
+use axfs_vfs::{
+    VfsDirEntry, VfsError, VfsNodeAttr, VfsNodeOps, VfsNodeRef, VfsNodeType, VfsOps, VfsResult,
+};
+use axsync::Mutex;
+
+use super::FileSystem;
+use crate::fops::{FileTimes, FsStat};
+
+const WHITEOUT_PREFIX: &str = ".wh.";
+const OPAQUE_MARKER: &str = ".wh..wh..opq";
+/// Size of the buffer to copy files up.
+const COPY_BUF_SIZE: usize = 4096;
+
+/// An overlay filesystem.
+pub struct OverlayFileSystem {
+    layers: Arc<Layers>,
+    root: Arc<OverlayNode>,
+}
+
+struct Layers {
+    upper: Arc<dyn FileSystem>,
+    lower: Arc<dyn FileSystem>,
+    /// Serializes changes to the upper filesystem.
+    lock: Mutex<()>,
+}
+
+/// The nodes at a path in both layers.
+struct Entry {
+    upper: Option<VfsNodeRef>,
+    /// The lower node, unless it is hidden by a whiteout. It may still be
+    /// covered by the upper node.
+    lower: Option<VfsNodeRef>,
+}
+
+/// A file or directory in an overlay filesystem.
+struct OverlayNode {
+    layers: Arc<Layers>,
+    /// The path relative to the root, without `.` and `..` components.
+    path: String,
+    entry: Mutex<Entry>,
+}
+
+fn whiteout_of(name: &str) -> String {
+    format!("{}{}", WHITEOUT_PREFIX, name)
+}
+
+fn is_dir(node: &VfsNodeRef) -> VfsResult<bool> {
+    Ok(node.get_attr()?.is_dir())
+}
+
+fn is_opaque(dir: &VfsNodeRef) -> bool {
+    dir.clone().lookup(OPAQUE_MARKER).is_ok()
+}
+
+/// Splits `path` into its parent directory and the last component.
+fn split_parent(path: &str) -> (&str, &str) {
+    path.rsplit_once('/').unwrap_or(("", path))
+}
+
+/// Joins `rel` to `base`, and resolves `.` and `..` components. Returns
+/// `None` if the path goes above the root.
+fn join(base: &str, rel: &str) -> Option<String> {
+    let mut comps: Vec<&str> = base.split('/').filter(|c| !c.is_empty()).collect();
+    for comp in rel.split('/') {
+        match comp {
+            "" | "." => {}
+            ".." => {
+                comps.pop()?;
+            }
+            _ => comps.push(comp),
+        }
+    }
+    Some(comps.join("/"))
+}
+
+/// Returns the names and types of all entries in `dir`.
+fn list_dir(dir: &VfsNodeRef) -> VfsResult<Vec<(String, VfsNodeType)>> {
+    let mut buf = [const { VfsDirEntry::default() }; 16];
+    let mut entries = Vec::new();
+    loop {
+        let n = dir.read_dir(entries.len(), &mut buf)?;
+        if n == 0 {
+            return Ok(entries);
+        }
+        for e in &buf[..n] {
+            let name = String::from_utf8_lossy(e.name_as_bytes()).into_owned();
+            entries.push((name, e.entry_type()));
+        }
+    }
+}
+
+impl Entry {
+    fn node(&self) -> &VfsNodeRef {
+        self.upper.as_ref().or(self.lower.as_ref()).unwrap()
+    }
+
+    /// Returns the lower directory that is merged into this directory, if
+    /// any.
+    fn merged_lower(&self) -> VfsResult<Option<&VfsNodeRef>> {
+        match (&self.upper, &self.lower) {
+            (None, lower) => Ok(lower.as_ref()),
+            (Some(upper), Some(lower)) if is_dir(upper)? && is_dir(lower)? && !is_opaque(upper) => {
+                Ok(Some(lower))
+            }
+            _ => Ok(None),
+        }
+    }
+}
+
+impl Layers {
+    /// Looks up the nodes at `path` in both layers.
+    fn resolve(&self, path: &str) -> VfsResult<Entry> {
+        let mut entry = Entry {
+            upper: Some(self.upper.root_dir()),
+            lower: Some(self.lower.root_dir()),
+        };
+        for comp in path.split('/').filter(|c| !c.is_empty()) {
+            if comp.starts_with(WHITEOUT_PREFIX) {
+                return Err(VfsError::NotFound);
+            }
+            let upper_dir = entry.upper.as_ref();
+            let upper = upper_dir.and_then(|dir| dir.clone().lookup(comp).ok());
+            let hidden =
+                upper_dir.is_some_and(|dir| dir.clone().lookup(&whiteout_of(comp)).is_ok());
+            let lower = match entry.merged_lower()? {
+                Some(dir) if !hidden => dir.clone().lookup(comp).ok(),
+                _ => None,
+            };
+            if upper.is_none() && lower.is_none() {
+                return Err(VfsError::NotFound);
+            }
+            entry = Entry { upper, lower };
+        }
+        Ok(entry)
+    }
+
+    /// Returns the upper directory at `path`, and creates it and its parents
+    /// if they only exist in the lower filesystem.
+    fn copy_up_dir(&self, path: &str) -> VfsResult<VfsNodeRef> {
+        let root = self.upper.root_dir();
+        if path.is_empty() {
+            return Ok(root);
+        }
+        if let Ok(dir) = root.clone().lookup(path) {
+            return Ok(dir);
+        }
+        self.copy_up_dir(split_parent(path).0)?;
+        root.create(path, VfsNodeType::Dir)?;
+        root.lookup(path)
+    }
+
+    /// Copies the lower file `lower` at `path` up to the upper filesystem.
+    fn copy_up(&self, path: &str, lower: &VfsNodeRef) -> VfsResult<VfsNodeRef> {
+        let root = self.upper.root_dir();
+        if let Ok(upper) = root.clone().lookup(path) {
+            return Ok(upper); // copied up by another node
+        }
+        debug!("copy up at overlayfs: {}", path);
+        self.copy_up_dir(split_parent(path).0)?;
+        root.create(path, VfsNodeType::File)?;
+        let upper = root.lookup(path)?;
+        let mut buf = vec![0; COPY_BUF_SIZE];
+        let mut offset = 0;
+        loop {
+            let n = lower.read_at(offset, &mut buf)?;
+            if n == 0 {
+                return Ok(upper);
+            }
+            upper.write_at(offset, &buf[..n])?;
+            offset += n as u64;
+        }
+    }
+
+    /// Lists the directory `entry`, with the entries of both layers.
+    fn read_dir(&self, entry: &Entry) -> VfsResult<Vec<(String, VfsNodeType)>> {
+        let mut entries = Vec::new();
+        let mut hidden = BTreeSet::new();
+        if let Some(upper) = &entry.upper {
+            for (name, ty) in list_dir(upper)? {
+                match name.strip_prefix(WHITEOUT_PREFIX) {
+                    Some(name) => {
+                        hidden.insert(String::from(name));
+                    }
+                    None => entries.push((name, ty)),
+                }
+            }
+        }
+        if let Some(lower) = entry.merged_lower()? {
+            for (name, ty) in list_dir(lower)? {
+                if !hidden.contains(&name) && !entries.iter().any(|(n, _)| *n == name) {
+                    entries.push((name, ty));
+                }
+            }
+        }
+        Ok(entries)
+    }
+
+    fn create(&self, path: &str, ty: VfsNodeType) -> VfsResult {
+        let (parent, name) = split_parent(path);
+        if name.is_empty() {
+            return Ok(());
+        } else if name.starts_with(WHITEOUT_PREFIX) {
+            return ax_err!(InvalidInput, "reserved name in overlayfs");
+        } else if self.resolve(path).is_ok() {
+            return ax_err!(AlreadyExists);
+        } else if !is_dir(self.resolve(parent)?.node())? {
+            return ax_err!(NotADirectory);
+        }
+        let dir = self.copy_up_dir(parent)?;
+        let whiteout = whiteout_of(name);
+        let has_whiteout = dir.clone().lookup(&whiteout).is_ok();
+        if has_whiteout {
+            dir.remove(&whiteout)?;
+        }
+        let is_dir = matches!(ty, VfsNodeType::Dir);
+        dir.create(name, ty)?;
+        if has_whiteout && is_dir {
+            dir.lookup(name)?.create(OPAQUE_MARKER, VfsNodeType::File)?;
+        }
+        Ok(())
+    }
+
+    fn remove(&self, path: &str) -> VfsResult {
+        let (parent, name) = split_parent(path);
+        if name.is_empty() {
+            return ax_err!(PermissionDenied);
+        }
+        let entry = self.resolve(path)?;
+        if is_dir(entry.node())? {
+            let entries = self.read_dir(&entry)?;
+            if entries.iter().any(|(n, _)| n != "." && n != "..") {
+                return ax_err!(DirectoryNotEmpty);
+            }
+            // only whiteouts are left in the upper directory
+            if let Some(upper) = &entry.upper {
+                for (n, _) in list_dir(upper)? {
+                    if n.starts_with(WHITEOUT_PREFIX) {
+                        upper.remove(&n)?;
+                    }
+                }
+            }
+        }
+        let dir = self.copy_up_dir(parent)?;
+        if entry.upper.is_some() {
+            dir.remove(name)?;
+        }
+        if entry.lower.is_some() {
+            dir.create(&whiteout_of(name), VfsNodeType::File)?;
+        }
+        Ok(())
+    }
+
+    fn rename(&self, src_path: &str, dst_path: &str) -> VfsResult {
+        let entry = self.resolve(src_path)?;
+        if is_dir(entry.node())? && (entry.upper.is_none() || entry.merged_lower()?.is_some()) {
+            return ax_err!(Unsupported, "cannot rename lower directories in overlayfs");
+        }
+        let (dst_parent, dst_name) = split_parent(dst_path);
+        if dst_name.starts_with(WHITEOUT_PREFIX) {
+            return ax_err!(InvalidInput, "reserved name in overlayfs");
+        }
+        if self.resolve(dst_path).is_ok() {
+            self.remove(dst_path)?;
+        }
+        if !is_dir(self.resolve(dst_parent)?.node())? {
+            return ax_err!(NotADirectory);
+        }
+        if let (None, Some(lower)) = (&entry.upper, &entry.lower) {
+            self.copy_up(src_path, lower)?;
+        }
+        self.copy_up_dir(dst_parent)?;
+        self.upper.root_dir().rename(src_path, dst_path)?;
+        if entry.lower.is_some() {
+            let (src_parent, src_name) = split_parent(src_path);
+            self.copy_up_dir(src_parent)?
+                .create(&whiteout_of(src_name), VfsNodeType::File)?;
+        }
+        Ok(())
+    }
+}
+
+impl OverlayFileSystem {
+    /// Creates an overlay filesystem with `upper` over `lower`.
+    pub(crate) fn new(upper: Arc<dyn FileSystem>, lower: Arc<dyn FileSystem>) -> Self {
+        let entry = Entry {
+            upper: Some(upper.root_dir()),
+            lower: Some(lower.root_dir()),
+        };
+        let layers = Arc::new(Layers {
+            upper,
+            lower,
+            lock: Mutex::new(()),
+        });
+        Self {
+            root: Arc::new(OverlayNode::new(layers.clone(), String::new(), entry)),
+            layers,
+        }
+    }
+}
+
+impl VfsOps for OverlayFileSystem {
+    fn mount(&self, path: &str, mount_point: VfsNodeRef) -> VfsResult {
+        self.layers.upper.mount(path, mount_point.clone())?;
+        self.layers.lower.mount(path, mount_point)
+    }
+
+    fn umount(&self) -> VfsResult {
+        self.layers.upper.umount()?;
+        self.layers.lower.umount()
+    }
+
+    fn root_dir(&self) -> VfsNodeRef {
+        self.root.clone()
+    }
+}
+
+impl OverlayFileSystem {
+    /// Returns the layer that `node` is currently in, and the node in it.
+    fn layer_of(&self, node: &dyn VfsNodeOps) -> Option<(&Arc<dyn FileSystem>, VfsNodeRef)> {
+        let node = node.as_any().downcast_ref::<OverlayNode>()?;
+        let entry = node.entry.lock();
+        match (&entry.upper, &entry.lower) {
+            (Some(upper), _) => Some((&self.layers.upper, upper.clone())),
+            (None, Some(lower)) => Some((&self.layers.lower, lower.clone())),
+            (None, None) => None,
+        }
+    }
+}
+
+impl FileSystem for OverlayFileSystem {
+    fn node_times(&self, node: &dyn VfsNodeOps) -> Option<FileTimes> {
+        let (fs, node) = self.layer_of(node)?;
+        fs.node_times(node.as_ref())
+    }
+
+    fn node_nlink(&self, node: &dyn VfsNodeOps) -> Option<u64> {
+        let (fs, node) = self.layer_of(node)?;
+        fs.node_nlink(node.as_ref())
+    }
+
+    /// Reports the upper layer, where new files are written to.
+    fn statfs(&self) -> VfsResult<FsStat> {
+        self.layers.upper.statfs()
+    }
+}
+
+impl OverlayNode {
+    fn new(layers: Arc<Layers>, path: String, entry: Entry) -> Self {
+        Self {
+            layers,
+            path,
+            entry: Mutex::new(entry),
+        }
+    }
+
+    /// Returns the node to read from, which is the upper one if the file has
+    /// been copied up, possibly by another node.
+    fn current(&self) -> VfsNodeRef {
+        let mut entry = self.entry.lock();
+        if entry.upper.is_none() {
+            entry.upper = self.layers.upper.root_dir().lookup(&self.path).ok();
+        }
+        entry.node().clone()
+    }
+
+    /// Returns the upper node to write to, and copies the file up if needed.
+    fn upper(&self) -> VfsResult<VfsNodeRef> {
+        let _guard = self.layers.lock.lock();
+        let mut entry = self.entry.lock();
+        if let Some(upper) = &entry.upper {
+            return Ok(upper.clone());
+        } else if is_dir(entry.node())? {
+            return ax_err!(IsADirectory);
+        }
+        let upper = self.layers.copy_up(&self.path, entry.node())?;
+        entry.upper = Some(upper.clone());
+        Ok(upper)
+    }
+
+    fn path_of(&self, rel_path: &str) -> VfsResult<String> {
+        join(&self.path, rel_path).ok_or(VfsError::NotFound)
+    }
+}
+
+impl VfsNodeOps for OverlayNode {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        self.current().get_attr()
+    }
+
+    fn parent(&self) -> Option<VfsNodeRef> {
+        if self.path.is_empty() {
+            return self.layers.upper.root_dir().parent();
+        }
+        let path = split_parent(&self.path).0;
+        let entry = self.layers.resolve(path).ok()?;
+        let node = OverlayNode::new(self.layers.clone(), path.into(), entry);
+        Some(Arc::new(node))
+    }
+
+    fn lookup(self: Arc<Self>, path: &str) -> VfsResult<VfsNodeRef> {
+        debug!("lookup at overlayfs: {}/{}", self.path, path);
+        let path = self.path_of(path)?;
+        if path == self.path {
+            return Ok(self);
+        }
+        let entry = self.layers.resolve(&path)?;
+        Ok(Arc::new(OverlayNode::new(self.layers.clone(), path, entry)))
+    }
+
+    fn read_dir(&self, start_idx: usize, dirents: &mut [VfsDirEntry]) -> VfsResult<usize> {
+        let entry = self.layers.resolve(&self.path)?;
+        if !is_dir(entry.node())? {
+            return ax_err!(NotADirectory);
+        }
+        let entries = self.layers.read_dir(&entry)?;
+        let mut n = 0;
+        for (ent, (name, ty)) in dirents.iter_mut().zip(entries.iter().skip(start_idx)) {
+            *ent = VfsDirEntry::new(name, *ty);
+            n += 1;
+        }
+        Ok(n)
+    }
+
+    fn create(&self, path: &str, ty: VfsNodeType) -> VfsResult {
+        let path = self.path_of(path)?;
+        debug!("create {:?} at overlayfs: {}", ty, path);
+        let _guard = self.layers.lock.lock();
+        self.layers.create(&path, ty)
+    }
+
+    fn remove(&self, path: &str) -> VfsResult {
+        let path = self.path_of(path)?;
+        debug!("remove at overlayfs: {}", path);
+        let _guard = self.layers.lock.lock();
+        self.layers.remove(&path)
+    }
+
+    fn rename(&self, src_path: &str, dst_path: &str) -> VfsResult {
+        let src_path = self.path_of(src_path)?;
+        let dst_path = self.path_of(dst_path)?;
+        debug!("rename at overlayfs: {} -> {}", src_path, dst_path);
+        let _guard = self.layers.lock.lock();
+        self.layers.rename(&src_path, &dst_path)
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        self.current().read_at(offset, buf)
+    }
+
+    fn write_at(&self, offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        self.upper()?.write_at(offset, buf)
+    }
+
+    fn truncate(&self, size: u64) -> VfsResult {
+        self.upper()?.truncate(size)
+    }
+
+    fn fsync(&self) -> VfsResult {
+        match &self.entry.lock().upper {
+            Some(upper) => upper.fsync(),
+            None => Ok(()),
+        }
+    }
+
+    fn as_any(&self) -> &dyn core::any::Any {
+        self
+    }
+}
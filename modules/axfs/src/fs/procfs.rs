@@ -0,0 +1,346 @@
+//! The proc filesystem.
+//!
+//! Static entries such as `/proc/sys/...` are kept in a ramfs, while the
+//! following entries are generated on every read:
+//!
+//! - `/proc/meminfo`, `/proc/cpuinfo`, `/proc/uptime`, `/proc/mounts` and
+//!   `/proc/pagecache`.
+//! - `/proc/<tid>/{stat,status,comm}` of every task, and `/proc/self` for the
+//!   current task, if the `multitask` feature is enabled.
+
+use alloc::{format, string::String, sync::Arc, vec::Vec};
+use axfs_vfs::{VfsDirEntry, VfsNodeAttr, VfsNodeOps, VfsNodeRef, VfsNodeType, VfsOps, VfsResult};
+
+use super::ramfs::{DynamicFile, RamFileSystem};
+
+/// The proc filesystem, with dynamic entries on top of a ramfs.
+pub struct ProcFileSystem {
+    statics: RamFileSystem,
+    root: Arc<ProcRootDir>,
+}
+
+/// The root directory of the proc filesystem.
+struct ProcRootDir {
+    /// The root directory of the static entries.
+    statics: VfsNodeRef,
+}
+
+impl ProcFileSystem {
+    /// Creates a proc filesystem with the static entries in `statics`, and
+    /// adds the generated entries to its root.
+    pub fn new(statics: RamFileSystem) -> VfsResult<Self> {
+        let root = statics.root_dir();
+        let generated: [(&'static str, fn() -> String); 5] = [
+            ("meminfo", meminfo),
+            ("cpuinfo", cpuinfo),
+            ("uptime", uptime),
+            ("mounts", mounts),
+            ("pagecache", pagecache),
+        ];
+        for (name, generate) in generated {
+            root.create(name, VfsNodeType::File)?;
+            root.add_node(name, Arc::new(DynamicFile::new(generate)))?;
+        }
+        Ok(Self {
+            statics,
+            root: Arc::new(ProcRootDir { statics: root }),
+        })
+    }
+}
+
+impl VfsOps for ProcFileSystem {
+    fn mount(&self, path: &str, mount_point: VfsNodeRef) -> VfsResult {
+        self.statics.mount(path, mount_point)
+    }
+
+    fn umount(&self) -> VfsResult {
+        self.statics.umount()
+    }
+
+    fn root_dir(&self) -> VfsNodeRef {
+        self.root.clone()
+    }
+}
+
+impl ProcRootDir {
+    /// Returns the number of entries in the static root directory.
+    fn num_statics(&self) -> VfsResult<usize> {
+        let mut buf = [const { VfsDirEntry::default() }; 16];
+        let mut count = 0;
+        loop {
+            match self.statics.read_dir(count, &mut buf)? {
+                0 => return Ok(count),
+                n => count += n,
+            }
+        }
+    }
+}
+
+impl VfsNodeOps for ProcRootDir {
+    axfs_vfs::impl_vfs_dir_default! {}
+
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        self.statics.get_attr()
+    }
+
+    fn parent(&self) -> Option<VfsNodeRef> {
+        self.statics.parent()
+    }
+
+    fn lookup(self: Arc<Self>, path: &str) -> VfsResult<VfsNodeRef> {
+        let path = path.trim_start_matches('/');
+        let (name, rest) = path.split_once('/').unwrap_or((path, ""));
+        if matches!(name, "" | ".") {
+            return if rest.is_empty() {
+                Ok(self)
+            } else {
+                self.lookup(rest)
+            };
+        }
+        #[cfg(feature = "multitask")]
+        if let Some(tid) = task::task_id(name) {
+            let parent = Arc::downgrade(&self);
+            return Arc::new(task::TaskDir::new(tid, parent)).lookup(rest);
+        }
+        self.statics.clone().lookup(path)
+    }
+
+    /// Lists the static entries, followed by the task directories.
+    fn read_dir(&self, start_idx: usize, dirents: &mut [VfsDirEntry]) -> VfsResult<usize> {
+        let mut n = self.statics.read_dir(start_idx, dirents)?;
+        let skip = (start_idx + n).saturating_sub(self.num_statics()?);
+        for (ent, name) in dirents[n..]
+            .iter_mut()
+            .zip(task_entries().iter().skip(skip))
+        {
+            *ent = VfsDirEntry::new(name, VfsNodeType::Dir);
+            n += 1;
+        }
+        Ok(n)
+    }
+
+    fn create(&self, path: &str, ty: VfsNodeType) -> VfsResult {
+        self.statics.create(path, ty)
+    }
+
+    fn remove(&self, path: &str) -> VfsResult {
+        self.statics.remove(path)
+    }
+
+    fn rename(&self, src_path: &str, dst_path: &str) -> VfsResult {
+        self.statics.rename(src_path, dst_path)
+    }
+}
+
+/// Returns the names of the task directories in the root.
+#[cfg(feature = "multitask")]
+fn task_entries() -> Vec<String> {
+    let tids = axtask::all_tasks()
+        .iter()
+        .map(|t| format!("{}", t.id().as_u64()));
+    core::iter::once("self".into()).chain(tids).collect()
+}
+
+#[cfg(not(feature = "multitask"))]
+fn task_entries() -> Vec<String> {
+    Vec::new()
+}
+
+/// Generates `/proc/meminfo`, in kB like Linux.
+fn meminfo() -> String {
+    // the global allocator manages 4 KiB pages
+    let allocator = axalloc::global_allocator();
+    let free = allocator.available_pages() * 4;
+    let total = free + allocator.used_pages() * 4;
+    let cache = crate::cache::stats();
+    let cached = cache.pages * crate::cache::PAGE_SIZE / 1024;
+    let dirty = cache.dirty * crate::cache::PAGE_SIZE / 1024;
+    let mut output = String::new();
+    for (name, kb) in [
+        ("MemTotal", total),
+        ("MemFree", free),
+        ("MemAvailable", free + cached - dirty),
+        ("Cached", cached),
+        ("Dirty", dirty),
+    ] {
+        output += &format!("{:<16}{:>8} kB\n", format!("{}:", name), kb);
+    }
+    output
+}
+
+/// Generates `/proc/cpuinfo`.
+fn cpuinfo() -> String {
+    (0..axconfig::SMP)
+        .map(|cpu| {
+            format!(
+                "processor\t: {}\narch\t\t: {}\nplatform\t: {}\n\n",
+                cpu,
+                axconfig::ARCH,
+                axconfig::PLATFORM,
+            )
+        })
+        .collect()
+}
+
+/// Generates `/proc/uptime`. The idle time is not accounted.
+fn uptime() -> String {
+    let now = axhal::time::monotonic_time();
+    format!("{}.{:02} 0.00\n", now.as_secs(), now.subsec_millis() / 10)
+}
+
+/// Generates `/proc/mounts`.
+fn mounts() -> String {
+    crate::root::mount_table()
+}
+
+/// Generates `/proc/pagecache`.
+fn pagecache() -> String {
+    let stats = crate::cache::stats();
+    format!(
+        "pages {}\ndirty {}\nhits {}\nmisses {}\nreadahead {}\nevictions {}\nwritebacks {}\n",
+        stats.pages,
+        stats.dirty,
+        stats.hits,
+        stats.misses,
+        stats.readahead,
+        stats.evictions,
+        stats.writebacks,
+    )
+}
+
+#[cfg(feature = "multitask")]
+mod task {
+    use alloc::{
+        format,
+        string::String,
+        sync::{Arc, Weak},
+    };
+    use axfs_vfs::{
+        VfsDirEntry, VfsError, VfsNodeAttr, VfsNodeOps, VfsNodePerm, VfsNodeRef, VfsNodeType,
+        VfsResult,
+    };
+    use axtask::TaskState;
+
+    use super::{DynamicFile, ProcRootDir};
+
+    /// The generated files in a task directory.
+    const TASK_FILES: [(&str, fn(u64) -> String); 3] =
+        [("comm", comm), ("stat", stat), ("status", status)];
+
+    /// The directory `/proc/<tid>` of a task.
+    pub(super) struct TaskDir {
+        tid: u64,
+        parent: Weak<ProcRootDir>,
+    }
+
+    /// Returns the ID of the task named by `name` in `/proc`, if it exists.
+    pub(super) fn task_id(name: &str) -> Option<u64> {
+        let tid = match name {
+            "self" => axtask::current().id().as_u64(),
+            _ => name.parse().ok()?,
+        };
+        axtask::get_task(tid).map(|_| tid)
+    }
+
+    impl TaskDir {
+        pub(super) fn new(tid: u64, parent: Weak<ProcRootDir>) -> Self {
+            Self { tid, parent }
+        }
+    }
+
+    impl VfsNodeOps for TaskDir {
+        axfs_vfs::impl_vfs_dir_default! {}
+
+        fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+            Ok(VfsNodeAttr::new(
+                VfsNodePerm::from_bits_truncate(0o555),
+                VfsNodeType::Dir,
+                0,
+                0,
+            ))
+        }
+
+        fn parent(&self) -> Option<VfsNodeRef> {
+            self.parent.upgrade().map(|p| p as VfsNodeRef)
+        }
+
+        fn lookup(self: Arc<Self>, path: &str) -> VfsResult<VfsNodeRef> {
+            let path = path.trim_start_matches('/');
+            let (name, rest) = path.split_once('/').unwrap_or((path, ""));
+            let node: VfsNodeRef = match name {
+                "" | "." => self.clone(),
+                ".." => self.parent().ok_or(VfsError::NotFound)?,
+                _ => {
+                    let &(_, generate) = TASK_FILES
+                        .iter()
+                        .find(|(file, _)| *file == name)
+                        .ok_or(VfsError::NotFound)?;
+                    if !rest.is_empty() {
+                        return Err(VfsError::NotADirectory);
+                    }
+                    let tid = self.tid;
+                    return Ok(Arc::new(DynamicFile::new(move || generate(tid))));
+                }
+            };
+            if rest.is_empty() {
+                Ok(node)
+            } else {
+                node.lookup(rest)
+            }
+        }
+
+        fn read_dir(&self, start_idx: usize, dirents: &mut [VfsDirEntry]) -> VfsResult<usize> {
+            let entries = [(".", VfsNodeType::Dir), ("..", VfsNodeType::Dir)]
+                .into_iter()
+                .chain(
+                    TASK_FILES
+                        .iter()
+                        .map(|&(name, _)| (name, VfsNodeType::File)),
+                );
+            let mut n = 0;
+            for (ent, (name, ty)) in dirents.iter_mut().zip(entries.skip(start_idx)) {
+                *ent = VfsDirEntry::new(name, ty);
+                n += 1;
+            }
+            Ok(n)
+        }
+    }
+
+    /// Formats `state` like the `State` field of `/proc/<tid>/status`.
+    fn state_of(state: TaskState) -> &'static str {
+        match state {
+            TaskState::Running | TaskState::Ready => "R (running)",
+            TaskState::Blocked => "S (sleeping)",
+            TaskState::Exited => "Z (zombie)",
+        }
+    }
+
+    /// Generates `/proc/<tid>/comm`.
+    fn comm(tid: u64) -> String {
+        axtask::get_task(tid).map_or_else(String::new, |task| format!("{}\n", task.name()))
+    }
+
+    /// Generates `/proc/<tid>/stat`. Fields that are not accounted are zero.
+    fn stat(tid: u64) -> String {
+        let Some(task) = axtask::get_task(tid) else {
+            return String::new();
+        };
+        format!(
+            "{tid} ({}) {} 0 {tid} {tid} 0 -1 0 0 0 0 0 0 0 0 0 0 20 0 1 0 0\n",
+            task.name(),
+            &state_of(task.state())[..1],
+        )
+    }
+
+    /// Generates `/proc/<tid>/status`.
+    fn status(tid: u64) -> String {
+        let Some(task) = axtask::get_task(tid) else {
+            return String::new();
+        };
+        format!(
+            "Name:\t{}\nState:\t{}\nTgid:\t{tid}\nPid:\t{tid}\nPPid:\t0\nThreads:\t1\n",
+            task.name(),
+            state_of(task.state()),
+        )
+    }
+}
@@ -1,4 +1,4 @@
-use alloc::string::String;
+use alloc::{boxed::Box, string::String};
 pub use axfs_ramfs::*;
 use axfs_vfs::{VfsError, VfsNodeAttr, VfsNodeOps, VfsNodePerm, VfsNodeType, VfsResult};
 
@@ -54,3 +54,43 @@ impl VfsNodeOps for InterruptFile {
 
     axfs_vfs::impl_vfs_non_dir_default! {}
 }
+
+/// A read-only virtual file whose content is generated on every read.
+pub struct DynamicFile(Box<dyn Fn() -> String + Send + Sync>);
+
+impl DynamicFile {
+    /// Creates a file whose content is generated by `generate`.
+    pub fn new(generate: impl Fn() -> String + Send + Sync + 'static) -> Self {
+        Self(Box::new(generate))
+    }
+}
+
+impl VfsNodeOps for DynamicFile {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        Ok(VfsNodeAttr::new(
+            VfsNodePerm::from_bits_truncate(0o444),
+            VfsNodeType::File,
+            0,
+            0,
+        ))
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        let output = (self.0)();
+        let bytes = output.as_bytes();
+        let start = bytes.len().min(offset as usize);
+        let len = buf.len().min(bytes.len() - start);
+        buf[..len].copy_from_slice(&bytes[start..start + len]);
+        Ok(len)
+    }
+
+    fn write_at(&self, _offset: u64, _buf: &[u8]) -> VfsResult<usize> {
+        Err(VfsError::PermissionDenied)
+    }
+
+    fn truncate(&self, _size: u64) -> VfsResult {
+        Err(VfsError::Unsupported)
+    }
+
+    axfs_vfs::impl_vfs_non_dir_default! {}
+}
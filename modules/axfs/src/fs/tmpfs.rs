@@ -0,0 +1,364 @@
+//! A ramfs with limits on its size and number of files, like tmpfs.
+//!
+//! The data of regular files is counted against the size limit, and every
+//! file, directory and symbolic link against the inode limit, including the
+//! root directory. Operations that would exceed a limit fail with
+//! [`VfsError::StorageFull`], i.e., `ENOSPC`, instead of exhausting the memory
+//! of the whole system.
+//!
+//! A removed or replaced file is counted until its last opened handle is
+//! closed, as its data is kept for the handles until then.
+
+use alloc::{collections::BTreeMap, sync::Arc};
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use axfs_vfs::{
+    VfsDirEntry, VfsError, VfsNodeAttr, VfsNodeOps, VfsNodePerm, VfsNodeRef, VfsNodeType, VfsOps,
+    VfsResult,
+};
+use axsync::Mutex;
+
+use super::{FileSystem, ramfs::RamFileSystem};
+use crate::fops::FsStat;
+
+/// Magic number of tmpfs in `statfs(2)`.
+const TMPFS_MAGIC: u64 = 0x0102_1994;
+/// Size of the blocks reported by `statfs`, which is the page size.
+const BLOCK_SIZE: u64 = 4096;
+const NAME_MAX: u64 = 255;
+
+/// A ramfs with size and inode limits.
+pub struct TmpFileSystem {
+    inner: RamFileSystem,
+    usage: Arc<Usage>,
+    root: Arc<TmpNode>,
+}
+
+/// The limits of a tmpfs and how much of them are used.
+struct Usage {
+    max_bytes: u64,
+    max_inodes: u64,
+    bytes: AtomicU64,
+    inodes: AtomicU64,
+    /// Serializes changes to the sizes of files, which are counted by the
+    /// sizes before the changes.
+    lock: Mutex<()>,
+    /// Opened files, by the addresses of their ramfs nodes.
+    opened: Mutex<BTreeMap<u64, OpenedFile>>,
+    /// Permissions of the files whose permissions have been changed, by the
+    /// addresses of their ramfs nodes, as ramfs keeps the initial ones.
+    perms: Mutex<BTreeMap<u64, VfsNodePerm>>,
+}
+
+/// The opened handles of a file.
+#[derive(Default)]
+struct OpenedFile {
+    count: usize,
+    /// Whether the file has been removed, and is to be freed when the last
+    /// handle is closed.
+    removed: bool,
+}
+
+/// A node of a tmpfs, which counts the changes of the ramfs node it wraps.
+struct TmpNode {
+    inner: VfsNodeRef,
+    usage: Arc<Usage>,
+    /// Whether it is the root directory, whose parent is not in the tmpfs.
+    is_root: bool,
+}
+
+impl Usage {
+    /// Adds `n` to `counter`, or fails with `StorageFull` if it would exceed
+    /// `max`.
+    fn reserve(counter: &AtomicU64, max: u64, n: u64) -> VfsResult {
+        counter
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |used| {
+                used.checked_add(n).filter(|&used| used <= max)
+            })
+            .map(|_| ())
+            .map_err(|_| VfsError::StorageFull)
+    }
+
+    fn reserve_bytes(&self, n: u64) -> VfsResult {
+        Self::reserve(&self.bytes, self.max_bytes, n)
+    }
+
+    fn release_bytes(&self, n: u64) {
+        self.bytes.fetch_sub(n, Ordering::AcqRel);
+    }
+
+    fn reserve_inode(&self) -> VfsResult {
+        Self::reserve(&self.inodes, self.max_inodes, 1)
+    }
+
+    fn release_inode(&self) {
+        self.inodes.fetch_sub(1, Ordering::AcqRel);
+    }
+
+    /// Counts the inode and the data of `node` as freed.
+    fn free(&self, node: &dyn VfsNodeOps) -> VfsResult {
+        let attr = node.get_attr()?;
+        self.perms.lock().remove(&super::node_addr(node));
+        self.release_inode();
+        if attr.is_file() {
+            self.release_bytes(attr.size());
+        }
+        Ok(())
+    }
+
+    /// Frees `node`, which has been removed from the tree, or defers it to
+    /// the last close of the node if it is still opened.
+    fn removed(&self, node: &dyn VfsNodeOps) -> VfsResult {
+        match self.opened.lock().get_mut(&super::node_addr(node)) {
+            Some(file) => {
+                file.removed = true;
+                Ok(())
+            }
+            None => self.free(node),
+        }
+    }
+
+    /// Runs `f`, which changes the size of a file from `old_size` to
+    /// `new_size`, and counts the change if it succeeds.
+    fn resize<T>(
+        &self,
+        old_size: u64,
+        new_size: u64,
+        f: impl FnOnce() -> VfsResult<T>,
+    ) -> VfsResult<T> {
+        if new_size > old_size {
+            self.reserve_bytes(new_size - old_size)?;
+            f().inspect_err(|_| self.release_bytes(new_size - old_size))
+        } else {
+            let ret = f()?;
+            self.release_bytes(old_size - new_size);
+            Ok(ret)
+        }
+    }
+}
+
+impl TmpFileSystem {
+    /// Creates a tmpfs of at most `max_bytes` bytes of file data and
+    /// `max_inodes` files. A limit of 0 means no limit.
+    pub fn new(max_bytes: u64, max_inodes: u64) -> Self {
+        let inner = RamFileSystem::new();
+        let limit = |max| if max == 0 { u64::MAX } else { max };
+        let usage = Arc::new(Usage {
+            max_bytes: limit(max_bytes),
+            max_inodes: limit(max_inodes),
+            bytes: AtomicU64::new(0),
+            inodes: AtomicU64::new(1), // the root directory
+            lock: Mutex::new(()),
+            opened: Mutex::new(BTreeMap::new()),
+            perms: Mutex::new(BTreeMap::new()),
+        });
+        let root = Arc::new(TmpNode {
+            inner: inner.root_dir(),
+            usage: usage.clone(),
+            is_root: true,
+        });
+        Self { inner, usage, root }
+    }
+}
+
+impl VfsOps for TmpFileSystem {
+    fn mount(&self, path: &str, mount_point: VfsNodeRef) -> VfsResult {
+        self.inner.mount(path, mount_point)
+    }
+
+    fn umount(&self) -> VfsResult {
+        self.inner.umount()
+    }
+
+    fn root_dir(&self) -> VfsNodeRef {
+        self.root.clone()
+    }
+}
+
+impl FileSystem for TmpFileSystem {
+    /// Identifies files by the ramfs nodes, as nodes of the tmpfs are created
+    /// on every lookup.
+    fn persistent_node(&self, node: &VfsNodeRef) -> Option<VfsNodeRef> {
+        let node = node.as_any().downcast_ref::<TmpNode>()?;
+        Some(node.inner.clone())
+    }
+
+    fn set_node_perm(&self, node: &dyn VfsNodeOps, perm: VfsNodePerm) -> VfsResult {
+        let node = node
+            .as_any()
+            .downcast_ref::<TmpNode>()
+            .ok_or(VfsError::Unsupported)?;
+        let addr = super::node_addr(node.inner.as_ref());
+        self.usage.perms.lock().insert(addr, perm);
+        Ok(())
+    }
+
+    /// Reports the limits as the capacity. Without a size limit, the free
+    /// memory of the system is reported as free space.
+    fn statfs(&self) -> VfsResult<FsStat> {
+        let usage = &self.usage;
+        let used = usage.bytes.load(Ordering::Acquire).div_ceil(BLOCK_SIZE);
+        let blocks = if usage.max_bytes == u64::MAX {
+            used + axalloc::global_allocator().available_pages() as u64
+        } else {
+            usage.max_bytes.div_ceil(BLOCK_SIZE)
+        };
+        let inodes = usage.inodes.load(Ordering::Acquire);
+        Ok(FsStat {
+            fs_type: TMPFS_MAGIC,
+            block_size: BLOCK_SIZE,
+            blocks,
+            blocks_free: blocks.saturating_sub(used),
+            blocks_available: blocks.saturating_sub(used),
+            files: usage.max_inodes,
+            files_free: usage.max_inodes - inodes,
+            name_max: NAME_MAX,
+        })
+    }
+}
+
+impl TmpNode {
+    fn wrap(&self, inner: VfsNodeRef) -> VfsNodeRef {
+        Arc::new(TmpNode {
+            inner,
+            usage: self.usage.clone(),
+            is_root: false,
+        })
+    }
+
+    fn size(&self) -> VfsResult<u64> {
+        Ok(self.inner.get_attr()?.size())
+    }
+}
+
+impl VfsNodeOps for TmpNode {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        let attr = self.inner.get_attr()?;
+        let addr = super::node_addr(self.inner.as_ref());
+        match self.usage.perms.lock().get(&addr) {
+            Some(&perm) => Ok(VfsNodeAttr::new(
+                perm,
+                attr.file_type(),
+                attr.size(),
+                attr.blocks(),
+            )),
+            None => Ok(attr),
+        }
+    }
+
+    fn parent(&self) -> Option<VfsNodeRef> {
+        let parent = self.inner.parent()?;
+        if self.is_root {
+            Some(parent)
+        } else {
+            Some(self.wrap(parent))
+        }
+    }
+
+    fn lookup(self: Arc<Self>, path: &str) -> VfsResult<VfsNodeRef> {
+        let node = self.inner.clone().lookup(path)?;
+        if Arc::ptr_eq(&node, &self.inner) {
+            return Ok(self);
+        }
+        Ok(self.wrap(node))
+    }
+
+    fn read_dir(&self, start_idx: usize, dirents: &mut [VfsDirEntry]) -> VfsResult<usize> {
+        self.inner.read_dir(start_idx, dirents)
+    }
+
+    fn create(&self, path: &str, ty: VfsNodeType) -> VfsResult {
+        self.usage.reserve_inode()?;
+        self.inner
+            .create(path, ty)
+            .inspect_err(|_| self.usage.release_inode())
+    }
+
+    /// Removes the node at `path`, and frees its data once it is no longer
+    /// opened.
+    fn remove(&self, path: &str) -> VfsResult {
+        let _guard = self.usage.lock.lock();
+        let node = self.inner.clone().lookup(path)?;
+        self.inner.remove(path)?;
+        self.usage.removed(node.as_ref())
+    }
+
+    /// Renames the node at `src_path` to `dst_path`, and frees the node it
+    /// replaces once it is no longer opened.
+    fn rename(&self, src_path: &str, dst_path: &str) -> VfsResult {
+        let _guard = self.usage.lock.lock();
+        let replaced = self.inner.clone().lookup(dst_path).ok();
+        self.inner.rename(src_path, dst_path)?;
+        match replaced {
+            // renaming a node to itself replaces nothing
+            Some(node) if !Arc::ptr_eq(&node, &self.inner.clone().lookup(dst_path)?) => {
+                self.usage.removed(node.as_ref())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn symlink(&self, target: &str, path: &str) -> VfsResult {
+        self.usage.reserve_inode()?;
+        self.inner
+            .symlink(target, path)
+            .inspect_err(|_| self.usage.release_inode())
+    }
+
+    fn readlink(&self, path: &str, buf: &mut [u8]) -> VfsResult<usize> {
+        self.inner.readlink(path, buf)
+    }
+
+    fn is_symlink(&self) -> bool {
+        self.inner.is_symlink()
+    }
+
+    fn open(&self) -> VfsResult {
+        self.inner.open()?;
+        let addr = super::node_addr(self.inner.as_ref());
+        self.usage.opened.lock().entry(addr).or_default().count += 1;
+        Ok(())
+    }
+
+    /// Closes a handle of the node, and frees the node if it has been removed
+    /// and this is its last handle.
+    fn release(&self) -> VfsResult {
+        let addr = super::node_addr(self.inner.as_ref());
+        let mut opened = self.usage.opened.lock();
+        if let Some(file) = opened.get_mut(&addr) {
+            file.count -= 1;
+            if file.count == 0 && opened.remove(&addr).is_some_and(|file| file.removed) {
+                self.usage.free(self.inner.as_ref())?;
+            }
+        }
+        drop(opened);
+        self.inner.release()
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        self.inner.read_at(offset, buf)
+    }
+
+    fn write_at(&self, offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        let _guard = self.usage.lock.lock();
+        let old_size = self.size()?;
+        let new_size = old_size.max(offset.saturating_add(buf.len() as u64));
+        self.usage
+            .resize(old_size, new_size, || self.inner.write_at(offset, buf))
+    }
+
+    fn truncate(&self, size: u64) -> VfsResult {
+        let _guard = self.usage.lock.lock();
+        let old_size = self.size()?;
+        self.usage
+            .resize(old_size, size, || self.inner.truncate(size))
+    }
+
+    fn fsync(&self) -> VfsResult {
+        self.inner.fsync()
+    }
+
+    fn as_any(&self) -> &dyn core::any::Any {
+        self
+    }
+}
@@ -0,0 +1,291 @@
+//! Unpacking of initramfs archives.
+//!
+//! An initramfs is a cpio archive in the "newc" format, as made by
+//! `cpio -H newc`, or a POSIX tar archive, which is unpacked into a ramfs as
+//! the root filesystem. Regular files, directories and symbolic links are
+//! unpacked, while other entries such as device nodes are skipped. Hard links
+//! of tar archives are unpacked as copies of their targets, as ramfs has no
+//! hard links, and the paths and link targets in pax extended headers are
+//! honored.
+
+use alloc::{format, string::String, vec, vec::Vec};
+use axerrno::{AxError, AxResult, ax_err};
+use axfs_vfs::{VfsNodeRef, VfsNodeType};
+
+const CPIO_MAGICS: [&[u8]; 2] = [b"070701", b"070702"];
+const CPIO_HEADER_SIZE: usize = 110;
+const CPIO_TRAILER: &str = "TRAILER!!!";
+
+const TAR_BLOCK_SIZE: usize = 512;
+const TAR_MAGIC: &[u8] = b"ustar";
+/// Magic and version of POSIX tar headers, which have a name prefix field.
+const TAR_POSIX_MAGIC: &[u8] = b"ustar\x0000";
+
+/// File type bits in the mode of a cpio entry.
+const S_IFMT: u32 = 0o170000;
+const S_IFREG: u32 = 0o100000;
+const S_IFDIR: u32 = 0o040000;
+const S_IFLNK: u32 = 0o120000;
+
+enum EntryKind {
+    File,
+    Dir,
+    Symlink,
+    /// A hard link, whose data is the path of its target.
+    HardLink,
+    Other,
+}
+
+/// An entry of an archive.
+struct Entry<'a> {
+    path: String,
+    kind: EntryKind,
+    /// Contents of a file, or the target of a symbolic link.
+    data: &'a [u8],
+}
+
+/// Returns `len` bytes of `archive` at `offset`, or fails if the archive is
+/// truncated.
+fn slice(archive: &[u8], offset: usize, len: usize) -> AxResult<&[u8]> {
+    archive
+        .get(offset..offset.checked_add(len).ok_or(AxError::InvalidData)?)
+        .ok_or(AxError::InvalidData)
+}
+
+fn parse_hex(field: &[u8]) -> AxResult<u32> {
+    let s = core::str::from_utf8(field).map_err(|_| AxError::InvalidData)?;
+    u32::from_str_radix(s, 16).map_err(|_| AxError::InvalidData)
+}
+
+/// Parses a NUL or space terminated octal number of a tar header.
+fn parse_octal(field: &[u8]) -> AxResult<usize> {
+    let digits = field
+        .split(|&b| b == 0 || b == b' ')
+        .find(|s| !s.is_empty());
+    let Some(digits) = digits else {
+        return Ok(0);
+    };
+    let s = core::str::from_utf8(digits).map_err(|_| AxError::InvalidData)?;
+    usize::from_str_radix(s, 8).map_err(|_| AxError::InvalidData)
+}
+
+/// Parses the records of a pax extended header, and returns the values of
+/// `path` and `linkpath` in it. Other records are ignored, as the metadata is
+/// not kept, except `size`, which would change the layout of the archive and
+/// is refused.
+fn parse_pax(mut data: &[u8]) -> AxResult<(Option<String>, Option<String>)> {
+    let (mut path, mut linkpath) = (None, None);
+    while !data.is_empty() {
+        // each record is "<length> <key>=<value>\n"
+        let space = data
+            .iter()
+            .position(|&b| b == b' ')
+            .ok_or(AxError::InvalidData)?;
+        let len = core::str::from_utf8(&data[..space])
+            .ok()
+            .and_then(|s| s.parse::<usize>().ok())
+            .ok_or(AxError::InvalidData)?;
+        let record = data.get(space + 1..len).ok_or(AxError::InvalidData)?;
+        let record = record.strip_suffix(b"\n").ok_or(AxError::InvalidData)?;
+        let record = core::str::from_utf8(record).map_err(|_| AxError::InvalidData)?;
+        let (key, value) = record.split_once('=').ok_or(AxError::InvalidData)?;
+        match key {
+            "path" => path = Some(value.into()),
+            "linkpath" => linkpath = Some(value.into()),
+            "size" => return ax_err!(Unsupported, "pax size records are not supported"),
+            _ => {}
+        }
+        data = &data[len..];
+    }
+    Ok((path, linkpath))
+}
+
+/// Returns the string in `field` up to the first NUL.
+fn parse_str(field: &[u8]) -> AxResult<&str> {
+    let len = field.iter().position(|&b| b == 0).unwrap_or(field.len());
+    core::str::from_utf8(&field[..len]).map_err(|_| AxError::InvalidData)
+}
+
+/// Parses the cpio archive, and calls `f` on each entry.
+fn parse_cpio(archive: &[u8], mut f: impl FnMut(Entry<'_>) -> AxResult) -> AxResult {
+    let mut offset = 0;
+    loop {
+        let header = slice(archive, offset, CPIO_HEADER_SIZE)?;
+        if !CPIO_MAGICS.contains(&&header[..6]) {
+            return ax_err!(InvalidData, "bad cpio magic");
+        }
+        // fields after the magic: ino, mode, uid, gid, nlink, mtime,
+        // filesize, devmajor, devminor, rdevmajor, rdevminor, namesize, check
+        let field = |i: usize| parse_hex(&header[6 + i * 8..14 + i * 8]);
+        let mode = field(1)?;
+        let file_size = field(6)? as usize;
+        let name_size = field(11)? as usize;
+
+        let name = parse_str(slice(archive, offset + CPIO_HEADER_SIZE, name_size)?)?;
+        if name == CPIO_TRAILER {
+            return Ok(());
+        }
+        offset = (offset + CPIO_HEADER_SIZE + name_size).next_multiple_of(4);
+        let data = slice(archive, offset, file_size)?;
+        offset = (offset + file_size).next_multiple_of(4);
+
+        let kind = match mode & S_IFMT {
+            S_IFREG => EntryKind::File,
+            S_IFDIR => EntryKind::Dir,
+            S_IFLNK => EntryKind::Symlink,
+            _ => EntryKind::Other,
+        };
+        f(Entry {
+            path: name.into(),
+            kind,
+            data,
+        })?;
+    }
+}
+
+/// Parses the tar archive, and calls `f` on each entry.
+fn parse_tar(archive: &[u8], mut f: impl FnMut(Entry<'_>) -> AxResult) -> AxResult {
+    let mut offset = 0;
+    // the name of the next entry, given by a GNU long name entry
+    let mut long_name = None;
+    // the path and link target of the next entry, given by a pax extended
+    // header
+    let mut pax = None;
+    while offset + TAR_BLOCK_SIZE <= archive.len() {
+        let header = &archive[offset..offset + TAR_BLOCK_SIZE];
+        if header.iter().all(|&b| b == 0) {
+            return Ok(()); // end of archive
+        }
+        if &header[257..262] != TAR_MAGIC {
+            return ax_err!(InvalidData, "bad tar magic");
+        }
+        let size = parse_octal(&header[124..136])?;
+        let data = slice(archive, offset + TAR_BLOCK_SIZE, size)?;
+        offset += TAR_BLOCK_SIZE + size.next_multiple_of(TAR_BLOCK_SIZE);
+
+        let typeflag = header[156];
+        if typeflag == b'L' {
+            long_name = Some(String::from(parse_str(data)?));
+            continue;
+        }
+        if typeflag == b'x' {
+            pax = Some(parse_pax(data)?);
+            continue;
+        }
+        let (pax_path, pax_linkpath) = pax.take().unwrap_or_default();
+        let long_name = long_name.take();
+        let path = match pax_path.or(long_name) {
+            Some(name) => name,
+            None if &header[257..265] == TAR_POSIX_MAGIC => match parse_str(&header[345..500])? {
+                "" => parse_str(&header[..100])?.into(),
+                prefix => format!("{}/{}", prefix, parse_str(&header[..100])?),
+            },
+            None => parse_str(&header[..100])?.into(),
+        };
+        let link = match pax_linkpath {
+            Some(link) => link,
+            None => parse_str(&header[157..257])?.into(),
+        };
+        let (kind, data) = match typeflag {
+            b'0' | b'\0' => (EntryKind::File, data),
+            b'5' => (EntryKind::Dir, data),
+            b'2' => (EntryKind::Symlink, link.as_bytes()),
+            b'1' => (EntryKind::HardLink, link.as_bytes()),
+            _ => (EntryKind::Other, data),
+        };
+        f(Entry { path, kind, data })?;
+    }
+    Ok(())
+}
+
+/// Creates the missing directories of `path` relative to `root`.
+fn create_dirs(root: &VfsNodeRef, path: &str) -> AxResult {
+    let mut end = 0;
+    for comp in path.split('/') {
+        end += comp.len();
+        match root.create(&path[..end], VfsNodeType::Dir) {
+            Ok(()) | Err(AxError::AlreadyExists) => {}
+            Err(e) => return Err(e),
+        }
+        end += 1;
+    }
+    Ok(())
+}
+
+/// Removes the leading `./` and slashes of a path in an archive.
+fn trim_path(path: &str) -> &str {
+    path.trim_start_matches("./").trim_matches('/')
+}
+
+/// Creates the file at `path` relative to `root` with contents `data`,
+/// replacing an existing one.
+fn write_file(root: &VfsNodeRef, path: &str, data: &[u8]) -> AxResult {
+    match root.create(path, VfsNodeType::File) {
+        Ok(()) | Err(AxError::AlreadyExists) => {}
+        Err(e) => return Err(e),
+    }
+    let file = root.clone().lookup(path)?;
+    file.truncate(0)?;
+    file.write_at(0, data)?;
+    Ok(())
+}
+
+/// Reads the whole regular file at `path` relative to `root`.
+fn read_file(root: &VfsNodeRef, path: &str) -> AxResult<Vec<u8>> {
+    let file = root.clone().lookup(path)?;
+    let attr = file.get_attr()?;
+    if !attr.is_file() {
+        return ax_err!(InvalidData, "hard link to a non-regular file");
+    }
+    let mut data = vec![0; attr.size() as usize];
+    let mut pos = 0;
+    while pos < data.len() {
+        match file.read_at(pos as u64, &mut data[pos..])? {
+            0 => break,
+            n => pos += n,
+        }
+    }
+    data.truncate(pos);
+    Ok(data)
+}
+
+fn unpack_entry(root: &VfsNodeRef, entry: Entry<'_>) -> AxResult {
+    let path = trim_path(&entry.path);
+    if path.is_empty() || path == "." {
+        return Ok(());
+    }
+    if let Some((parent, _)) = path.rsplit_once('/') {
+        create_dirs(root, parent)?;
+    }
+    match entry.kind {
+        EntryKind::Dir => create_dirs(root, path)?,
+        EntryKind::File => write_file(root, path, entry.data)?,
+        EntryKind::Symlink => {
+            let target = core::str::from_utf8(entry.data).map_err(|_| AxError::InvalidData)?;
+            if let Err(e) = root.symlink(target, path) {
+                warn!("initramfs: failed to create symlink {}: {:?}", path, e);
+            }
+        }
+        EntryKind::HardLink => {
+            let target = core::str::from_utf8(entry.data).map_err(|_| AxError::InvalidData)?;
+            let data = read_file(root, trim_path(target))?;
+            write_file(root, path, &data)?;
+        }
+        EntryKind::Other => warn!("initramfs: skip special file {}", path),
+    }
+    Ok(())
+}
+
+/// Unpacks the cpio or tar `archive` into the directory `root`. An empty
+/// archive leaves the directory empty.
+pub fn unpack(archive: &[u8], root: &VfsNodeRef) -> AxResult {
+    if archive.is_empty() {
+        Ok(())
+    } else if archive.len() >= 6 && CPIO_MAGICS.contains(&&archive[..6]) {
+        parse_cpio(archive, |entry| unpack_entry(root, entry))
+    } else if archive.len() >= TAR_BLOCK_SIZE && &archive[257..262] == TAR_MAGIC {
+        parse_tar(archive, |entry| unpack_entry(root, entry))
+    } else {
+        ax_err!(InvalidData, "unknown initramfs format")
+    }
+}
@@ -8,8 +8,25 @@
 //!   is **enabled** by default.
 //! - `devfs`: Mount [`axfs_devfs::DeviceFileSystem`] on `/dev`. This feature is
 //!   **enabled** by default.
-//! - `ramfs`: Mount [`axfs_ramfs::RamFileSystem`] on `/tmp`. This feature is
-//!   **enabled** by default.
+//! - `ramfs`: Mount a tmpfs, i.e., a [`axfs_ramfs::RamFileSystem`] with the
+//!   size and inode limits of `axconfig::TMPFS_SIZE` and
+//!   `axconfig::TMPFS_INODES`, on `/tmp`. This feature is **enabled** by
+//!   default.
+//! - `procfs`: Mount a proc filesystem on `/proc`, with entries such as
+//!   `/proc/meminfo` and `/proc/mounts` generated on every read. With the
+//!   `multitask` feature, `/proc/<tid>` of every task is generated as well.
+//!   This feature is **enabled** by default.
+//! - `lwext4_rs`: Use ext4 as the main filesystem instead of FAT. Together with
+//!   `fatfs`, both kinds of disks can be mounted with [`api::mount`].
+//! - `overlayfs`: Keep the main filesystem read-only, and overlay it with a
+//!   [`axfs_ramfs::RamFileSystem`] that takes all changes, e.g., to boot from
+//!   a read-only image. Files are copied to the ramfs when they are written.
+//!   This feature is **disabled** by default.
+//! - `initramfs`: Use a ramfs as the root filesystem, unpacked from a cpio or
+//!   tar archive, which is either embedded from the path in the `AX_INITRAMFS`
+//!   environment variable at build time, or loaded by the bootloader and given
+//!   by [`set_initramfs`]. Block devices are still available to be mounted.
+//!   This feature is **disabled** by default.
 //! - `myfs`: Allow users to define their custom filesystems to override the
 //!   default. In this case, [`MyFileSystemIf`] is required to be implemented
 //!   to create and initialize other filesystems. This feature is **disabled** by
@@ -27,24 +44,94 @@
 extern crate log;
 extern crate alloc;
 
+mod cache;
 mod dev;
 mod fs;
+#[cfg(feature = "initramfs")]
+mod initramfs;
+mod lock;
 mod mounts;
+mod notify;
+mod partition;
 mod root;
+mod tunable;
+mod wait;
 
 pub mod api;
 pub mod fops;
 pub use root::{CURRENT_DIR, CURRENT_DIR_PATH};
 
+use alloc::vec::Vec;
 use axdriver::{AxDeviceContainer, prelude::*};
 
+/// The initramfs archive embedded in the kernel image, given by the
+/// `AX_INITRAMFS` environment variable at build time.
+#[cfg(feature = "initramfs")]
+static EMBEDDED_INITRAMFS: &[u8] = include_bytes!(env!("AX_INITRAMFS_PATH"));
+
+/// The initramfs archive loaded by the bootloader.
+#[cfg(feature = "initramfs")]
+static INITRAMFS: spin::Once<&'static [u8]> = spin::Once::new();
+
+/// Sets the initramfs archive loaded by the bootloader, which is used instead
+/// of the embedded one.
+///
+/// It must be called before [`init_filesystems`].
+#[cfg(feature = "initramfs")]
+pub fn set_initramfs(archive: &'static [u8]) {
+    INITRAMFS.call_once(|| archive);
+}
+
+/// The root device given at boot, e.g., by the `root=` kernel parameter.
+static ROOT_DEVICE: spin::Once<&'static str> = spin::Once::new();
+
+/// Sets the block device or partition of the root filesystem, which is used
+/// instead of `axconfig::FS_ROOT`. See [`init_filesystems`] for its format.
+///
+/// It must be called before [`init_filesystems`].
+pub fn set_root_device(spec: &'static str) {
+    ROOT_DEVICE.call_once(|| spec);
+}
+
 /// Initializes filesystems by block devices.
+///
+/// Every block device is available as `/dev/vda`, `/dev/vdb`, ..., and its
+/// MBR or GPT partitions as `/dev/vda1`, `/dev/vda2`, ... The root filesystem
+/// is on the device or partition selected by [`set_root_device`] or
+/// `axconfig::FS_ROOT`, which is a device name like `/dev/vda2`, a partition
+/// number of the first device like `2`, or `PARTUUID=<uuid>`, or on the whole
+/// first device if it is empty. With the `initramfs` feature, the root
+/// filesystem is the initramfs instead, and no block device is required.
+/// Filesystems listed in `axconfig::FS_MOUNTS` are then mounted in order.
 pub fn init_filesystems(mut blk_devs: AxDeviceContainer<AxBlockDevice>) {
     info!("Initialize filesystems...");
 
-    let dev = blk_devs.take_one().expect("No block device found!");
-    info!("  use block device 0: {:?}", dev.device_name());
-    self::root::init_rootfs(self::dev::Disk::new(dev));
+    let mut devs = Vec::new();
+    while let Some(dev) = blk_devs.take_one() {
+        devs.push(dev);
+    }
+    self::dev::init_block_devices(devs);
+
+    cfg_if::cfg_if! {
+        if #[cfg(all(feature = "initramfs", not(feature = "myfs")))] {
+            let archive = INITRAMFS.get().copied().unwrap_or(EMBEDDED_INITRAMFS);
+            info!("  use initramfs of {} bytes as root", archive.len());
+            self::root::init_rootfs_from_initramfs(archive).expect("failed to unpack initramfs");
+        } else {
+            let spec = ROOT_DEVICE.get().copied().unwrap_or(axconfig::FS_ROOT);
+            let (name, disk) =
+                self::dev::root_device(spec).expect("No root block device found!");
+            info!("  use block device {} as root", name);
+            self::root::init_rootfs(name, disk);
+        }
+    }
+
+    for &(source, target, fstype) in axconfig::FS_MOUNTS {
+        info!("  mount {} on {} as {}", source, target, fstype);
+        if let Err(e) = api::mount(source, target, fstype) {
+            warn!("failed to mount {} on {}: {:?}", source, target, e);
+        }
+    }
 }
 
 #[cfg(test)]
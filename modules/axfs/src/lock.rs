@@ -0,0 +1,361 @@
+//! Advisory file locks.
+//!
+//! Two kinds of locks are supported, which do not conflict with each other,
+//! as in Linux:
+//!
+//! - Locks on whole files of `flock`, owned by opened files.
+//! - Byte-range record locks of `fcntl`, owned by processes, or whatever the
+//!   caller identifies as the owner.
+//!
+//! Like the page cache, files are identified by their [`NodeKey`]s, so all
+//! hard links of a file share its locks. Waiting for a lock fails with
+//! [`AxError::WouldBlock`] without the `multitask` feature, as the lock could
+//! never be released while waiting.
+//!
+//! Waiting for a record lock fails with [`LockError::Deadlock`] if the owners
+//! of the conflicting locks wait, directly or through other owners, for locks
+//! of the waiting owner, like `EDEADLK` of `fcntl`.
+
+use alloc::{
+    collections::{BTreeMap, BTreeSet},
+    string::String,
+    vec,
+    vec::Vec,
+};
+use axerrno::{AxError, AxResult, ax_err_type};
+use core::sync::atomic::{AtomicU64, Ordering};
+use spin::Mutex;
+
+use crate::root::NodeKey;
+use crate::wait::Waiters;
+
+/// Type of an advisory lock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockType {
+    /// A shared (read) lock, which can be held by several owners.
+    Shared,
+    /// An exclusive (write) lock, which can be held by only one owner.
+    Exclusive,
+    /// No lock, used to remove locks.
+    Unlocked,
+}
+
+impl LockType {
+    const fn conflicts_with(self, other: Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::Exclusive, Self::Shared | Self::Exclusive) | (Self::Shared, Self::Exclusive)
+        )
+    }
+}
+
+/// A lock on a byte range of a file, like `struct flock`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecordLock {
+    /// Type of the lock.
+    pub ty: LockType,
+    /// Start offset of the range.
+    pub start: u64,
+    /// End offset of the range, exclusive. It is `u64::MAX` if the range
+    /// extends to the end of the file however it grows.
+    pub end: u64,
+    /// Owner of the lock, e.g., a process ID.
+    pub owner: u64,
+}
+
+impl RecordLock {
+    fn overlaps(&self, other: &Self) -> bool {
+        self.start < other.end && other.start < self.end
+    }
+
+    fn conflicts_with(&self, other: &Self) -> bool {
+        self.owner != other.owner && self.overlaps(other) && self.ty.conflicts_with(other.ty)
+    }
+}
+
+/// Error of setting a record lock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockError {
+    /// Waiting for the lock would never end, i.e., `EDEADLK`.
+    Deadlock,
+    /// Other errors, e.g., [`AxError::WouldBlock`] if the lock is held by
+    /// others and it is not waited for.
+    Other(AxError),
+}
+
+impl From<AxError> for LockError {
+    fn from(e: AxError) -> Self {
+        Self::Other(e)
+    }
+}
+
+/// Locks held on a file.
+#[derive(Default)]
+struct FileLocks {
+    /// `flock` locks, with the IDs of the opened files owning them.
+    flocks: Vec<(u64, LockType)>,
+    records: Vec<RecordLock>,
+}
+
+impl FileLocks {
+    fn is_empty(&self) -> bool {
+        self.flocks.is_empty() && self.records.is_empty()
+    }
+}
+
+/// Locks of all files, and the record locks that owners wait for.
+struct LockTable {
+    files: BTreeMap<NodeKey, FileLocks>,
+    /// The file and the record lock each waiting owner waits for.
+    waiting: BTreeMap<u64, (NodeKey, RecordLock)>,
+}
+
+impl LockTable {
+    /// Runs `f` on the locks of `key`, and removes them if none are left.
+    fn with_locks<T>(&mut self, key: &NodeKey, f: impl FnOnce(&mut FileLocks) -> T) -> T {
+        let file_locks = self.files.entry(key.clone()).or_default();
+        let ret = f(file_locks);
+        if file_locks.is_empty() {
+            self.files.remove(key);
+        }
+        ret
+    }
+
+    /// Whether waiting for `lock` on `key` would deadlock, i.e., the owner of
+    /// `lock` holds a lock that the owners of the conflicting locks wait for,
+    /// directly or through other owners.
+    fn would_deadlock(&self, key: &NodeKey, lock: &RecordLock) -> bool {
+        let mut pending = vec![(key, *lock)];
+        let mut visited = BTreeSet::new();
+        while let Some((key, waited)) = pending.pop() {
+            let Some(locks) = self.files.get(key) else {
+                continue;
+            };
+            for other in locks.records.iter().filter(|o| waited.conflicts_with(o)) {
+                if other.owner == lock.owner {
+                    return true;
+                }
+                if !visited.insert(other.owner) {
+                    continue;
+                }
+                if let Some((key, next)) = self.waiting.get(&other.owner) {
+                    pending.push((key, *next));
+                }
+            }
+        }
+        false
+    }
+}
+
+/// Locks of all files.
+///
+/// It is a spinlock, as it is also accessed while holding the lock of the
+/// wait queue.
+static LOCKS: Mutex<LockTable> = Mutex::new(LockTable {
+    files: BTreeMap::new(),
+    waiting: BTreeMap::new(),
+});
+
+static WAITERS: Waiters = Waiters::new();
+
+/// Runs `f` on the locks of `key`, and removes them if none are left.
+fn with_locks<T>(key: &NodeKey, f: impl FnOnce(&mut FileLocks) -> T) -> T {
+    LOCKS.lock().with_locks(key, f)
+}
+
+/// Drops the locks of `key`, whose file no longer exists.
+pub(crate) fn forget(key: &NodeKey) {
+    LOCKS.lock().files.remove(key);
+}
+
+/// Takes a lock on `key` by `try_lock`, which returns whether the lock was
+/// taken. Waits for other locks to be released if `wait` is true, or fails
+/// with `WouldBlock` otherwise.
+///
+/// If the lock is the record lock `record`, the owner is recorded as waiting
+/// for it, and it fails with [`LockError::Deadlock`] instead of waiting if
+/// that would deadlock.
+fn acquire(
+    key: &NodeKey,
+    wait: bool,
+    record: Option<&RecordLock>,
+    try_lock: impl Fn(&mut FileLocks) -> bool,
+) -> Result<(), LockError> {
+    {
+        let mut table = LOCKS.lock();
+        if table.with_locks(key, &try_lock) {
+            return Ok(());
+        }
+        if !wait {
+            return Err(ax_err_type!(WouldBlock, "file is locked").into());
+        }
+        if let Some(lock) = record {
+            if table.would_deadlock(key, lock) {
+                return Err(LockError::Deadlock);
+            }
+            table.waiting.insert(lock.owner, (key.clone(), *lock));
+        }
+    }
+    let ret = WAITERS.wait_until(|| with_locks(key, &try_lock));
+    if let Some(lock) = record {
+        LOCKS.lock().waiting.remove(&lock.owner);
+    }
+    Ok(ret?)
+}
+
+/// Wakes up the tasks waiting for locks, after some locks are released.
+fn notify_waiters() {
+    WAITERS.notify_all();
+}
+
+/// Removes the range of `lock` from the record locks of its owner.
+fn unlock_range(records: &mut Vec<RecordLock>, lock: &RecordLock) {
+    let mut split = Vec::new();
+    records.retain(|other| {
+        if other.owner != lock.owner || !other.overlaps(lock) {
+            return true;
+        }
+        if other.start < lock.start {
+            split.push(RecordLock {
+                end: lock.start,
+                ..*other
+            });
+        }
+        if other.end > lock.end {
+            split.push(RecordLock {
+                start: lock.end,
+                ..*other
+            });
+        }
+        false
+    });
+    records.extend(split);
+}
+
+/// Handle to the advisory locks of an opened file.
+///
+/// It can be cloned to wait for a lock without borrowing the file.
+#[derive(Debug, Clone)]
+pub struct LockHandle {
+    /// Identity of the file.
+    key: NodeKey,
+    /// The absolute path of the file when it was opened.
+    path: String,
+    /// Unique ID of the opened file, which owns its `flock` lock.
+    id: u64,
+}
+
+impl LockHandle {
+    pub(crate) fn new(key: NodeKey, path: &str) -> Self {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+        Self {
+            key,
+            path: path.into(),
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+        }
+    }
+
+    /// Returns the identity of the file.
+    pub(crate) fn key(&self) -> &NodeKey {
+        &self.key
+    }
+
+    /// Returns the absolute path of the file when it was opened.
+    pub(crate) fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Applies a lock of type `ty` on the whole file, like `flock`, replacing
+    /// the lock already held by the opened file, or removes it if `ty` is
+    /// [`LockType::Unlocked`]. The lock is released when the file is closed.
+    ///
+    /// If the file is locked by others, it blocks if `wait` is true, or fails
+    /// with `WouldBlock` otherwise.
+    pub fn flock(&self, ty: LockType, wait: bool) -> AxResult {
+        // as in Linux, an existing lock is removed before converting it
+        self.release_flock();
+        if ty == LockType::Unlocked {
+            return Ok(());
+        }
+        let ret = acquire(&self.key, wait, None, |locks| {
+            if locks
+                .flocks
+                .iter()
+                .any(|&(_, other)| ty.conflicts_with(other))
+            {
+                return false;
+            }
+            locks.flocks.push((self.id, ty));
+            true
+        });
+        match ret {
+            Ok(()) => Ok(()),
+            Err(LockError::Other(e)) => Err(e),
+            Err(LockError::Deadlock) => {
+                unreachable!("deadlocks are only detected for record locks")
+            }
+        }
+    }
+
+    /// Returns the first record lock on the file that conflicts with `lock`,
+    /// like `F_GETLK`.
+    pub fn test(&self, lock: &RecordLock) -> Option<RecordLock> {
+        with_locks(&self.key, |locks| {
+            locks
+                .records
+                .iter()
+                .find(|other| lock.conflicts_with(other))
+                .copied()
+        })
+    }
+
+    /// Sets the record lock `lock` on the file, replacing the locks of the
+    /// same owner in the range, or removes them if the type is
+    /// [`LockType::Unlocked`], like `F_SETLK` and `F_SETLKW`.
+    ///
+    /// If the range is locked by others, it blocks if `wait` is true, or
+    /// fails with `WouldBlock` otherwise. Instead of waiting, it fails with
+    /// [`LockError::Deadlock`] if the lock would never be released.
+    pub fn set(&self, lock: RecordLock, wait: bool) -> Result<(), LockError> {
+        if lock.start >= lock.end {
+            return Err(ax_err_type!(InvalidInput, "empty lock range").into());
+        }
+        if lock.ty == LockType::Unlocked {
+            with_locks(&self.key, |locks| unlock_range(&mut locks.records, &lock));
+            notify_waiters();
+            return Ok(());
+        }
+        acquire(&self.key, wait, Some(&lock), |locks| {
+            if locks.records.iter().any(|other| lock.conflicts_with(other)) {
+                return false;
+            }
+            unlock_range(&mut locks.records, &lock);
+            locks.records.push(lock);
+            true
+        })?;
+        // a lock downgraded from exclusive to shared may unblock others
+        notify_waiters();
+        Ok(())
+    }
+
+    /// Removes all record locks of `owner` on the file, e.g., when the owner
+    /// closes the file.
+    pub fn release(&self, owner: u64) {
+        with_locks(&self.key, |locks| {
+            locks.records.retain(|l| l.owner != owner)
+        });
+        notify_waiters();
+    }
+
+    /// Removes the `flock` lock of the opened file.
+    pub(crate) fn release_flock(&self) {
+        let held = with_locks(&self.key, |locks| {
+            let len = locks.flocks.len();
+            locks.flocks.retain(|&(owner, _)| owner != self.id);
+            locks.flocks.len() != len
+        });
+        if held {
+            notify_waiters();
+        }
+    }
+}
@@ -1,8 +1,12 @@
 use alloc::string::{String, ToString};
 use alloc::sync::Arc;
+use axerrno::{AxError, AxResult, ax_err};
 use axfs_vfs::{VfsNodeType, VfsOps, VfsResult};
 
-use crate::fs;
+use crate::{
+    dev,
+    fs::{self, FileSystem},
+};
 
 /// Weak linkage function to get current process executable path.
 /// This will be overridden by the actual implementation in higher layers.
@@ -12,6 +16,35 @@ unsafe fn get_current_process_exe_path() -> String {
     "/musl/busybox".to_string()
 }
 
+/// Creates a new filesystem of the given type, as named by `mount(8)`.
+///
+/// `source` is the block device holding the filesystem, and is ignored for
+/// virtual filesystems. `options` are the comma-separated mount options,
+/// which are ignored by filesystems other than tmpfs for now.
+pub(crate) fn new_fs(source: &str, fstype: &str, options: &str) -> AxResult<Arc<dyn FileSystem>> {
+    match fstype {
+        #[cfg(feature = "fatfs")]
+        "vfat" | "fat" => Ok(fs::fatfs::FatFileSystem::open(dev::open_block_device(
+            source,
+        )?)?),
+        #[cfg(feature = "lwext4_rs")]
+        "ext4" => Ok(Arc::new(fs::lwext4_rust::Ext4FileSystem::open(
+            dev::open_block_device(source)?,
+        )?)),
+        #[cfg(feature = "devfs")]
+        "devfs" | "devtmpfs" => Ok(devfs()),
+        #[cfg(feature = "ramfs")]
+        "ramfs" => Ok(ramfs()),
+        #[cfg(feature = "ramfs")]
+        "tmpfs" => Ok(tmpfs(options)?),
+        #[cfg(feature = "procfs")]
+        "proc" => Ok(procfs()?),
+        #[cfg(feature = "sysfs")]
+        "sysfs" => Ok(sysfs()?),
+        _ => ax_err!(InvalidInput, "unknown filesystem type"),
+    }
+}
+
 #[cfg(feature = "devfs")]
 pub(crate) fn devfs() -> Arc<fs::devfs::DeviceFileSystem> {
     let null = fs::devfs::NullDev;
@@ -24,6 +57,9 @@ pub(crate) fn devfs() -> Arc<fs::devfs::DeviceFileSystem> {
     devfs.add("zero", Arc::new(zero));
     devfs.add("urandom", Arc::new(urandom));
     foo_dir.add("bar", Arc::new(bar));
+    for (name, disk) in dev::block_devices() {
+        devfs.add(name, Arc::new(fs::devfs::BlockDev::new(disk)));
+    }
     Arc::new(devfs)
 }
 
@@ -32,8 +68,46 @@ pub(crate) fn ramfs() -> Arc<fs::ramfs::RamFileSystem> {
     Arc::new(fs::ramfs::RamFileSystem::new())
 }
 
+/// Creates a tmpfs with the mount options `size=<bytes>` and
+/// `nr_inodes=<number>`, where the numbers may have a `k`, `m` or `g` suffix.
+///
+/// The limits default to `axconfig::TMPFS_SIZE` and `axconfig::TMPFS_INODES`,
+/// and a limit of 0 means no limit.
+#[cfg(feature = "ramfs")]
+pub(crate) fn tmpfs(options: &str) -> AxResult<Arc<fs::tmpfs::TmpFileSystem>> {
+    let mut max_bytes = match axconfig::TMPFS_SIZE {
+        0 => axconfig::plat::PHYS_MEMORY_SIZE / 2,
+        size => size,
+    } as u64;
+    let mut max_inodes = axconfig::TMPFS_INODES as u64;
+    for option in options.split(',').filter(|o| !o.is_empty()) {
+        match option.split_once('=') {
+            Some(("size", size)) => max_bytes = parse_size(size)?,
+            Some(("nr_inodes", num)) => max_inodes = parse_size(num)?,
+            _ => return ax_err!(InvalidInput, "unknown tmpfs option"),
+        }
+    }
+    let tmpfs = fs::tmpfs::TmpFileSystem::new(max_bytes, max_inodes);
+    Ok(Arc::new(tmpfs))
+}
+
+/// Parses a number with an optional `k`, `m` or `g` suffix of binary units.
+#[cfg(feature = "ramfs")]
+fn parse_size(s: &str) -> AxResult<u64> {
+    let (num, shift) = match s.as_bytes().last() {
+        Some(b'k' | b'K') => (&s[..s.len() - 1], 10),
+        Some(b'm' | b'M') => (&s[..s.len() - 1], 20),
+        Some(b'g' | b'G') => (&s[..s.len() - 1], 30),
+        _ => (s, 0),
+    };
+    num.parse::<u64>()
+        .ok()
+        .and_then(|n| n.checked_mul(1 << shift))
+        .ok_or(AxError::InvalidInput)
+}
+
 #[cfg(feature = "procfs")]
-pub(crate) fn procfs() -> VfsResult<Arc<fs::ramfs::RamFileSystem>> {
+pub(crate) fn procfs() -> VfsResult<Arc<fs::procfs::ProcFileSystem>> {
     let procfs = fs::ramfs::RamFileSystem::new();
     let proc_root = procfs.root_dir();
 
@@ -51,16 +125,20 @@ pub(crate) fn procfs() -> VfsResult<Arc<fs::ramfs::RamFileSystem>> {
     let file_over = proc_root.clone().lookup("./sys/vm/overcommit_memory")?;
     file_over.write_at(0, b"0\n")?;
 
-    // Create /proc/self/stat
-    proc_root.create("self", VfsNodeType::Dir)?;
-    proc_root.create("self/stat", VfsNodeType::File)?;
+    // Create /proc/self/stat, which is generated from the task otherwise
+    #[cfg(not(feature = "multitask"))]
+    {
+        proc_root.create("self", VfsNodeType::Dir)?;
+        proc_root.create("self/stat", VfsNodeType::File)?;
+    }
 
     // Create /proc/interrupts
     proc_root.create("interrupts", VfsNodeType::File)?;
     let intr_file = fs::ramfs::InterruptFile;
     proc_root.add_node("interrupts", Arc::new(intr_file))?;
 
-    Ok(Arc::new(procfs))
+    // Add generated entries, e.g., /proc/meminfo and /proc/<tid>/stat
+    Ok(Arc::new(fs::procfs::ProcFileSystem::new(procfs)?))
 }
 
 #[cfg(feature = "sysfs")]
@@ -92,5 +170,8 @@ pub(crate) fn sysfs() -> VfsResult<Arc<fs::ramfs::RamFileSystem>> {
         .lookup("devices/system/clocksource/clocksource0/current_clocksource")?;
     file_cc.write_at(0, b"tsc\n")?;
 
+    // Add registered tunables, e.g., /sys/kernel/log_level
+    crate::tunable::attach(&sys_root)?;
+
     Ok(Arc::new(sysfs))
 }
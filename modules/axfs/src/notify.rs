@@ -0,0 +1,483 @@
+//! Notification of file changes, like `inotify`.
+//!
+//! A [`Watcher`] watches files and directories by their [`NodeKey`]s, like
+//! the page cache and file locks, and receives [`Event`]s on the changes made
+//! through `axfs`:
+//!
+//! - The watch of a file reports changes to the file itself, e.g., it is
+//!   modified or deleted, through any of its hard links.
+//! - The watch of a directory also reports changes to the entries in it,
+//!   with the names of the entries.
+//!
+//! Symbolic links are not followed, and a watch follows its file or
+//! directory when it is renamed. Waiting for events fails with `WouldBlock`
+//! without the `multitask` feature.
+
+use alloc::{collections::BTreeMap, collections::VecDeque, string::String, sync::Arc, sync::Weak};
+use alloc::{format, vec::Vec};
+use axerrno::{AxResult, ax_err};
+use core::sync::atomic::{AtomicI32, AtomicU32, AtomicUsize, Ordering};
+use spin::Mutex;
+
+use crate::root::NodeKey;
+use crate::wait::Waiters;
+
+bitflags::bitflags! {
+    /// Kinds of events and options of watches, with the values of Linux.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct EventMask: u32 {
+        /// The file was modified, e.g., written or truncated.
+        const MODIFY = 0x2;
+        /// The metadata of the file was changed, e.g., permissions and
+        /// timestamps.
+        const ATTRIB = 0x4;
+        /// A file opened for writing was closed.
+        const CLOSE_WRITE = 0x8;
+        /// An entry was moved out of the watched directory.
+        const MOVED_FROM = 0x40;
+        /// An entry was moved into the watched directory.
+        const MOVED_TO = 0x80;
+        /// An entry was created in the watched directory.
+        const CREATE = 0x100;
+        /// An entry was deleted from the watched directory.
+        const DELETE = 0x200;
+        /// The watched file or directory itself was deleted.
+        const DELETE_SELF = 0x400;
+        /// The watched file or directory itself was moved.
+        const MOVE_SELF = 0x800;
+
+        /// Events were dropped, as the queue of the watcher was full.
+        const Q_OVERFLOW = 0x4000;
+        /// The watch was removed, explicitly or as its file was deleted.
+        const IGNORED = 0x8000;
+
+        /// Only watch the path if it is a directory.
+        const ONLYDIR = 0x0100_0000;
+        /// Add the events to the watch of the path instead of replacing them.
+        const MASK_ADD = 0x2000_0000;
+        /// The subject of the event is a directory.
+        const ISDIR = 0x4000_0000;
+        /// Remove the watch after its first event.
+        const ONESHOT = 0x8000_0000;
+    }
+}
+
+impl EventMask {
+    /// All kinds of events that can be watched.
+    pub const ALL_EVENTS: Self = Self::SELF_EVENTS.union(Self::CHILD_EVENTS);
+    /// Events reported to the watch of the changed file itself.
+    const SELF_EVENTS: Self = Self::MODIFY
+        .union(Self::ATTRIB)
+        .union(Self::CLOSE_WRITE)
+        .union(Self::DELETE_SELF)
+        .union(Self::MOVE_SELF);
+    /// Events reported to the watch of the parent directory.
+    const CHILD_EVENTS: Self = Self::MODIFY
+        .union(Self::ATTRIB)
+        .union(Self::CLOSE_WRITE)
+        .union(Self::MOVED_FROM)
+        .union(Self::MOVED_TO)
+        .union(Self::CREATE)
+        .union(Self::DELETE);
+}
+
+/// A change to a watched file or directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Event {
+    /// The watch descriptor of the watch that reports the event.
+    pub wd: i32,
+    /// The kind of the event, with [`EventMask::ISDIR`] if the subject is a
+    /// directory.
+    pub mask: EventMask,
+    /// A number shared by the [`EventMask::MOVED_FROM`] and
+    /// [`EventMask::MOVED_TO`] events of the same rename, or 0.
+    pub cookie: u32,
+    /// Name of the entry in the watched directory that the event is about,
+    /// or empty if it is about the watched file or directory itself.
+    pub name: String,
+}
+
+/// Maximum number of events queued in a watcher, as in Linux.
+const MAX_QUEUED_EVENTS: usize = 16384;
+
+/// A watch of a file or directory by a watcher.
+struct Watch {
+    queue: Weak<EventQueue>,
+    wd: i32,
+    mask: EventMask,
+}
+
+/// Events of a watcher, and the files it watches.
+#[derive(Default)]
+struct EventQueue {
+    events: Mutex<VecDeque<Event>>,
+    /// Identities of the watched files, by their watch descriptors.
+    keys: Mutex<BTreeMap<i32, NodeKey>>,
+    next_wd: AtomicI32,
+}
+
+/// Watches of all watchers, by the files they watch.
+///
+/// It is a spinlock, as it is also accessed while holding the lock of the
+/// wait queue.
+static WATCHES: Mutex<BTreeMap<NodeKey, Vec<Watch>>> = Mutex::new(BTreeMap::new());
+/// Number of watches of all watchers, so that changes are not looked up when
+/// there are none.
+static NUM_WATCHES: AtomicUsize = AtomicUsize::new(0);
+
+static WAITERS: Waiters = Waiters::new();
+
+impl EventQueue {
+    /// Adds `event` to the queue, unless it is the same as the last one.
+    fn push(&self, event: Event) {
+        let mut events = self.events.lock();
+        if events.back() == Some(&event) {
+            return;
+        }
+        if events.len() >= MAX_QUEUED_EVENTS {
+            if events
+                .back()
+                .is_some_and(|e| e.mask != EventMask::Q_OVERFLOW)
+            {
+                events.push_back(Event {
+                    wd: -1,
+                    mask: EventMask::Q_OVERFLOW,
+                    cookie: 0,
+                    name: String::new(),
+                });
+            }
+            return;
+        }
+        events.push_back(event);
+    }
+}
+
+/// Removes the watch `wd` of `queue` on `key` from the global watches.
+fn unregister(
+    watches: &mut BTreeMap<NodeKey, Vec<Watch>>,
+    key: &NodeKey,
+    queue: &EventQueue,
+    wd: i32,
+) {
+    if let Some(list) = watches.get_mut(key) {
+        let len = list.len();
+        list.retain(|w| !(w.wd == wd && core::ptr::eq(w.queue.as_ptr(), queue)));
+        NUM_WATCHES.fetch_sub(len - list.len(), Ordering::Relaxed);
+        if list.is_empty() {
+            watches.remove(key);
+        }
+    }
+}
+
+/// Returns the identity of the file or directory at the absolute path
+/// `path`, without following a symbolic link at the end.
+fn key_of(path: &str) -> Option<NodeKey> {
+    let node = crate::root::lookup(None, path).ok()?;
+    let mount = crate::root::mount_of(path);
+    Some(crate::root::node_key(mount.as_ref(), &node, path))
+}
+
+/// Reports `mask` to the watches of the file `key`, and the child events in
+/// it to the watches of the parent directory of the absolute path `path`,
+/// with the name of the entry.
+fn notify(key: Option<&NodeKey>, path: Option<&str>, mask: EventMask, cookie: u32) {
+    if NUM_WATCHES.load(Ordering::Relaxed) == 0 {
+        return;
+    }
+    let flags = mask & EventMask::ISDIR;
+    let mut targets = Vec::new();
+    let self_events = mask & EventMask::SELF_EVENTS;
+    if let Some(key) = key.filter(|_| !self_events.is_empty()) {
+        targets.push((key.clone(), self_events, ""));
+    }
+    let child_events = mask & EventMask::CHILD_EVENTS;
+    let entry = path
+        .and_then(|path| path.trim_end_matches('/').rsplit_once('/'))
+        .filter(|(_, name)| !name.is_empty() && !child_events.is_empty());
+    if let Some((parent, name)) = entry {
+        let parent = if parent.is_empty() { "/" } else { parent };
+        targets.extend(key_of(parent).map(|parent| (parent, child_events, name)));
+    }
+    let mut watches = WATCHES.lock();
+    let mut oneshots = Vec::new();
+    for (key, kinds, name) in targets {
+        let Some(list) = watches.get(&key) else {
+            continue;
+        };
+        for watch in list {
+            let matched = watch.mask & kinds;
+            let Some(queue) = watch.queue.upgrade() else {
+                continue;
+            };
+            for kind in matched.iter() {
+                // as in Linux, a deleted or moved directory is not marked
+                let flags = if kind.intersects(EventMask::DELETE_SELF | EventMask::MOVE_SELF) {
+                    EventMask::empty()
+                } else {
+                    flags
+                };
+                queue.push(Event {
+                    wd: watch.wd,
+                    mask: kind | flags,
+                    cookie,
+                    name: name.into(),
+                });
+            }
+            if !matched.is_empty() && watch.mask.contains(EventMask::ONESHOT) {
+                oneshots.push((key.clone(), queue, watch.wd));
+            }
+        }
+    }
+    for (key, queue, wd) in oneshots {
+        unregister(&mut watches, &key, &queue, wd);
+        queue.keys.lock().remove(&wd);
+        queue.push(ignored(wd));
+    }
+    drop(watches);
+    notify_waiters();
+}
+
+fn ignored(wd: i32) -> Event {
+    Event {
+        wd,
+        mask: EventMask::IGNORED,
+        cookie: 0,
+        name: String::new(),
+    }
+}
+
+/// Wakes up the tasks waiting for events.
+fn notify_waiters() {
+    WAITERS.notify_all();
+}
+
+fn isdir(is_dir: bool) -> EventMask {
+    if is_dir {
+        EventMask::ISDIR
+    } else {
+        EventMask::empty()
+    }
+}
+
+/// Reports that the file or directory at the absolute path `path` was
+/// created.
+pub(crate) fn created(path: &str, is_dir: bool) {
+    notify(None, Some(path), EventMask::CREATE | isdir(is_dir), 0);
+}
+
+/// Reports that the file `key` at the absolute path `path` was modified.
+pub(crate) fn modified(key: &NodeKey, path: &str) {
+    notify(Some(key), Some(path), EventMask::MODIFY, 0);
+}
+
+/// Reports that the metadata of the file or directory `key` at the absolute
+/// path `path` was changed.
+pub(crate) fn attrib_changed(key: &NodeKey, path: &str, is_dir: bool) {
+    notify(Some(key), Some(path), EventMask::ATTRIB | isdir(is_dir), 0);
+}
+
+/// Reports that the file `key` at the absolute path `path`, opened for
+/// writing, was closed.
+pub(crate) fn closed_write(key: &NodeKey, path: &str) {
+    notify(Some(key), Some(path), EventMask::CLOSE_WRITE, 0);
+}
+
+/// Reports that the file or directory `key` at the absolute path `path` was
+/// removed.
+///
+/// If it was the last link of the file, the file itself is deleted, and its
+/// watches are removed. Otherwise, only its link count changed.
+pub(crate) fn removed(key: &NodeKey, path: &str, is_dir: bool, last_link: bool) {
+    if !last_link {
+        notify(None, Some(path), EventMask::DELETE, 0);
+        notify(Some(key), None, EventMask::ATTRIB, 0);
+        return;
+    }
+    notify(
+        Some(key),
+        Some(path),
+        EventMask::DELETE | EventMask::DELETE_SELF | isdir(is_dir),
+        0,
+    );
+    forget(key);
+}
+
+/// Removes the watches of the file or directory `key`, which no longer
+/// exists, and reports it to them with `IGNORED`.
+pub(crate) fn forget(key: &NodeKey) {
+    if NUM_WATCHES.load(Ordering::Relaxed) == 0 {
+        return;
+    }
+    let removed = WATCHES.lock().remove(key).unwrap_or_default();
+    NUM_WATCHES.fetch_sub(removed.len(), Ordering::Relaxed);
+    for watch in removed {
+        if let Some(queue) = watch.queue.upgrade() {
+            queue.keys.lock().remove(&watch.wd);
+            queue.push(ignored(watch.wd));
+        }
+    }
+    notify_waiters();
+}
+
+/// Reports that the file or directory `key` at the absolute path `old` was
+/// renamed to `new`.
+///
+/// Watches of files identified by their paths, at `old` or under it, are
+/// moved to the new paths.
+pub(crate) fn renamed(key: &NodeKey, old: &str, new: &str, is_dir: bool) {
+    static NEXT_COOKIE: AtomicU32 = AtomicU32::new(1);
+    if NUM_WATCHES.load(Ordering::Relaxed) == 0 {
+        return;
+    }
+    let cookie = NEXT_COOKIE.fetch_add(1, Ordering::Relaxed);
+    let flags = isdir(is_dir);
+    notify(
+        Some(key),
+        Some(old),
+        EventMask::MOVED_FROM | EventMask::MOVE_SELF | flags,
+        cookie,
+    );
+    notify(None, Some(new), EventMask::MOVED_TO | flags, cookie);
+
+    let mut watches = WATCHES.lock();
+    let (old, new) = (old.trim_end_matches('/'), new.trim_end_matches('/'));
+    let moved = watches
+        .keys()
+        .filter(|key| key.is_under(old))
+        .cloned()
+        .collect::<Vec<_>>();
+    for key in moved {
+        let NodeKey::Path(path) = &key else {
+            continue;
+        };
+        let new_key = NodeKey::Path(format!("{}{}", new, &path[old.len()..]));
+        let list = watches.remove(&key).unwrap();
+        for watch in &list {
+            if let Some(queue) = watch.queue.upgrade() {
+                queue.keys.lock().insert(watch.wd, new_key.clone());
+            }
+        }
+        watches.entry(new_key).or_default().extend(list);
+    }
+}
+
+/// A set of watches and the queue of their events, like an `inotify`
+/// instance.
+///
+/// The watches are removed when it is dropped.
+pub struct Watcher {
+    queue: Arc<EventQueue>,
+}
+
+impl Watcher {
+    /// Creates a watcher without watches.
+    pub fn new() -> Self {
+        let queue = EventQueue {
+            next_wd: AtomicI32::new(1),
+            ..Default::default()
+        };
+        Self {
+            queue: Arc::new(queue),
+        }
+    }
+
+    /// Watches the file or directory at `path` for the events in `mask`, and
+    /// returns the watch descriptor, which identifies the watch in events.
+    ///
+    /// If the path is already watched by this watcher, the watch is updated
+    /// and keeps its descriptor. The options [`EventMask::ONLYDIR`],
+    /// [`EventMask::MASK_ADD`] and [`EventMask::ONESHOT`] are supported.
+    pub fn add_watch(&self, path: &str, mask: EventMask) -> AxResult<i32> {
+        if (mask & EventMask::ALL_EVENTS).is_empty() {
+            return ax_err!(InvalidInput, "no events to watch");
+        }
+        let node = crate::root::lookup(None, path)?;
+        if mask.contains(EventMask::ONLYDIR) && !node.get_attr()?.is_dir() {
+            return ax_err!(NotADirectory);
+        }
+        let path = crate::root::absolute_path(path)?;
+        let key = crate::root::node_key(crate::root::mount_of(&path).as_ref(), &node, &path);
+        let new_mask = mask & (EventMask::ALL_EVENTS | EventMask::ONESHOT);
+
+        let mut watches = WATCHES.lock();
+        let list = watches.entry(key.clone()).or_default();
+        let existing = list
+            .iter_mut()
+            .find(|w| core::ptr::eq(w.queue.as_ptr(), Arc::as_ptr(&self.queue)));
+        if let Some(watch) = existing {
+            if mask.contains(EventMask::MASK_ADD) {
+                watch.mask |= new_mask;
+            } else {
+                watch.mask = new_mask;
+            }
+            return Ok(watch.wd);
+        }
+        let wd = self.queue.next_wd.fetch_add(1, Ordering::Relaxed);
+        list.push(Watch {
+            queue: Arc::downgrade(&self.queue),
+            wd,
+            mask: new_mask,
+        });
+        NUM_WATCHES.fetch_add(1, Ordering::Relaxed);
+        self.queue.keys.lock().insert(wd, key);
+        Ok(wd)
+    }
+
+    /// Removes the watch `wd`, which reports a last [`EventMask::IGNORED`]
+    /// event.
+    pub fn remove_watch(&self, wd: i32) -> AxResult {
+        let mut watches = WATCHES.lock();
+        let Some(key) = self.queue.keys.lock().remove(&wd) else {
+            return ax_err!(InvalidInput, "no such watch");
+        };
+        unregister(&mut watches, &key, &self.queue, wd);
+        drop(watches);
+        self.queue.push(ignored(wd));
+        notify_waiters();
+        Ok(())
+    }
+
+    /// Whether there are events to read.
+    pub fn has_events(&self) -> bool {
+        !self.queue.events.lock().is_empty()
+    }
+
+    /// Reads events in order, while `take` accepts them, e.g., until a buffer
+    /// is full. Returns the number of events taken.
+    ///
+    /// If there are no events, it blocks until there are if `wait` is true,
+    /// or fails with `WouldBlock` otherwise.
+    pub fn read_events(&self, wait: bool, mut take: impl FnMut(&Event) -> bool) -> AxResult<usize> {
+        if !self.has_events() {
+            if !wait {
+                return ax_err!(WouldBlock, "no events");
+            }
+            WAITERS.wait_until(|| self.has_events())?;
+        }
+        let mut events = self.queue.events.lock();
+        let mut count = 0;
+        while let Some(event) = events.front() {
+            if !take(event) {
+                break;
+            }
+            events.pop_front();
+            count += 1;
+        }
+        Ok(count)
+    }
+}
+
+impl Default for Watcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for Watcher {
+    fn drop(&mut self) {
+        let mut watches = WATCHES.lock();
+        for (wd, key) in core::mem::take(&mut *self.queue.keys.lock()) {
+            unregister(&mut watches, &key, &self.queue, wd);
+        }
+    }
+}
@@ -0,0 +1,216 @@
+//! MBR and GPT partition table parsing.
+//!
+//! Partitions are numbered like Linux does: GPT partitions by their entry
+//! index starting from 1, MBR primary partitions from 1 to 4, and logical
+//! partitions in the extended partition from 5.
+
+use alloc::{format, string::String, vec::Vec};
+
+use crate::dev::{BLOCK_SIZE, Disk};
+
+/// Maximum number of GPT partition entries to parse.
+const GPT_MAX_ENTRIES: u32 = 128;
+/// Maximum number of logical partitions to parse in an extended partition.
+const MBR_MAX_LOGICAL: usize = 64;
+
+const MBR_TYPE_EMPTY: u8 = 0x00;
+const MBR_TYPE_GPT_PROTECTIVE: u8 = 0xee;
+const MBR_TYPES_EXTENDED: [u8; 3] = [0x05, 0x0f, 0x85];
+
+/// A partition found in the partition table of a disk.
+pub struct Partition {
+    /// The partition number, starting from 1.
+    pub number: usize,
+    /// The first block of the partition.
+    pub start_block: u64,
+    /// The number of blocks of the partition.
+    pub num_blocks: u64,
+    /// The partition UUID, as shown by `blkid` in `PARTUUID`.
+    pub uuid: String,
+}
+
+/// An entry in the partition table of an MBR or EBR.
+struct MbrEntry {
+    boot_flag: u8,
+    ty: u8,
+    start_lba: u32,
+    num_sectors: u32,
+}
+
+fn read_u32(buf: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(buf[offset..offset + 4].try_into().unwrap())
+}
+
+fn read_u64(buf: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(buf[offset..offset + 8].try_into().unwrap())
+}
+
+fn read_block(disk: &Disk, block_id: u64) -> Option<[u8; BLOCK_SIZE]> {
+    let mut buf = [0u8; BLOCK_SIZE];
+    disk.read_block(block_id, &mut buf).ok()?;
+    Some(buf)
+}
+
+/// Parses the partition table of `disk`.
+///
+/// Returns an empty list if the disk has no valid partition table, e.g., it
+/// holds a filesystem directly.
+pub fn parse(disk: &Disk) -> Vec<Partition> {
+    let Some(mbr) = read_block(disk, 0) else {
+        return Vec::new();
+    };
+    let Some(entries) = mbr_entries(&mbr) else {
+        return Vec::new();
+    };
+    if entries.iter().any(|e| e.ty == MBR_TYPE_GPT_PROTECTIVE) {
+        parse_gpt(disk).unwrap_or_default()
+    } else {
+        parse_mbr(disk, &mbr, &entries)
+    }
+}
+
+/// Returns the four partition table entries of an MBR, or `None` if `mbr` is
+/// not a valid MBR.
+fn mbr_entries(mbr: &[u8; BLOCK_SIZE]) -> Option<[MbrEntry; 4]> {
+    if mbr[510..512] != [0x55, 0xaa] {
+        return None;
+    }
+    // A FAT boot sector also ends with 0x55aa, but has a jump instruction
+    // at the beginning and a filesystem type string.
+    let has_fat_type = &mbr[0x36..0x39] == b"FAT" || &mbr[0x52..0x55] == b"FAT";
+    if matches!(mbr[0], 0xeb | 0xe9) && has_fat_type {
+        return None;
+    }
+    let entries: [MbrEntry; 4] = core::array::from_fn(|i| {
+        let e = &mbr[0x1be + i * 16..0x1be + (i + 1) * 16];
+        MbrEntry {
+            boot_flag: e[0],
+            ty: e[4],
+            start_lba: read_u32(e, 8),
+            num_sectors: read_u32(e, 12),
+        }
+    });
+    let valid = entries
+        .iter()
+        .all(|e| e.boot_flag == 0 || e.boot_flag == 0x80)
+        && entries.iter().any(|e| e.ty != MBR_TYPE_EMPTY);
+    valid.then_some(entries)
+}
+
+fn parse_mbr(disk: &Disk, mbr: &[u8; BLOCK_SIZE], entries: &[MbrEntry; 4]) -> Vec<Partition> {
+    let disk_id = read_u32(mbr, 0x1b8);
+    let mut parts = Vec::new();
+    let mut push = |number: usize, start_block: u64, num_blocks: u64| {
+        if num_blocks > 0 && start_block + num_blocks <= disk.num_blocks() {
+            parts.push(Partition {
+                number,
+                start_block,
+                num_blocks,
+                uuid: format!("{:08x}-{:02x}", disk_id, number),
+            });
+        } else {
+            warn!("partition {} is out of the disk, ignored", number);
+        }
+    };
+
+    let mut extended = None;
+    for (i, e) in entries.iter().enumerate() {
+        if e.ty == MBR_TYPE_EMPTY {
+            continue;
+        }
+        if MBR_TYPES_EXTENDED.contains(&e.ty) {
+            extended.get_or_insert(e.start_lba as u64);
+        } else {
+            push(i + 1, e.start_lba as u64, e.num_sectors as u64);
+        }
+    }
+
+    // Logical partitions are chained by EBRs, whose first entry is the
+    // partition relative to the EBR, and second entry is the next EBR
+    // relative to the extended partition.
+    if let Some(ext_start) = extended {
+        let mut ebr_block = ext_start;
+        for number in 5..5 + MBR_MAX_LOGICAL {
+            let Some(ebr) = read_block(disk, ebr_block) else {
+                break;
+            };
+            if ebr[510..512] != [0x55, 0xaa] {
+                break;
+            }
+            let e = &ebr[0x1be..0x1ce];
+            if e[4] != MBR_TYPE_EMPTY {
+                push(
+                    number,
+                    ebr_block + read_u32(e, 8) as u64,
+                    read_u32(e, 12) as u64,
+                );
+            }
+            let next = &ebr[0x1ce..0x1de];
+            if !MBR_TYPES_EXTENDED.contains(&next[4]) {
+                break;
+            }
+            ebr_block = ext_start + read_u32(next, 8) as u64;
+        }
+    }
+    parts
+}
+
+/// Formats a GUID stored in the mixed-endian on-disk layout.
+fn format_guid(g: &[u8]) -> String {
+    format!(
+        "{:08x}-{:04x}-{:04x}-{:02x}{:02x}-{:02x}{:02x}{:02x}{:02x}{:02x}{:02x}",
+        read_u32(g, 0),
+        u16::from_le_bytes([g[4], g[5]]),
+        u16::from_le_bytes([g[6], g[7]]),
+        g[8],
+        g[9],
+        g[10],
+        g[11],
+        g[12],
+        g[13],
+        g[14],
+        g[15],
+    )
+}
+
+fn parse_gpt(disk: &Disk) -> Option<Vec<Partition>> {
+    let header = read_block(disk, 1)?;
+    if &header[0..8] != b"EFI PART" {
+        warn!("invalid GPT header signature");
+        return None;
+    }
+    let entries_lba = read_u64(&header, 72);
+    let num_entries = read_u32(&header, 80).min(GPT_MAX_ENTRIES);
+    let entry_size = read_u32(&header, 84) as usize;
+    if entry_size < 128 || entry_size > BLOCK_SIZE || BLOCK_SIZE % entry_size != 0 {
+        warn!("unsupported GPT entry size {}", entry_size);
+        return None;
+    }
+
+    let entries_per_block = BLOCK_SIZE / entry_size;
+    let mut parts = Vec::new();
+    let mut block = [0u8; BLOCK_SIZE];
+    for i in 0..num_entries as usize {
+        if i % entries_per_block == 0 {
+            block = read_block(disk, entries_lba + (i / entries_per_block) as u64)?;
+        }
+        let offset = (i % entries_per_block) * entry_size;
+        let e = &block[offset..offset + entry_size];
+        if e[0..16].iter().all(|&b| b == 0) {
+            continue; // unused entry
+        }
+        let first_lba = read_u64(e, 32);
+        let last_lba = read_u64(e, 40);
+        if last_lba < first_lba || last_lba >= disk.num_blocks() {
+            warn!("GPT partition {} is out of the disk, ignored", i + 1);
+            continue;
+        }
+        parts.push(Partition {
+            number: i + 1,
+            start_block: first_lba,
+            num_blocks: last_lba - first_lba + 1,
+            uuid: format_guid(&e[16..32]),
+        });
+    }
+    Some(parts)
+}
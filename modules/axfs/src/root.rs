@@ -1,19 +1,37 @@
 //! Root directory of the filesystem
 //!
-//! TODO: it doesn't work very well if the mount points have containment relationships.
+//! Filesystems can be mounted on any directory, including directories of
+//! other mounted filesystems. Mount points are kept in a tree indexed by path
+//! components, and a path is always resolved by the deepest filesystem mounted
+//! on its way. Mounting on an existing mount point stacks the new filesystem on
+//! top of the old one, which becomes visible again after unmounting.
 
-use alloc::{format, string::String, string::ToString, sync::Arc, vec::Vec};
+use alloc::{
+    borrow::Cow,
+    collections::BTreeMap,
+    format,
+    string::String,
+    string::ToString,
+    sync::{Arc, Weak},
+    vec::Vec,
+};
 use axerrno::{AxError, AxResult, ax_err};
 use axfs_vfs::{VfsNodeAttr, VfsNodeOps, VfsNodePerm, VfsNodeRef, VfsNodeType, VfsOps, VfsResult};
 use axns::{ResArc, def_resource};
 use axsync::Mutex;
+use core::{
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+    time::Duration,
+};
 use lazyinit::LazyInit;
 use spin::RwLock;
 
 use crate::{
     api::FileType,
-    fs::{self},
-    mounts,
+    cache, dev,
+    fops::{FileAttr, FsStat},
+    fs::{self, FileSystem},
+    lock, mounts, notify,
 };
 
 def_resource! {
@@ -36,20 +54,210 @@ impl CURRENT_DIR {
 }
 
 struct MountPoint {
-    path: &'static str,
-    fs: Arc<dyn VfsOps>,
+    /// Unique id of the mount, which is never 0, the id of the root
+    /// filesystem.
+    id: u64,
+    /// The device or name the filesystem is mounted from.
+    source: String,
+    path: String,
+    /// The filesystem type, as named by `mount(8)`.
+    fstype: String,
+    fs: Arc<dyn FileSystem>,
+    /// Number of files and directories opened in this filesystem.
+    open_count: AtomicUsize,
+}
+
+/// A node in the mount tree, indexed by path components.
+struct MountNode {
+    /// Filesystems mounted at this path, the last one is visible.
+    mounts: Vec<Arc<MountPoint>>,
+    children: BTreeMap<String, MountNode>,
 }
 
+/// A reference to a mounted filesystem, held by files and directories opened
+/// in it to prevent it from being unmounted.
+pub(crate) struct MountRef(Arc<MountPoint>);
+
 struct RootDirectory {
-    main_fs: Arc<dyn VfsOps>,
-    mounts: RwLock<Vec<MountPoint>>,
+    main_fs: Arc<dyn FileSystem>,
+    /// The device and type of the root filesystem.
+    main_source: String,
+    main_fstype: &'static str,
+    mounts: RwLock<MountNode>,
 }
 
 static ROOT_DIR: LazyInit<Arc<RootDirectory>> = LazyInit::new();
+static NEXT_MOUNT_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Ids of the persistent nodes of files in filesystems without inode numbers,
+/// by the ids of their mounts and the addresses of the nodes.
+///
+/// The nodes are referenced weakly, so that their memory, and thus their
+/// addresses, are not reused while they have ids. Ids are never reused.
+struct NodeIds {
+    ids: BTreeMap<(u64, u64), (Weak<dyn VfsNodeOps>, u64)>,
+    next_id: u64,
+    /// Number of ids after the ids of released nodes were last removed.
+    pruned_len: usize,
+}
+
+static NODE_IDS: spin::Mutex<NodeIds> = spin::Mutex::new(NodeIds {
+    ids: BTreeMap::new(),
+    next_id: 1,
+    pruned_len: 0,
+});
+
+/// Identity of a file, which keys the state shared by its opened instances,
+/// e.g., cached pages and locks.
+///
+/// Files in filesystems with inode numbers are identified by them, which are
+/// the same for all paths and hard links of a file, and other files by their
+/// absolute paths.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum NodeKey {
+    /// The id of the mount, which is 0 for the root filesystem, and the inode
+    /// number in it.
+    Inode(u64, u64),
+    /// The absolute path, without trailing slashes.
+    Path(String),
+}
+
+impl NodeKey {
+    /// Whether the file is identified by the path `dir` or a path under it.
+    pub(crate) fn is_under(&self, dir: &str) -> bool {
+        let Self::Path(path) = self else {
+            return false;
+        };
+        path.strip_prefix(dir.trim_end_matches('/'))
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+    }
+
+    /// Returns the inode number of the file, or a hash of the path of a file
+    /// identified by it, which is stable as long as the path is.
+    pub(crate) fn ino(&self) -> u64 {
+        match self {
+            Self::Inode(_, ino) => *ino,
+            // FNV-1a
+            Self::Path(path) => path.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, b| {
+                (hash ^ b as u64).wrapping_mul(0x0100_0000_01b3)
+            }),
+        }
+    }
+
+    /// Whether the file is in the filesystem with mount id `id`, which is
+    /// mounted at `path`.
+    fn is_in_mount(&self, id: u64, path: &str) -> bool {
+        match self {
+            Self::Inode(mount_id, _) => *mount_id == id,
+            Self::Path(_) => self.is_under(path),
+        }
+    }
+}
+
+impl NodeIds {
+    /// Returns the id of the persistent node `node` in the mount `mount_id`,
+    /// and allocates one if it has none.
+    fn id_of(&mut self, mount_id: u64, node: &VfsNodeRef) -> u64 {
+        let next_id = &mut self.next_id;
+        let (_, id) = self
+            .ids
+            .entry((mount_id, fs::node_addr(node.as_ref())))
+            .or_insert_with(|| {
+                *next_id += 1;
+                (Arc::downgrade(node), *next_id - 1)
+            });
+        *id
+    }
+
+    /// Removes the id of the node at `addr` in the mount `mount_id` if the
+    /// node has been released, and returns the identity of its file.
+    fn release(&mut self, mount_id: u64, addr: u64) -> Option<NodeKey> {
+        let (node, id) = self.ids.get(&(mount_id, addr))?;
+        if node.strong_count() > 0 {
+            return None;
+        }
+        let key = NodeKey::Inode(mount_id, *id);
+        self.ids.remove(&(mount_id, addr));
+        Some(key)
+    }
+
+    /// Removes the ids of all released nodes if the number of ids doubled
+    /// since the last time, and returns the identities of their files.
+    ///
+    /// It catches nodes released without being opened, e.g., the current
+    /// directory after it is removed.
+    fn prune(&mut self) -> Vec<NodeKey> {
+        if self.ids.len() < (self.pruned_len * 2).max(64) {
+            return Vec::new();
+        }
+        let mut released = Vec::new();
+        self.ids.retain(|&(mount_id, _), (node, id)| {
+            let alive = node.strong_count() > 0;
+            if !alive {
+                released.push(NodeKey::Inode(mount_id, *id));
+            }
+            alive
+        });
+        self.pruned_len = self.ids.len();
+        released
+    }
+}
+
+/// Drops the cached pages, locks and watches of the files `keys`, whose
+/// nodes have been released.
+fn forget_released(keys: Vec<NodeKey>) {
+    for key in keys {
+        cache::forget(|k| *k == key, true).ok();
+        lock::forget(&key);
+        notify::forget(&key);
+    }
+}
+
+/// A guard of an opened file or directory whose file is identified by the id
+/// of its persistent node, which drops the state of the file when it is
+/// dropped after the last reference to the node, e.g., the file is closed
+/// after it is removed.
+pub(crate) struct NodeRelease(Option<(u64, u64)>);
+
+impl NodeRelease {
+    /// Creates a guard of `node` in the filesystem referenced by `mount`.
+    pub(crate) fn new(mount: Option<&MountRef>, node: &VfsNodeRef) -> Self {
+        let node = fs_of(mount).persistent_node(node);
+        Self(node.map(|node| (mount_id(mount), fs::node_addr(node.as_ref()))))
+    }
+}
+
+impl Drop for NodeRelease {
+    fn drop(&mut self) {
+        if let Some((mount_id, addr)) = self.0 {
+            let released = NODE_IDS.lock().release(mount_id, addr);
+            forget_released(released.into_iter().collect());
+        }
+    }
+}
+
+/// Drops `node` in the filesystem referenced by `mount`, and the state of its
+/// file if it was the last reference to the node.
+fn release_node(mount: Option<&MountRef>, node: VfsNodeRef) {
+    let guard = NodeRelease::new(mount, &node);
+    drop(node);
+    drop(guard);
+}
 
 impl MountPoint {
-    pub fn new(path: &'static str, fs: Arc<dyn VfsOps>) -> Self {
-        Self { path, fs }
+    pub fn new(source: &str, path: String, fstype: &str, fs: Arc<dyn FileSystem>) -> Self {
+        Self {
+            id: NEXT_MOUNT_ID.fetch_add(1, Ordering::Relaxed),
+            source: if source.is_empty() { "none" } else { source }.into(),
+            path,
+            fstype: fstype.into(),
+            fs,
+            open_count: AtomicUsize::new(0),
+        }
+    }
+
+    fn is_busy(&self) -> bool {
+        self.open_count.load(Ordering::Acquire) > 0
     }
 }
 
@@ -59,42 +267,236 @@ impl Drop for MountPoint {
     }
 }
 
+impl MountRef {
+    fn new(mp: Arc<MountPoint>) -> Self {
+        mp.open_count.fetch_add(1, Ordering::AcqRel);
+        Self(mp)
+    }
+
+    /// Whether `a` and `b` refer to the same mount, where `None` stands for
+    /// the root filesystem.
+    pub(crate) fn same_fs(a: Option<&Self>, b: Option<&Self>) -> bool {
+        match (a, b) {
+            (None, None) => true,
+            (Some(a), Some(b)) => Arc::ptr_eq(&a.0, &b.0),
+            _ => false,
+        }
+    }
+}
+
+impl Clone for MountRef {
+    fn clone(&self) -> Self {
+        Self::new(self.0.clone())
+    }
+}
+
+impl Drop for MountRef {
+    fn drop(&mut self) {
+        self.0.open_count.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+impl MountNode {
+    const fn new() -> Self {
+        Self {
+            mounts: Vec::new(),
+            children: BTreeMap::new(),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.mounts.is_empty() && self.children.is_empty()
+    }
+
+    fn has_mounts(&self) -> bool {
+        !self.mounts.is_empty() || self.children.values().any(Self::has_mounts)
+    }
+
+    /// Calls `f` on all filesystems mounted at or under this node, parents
+    /// before children.
+    fn for_each(&self, f: &mut impl FnMut(&MountPoint)) {
+        self.mounts.iter().for_each(|mp| f(mp));
+        for child in self.children.values() {
+            child.for_each(f);
+        }
+    }
+
+    fn get(&self, comps: &[&str]) -> Option<&Self> {
+        comps
+            .iter()
+            .try_fold(self, |node, comp| node.children.get(*comp))
+    }
+
+    fn insert(&mut self, mp: Arc<MountPoint>) {
+        let mut node = self;
+        for comp in mp.path.split('/').filter(|c| !c.is_empty()) {
+            node = node.children.entry(comp.into()).or_insert_with(Self::new);
+        }
+        node.mounts.push(mp);
+    }
+
+    /// Removes the topmost filesystem mounted at `comps`, and prunes the nodes
+    /// that become empty.
+    fn pop(&mut self, comps: &[&str]) -> AxResult<Arc<MountPoint>> {
+        match comps.split_first() {
+            None => {
+                let mp = self.mounts.last().ok_or(AxError::InvalidInput)?;
+                if mp.is_busy() || self.children.values().any(Self::has_mounts) {
+                    return ax_err!(ResourceBusy);
+                }
+                Ok(self.mounts.pop().unwrap())
+            }
+            Some((first, rest)) => {
+                let child = self.children.get_mut(*first).ok_or(AxError::InvalidInput)?;
+                let mp = child.pop(rest)?;
+                if child.is_empty() {
+                    self.children.remove(*first);
+                }
+                Ok(mp)
+            }
+        }
+    }
+
+    /// Finds the deepest filesystem mounted on the way of `path`, returns it
+    /// with the rest of `path` relative to its root.
+    ///
+    /// `..` components are left to the filesystems to resolve, so the search
+    /// stops at the first of them.
+    fn find<'a>(&self, path: &'a str) -> Option<(Arc<MountPoint>, &'a str)> {
+        let mut node = self;
+        let mut found = None;
+        let mut pos = 0;
+        for comp in path.split('/') {
+            let end = pos + comp.len();
+            match comp {
+                "" | "." => {}
+                ".." => break,
+                _ => match node.children.get(comp) {
+                    Some(child) => {
+                        node = child;
+                        if let Some(mp) = node.mounts.last() {
+                            found = Some((mp.clone(), end));
+                        }
+                    }
+                    None => break,
+                },
+            }
+            pos = end + 1;
+        }
+        found.map(|(mp, end)| (mp, path[end..].trim_start_matches('/')))
+    }
+}
+
+fn path_components(path: &str) -> Vec<&str> {
+    path.split('/').filter(|c| !c.is_empty()).collect()
+}
+
 impl RootDirectory {
-    pub const fn new(main_fs: Arc<dyn VfsOps>) -> Self {
+    pub const fn new(
+        main_fs: Arc<dyn FileSystem>,
+        main_source: String,
+        main_fstype: &'static str,
+    ) -> Self {
         Self {
             main_fs,
-            mounts: RwLock::new(Vec::new()),
+            main_source,
+            main_fstype,
+            mounts: RwLock::new(MountNode::new()),
         }
     }
 
-    pub fn mount(&self, path: &'static str, fs: Arc<dyn VfsOps>) -> AxResult {
-        if path == "/" {
-            return ax_err!(InvalidInput, "cannot mount root filesystem");
-        }
+    pub fn mount(
+        &self,
+        source: &str,
+        path: &str,
+        fstype: &str,
+        fs: Arc<dyn FileSystem>,
+    ) -> AxResult {
         if !path.starts_with('/') {
             return ax_err!(InvalidInput, "mount path must start with '/'");
         }
-        if self.mounts.read().iter().any(|mp| mp.path == path) {
-            return ax_err!(InvalidInput, "mount point already exists");
+        let path = axfs_vfs::path::canonicalize(path);
+        if path == "/" {
+            return ax_err!(InvalidInput, "cannot mount root filesystem");
+        }
+        // create the mount point in the filesystem it resides in if it does not exist
+        let mount_point = self.lookup_mounted_fs(&path, |parent_fs, rest_path| {
+            let dir = parent_fs.root_dir();
+            if rest_path.is_empty() {
+                return Ok(dir); // stacked on another mount point
+            }
+            match dir.create(rest_path, FileType::Dir) {
+                Ok(()) | Err(AxError::AlreadyExists) => {}
+                Err(e) => return Err(e),
+            }
+            dir.lookup(rest_path)
+        })?;
+        if !mount_point.get_attr()?.is_dir() {
+            return ax_err!(NotADirectory);
         }
-        // create the mount point in the main filesystem if it does not exist
-        self.main_fs.root_dir().create(path, FileType::Dir)?;
-        fs.mount(path, self.main_fs.root_dir().lookup(path)?)?;
-        self.mounts.write().push(MountPoint::new(path, fs));
+        fs.mount(&path, mount_point)?;
+        // files under the mount point are hidden from now on
+        cache::forget(|key| key.is_under(&path), false)?;
+        self.mounts
+            .write()
+            .insert(Arc::new(MountPoint::new(source, path, fstype, fs)));
         Ok(())
     }
 
-    pub fn _umount(&self, path: &str) {
-        self.mounts.write().retain(|mp| mp.path != path);
+    pub fn umount(&self, path: &str) -> AxResult {
+        let path = axfs_vfs::path::canonicalize(path);
+        let comps = path_components(&path);
+        if comps.is_empty() {
+            return ax_err!(InvalidInput, "cannot unmount root filesystem");
+        }
+        if path_components(&CURRENT_DIR_PATH.lock()).starts_with(&comps) {
+            return ax_err!(ResourceBusy);
+        }
+        let mp = self.mounts.write().pop(&comps)?;
+        if let Err(e) = cache::forget(|key| key.is_in_mount(mp.id, &path), false) {
+            warn!("failed to write back cached files on {}: {:?}", path, e);
+        }
+        let source = mp.source.clone();
+        drop(mp); // `VfsOps::umount` is called on drop
+        // write back what the filesystem left in the block queue
+        if let Err(e) = dev::flush_block_device(&source) {
+            warn!("failed to flush {}: {:?}", source, e);
+        }
+        Ok(())
     }
 
     pub fn contains(&self, path: &str) -> bool {
-        self.mounts.read().iter().any(|mp| mp.path == path)
+        self.mounts
+            .read()
+            .get(&path_components(path))
+            .is_some_and(|node| !node.mounts.is_empty())
+    }
+
+    fn mount_of(&self, path: &str) -> Option<MountRef> {
+        // take the reference under the lock, so that an umount in between
+        // can not see the mount point unused
+        let mounts = self.mounts.read();
+        let found = mounts.find(path.trim_matches('/'));
+        found.map(|(mp, _)| MountRef::new(mp))
+    }
+
+    fn link(&self, old_path: &str, new_path: &str) -> AxResult {
+        self.lookup_mounted_fs(old_path, |old_fs, old_rest| {
+            self.lookup_mounted_fs(new_path, |new_fs, new_rest| {
+                if old_rest.is_empty() || new_rest.is_empty() {
+                    ax_err!(PermissionDenied) // cannot link mount points
+                } else if !Arc::ptr_eq(&old_fs, &new_fs) {
+                    ax_err!(InvalidInput, "cannot link across filesystems")
+                } else {
+                    old_fs.link(old_rest, new_rest)
+                }
+            })
+        })
     }
 
     fn lookup_mounted_fs<F, T>(&self, path: &str, f: F) -> AxResult<T>
     where
-        F: FnOnce(Arc<dyn VfsOps>, &str) -> AxResult<T>,
+        F: FnOnce(Arc<dyn FileSystem>, &str) -> AxResult<T>,
     {
         debug!("lookup at root: {}", path);
         let path = path.trim_matches('/');
@@ -102,30 +504,10 @@ impl RootDirectory {
             return self.lookup_mounted_fs(rest, f);
         }
 
-        let mut idx = 0;
-        let mut max_len = 0;
-
-        // Find the filesystem that has the longest mounted path match
-        // TODO: more efficient, e.g. trie
-        for (i, mp) in self.mounts.read().iter().enumerate() {
-            // skip the first '/'
-            if path.starts_with(&mp.path[1..]) && mp.path.len() - 1 > max_len {
-                max_len = mp.path.len() - 1;
-                idx = i;
-            }
-        }
-
-        if max_len == 0 {
-            f(self.main_fs.clone(), path) // not matched any mount point
-        } else {
-            let rest_path = if path.len() > max_len && path.as_bytes()[max_len] == b'/' {
-                &path[max_len + 1..] // skip mount point and the '/'
-            } else if path.len() == max_len {
-                "" // exact match, empty rest
-            } else {
-                &path[max_len..] // fallback
-            };
-            f(self.mounts.read()[idx].fs.clone(), rest_path) // matched at `idx`
+        let found = self.mounts.read().find(path);
+        match found {
+            Some((mp, rest_path)) => f(mp.fs.clone(), rest_path),
+            None => f(self.main_fs.clone(), path), // not matched any mount point
         }
     }
 }
@@ -162,12 +544,19 @@ impl VfsNodeOps for RootDirectory {
     }
 
     fn rename(&self, src_path: &str, dst_path: &str) -> VfsResult {
-        self.lookup_mounted_fs(src_path, |fs, rest_path| {
-            if rest_path.is_empty() {
-                ax_err!(PermissionDenied) // cannot rename mount points
-            } else {
-                fs.root_dir().rename(src_path, dst_path)
+        self.lookup_mounted_fs(src_path, |src_fs, src_rest| {
+            if src_rest.is_empty() {
+                return ax_err!(PermissionDenied); // cannot rename mount points
             }
+            self.lookup_mounted_fs(dst_path, |dst_fs, dst_rest| {
+                if dst_rest.is_empty() {
+                    ax_err!(PermissionDenied) // cannot overwrite mount points
+                } else if !Arc::ptr_eq(&src_fs, &dst_fs) {
+                    ax_err!(InvalidInput, "cannot rename across filesystems")
+                } else {
+                    src_fs.root_dir().rename(src_rest, dst_rest)
+                }
+            })
         })
     }
 
@@ -186,50 +575,78 @@ impl VfsNodeOps for RootDirectory {
             if rest_path.is_empty() {
                 ax_err!(NotFound) // cannot read link of mount points
             } else {
-                fs.root_dir().readlink(path, buf)
+                fs.root_dir().readlink(rest_path, buf)
             }
         })
     }
 }
 
-pub(crate) fn init_rootfs(disk: crate::dev::Disk) {
+#[cfg(any(not(feature = "initramfs"), feature = "myfs"))]
+pub(crate) fn init_rootfs(name: &str, disk: crate::dev::Disk) {
     cfg_if::cfg_if! {
         if #[cfg(feature = "myfs")] { // override the default filesystem
-            let main_fs = fs::myfs::new_myfs(disk);
+            let main_fs = Arc::new(fs::ForeignFs(fs::myfs::new_myfs(disk)));
+            let fstype = "myfs";
         } else if #[cfg(feature = "lwext4_rs")] {
             static EXT4_FS: LazyInit<Arc<fs::lwext4_rust::Ext4FileSystem>> = LazyInit::new();
             EXT4_FS.init_once(Arc::new(fs::lwext4_rust::Ext4FileSystem::new(disk)));
             let main_fs = EXT4_FS.clone();
+            let fstype = "ext4";
         } else if #[cfg(feature = "fatfs")] {
             static FAT_FS: LazyInit<Arc<fs::fatfs::FatFileSystem>> = LazyInit::new();
             FAT_FS.init_once(Arc::new(fs::fatfs::FatFileSystem::new(disk)));
             FAT_FS.init();
             let main_fs = FAT_FS.clone();
+            let fstype = "vfat";
         }
     }
 
-    let root_dir = RootDirectory::new(main_fs);
+    // keep changes in memory, over the read-only disk
+    #[cfg(feature = "overlayfs")]
+    let (main_fs, fstype): (Arc<dyn FileSystem>, _) = {
+        let upper = Arc::new(fs::ramfs::RamFileSystem::new());
+        let overlay = fs::overlayfs::OverlayFileSystem::new(upper, main_fs);
+        (Arc::new(overlay), "overlay")
+    };
+
+    init_root_dir(main_fs, format!("/dev/{}", name), fstype);
+}
+
+/// Initializes the root filesystem with a ramfs, into which the initramfs
+/// `archive` is unpacked.
+#[cfg(feature = "initramfs")]
+pub(crate) fn init_rootfs_from_initramfs(archive: &[u8]) -> AxResult {
+    let main_fs = Arc::new(fs::ramfs::RamFileSystem::new());
+    crate::initramfs::unpack(archive, &main_fs.root_dir())?;
+    init_root_dir(main_fs, "rootfs".into(), "rootfs");
+    Ok(())
+}
+
+/// Sets the root directory with `main_fs` on `/`, and mounts the pseudo
+/// filesystems on it.
+fn init_root_dir(main_fs: Arc<dyn FileSystem>, source: String, fstype: &'static str) {
+    let root_dir = RootDirectory::new(main_fs, source, fstype);
 
     #[cfg(feature = "devfs")]
     root_dir
-        .mount("/dev", mounts::devfs())
+        .mount("devtmpfs", "/dev", "devtmpfs", mounts::devfs())
         .expect("failed to mount devfs at /dev");
 
     #[cfg(feature = "ramfs")]
     root_dir
-        .mount("/tmp", mounts::ramfs())
-        .expect("failed to mount ramfs at /tmp");
+        .mount("tmpfs", "/tmp", "tmpfs", mounts::tmpfs("").unwrap())
+        .expect("failed to mount tmpfs at /tmp");
 
     // Mount another ramfs as procfs
     #[cfg(feature = "procfs")]
     root_dir // should not fail
-        .mount("/proc", mounts::procfs().unwrap())
+        .mount("proc", "/proc", "proc", mounts::procfs().unwrap())
         .expect("fail to mount procfs at /proc");
 
     // Mount another ramfs as sysfs
     #[cfg(feature = "sysfs")]
     root_dir // should not fail
-        .mount("/sys", mounts::sysfs().unwrap())
+        .mount("sysfs", "/sys", "sysfs", mounts::sysfs().unwrap())
         .expect("fail to mount sysfs at /sys");
 
     ROOT_DIR.init_once(Arc::new(root_dir));
@@ -245,6 +662,128 @@ fn parent_node_of(dir: Option<&VfsNodeRef>, path: &str) -> VfsNodeRef {
     }
 }
 
+/// Returns the directory to resolve `path` from, and `path` relative to it.
+///
+/// Paths that are not relative to an opened directory are resolved from the
+/// root directory, so that every mount point on the way is honored.
+fn start_of<'a>(dir: Option<&VfsNodeRef>, path: &'a str) -> (VfsNodeRef, Cow<'a, str>) {
+    match dir {
+        _ if path.starts_with('/') => (ROOT_DIR.clone(), Cow::Borrowed(path)),
+        Some(dir) => (dir.clone(), Cow::Borrowed(path)),
+        None => (
+            ROOT_DIR.clone(),
+            Cow::Owned(CURRENT_DIR_PATH.lock().clone() + path),
+        ),
+    }
+}
+
+/// Returns the attributes of `node`, which is in the filesystem referenced by
+/// `mount`, or in the root filesystem if `mount` is `None`, and identified by
+/// `key`.
+///
+/// Filesystems without hard links report one link to files, and two to
+/// directories.
+pub(crate) fn node_attr(
+    mount: Option<&MountRef>,
+    node: &VfsNodeRef,
+    key: &NodeKey,
+) -> AxResult<FileAttr> {
+    let fs = fs_of(mount);
+    let attr = node.get_attr()?;
+    let times = fs.node_times(node.as_ref()).unwrap_or_default();
+    let nlink = fs
+        .node_nlink(node.as_ref())
+        .unwrap_or(if attr.is_dir() { 2 } else { 1 });
+    Ok(FileAttr::new(attr, times, nlink, key.ino()))
+}
+
+/// Returns the inode number of `node` in the filesystem referenced by
+/// `mount`, or the id of its persistent node, which is allocated if it has
+/// none.
+fn node_ino(mount: Option<&MountRef>, node: &VfsNodeRef) -> Option<u64> {
+    let fs = fs_of(mount);
+    if let Some(ino) = fs.node_ino(node.as_ref()) {
+        return Some(ino);
+    }
+    let node = fs.persistent_node(node)?;
+    let mut ids = NODE_IDS.lock();
+    let released = ids.prune();
+    let id = ids.id_of(mount_id(mount), &node);
+    drop(ids);
+    forget_released(released);
+    Some(id)
+}
+
+/// Returns the identity of `node`, which is in the filesystem referenced by
+/// `mount`, or in the root filesystem if `mount` is `None`, at `abs_path`.
+pub(crate) fn node_key(mount: Option<&MountRef>, node: &VfsNodeRef, abs_path: &str) -> NodeKey {
+    match node_ino(mount, node) {
+        Some(ino) => NodeKey::Inode(mount_id(mount), ino),
+        None => NodeKey::Path(abs_path.trim_end_matches('/').into()),
+    }
+}
+
+/// Returns the identity of the file at the absolute path `abs_path`, without
+/// following a symbolic link at the end, or the path itself if it does not
+/// exist.
+pub(crate) fn path_key(abs_path: &str) -> NodeKey {
+    match lookup(None, abs_path) {
+        Ok(node) => node_key(mount_of(abs_path).as_ref(), &node, abs_path),
+        Err(_) => NodeKey::Path(abs_path.trim_end_matches('/').into()),
+    }
+}
+
+/// Returns the attributes of the file at `path`.
+pub(crate) fn path_attr(path: &str) -> AxResult<FileAttr> {
+    let node = lookup(None, path)?;
+    let mount = mount_of(path);
+    let key = node_key(mount.as_ref(), &node, &absolute_path(path)?);
+    let attr = node_attr(mount.as_ref(), &node, &key)?;
+    match cache::file_size(&key) {
+        Some(size) => Ok(attr.with_size(size)),
+        None => Ok(attr),
+    }
+}
+
+/// Returns the statistics of the filesystem that `path` is in.
+pub(crate) fn statfs(path: &str) -> AxResult<FsStat> {
+    lookup(None, path)?;
+    fs_of(mount_of(path).as_ref()).statfs()
+}
+
+/// Returns the filesystem referenced by `mount`, or the root filesystem if
+/// `mount` is `None`.
+fn fs_of(mount: Option<&MountRef>) -> &Arc<dyn FileSystem> {
+    mount.map_or(&ROOT_DIR.main_fs, |mount| &mount.0.fs)
+}
+
+/// Returns the id of the mount referenced by `mount`, which is 0 for the root
+/// filesystem.
+fn mount_id(mount: Option<&MountRef>) -> u64 {
+    mount.map_or(0, |mount| mount.0.id)
+}
+
+/// Whether the regular file `node` in the filesystem referenced by `mount` is
+/// kept in the page cache.
+///
+/// Files with more than one hard link are not if the filesystem has no inode
+/// numbers, as they are then identified by paths, and the page cache would
+/// keep a copy of the data for each link.
+pub(crate) fn page_cached(mount: Option<&MountRef>, node: &VfsNodeRef) -> bool {
+    let fs = fs_of(mount);
+    fs.page_cached()
+        && (fs.node_ino(node.as_ref()).is_some()
+            || fs.persistent_node(node).is_some()
+            || fs.node_nlink(node.as_ref()).unwrap_or(1) <= 1)
+}
+
+/// Returns a reference to the filesystem mounted on the way of `path`, or
+/// `None` if `path` is in the root filesystem.
+pub(crate) fn mount_of(path: &str) -> Option<MountRef> {
+    let (_, path) = start_of(None, path);
+    ROOT_DIR.mount_of(&path)
+}
+
 pub(crate) fn absolute_path(path: &str) -> AxResult<String> {
     if path.starts_with('/') {
         Ok(axfs_vfs::path::canonicalize(path))
@@ -258,7 +797,8 @@ pub(crate) fn lookup(dir: Option<&VfsNodeRef>, path: &str) -> AxResult<VfsNodeRe
     if path.is_empty() {
         return ax_err!(NotFound);
     }
-    let node = parent_node_of(dir, path).lookup(path)?;
+    let (parent, rel_path) = start_of(dir, path);
+    let node = parent.lookup(&rel_path)?;
     if path.ends_with('/') && !node.get_attr()?.is_dir() {
         ax_err!(NotADirectory)
     } else {
@@ -271,6 +811,12 @@ pub(crate) fn lookup_follow_symlinks_public(
     dir: Option<&VfsNodeRef>,
     path: &str,
 ) -> AxResult<VfsNodeRef> {
+    Ok(lookup_follow_symlinks(dir, path)?.0)
+}
+
+/// Lookup a path and follow symbolic links, returning the final target and
+/// its path.
+fn lookup_follow_symlinks(dir: Option<&VfsNodeRef>, path: &str) -> AxResult<(VfsNodeRef, String)> {
     const MAX_SYMLINK_DEPTH: u32 = 8;
 
     let mut current_path = String::from(path);
@@ -284,7 +830,7 @@ pub(crate) fn lookup_follow_symlinks_public(
         match lookup(dir, &current_path) {
             Ok(node) => {
                 if !node.is_symlink() {
-                    return Ok(node);
+                    return Ok((node, current_path));
                 }
 
                 // Read symlink target
@@ -316,20 +862,48 @@ pub(crate) fn create_file(dir: Option<&VfsNodeRef>, path: &str) -> AxResult<VfsN
     } else if path.ends_with('/') {
         return ax_err!(NotADirectory);
     }
-    let parent = parent_node_of(dir, path);
-    parent.create(path, VfsNodeType::File)?;
-    parent.lookup(path)
+    let (parent, path) = start_of(dir, path);
+    parent.create(&path, VfsNodeType::File)?;
+    parent.lookup(&path)
 }
 
 pub(crate) fn create_dir(dir: Option<&VfsNodeRef>, path: &str) -> AxResult {
     match lookup(dir, path) {
         Ok(_) => ax_err!(AlreadyExists),
-        Err(AxError::NotFound) => parent_node_of(dir, path).create(path, VfsNodeType::Dir),
+        Err(AxError::NotFound) => {
+            let (parent, rel_path) = start_of(dir, path);
+            parent.create(&rel_path, VfsNodeType::Dir)?;
+            if dir.is_none() {
+                notify::created(&absolute_path(path)?, true);
+            }
+            Ok(())
+        }
         Err(e) => Err(e),
     }
 }
 
-pub(crate) fn remove_file(dir: Option<&VfsNodeRef>, path: &str) -> AxResult {
+pub(crate) fn remove_file(path: &str) -> AxResult {
+    let abs_path = absolute_path(path)?;
+    unlink_file(None, mount_of(path).as_ref(), path, &abs_path)
+}
+
+/// Removes the file at `path` relative to `dir`, which is in the filesystem
+/// referenced by `mount`. `abs_path` is the absolute path of the file.
+pub(crate) fn remove_file_at(
+    dir: &VfsNodeRef,
+    mount: Option<&MountRef>,
+    path: &str,
+    abs_path: &str,
+) -> AxResult {
+    unlink_file(Some(dir), mount, path, abs_path)
+}
+
+fn unlink_file(
+    dir: Option<&VfsNodeRef>,
+    mount: Option<&MountRef>,
+    path: &str,
+    abs_path: &str,
+) -> AxResult {
     let node = lookup(dir, path)?;
     let attr = node.get_attr()?;
     if attr.is_dir() {
@@ -337,11 +911,23 @@ pub(crate) fn remove_file(dir: Option<&VfsNodeRef>, path: &str) -> AxResult {
     } else if !attr.perm().owner_writable() {
         ax_err!(PermissionDenied)
     } else {
-        parent_node_of(dir, path).remove(path)
+        let nlink = fs_of(mount).node_nlink(node.as_ref()).unwrap_or(1);
+        let key = node_key(mount, &node, abs_path);
+        let (parent, rel_path) = start_of(dir, path);
+        parent.remove(&rel_path)?;
+        notify::removed(&key, abs_path, false, nlink <= 1);
+        if nlink <= 1 {
+            // the data is gone with the last link
+            cache::forget(|k| *k == key, true)?;
+        }
+        release_node(mount, node);
+        Ok(())
     }
 }
 
-pub(crate) fn remove_dir(dir: Option<&VfsNodeRef>, path: &str) -> AxResult {
+/// Removes the empty directory at `path` relative to `dir`, whose absolute
+/// path is `abs_path`.
+pub(crate) fn remove_dir(dir: Option<&VfsNodeRef>, path: &str, abs_path: &str) -> AxResult {
     if path.is_empty() {
         return ax_err!(NotFound);
     }
@@ -355,7 +941,7 @@ pub(crate) fn remove_dir(dir: Option<&VfsNodeRef>, path: &str) -> AxResult {
     {
         return ax_err!(InvalidInput);
     }
-    if ROOT_DIR.contains(&absolute_path(path)?) {
+    if ROOT_DIR.contains(abs_path) {
         return ax_err!(PermissionDenied);
     }
 
@@ -366,7 +952,13 @@ pub(crate) fn remove_dir(dir: Option<&VfsNodeRef>, path: &str) -> AxResult {
     } else if !attr.perm().owner_writable() {
         ax_err!(PermissionDenied)
     } else {
-        parent_node_of(dir, path).remove(path)
+        let mount = mount_of(abs_path);
+        let key = node_key(mount.as_ref(), &node, abs_path);
+        let (parent, rel_path) = start_of(dir, path);
+        parent.remove(&rel_path)?;
+        notify::removed(&key, abs_path, true, true);
+        release_node(mount.as_ref(), node);
+        Ok(())
     }
 }
 
@@ -398,12 +990,56 @@ pub(crate) fn set_current_dir(path: &str) -> AxResult {
     }
 }
 
+pub(crate) fn mount(source: &str, path: &str, fstype: &str, fs: Arc<dyn FileSystem>) -> AxResult {
+    ROOT_DIR.mount(source, &absolute_path(path)?, fstype, fs)
+}
+
+pub(crate) fn umount(path: &str) -> AxResult {
+    ROOT_DIR.umount(&absolute_path(path)?)
+}
+
+/// Returns the mounted filesystems, one per line in the format of
+/// `/proc/mounts`.
+pub(crate) fn mount_table() -> String {
+    let mut table = format!(
+        "{} / {} rw 0 0\n",
+        ROOT_DIR.main_source, ROOT_DIR.main_fstype
+    );
+    ROOT_DIR.mounts.read().for_each(&mut |mp| {
+        table += &format!("{} {} {} rw 0 0\n", mp.source, mp.path, mp.fstype);
+    });
+    table
+}
+
 pub(crate) fn rename(old: &str, new: &str) -> AxResult {
-    if parent_node_of(None, new).lookup(new).is_ok() {
+    let (parent, new_path) = start_of(None, new);
+    if parent.lookup(&new_path).is_ok() {
         warn!("dst file already exist, now remove it");
-        remove_file(None, new)?;
+        remove_file(new)?;
+    }
+    let (parent, old_path) = start_of(None, old);
+    let node = parent.clone().lookup(&old_path)?;
+    let is_dir = node.get_attr()?.is_dir();
+    let abs_old = absolute_path(old)?;
+    let key = node_key(mount_of(old).as_ref(), &node, &abs_old);
+    // files identified by paths get new identities
+    cache::forget(|key| key.is_under(&abs_old), false)?;
+    parent.rename(&old_path, &new_path)?;
+    notify::renamed(&key, &abs_old, &absolute_path(new)?, is_dir);
+    Ok(())
+}
+
+pub(crate) fn create_link(old: &str, new: &str) -> AxResult {
+    if lookup(None, old)?.get_attr()?.is_dir() {
+        return ax_err!(PermissionDenied, "cannot link directories");
+    } else if lookup(None, new).is_ok() {
+        return ax_err!(AlreadyExists);
     }
-    parent_node_of(None, old).rename(old, new)
+    let (_, old_path) = start_of(None, old);
+    let (_, new_path) = start_of(None, new);
+    ROOT_DIR.link(&old_path, &new_path)?;
+    notify::created(&absolute_path(new)?, false);
+    Ok(())
 }
 
 pub(crate) fn create_symlink(target: &str, path: &str) -> AxResult {
@@ -411,8 +1047,10 @@ pub(crate) fn create_symlink(target: &str, path: &str) -> AxResult {
         return ax_err!(InvalidInput);
     }
 
-    // For EXT4, try using the root directory directly with full path
-    ROOT_DIR.main_fs.root_dir().symlink(target, path)
+    let abs_path = absolute_path(path)?;
+    ROOT_DIR.symlink(target, &abs_path)?;
+    notify::created(&abs_path, false);
+    Ok(())
 }
 
 pub(crate) fn read_link(path: &str, buf: &mut [u8]) -> AxResult<usize> {
@@ -420,22 +1058,69 @@ pub(crate) fn read_link(path: &str, buf: &mut [u8]) -> AxResult<usize> {
         return ax_err!(NotFound);
     }
 
-    // Try EXT4 filesystem first for regular symlinks
+    // links in directories of the filesystem the path is in, or nodes that
+    // are links themselves, e.g., in procfs
     ROOT_DIR
-        .main_fs
-        .root_dir()
-        .readlink(path, buf)
-        .or_else(|_| {
-            // Fallback to VFS layer for mount points (procfs, ramfs, etc.)
-            lookup(None, path)?.readlink("", buf)
-        })
+        .readlink(&absolute_path(path)?, buf)
+        .or_else(|_| lookup(None, path)?.readlink("", buf))
 }
 
 pub(crate) fn set_perm(path: &str, mode: u16) -> AxResult {
     let abs_path = absolute_path(path)?;
     let node = lookup(None, &abs_path)?;
-    let mut attr = node.get_attr()?;
-    attr.set_perm(VfsNodePerm::from_bits(mode).ok_or(AxError::InvalidInput)?);
+    set_node_perm(mount_of(&abs_path).as_ref(), &node, &abs_path, mode)
+}
+
+/// Sets the permissions of `node` in the filesystem referenced by `mount`,
+/// whose absolute path is `abs_path`.
+pub(crate) fn set_node_perm(
+    mount: Option<&MountRef>,
+    node: &VfsNodeRef,
+    abs_path: &str,
+    mode: u16,
+) -> AxResult {
+    let perm = VfsNodePerm::from_bits(mode).ok_or(AxError::InvalidInput)?;
+    fs_of(mount).set_node_perm(node.as_ref(), perm)?;
+    let is_dir = node.get_attr()?.is_dir();
+    notify::attrib_changed(&node_key(mount, node, abs_path), abs_path, is_dir);
+    Ok(())
+}
+
+/// Sets the access and modification times of the file at `path`, following
+/// symbolic links, and leaves the ones that are `None` unchanged.
+pub(crate) fn set_times(
+    path: &str,
+    accessed: Option<Duration>,
+    modified: Option<Duration>,
+) -> AxResult {
+    let (node, target) = lookup_follow_symlinks(None, path)?;
+    // the target may be in another filesystem than the link
+    let abs_path = absolute_path(&target)?;
+    set_node_times(
+        mount_of(&abs_path).as_ref(),
+        &node,
+        &abs_path,
+        accessed,
+        modified,
+    )
+}
+
+/// Sets the access and modification times of `node` in the filesystem
+/// referenced by `mount`, whose absolute path is `abs_path`, and leaves the
+/// ones that are `None` unchanged.
+pub(crate) fn set_node_times(
+    mount: Option<&MountRef>,
+    node: &VfsNodeRef,
+    abs_path: &str,
+    accessed: Option<Duration>,
+    modified: Option<Duration>,
+) -> AxResult {
+    // write back the cached pages first, or writing them would change the
+    // modification time again
+    let key = node_key(mount, node, abs_path);
+    cache::write_back(&key)?;
+    fs_of(mount).set_node_times(node.as_ref(), accessed, modified)?;
+    notify::attrib_changed(&key, abs_path, node.get_attr()?.is_dir());
     Ok(())
 }
 
@@ -0,0 +1,191 @@
+//! Kernel tunables exposed in sysfs.
+//!
+//! Kernel modules register typed [`Tunable`]s at paths relative to `/sys`,
+//! e.g., `kernel/log_level`. Reading the file shows the current value, and
+//! writing to it parses and applies a new value, or fails with `EINVAL` if the
+//! value is invalid.
+
+use alloc::{
+    boxed::Box,
+    format,
+    string::String,
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use core::{fmt::Display, ops::RangeInclusive, str::FromStr};
+
+use axerrno::{AxError, AxResult, ax_err};
+use axfs_vfs::{VfsNodeAttr, VfsNodeOps, VfsNodePerm, VfsNodeRef, VfsNodeType, VfsResult};
+use axsync::Mutex;
+
+/// A kernel parameter that can be read and changed at runtime.
+pub struct Tunable {
+    show: Box<dyn Fn() -> String + Send + Sync>,
+    store: Box<dyn Fn(&str) -> AxResult + Send + Sync>,
+}
+
+impl Tunable {
+    /// Creates a tunable whose value is shown by `show`, and changed by
+    /// `store` from the written string without surrounding whitespace.
+    ///
+    /// `store` should fail with [`AxError::InvalidInput`] if the string is
+    /// not a valid value.
+    pub fn new(
+        show: impl Fn() -> String + Send + Sync + 'static,
+        store: impl Fn(&str) -> AxResult + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            show: Box::new(show),
+            store: Box::new(store),
+        }
+    }
+
+    /// Creates an integer tunable, whose value must be within `range`.
+    pub fn integer<T>(
+        get: impl Fn() -> T + Send + Sync + 'static,
+        set: impl Fn(T) + Send + Sync + 'static,
+        range: RangeInclusive<T>,
+    ) -> Self
+    where
+        T: FromStr + Display + PartialOrd + Send + Sync + 'static,
+    {
+        Self::new(
+            move || format!("{}", get()),
+            move |value| match value.parse::<T>() {
+                Ok(value) if range.contains(&value) => {
+                    set(value);
+                    Ok(())
+                }
+                _ => ax_err!(InvalidInput, "invalid tunable value"),
+            },
+        )
+    }
+
+    /// Creates a tunable whose value is one of `choices`. It is shown as all
+    /// the choices with the current one in brackets, e.g.,
+    /// `noop [elevator]`.
+    pub fn choice(
+        choices: &'static [&'static str],
+        get: impl Fn() -> &'static str + Send + Sync + 'static,
+        set: impl Fn(&'static str) + Send + Sync + 'static,
+    ) -> Self {
+        Self::new(
+            move || {
+                let current = get();
+                let shown: Vec<_> = choices
+                    .iter()
+                    .map(|&c| {
+                        if c == current {
+                            format!("[{}]", c)
+                        } else {
+                            c.into()
+                        }
+                    })
+                    .collect();
+                shown.join(" ")
+            },
+            move |value| match choices.iter().find(|&&c| c == value) {
+                Some(&choice) => {
+                    set(choice);
+                    Ok(())
+                }
+                None => ax_err!(InvalidInput, "invalid tunable value"),
+            },
+        )
+    }
+}
+
+/// The file of a tunable in sysfs.
+struct TunableFile(Arc<Tunable>);
+
+impl VfsNodeOps for TunableFile {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        Ok(VfsNodeAttr::new(
+            VfsNodePerm::from_bits_truncate(0o644),
+            VfsNodeType::File,
+            0,
+            0,
+        ))
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        let output = (self.0.show)() + "\n";
+        let bytes = output.as_bytes();
+        let start = bytes.len().min(offset as usize);
+        let len = buf.len().min(bytes.len() - start);
+        buf[..len].copy_from_slice(&bytes[start..start + len]);
+        Ok(len)
+    }
+
+    /// Applies the written value. Every write must contain a whole value.
+    fn write_at(&self, _offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        let value = core::str::from_utf8(buf).map_err(|_| AxError::InvalidInput)?;
+        (self.0.store)(value.trim())?;
+        Ok(buf.len())
+    }
+
+    /// Does nothing, so that the file can be opened with `O_TRUNC`.
+    fn truncate(&self, _size: u64) -> VfsResult {
+        Ok(())
+    }
+
+    axfs_vfs::impl_vfs_non_dir_default! {}
+}
+
+struct Registry {
+    tunables: Vec<(&'static str, Arc<Tunable>)>,
+    /// Root directories of the sysfs instances, to which the tunables are
+    /// added.
+    roots: Vec<Weak<dyn VfsNodeOps>>,
+}
+
+static REGISTRY: Mutex<Registry> = Mutex::new(Registry {
+    tunables: Vec::new(),
+    roots: Vec::new(),
+});
+
+/// Creates the file of `tunable` at `path` relative to `root`, and the
+/// directories on the way.
+fn add_file(root: &VfsNodeRef, path: &'static str, tunable: Arc<Tunable>) -> AxResult {
+    let (dir, name) = path.rsplit_once('/').unwrap_or(("", path));
+    let mut parent = root.clone();
+    for comp in dir.split('/').filter(|c| !c.is_empty()) {
+        match parent.create(comp, VfsNodeType::Dir) {
+            Ok(()) | Err(AxError::AlreadyExists) => {}
+            Err(e) => return Err(e),
+        }
+        parent = parent.lookup(comp)?;
+    }
+    parent.create(name, VfsNodeType::File)?;
+    parent.add_node(name, Arc::new(TunableFile(tunable)))
+}
+
+/// Registers `tunable` as the file `/sys/<path>`.
+pub(crate) fn register(path: &'static str, tunable: Tunable) -> AxResult {
+    if path.is_empty() || path.starts_with('/') || path.ends_with('/') {
+        return ax_err!(InvalidInput, "invalid tunable path");
+    }
+    let mut registry = REGISTRY.lock();
+    if registry.tunables.iter().any(|&(p, _)| p == path) {
+        return ax_err!(AlreadyExists);
+    }
+    let tunable = Arc::new(tunable);
+    registry.roots.retain(|root| root.strong_count() > 0);
+    for root in registry.roots.iter().filter_map(Weak::upgrade) {
+        add_file(&root, path, tunable.clone())?;
+    }
+    registry.tunables.push((path, tunable));
+    Ok(())
+}
+
+/// Adds the files of all tunables, including ones registered later, to the
+/// sysfs whose root directory is `root`.
+#[cfg(feature = "sysfs")]
+pub(crate) fn attach(root: &VfsNodeRef) -> AxResult {
+    let mut registry = REGISTRY.lock();
+    for (path, tunable) in &registry.tunables {
+        add_file(root, path, tunable.clone())?;
+    }
+    registry.roots.push(Arc::downgrade(root));
+    Ok(())
+}
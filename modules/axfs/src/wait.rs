@@ -0,0 +1,45 @@
+//! Waiting for conditions that other tasks change, e.g., for file locks to be
+//! released or for file change events to arrive.
+
+use axerrno::AxResult;
+
+/// Tasks waiting for conditions, which are woken up to check them again by
+/// [`notify_all`](Self::notify_all).
+///
+/// Waiting blocks the task on a wait queue if the `multitask` feature is
+/// enabled. Without it, nothing could change a condition while waiting, so
+/// waiting fails with `WouldBlock`.
+pub(crate) struct Waiters {
+    #[cfg(feature = "multitask")]
+    queue: axtask::WaitQueue,
+}
+
+impl Waiters {
+    pub(crate) const fn new() -> Self {
+        Self {
+            #[cfg(feature = "multitask")]
+            queue: axtask::WaitQueue::new(),
+        }
+    }
+
+    /// Blocks the current task until `condition` becomes true.
+    ///
+    /// The condition is checked while holding the lock of the wait queue, so
+    /// the locks it takes must be spinlocks.
+    #[cfg(feature = "multitask")]
+    pub(crate) fn wait_until(&self, condition: impl Fn() -> bool) -> AxResult {
+        self.queue.wait_until(condition);
+        Ok(())
+    }
+
+    #[cfg(not(feature = "multitask"))]
+    pub(crate) fn wait_until(&self, _condition: impl Fn() -> bool) -> AxResult {
+        axerrno::ax_err!(WouldBlock, "cannot wait without multitask")
+    }
+
+    /// Wakes up the waiting tasks to check their conditions again.
+    pub(crate) fn notify_all(&self) {
+        #[cfg(feature = "multitask")]
+        self.queue.notify_all(true);
+    }
+}
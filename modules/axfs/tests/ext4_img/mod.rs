@@ -0,0 +1,54 @@
+//! Generation of the ext4 image for tests, with the files that the common
+//! tests expect.
+//!
+//! The image is made by `mke2fs -d` from a directory, which needs neither
+//! root nor a loop device, so it is not kept in the repository.
+
+use std::path::Path;
+use std::process::Command;
+use std::{env, fs, io, process};
+
+use axdriver_block::ramdisk::RamDisk;
+
+/// Number of 1 KiB blocks of the image.
+const IMG_BLOCKS: usize = 4096;
+
+fn populate(root: &Path) -> io::Result<()> {
+    fs::write(root.join("long.txt"), "Rust is cool!\n".repeat(1000))?;
+    fs::write(root.join("short.txt"), "Rust is cool!\n")?;
+    fs::create_dir_all(root.join("very/long/path"))?;
+    fs::write(root.join("very/long/path/test.txt"), "Rust is cool!\n")?;
+    fs::create_dir_all(root.join("very-long-dir-name"))?;
+    fs::write(
+        root.join("very-long-dir-name/very-long-file-name.txt"),
+        "Rust is cool!\n",
+    )?;
+    Ok(())
+}
+
+/// Makes an ext4 image, and returns it as a ramdisk.
+pub fn make_disk() -> io::Result<RamDisk> {
+    let tmp = env::temp_dir().join(format!("axfs-ext4-{}", process::id()));
+    let root = tmp.join("root");
+    let img = tmp.join("ext4.img");
+    fs::create_dir_all(&root)?;
+    populate(&root)?;
+
+    println!("Making ext4 image {:?} ...", img);
+    let status = Command::new("mke2fs")
+        .args(["-q", "-F", "-t", "ext4", "-b", "1024"])
+        .args(["-O", "^has_journal,^metadata_csum,^64bit"])
+        .args(["-E", "root_owner=0:0", "-L", "Test!", "-d"])
+        .arg(&root)
+        .arg(&img)
+        .arg(IMG_BLOCKS.to_string())
+        .status()?;
+    let data = fs::read(&img);
+    fs::remove_dir_all(&tmp)?;
+    if !status.success() {
+        return Err(io::Error::other(format!("mke2fs failed: {}", status)));
+    }
+    let data = data?;
+    println!("size = {} bytes", data.len());
+    Ok(RamDisk::from(&data))
+}
@@ -1,4 +1,7 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
 use axfs::api as fs;
+use axfs::fops::{EventMask, Watcher};
 use axio as io;
 
 use fs::{File, FileType, OpenOptions};
@@ -97,6 +100,50 @@ fn test_file_permission() -> Result<()> {
     Ok(())
 }
 
+/// Changes the permissions of the file at `path` through the opened file,
+/// like `fchmod`, and reads them back like `fstat`.
+pub fn test_fchmod(path: &str) -> Result<()> {
+    println!("test fchmod {:?}:", path);
+
+    let opts = axfs::fops::OpenOptions::new()
+        .set_read(true)
+        .set_write(true)
+        .set_create(true, false);
+    let file = axfs::fops::File::open(path, &opts)?;
+    file.set_permissions(0o640)?;
+    assert_eq!(file.get_attr()?.perm().bits(), 0o640);
+    assert_eq!(fs::metadata(path)?.permissions().bits(), 0o640);
+
+    // the new permissions are checked by later opens
+    file.set_permissions(0o444)?;
+    assert_eq!(file.get_attr()?.perm().bits(), 0o444);
+    assert_err!(File::options().write(true).open(path), PermissionDenied);
+    drop(file);
+    fs::set_permissions(path, 0o644)?;
+    assert_eq!(fs::metadata(path)?.permissions().bits(), 0o644);
+    fs::remove_file(path)?;
+
+    println!("test_fchmod() OK!");
+    Ok(())
+}
+
+fn test_mounted_symlinks() -> Result<()> {
+    println!("test symlinks in mounted filesystems:");
+
+    // the link is created in the filesystem mounted at /tmp, not under the
+    // mount point in the root filesystem
+    fs::create_symlink("/tmp/target.txt", "/tmp/link")?;
+    assert!(fs::is_symlink("/tmp/link")?);
+    let mut buf = [0; 64];
+    let n = fs::read_link("/tmp/link", &mut buf)?;
+    assert_eq!(&buf[..n], b"/tmp/target.txt");
+    fs::remove_file("/tmp/link")?;
+    assert_err!(fs::read_link("/tmp/link", &mut buf), NotFound);
+
+    println!("test_mounted_symlinks() OK!");
+    Ok(())
+}
+
 fn test_create_file_dir() -> Result<()> {
     // create a file and test existence
     let fname = "././/very-long-dir-name/..///new-file.txt";
@@ -198,6 +245,12 @@ fn test_devfs_ramfs() -> Result<()> {
         .collect::<Vec<_>>();
     assert!(dirents.contains(&"null".into()));
     assert!(dirents.contains(&"zero".into()));
+    assert!(dirents.contains(&"vda".into()));
+
+    // stat the block device holding the root filesystem
+    let md = fs::metadata("/dev/vda")?;
+    assert_eq!(md.file_type(), FileType::BlockDevice);
+    assert!(fs::metadata("/dev/vdb").is_err());
 
     // stat /dev
     let dname = "/dev";
@@ -214,6 +267,13 @@ fn test_devfs_ramfs() -> Result<()> {
     assert_eq!(md.file_type(), FileType::CharDevice);
     assert!(!md.is_dir());
 
+    // /dev is reported as a tmpfs without capacity, like devtmpfs
+    let stat = fs::statfs("/dev/null")?;
+    assert_eq!(
+        (stat.fs_type, stat.blocks, stat.name_max),
+        (0x0102_1994, 0, 255)
+    );
+
     // error cases
     assert_err!(fs::metadata("/dev/null/"), NotADirectory);
     assert_err!(fs::create_dir("dev"), AlreadyExists);
@@ -250,11 +310,363 @@ fn test_devfs_ramfs() -> Result<()> {
     Ok(())
 }
 
+fn test_mount_umount() -> Result<()> {
+    println!("test nested and stacked mount points:");
+
+    // nested mount points
+    fs::mount("", "/tmp/mnt", "tmpfs")?;
+    fs::write("/tmp/mnt/test.txt", "outer")?;
+    fs::mount("", "///tmp/./mnt//sub", "tmpfs")?;
+    fs::write("/tmp/mnt/sub/test.txt", "inner")?;
+    let mounts = fs::read_to_string("/proc/mounts")?;
+    assert!(mounts.contains("none /tmp/mnt tmpfs rw 0 0\nnone /tmp/mnt/sub tmpfs rw 0 0\n"));
+    assert_eq!(fs::read_to_string("/tmp/mnt/test.txt")?, "outer");
+    assert_eq!(fs::read_to_string("tmp/mnt/sub/test.txt")?, "inner");
+    assert_err!(fs::umount("/tmp/mnt"), ResourceBusy);
+
+    // stacked mount points
+    fs::mount("", "/tmp/mnt/sub", "tmpfs")?;
+    assert_err!(fs::metadata("/tmp/mnt/sub/test.txt"), NotFound);
+    fs::umount("/tmp/mnt/sub")?;
+    assert_eq!(fs::read_to_string("/tmp/mnt/sub/test.txt")?, "inner");
+
+    // files still opened
+    let file = File::open("/tmp/mnt/sub/test.txt")?;
+    assert_err!(fs::umount("/tmp/mnt/sub"), ResourceBusy);
+    drop(file);
+    fs::umount("/tmp/mnt/sub")?;
+    assert_err!(fs::metadata("/tmp/mnt/sub/test.txt"), NotFound);
+
+    // error cases
+    assert_err!(fs::umount("/"), InvalidInput);
+    assert_err!(fs::umount("/tmp/mnt/sub"), InvalidInput);
+    assert_err!(fs::mount("", "/tmp/mnt/test.txt", "tmpfs"), NotADirectory);
+    assert_err!(fs::mount("", "/tmp/mnt/sub", "nofs"), InvalidInput);
+
+    fs::umount("/tmp/mnt")?;
+    assert_err!(fs::metadata("/tmp/mnt/test.txt"), NotFound);
+    assert!(!fs::read_to_string("/proc/mounts")?.contains("/tmp/mnt"));
+    fs::remove_dir("/tmp/mnt")?;
+
+    println!("test_mount_umount() OK!");
+    Ok(())
+}
+
+fn test_tmpfs_limits() -> Result<()> {
+    println!("test size and inode limits of tmpfs:");
+
+    fs::mount_with_options("", "/tmp/quota", "tmpfs", "size=8k,nr_inodes=3")?;
+    let stat = fs::statfs("/tmp/quota/")?;
+    assert_eq!(
+        (stat.block_size, stat.blocks, stat.blocks_free),
+        (4096, 2, 2)
+    );
+    assert_eq!((stat.files, stat.files_free), (3, 2));
+
+    fs::write("/tmp/quota/a.bin", [1; 6000])?;
+    assert_err!(fs::write("/tmp/quota/b.bin", [2; 3000]), StorageFull);
+    assert_eq!(fs::metadata("/tmp/quota/b.bin")?.len(), 0);
+    assert_eq!(fs::statfs("/tmp/quota")?.files_free, 0);
+    assert_err!(fs::create_dir("/tmp/quota/dir"), StorageFull);
+
+    // space is freed by truncating and removing files
+    File::options()
+        .write(true)
+        .open("/tmp/quota/a.bin")?
+        .set_len(1000)?;
+    fs::write("/tmp/quota/b.bin", [2; 3000])?;
+    fs::remove_file("/tmp/quota/a.bin")?;
+    fs::write("/tmp/quota/b.bin", [2; 8192])?;
+    assert_eq!(fs::statfs("/tmp/quota")?.blocks_free, 0);
+    fs::remove_file("/tmp/quota/b.bin")?;
+    assert_eq!(fs::statfs("/tmp/quota")?, stat);
+
+    // a removed file is counted until its last handle is closed
+    let mut file = File::create("/tmp/quota/open.bin")?;
+    file.write_all(&[3; 4096])?;
+    fs::remove_file("/tmp/quota/open.bin")?;
+    let removed = fs::statfs("/tmp/quota")?;
+    assert_eq!((removed.blocks_free, removed.files_free), (1, 1));
+    file.write_all(&[3; 4096])?;
+    assert_eq!(fs::statfs("/tmp/quota")?.blocks_free, 0);
+    drop(file);
+    assert_eq!(fs::statfs("/tmp/quota")?, stat);
+
+    // and so is a file replaced by renaming, which is freed at once if it is
+    // not opened
+    fs::write("/tmp/quota/old.bin", [4; 4096])?;
+    let file = File::open("/tmp/quota/old.bin")?;
+    fs::write("/tmp/quota/new.bin", [5; 100])?;
+    fs::rename("/tmp/quota/new.bin", "/tmp/quota/old.bin")?;
+    let replaced = fs::statfs("/tmp/quota")?;
+    assert_eq!((replaced.blocks_free, replaced.files_free), (0, 0));
+    drop(file);
+    let closed = fs::statfs("/tmp/quota")?;
+    assert_eq!((closed.blocks_free, closed.files_free), (1, 1));
+    fs::write("/tmp/quota/new.bin", [6; 4096])?;
+    fs::rename("/tmp/quota/new.bin", "/tmp/quota/old.bin")?;
+    assert_eq!(fs::read("/tmp/quota/old.bin")?, [6; 4096]);
+    let renamed = fs::statfs("/tmp/quota")?;
+    assert_eq!((renamed.blocks_free, renamed.files_free), (1, 1));
+    fs::remove_file("/tmp/quota/old.bin")?;
+    assert_eq!(fs::statfs("/tmp/quota")?, stat);
+
+    fs::umount("/tmp/quota")?;
+    fs::remove_dir("/tmp/quota")?;
+    assert_err!(
+        fs::mount_with_options("", "/tmp", "tmpfs", "size=1x"),
+        InvalidInput
+    );
+
+    println!("test_tmpfs_limits() OK!");
+    Ok(())
+}
+
+/// Reads the queued events of `watcher` as (wd, mask, cookie, name).
+fn read_events(watcher: &Watcher) -> Vec<(i32, EventMask, u32, String)> {
+    let mut events = Vec::new();
+    let ret = watcher.read_events(false, |e| {
+        events.push((e.wd, e.mask, e.cookie, e.name.clone()));
+        true
+    });
+    assert!(ret.is_ok() || ret == Err(Error::WouldBlock));
+    events
+}
+
+fn test_notify() -> Result<()> {
+    println!("test file change notification:");
+
+    fs::create_dir("/tmp/watched")?;
+    let watcher = Watcher::new();
+    let dir = watcher.add_watch("/tmp/watched", EventMask::ALL_EVENTS)?;
+    assert!(!watcher.has_events());
+
+    // writes to the same file are merged into one event
+    fs::write("/tmp/watched/a.txt", "hello")?;
+    let events = read_events(&watcher);
+    let kinds = events.iter().map(|e| (e.0, e.1, e.3.as_str()));
+    assert!(kinds.eq([
+        (dir, EventMask::CREATE, "a.txt"),
+        (dir, EventMask::MODIFY, "a.txt"),
+        (dir, EventMask::CLOSE_WRITE, "a.txt"),
+    ]));
+
+    // watches follow renamed files
+    let file = watcher.add_watch("/tmp/watched/a.txt", EventMask::DELETE_SELF)?;
+    assert_ne!(file, dir);
+    fs::rename("/tmp/watched/a.txt", "/tmp/watched/b.txt")?;
+    let events = read_events(&watcher);
+    assert_eq!(events.len(), 2);
+    assert_eq!(
+        (events[0].1, events[0].3.as_str()),
+        (EventMask::MOVED_FROM, "a.txt")
+    );
+    assert_eq!(
+        (events[1].1, events[1].3.as_str()),
+        (EventMask::MOVED_TO, "b.txt")
+    );
+    assert!(events[0].2 != 0 && events[0].2 == events[1].2);
+
+    // watches are removed with their files
+    fs::remove_file("/tmp/watched/b.txt")?;
+    let events = read_events(&watcher);
+    let kinds = events.iter().map(|e| (e.0, e.1, e.3.as_str()));
+    assert!(kinds.eq([
+        (file, EventMask::DELETE_SELF, ""),
+        (dir, EventMask::DELETE, "b.txt"),
+        (file, EventMask::IGNORED, ""),
+    ]));
+    fs::create_dir("/tmp/watched/sub")?;
+    assert_eq!(
+        read_events(&watcher)[0].1,
+        EventMask::CREATE | EventMask::ISDIR
+    );
+
+    // error cases
+    assert_err!(watcher.remove_watch(file), InvalidInput);
+    assert_err!(
+        watcher.add_watch("/tmp/watched/none", EventMask::ALL_EVENTS),
+        NotFound
+    );
+    assert_err!(
+        watcher.add_watch("/tmp/watched", EventMask::empty()),
+        InvalidInput
+    );
+    assert_err!(watcher.read_events(false, |_| true), WouldBlock);
+
+    watcher.remove_watch(dir)?;
+    fs::remove_dir("/tmp/watched/sub")?;
+    fs::remove_dir("/tmp/watched")?;
+    let events = read_events(&watcher);
+    assert_eq!(events, [(dir, EventMask::IGNORED, 0, String::new())]);
+
+    println!("test_notify() OK!");
+    Ok(())
+}
+
+fn test_procfs() -> Result<()> {
+    let dirents = fs::read_dir("/proc")?
+        .map(|e| e.unwrap().file_name())
+        .collect::<Vec<_>>();
+    for name in [
+        "meminfo",
+        "cpuinfo",
+        "uptime",
+        "mounts",
+        "interrupts",
+        "sys",
+    ] {
+        assert!(dirents.contains(&name.into()), "/proc/{} not found", name);
+    }
+    assert_eq!(fs::statfs("/proc/meminfo")?.fs_type, 0x9fa0);
+
+    let mounts = fs::read_to_string("/proc/mounts")?;
+    #[cfg(not(feature = "initramfs"))]
+    assert!(mounts.starts_with("/dev/vda / "));
+    #[cfg(feature = "initramfs")]
+    assert!(mounts.starts_with("rootfs / rootfs rw 0 0\n"));
+    assert!(mounts.contains("devtmpfs /dev devtmpfs rw 0 0\n"));
+    assert!(mounts.contains("tmpfs /tmp tmpfs rw 0 0\n"));
+    assert!(mounts.contains("proc /proc proc rw 0 0\n"));
+
+    let meminfo = fs::read_to_string("/proc/meminfo")?;
+    assert!(meminfo.starts_with("MemTotal:"));
+    assert!(meminfo.lines().all(|l| l.ends_with(" kB")));
+    assert!(fs::read_to_string("/proc/cpuinfo")?.starts_with("processor\t: 0\n"));
+    let uptime = fs::read_to_string("/proc/uptime")?;
+    assert!(uptime.split_whitespace().all(|t| t.parse::<f64>().is_ok()));
+
+    // generated entries are read-only
+    assert_err!(fs::write("/proc/meminfo", "0"), PermissionDenied);
+
+    println!("test_procfs() OK!");
+    Ok(())
+}
+
+fn test_tunables() -> Result<()> {
+    static VALUE: AtomicUsize = AtomicUsize::new(3);
+    let new_tunable = || {
+        let get = || VALUE.load(Ordering::Relaxed);
+        fs::Tunable::integer(get, |v| VALUE.store(v, Ordering::Relaxed), 1..=10)
+    };
+    fs::register_tunable("test/value", new_tunable())?;
+    assert_err!(
+        fs::register_tunable("test/value", new_tunable()),
+        AlreadyExists
+    );
+    assert_err!(fs::register_tunable("/test", new_tunable()), InvalidInput);
+
+    assert_eq!(fs::read_to_string("/sys/test/value")?, "3\n");
+    fs::write("/sys/test/value", "7\n")?;
+    assert_eq!(VALUE.load(Ordering::Relaxed), 7);
+    assert_err!(fs::write("/sys/test/value", "11"), InvalidInput);
+    assert_err!(fs::write("/sys/test/value", "abc"), InvalidInput);
+    assert_eq!(fs::read_to_string("/sys/test/value")?, "7\n");
+
+    println!("test_tunables() OK!");
+    Ok(())
+}
+
+fn test_file_locks() -> Result<()> {
+    use axfs::fops::{self, LockError, LockType::*, RecordLock};
+
+    fs::write("/lock.txt", "locked\n")?;
+    let mut opts = fops::OpenOptions::new();
+    opts.read(true);
+    opts.write(true);
+    let f1 = fops::File::open("/lock.txt", &opts)?;
+    let f2 = fops::File::open("/lock.txt", &opts)?;
+    let (l1, l2) = (f1.lock_handle(), f2.lock_handle());
+
+    // `flock` locks are owned by opened files
+    l1.flock(Shared, false)?;
+    l2.flock(Shared, false)?;
+    assert_err!(l2.flock(Exclusive, false), WouldBlock);
+    l1.flock(Unlocked, false)?;
+    l2.flock(Exclusive, false)?;
+    assert_err!(l1.flock(Shared, false), WouldBlock);
+    drop(f2); // released on close
+    l1.flock(Exclusive, false)?;
+
+    // record locks conflict if their owners differ and their ranges overlap
+    let lock = |ty, start, end, owner| RecordLock {
+        ty,
+        start,
+        end,
+        owner,
+    };
+    let set = |lock, wait| match set(lock, wait) {
+        Err(LockError::Other(e)) => Err(e),
+        ret => Ok(assert_eq!(ret, Ok(()))),
+    };
+    set(lock(Exclusive, 0, 10, 1), false)?;
+    set(lock(Shared, 10, u64::MAX, 2), false)?;
+    assert_eq!(
+        l1.test(&lock(Shared, 5, 6, 2)),
+        Some(lock(Exclusive, 0, 10, 1))
+    );
+    assert_eq!(l1.test(&lock(Exclusive, 5, 6, 1)), None);
+    assert_eq!(l1.test(&lock(Shared, 100, 200, 3)), None);
+    assert_err!(set(lock(Shared, 9, 11, 3), false), WouldBlock);
+    assert_err!(set(lock(Shared, 5, 5, 3), false), InvalidInput);
+
+    // unlocking a part of a range splits the lock
+    set(lock(Unlocked, 2, 4, 1), false)?;
+    set(lock(Exclusive, 2, 4, 3), false)?;
+    assert_err!(set(lock(Shared, 4, 5, 3), false), WouldBlock);
+    assert_err!(set(lock(Shared, 1, 2, 3), false), WouldBlock);
+
+    for owner in 1..=3 {
+        l1.release(owner);
+    }
+    assert_eq!(l1.test(&lock(Exclusive, 0, u64::MAX, 4)), None);
+    drop(f1);
+    fs::remove_file("/lock.txt")?;
+
+    println!("test_file_locks() OK!");
+    Ok(())
+}
+
+fn test_inode_numbers() -> Result<()> {
+    println!("test inode numbers:");
+
+    fs::write("/tmp/ino1.txt", "1")?;
+    fs::write("/tmp/ino2.txt", "2")?;
+    let ino = fs::metadata("/tmp/ino1.txt")?.ino();
+    assert_ne!(ino, fs::metadata("/tmp/ino2.txt")?.ino());
+    assert_eq!(File::open("/tmp/ino1.txt")?.metadata()?.ino(), ino);
+
+    // entries of a directory report the inode numbers of their files
+    let opts = axfs::fops::OpenOptions::new().set_read(true);
+    let dir = axfs::fops::Directory::open_dir("/tmp", &opts)?;
+    assert_eq!(dir.entry_ino("ino1.txt"), ino);
+    assert_eq!(dir.entry_ino("."), fs::metadata("/tmp")?.ino());
+
+    // the number of a removed file is not reused by a new one
+    fs::remove_file("/tmp/ino1.txt")?;
+    fs::write("/tmp/ino1.txt", "1")?;
+    assert_ne!(fs::metadata("/tmp/ino1.txt")?.ino(), ino);
+    fs::remove_file("/tmp/ino1.txt")?;
+    fs::remove_file("/tmp/ino2.txt")?;
+
+    println!("test_inode_numbers() OK!");
+    Ok(())
+}
+
 pub fn test_all() {
     test_read_write_file().expect("test_read_write_file() failed");
     test_read_dir().expect("test_read_dir() failed");
     test_file_permission().expect("test_file_permission() failed");
+    test_fchmod("/tmp/perm.txt").expect("test_fchmod() failed");
+    test_mounted_symlinks().expect("test_mounted_symlinks() failed");
     test_create_file_dir().expect("test_create_file_dir() failed");
     test_remove_file_dir().expect("test_remove_file_dir() failed");
     test_devfs_ramfs().expect("test_devfs_ramfs() failed");
+    test_mount_umount().expect("test_mount_umount() failed");
+    test_tmpfs_limits().expect("test_tmpfs_limits() failed");
+    test_procfs().expect("test_procfs() failed");
+    test_tunables().expect("test_tunables() failed");
+    test_file_locks().expect("test_file_locks() failed");
+    test_inode_numbers().expect("test_inode_numbers() failed");
+    test_notify().expect("test_notify() failed");
 }
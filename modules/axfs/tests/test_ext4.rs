@@ -0,0 +1,123 @@
+#![cfg(all(
+    feature = "lwext4_rs",
+    not(any(feature = "myfs", feature = "overlayfs", feature = "initramfs"))
+))]
+
+mod ext4_img;
+mod test_common;
+
+use axdriver::AxDeviceContainer;
+use axfs::api as fs;
+use axio::{Error, Result, Write};
+use std::time::Duration;
+
+fn test_path_resolution() -> Result<()> {
+    for path in ["/res", "/res/a", "/res/a/b"] {
+        fs::create_dir(path)?;
+    }
+    fs::write("/res/a/b/f.txt", "resolved\n")?;
+
+    // directories are looked up through absolute and relative symlinks
+    fs::create_symlink("/res/a", "/res/abs")?;
+    fs::create_symlink("../res/a/b", "/very/rel")?;
+    assert_eq!(fs::read_to_string("/res/abs/b/f.txt")?, "resolved\n");
+    assert_eq!(fs::read_to_string("/very/rel/f.txt")?, "resolved\n");
+    assert_eq!(fs::read_to_string("/res/abs/b/../b/f.txt")?, "resolved\n");
+    fs::write("/very/rel/g.txt", "created\n")?;
+    assert_eq!(fs::read_to_string("/res/a/b/g.txt")?, "created\n");
+    assert!(fs::is_symlink("/res/abs")?);
+    assert!(fs::metadata("/res/abs")?.is_dir());
+
+    // a symlink to a file is not a directory
+    fs::create_symlink("/res/a/b/f.txt", "/res/file_link")?;
+    assert_eq!(
+        fs::read_to_string("/res/file_link/x").err(),
+        Some(Error::NotADirectory)
+    );
+    // and `..` after a file does not go back to its directory
+    assert_eq!(
+        fs::read_to_string("/res/a/b/f.txt/../f.txt").err(),
+        Some(Error::NotADirectory)
+    );
+
+    // renaming across directories
+    fs::rename("/very/rel/g.txt", "/res/g.txt")?;
+    assert_eq!(fs::read_to_string("/res/g.txt")?, "created\n");
+    assert_eq!(fs::metadata("/res/a/b/g.txt").err(), Some(Error::NotFound));
+
+    for path in ["/res/g.txt", "/res/file_link", "/very/rel", "/res/abs"] {
+        fs::remove_file(path)?;
+    }
+    fs::remove_file("/res/a/b/f.txt")?;
+    for path in ["/res/a/b", "/res/a", "/res"] {
+        fs::remove_dir(path)?;
+    }
+
+    println!("test_path_resolution() OK!");
+    Ok(())
+}
+
+fn test_statfs() -> Result<()> {
+    let stat = fs::statfs("/")?;
+    assert_eq!(stat.fs_type, 0xef53); // EXT4_SUPER_MAGIC
+    assert!(stat.blocks_free > 0 && stat.blocks_free <= stat.blocks);
+    assert!(stat.files_free > 0 && stat.files_free <= stat.files);
+
+    // a new file takes an inode, and its blocks once written back
+    let mut file = fs::File::create("/statfs.bin")?;
+    file.write_all(&[1; 16 * 4096])?;
+    file.flush()?;
+    drop(file);
+    let new = fs::statfs("/very")?;
+    assert_eq!(new.files_free, stat.files_free - 1);
+    assert!(stat.blocks_free - new.blocks_free >= 16 * 4096 / stat.block_size);
+    fs::remove_file("/statfs.bin")?;
+    assert_eq!(fs::statfs("/")?.files_free, stat.files_free);
+
+    println!("test_statfs() OK!");
+    Ok(())
+}
+
+fn test_timestamps() -> Result<()> {
+    use axfs::fops;
+
+    // 2000-01-01 00:00:00, while ext4 keeps only seconds
+    let y2k = Duration::from_secs(946684800);
+    fs::write("/time.txt", "time\n")?;
+    fs::set_times(
+        "/time.txt",
+        Some(y2k),
+        Some(y2k + Duration::from_millis(1500)),
+    )?;
+    let md = fs::metadata("/time.txt")?;
+    assert_eq!(md.accessed(), y2k);
+    assert_eq!(md.modified(), y2k + Duration::from_secs(1));
+
+    // and through an opened file, like `futimens`
+    let mut opts = fops::OpenOptions::new();
+    opts.read(true);
+    let file = fops::File::open("/time.txt", &opts)?;
+    file.set_times(None, Some(y2k))?;
+    drop(file);
+    let md = fs::metadata("/time.txt")?;
+    assert_eq!((md.accessed(), md.modified()), (y2k, y2k));
+    fs::remove_file("/time.txt")?;
+
+    println!("test_timestamps() OK!");
+    Ok(())
+}
+
+#[test]
+fn test_ext4() {
+    println!("Testing ext4 with ramdisk ...");
+
+    let disk = ext4_img::make_disk().expect("failed to make disk image");
+    axtask::init_scheduler(); // call this to use `axsync::Mutex`.
+    axfs::init_filesystems(AxDeviceContainer::from_one(disk));
+
+    test_common::test_all();
+    test_path_resolution().expect("test path resolution failed");
+    test_statfs().expect("test statfs failed");
+    test_timestamps().expect("test timestamps failed");
+    test_common::test_fchmod("/perm.txt").expect("test fchmod failed");
+}
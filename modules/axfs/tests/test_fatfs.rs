@@ -1,11 +1,21 @@
-#![cfg(not(feature = "myfs"))]
+#![cfg(not(any(
+    feature = "myfs",
+    feature = "overlayfs",
+    feature = "initramfs",
+    feature = "lwext4_rs"
+)))]
 
 mod test_common;
 
 use axdriver::AxDeviceContainer;
 use axdriver_block::ramdisk::RamDisk;
+use axfs::api as fs;
+use axio::{Read, Write};
+use std::time::Duration;
 
 const IMG_PATH: &str = "resources/fat16.img";
+/// 1980-01-01 00:00:00, the earliest time FAT can keep.
+const FAT_EPOCH_SECS: u64 = 315532800;
 
 fn make_disk() -> std::io::Result<RamDisk> {
     let path = std::env::current_dir()?.join(IMG_PATH);
@@ -15,6 +25,112 @@ fn make_disk() -> std::io::Result<RamDisk> {
     Ok(RamDisk::from(&data))
 }
 
+fn test_timestamps() -> axio::Result<()> {
+    // files in the image were written by Linux with real timestamps
+    let md = fs::metadata("/long.txt")?;
+    assert!(md.modified().as_secs() > FAT_EPOCH_SECS);
+    assert!(md.created().as_secs() > FAT_EPOCH_SECS);
+    assert!(md.accessed().as_secs() > FAT_EPOCH_SECS);
+    assert_eq!(
+        fs::metadata("/very")?.created(),
+        fs::metadata("/very/")?.created()
+    );
+
+    // the root directory has no timestamps
+    assert_eq!(fs::metadata("/")?.modified().as_secs(), 0);
+
+    // new files get the wall time, which is at least the FAT epoch
+    fs::write("/time.txt", "Rust is cool!\n")?;
+    let file = fs::File::open("/time.txt")?;
+    assert!(file.metadata()?.modified().as_secs() >= FAT_EPOCH_SECS);
+    drop(file);
+    assert!(fs::metadata("/time.txt")?.created().as_secs() >= FAT_EPOCH_SECS);
+
+    // 2000-01-01 00:00:00, while FAT keeps only the date of the last access
+    let y2k = Duration::from_secs(946684800);
+    fs::set_times(
+        "/time.txt",
+        Some(y2k + Duration::from_secs(3600)),
+        Some(y2k),
+    )?;
+    let md = fs::metadata("/time.txt")?;
+    assert_eq!((md.accessed(), md.modified()), (y2k, y2k));
+    fs::set_times("/time.txt", None, Some(y2k + Duration::from_secs(60)))?;
+    let md = fs::metadata("/time.txt")?;
+    assert_eq!(md.accessed(), y2k);
+    assert_eq!(md.modified(), y2k + Duration::from_secs(60));
+    fs::remove_file("/time.txt")?;
+    Ok(())
+}
+
+fn test_hard_link() -> axio::Result<()> {
+    // FAT has no hard links
+    assert_eq!(
+        fs::hard_link("/short.txt", "/short-link.txt").err(),
+        Some(axio::Error::PermissionDenied)
+    );
+    assert!(fs::metadata("/short-link.txt").is_err());
+    assert_eq!(fs::metadata("/short.txt")?.nlink(), 1);
+    assert_eq!(fs::metadata("/very")?.nlink(), 2);
+    Ok(())
+}
+
+fn test_statfs() -> axio::Result<()> {
+    let stat = fs::statfs("/")?;
+    assert_eq!(stat.fs_type, 0x4d44); // MSDOS_SUPER_MAGIC
+    assert!(stat.blocks_free > 0 && stat.blocks_free <= stat.blocks);
+    assert_eq!((stat.files, stat.name_max), (0, 255));
+
+    // space is taken once the data is written back
+    let mut file = fs::File::create("/statfs.bin")?;
+    file.write_all(&[1; 4 * 4096])?;
+    file.flush()?;
+    drop(file);
+    let used = stat.blocks_free - fs::statfs("/very")?.blocks_free;
+    assert_eq!(used, (4 * 4096u64).div_ceil(stat.block_size));
+    fs::remove_file("/statfs.bin")?;
+    assert_eq!(fs::statfs("/")?, stat);
+    Ok(())
+}
+
+fn pagecache_stat(name: &str) -> u64 {
+    let stat = fs::read_to_string("/proc/pagecache").unwrap();
+    let line = stat.lines().find(|l| l.split(' ').next() == Some(name));
+    line.unwrap().split(' ').nth(1).unwrap().parse().unwrap()
+}
+
+fn test_page_cache() -> axio::Result<()> {
+    let data = (0..5 * 4096 + 100).map(|i| i as u8).collect::<Vec<_>>();
+
+    // written data stays in the cache until flushed
+    let mut file = fs::File::create("/cache.bin")?;
+    file.write_all(&data)?;
+    assert_eq!(fs::metadata("/cache.bin")?.len(), data.len() as u64);
+    assert!(pagecache_stat("dirty") >= 6);
+    file.flush()?;
+    assert_eq!(pagecache_stat("dirty"), 0);
+    drop(file);
+
+    // renaming drops the cached pages, so that they are read again
+    fs::rename("/cache.bin", "/cache2.bin")?;
+    let readahead = pagecache_stat("readahead");
+    let mut file = fs::File::open("/cache2.bin")?;
+    let mut buf = [0; 100];
+    file.read_exact(&mut buf)?;
+    assert_eq!(buf, data[..100]);
+    assert_eq!(pagecache_stat("readahead"), readahead + 4);
+
+    let hits = pagecache_stat("hits");
+    let mut rest = Vec::new();
+    file.read_to_end(&mut rest)?;
+    assert_eq!(rest, data[100..]);
+    assert!(pagecache_stat("hits") > hits);
+    drop(file);
+
+    fs::remove_file("/cache2.bin")?;
+    Ok(())
+}
+
 #[test]
 fn test_fatfs() {
     println!("Testing fatfs with ramdisk ...");
@@ -24,4 +140,8 @@ fn test_fatfs() {
     axfs::init_filesystems(AxDeviceContainer::from_one(disk));
 
     test_common::test_all();
+    test_timestamps().expect("test timestamps failed");
+    test_hard_link().expect("test hard link failed");
+    test_statfs().expect("test statfs failed");
+    test_page_cache().expect("test page cache failed");
 }
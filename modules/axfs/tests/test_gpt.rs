@@ -0,0 +1,120 @@
+#![cfg(feature = "myfs")]
+
+use std::sync::{Arc, OnceLock};
+
+use axdriver::AxDeviceContainer;
+use axdriver_block::ramdisk::RamDisk;
+use axfs::api::{self as fs, FileType};
+use axfs::fops::{Disk, MyFileSystemIf};
+use axfs_ramfs::RamFileSystem;
+use axfs_vfs::VfsOps;
+use axio::Result;
+
+const BLOCK_SIZE: usize = 512;
+const DISK_BLOCKS: usize = 256;
+const ROOT_MAGIC: &[u8] = b"root partition";
+
+/// The unique GUID of partition 3, in its on-disk layout.
+const PART3_GUID: [u8; 16] = [
+    0x04, 0x03, 0x02, 0x01, 0x06, 0x05, 0x08, 0x07, 0x09, 0x0a, 0x0b, 0x0c, 0x0d, 0x0e, 0x0f, 0x10,
+];
+/// The PARTUUID of partition 3, in upper case to be matched case-insensitively.
+const PART3_ROOT_SPEC: &str = "PARTUUID=01020304-0506-0708-090A-0B0C0D0E0F10";
+
+/// The first block of the disk given to the root filesystem.
+static ROOT_FIRST_BLOCK: OnceLock<Vec<u8>> = OnceLock::new();
+
+struct MyFileSystemIfImpl;
+
+#[crate_interface::impl_interface]
+impl MyFileSystemIf for MyFileSystemIfImpl {
+    fn new_myfs(mut disk: Disk) -> Arc<dyn VfsOps> {
+        let mut block = vec![0; BLOCK_SIZE];
+        disk.read_data(&mut block).unwrap();
+        ROOT_FIRST_BLOCK.set(block).unwrap();
+        Arc::new(RamFileSystem::new())
+    }
+}
+
+fn put_u32(buf: &mut [u8], offset: usize, val: u32) {
+    buf[offset..offset + 4].copy_from_slice(&val.to_le_bytes());
+}
+
+fn put_u64(buf: &mut [u8], offset: usize, val: u64) {
+    buf[offset..offset + 8].copy_from_slice(&val.to_le_bytes());
+}
+
+fn put_gpt_entry(data: &mut [u8], idx: usize, guid: [u8; 16], first: u64, last: u64) {
+    let e = &mut data[2 * BLOCK_SIZE + idx * 128..][..128];
+    e[0..16].fill(0xaf); // any non-zero partition type
+    e[16..32].copy_from_slice(&guid);
+    put_u64(e, 32, first);
+    put_u64(e, 40, last);
+}
+
+/// A GPT disk with 128 entries in blocks 2..34, where partition 1 is at blocks
+/// 34..98, partition 3 at blocks 98..162, entry 2 is unused, and entry 4 is
+/// out of the disk. Partition 3 starts with `ROOT_MAGIC`.
+fn make_gpt_disk() -> Vec<u8> {
+    let mut data = vec![0u8; DISK_BLOCKS * BLOCK_SIZE];
+    // protective MBR
+    data[0x1be + 4] = 0xee;
+    put_u32(&mut data, 0x1be + 8, 1);
+    put_u32(&mut data, 0x1be + 12, DISK_BLOCKS as u32 - 1);
+    data[510] = 0x55;
+    data[511] = 0xaa;
+
+    let header = &mut data[BLOCK_SIZE..2 * BLOCK_SIZE];
+    header[0..8].copy_from_slice(b"EFI PART");
+    put_u64(header, 72, 2);
+    put_u32(header, 80, 128);
+    put_u32(header, 84, 128);
+
+    put_gpt_entry(&mut data, 0, [0x11; 16], 34, 97);
+    put_gpt_entry(&mut data, 2, PART3_GUID, 98, 161);
+    put_gpt_entry(&mut data, 3, [0x44; 16], 200, DISK_BLOCKS as u64);
+    data[98 * BLOCK_SIZE..][..ROOT_MAGIC.len()].copy_from_slice(ROOT_MAGIC);
+    data
+}
+
+fn test_gpt_partitions() -> Result<()> {
+    let dirents = fs::read_dir("/dev")?
+        .map(|e| e.unwrap().file_name())
+        .collect::<Vec<_>>();
+    for name in ["vda", "vda1", "vda3"] {
+        assert!(dirents.contains(&name.into()), "/dev/{} not found", name);
+    }
+    for name in ["vda2", "vda4"] {
+        assert!(!dirents.contains(&name.into()), "/dev/{} found", name);
+    }
+
+    for (path, blocks) in [
+        ("/dev/vda", DISK_BLOCKS),
+        ("/dev/vda1", 64),
+        ("/dev/vda3", 64),
+    ] {
+        let md = fs::metadata(path)?;
+        assert_eq!(md.file_type(), FileType::BlockDevice);
+        assert_eq!(md.len(), (blocks * BLOCK_SIZE) as u64);
+    }
+    Ok(())
+}
+
+fn test_root_device() {
+    // selected by the PARTUUID of partition 3
+    let block = ROOT_FIRST_BLOCK.get().expect("root filesystem not created");
+    assert_eq!(&block[..ROOT_MAGIC.len()], ROOT_MAGIC);
+}
+
+#[test]
+fn test_gpt() {
+    println!("Testing GPT partitions ...");
+
+    axtask::init_scheduler(); // call this to use `axsync::Mutex`.
+    let disk = RamDisk::from(&make_gpt_disk());
+    axfs::set_root_device(PART3_ROOT_SPEC);
+    axfs::init_filesystems(AxDeviceContainer::from_one(disk));
+
+    test_root_device();
+    test_gpt_partitions().expect("test GPT partitions failed");
+}
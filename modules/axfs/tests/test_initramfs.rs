@@ -0,0 +1,83 @@
+#![cfg(all(feature = "initramfs", not(feature = "myfs")))]
+
+mod test_common;
+
+use axdriver::AxDeviceContainer;
+use axdriver_block::ramdisk::RamDisk;
+use axfs::api as fs;
+use axio::Result;
+
+const S_IFREG: u32 = 0o100644;
+const S_IFDIR: u32 = 0o040755;
+
+/// Appends an entry in the cpio "newc" format to `archive`.
+fn cpio_entry(archive: &mut Vec<u8>, name: &str, mode: u32, data: &[u8]) {
+    let fields = [0, mode, 0, 0, 1, 0, data.len() as u32, 0, 0, 0, 0];
+    archive.extend_from_slice(b"070701");
+    for field in fields.iter().chain(&[name.len() as u32 + 1, 0]) {
+        archive.extend_from_slice(format!("{:08x}", field).as_bytes());
+    }
+    archive.extend_from_slice(name.as_bytes());
+    archive.push(0);
+    archive.resize(archive.len().next_multiple_of(4), 0);
+    archive.extend_from_slice(data);
+    archive.resize(archive.len().next_multiple_of(4), 0);
+}
+
+/// Makes an initramfs with the files that the common tests expect.
+fn make_initramfs() -> Vec<u8> {
+    let mut archive = Vec::new();
+    cpio_entry(&mut archive, ".", S_IFDIR, b"");
+    cpio_entry(&mut archive, "short.txt", S_IFREG, b"Rust is cool!\n");
+    cpio_entry(
+        &mut archive,
+        "long.txt",
+        S_IFREG,
+        &b"Rust is cool!\n".repeat(1000),
+    );
+    cpio_entry(&mut archive, "very-long-dir-name", S_IFDIR, b"");
+    cpio_entry(
+        &mut archive,
+        "very-long-dir-name/very-long-file-name.txt",
+        S_IFREG,
+        b"Rust is cool!\n",
+    );
+    // parent directories are created if missing
+    cpio_entry(
+        &mut archive,
+        "very/long/path/test.txt",
+        S_IFREG,
+        b"Rust is cool!\n",
+    );
+    cpio_entry(&mut archive, "TRAILER!!!", 0, b"");
+    archive
+}
+
+fn test_unpacked() -> Result<()> {
+    assert_eq!(fs::metadata("/long.txt")?.len(), 14000);
+    assert!(fs::metadata("/very/long/path")?.is_dir());
+    assert_eq!(
+        fs::read_to_string("/very-long-dir-name/very-long-file-name.txt")?,
+        "Rust is cool!\n"
+    );
+
+    // the root filesystem is writable
+    fs::write("/new.txt", "new")?;
+    assert_eq!(fs::read_to_string("/new.txt")?, "new");
+    fs::remove_file("/new.txt")?;
+
+    println!("test_unpacked() OK!");
+    Ok(())
+}
+
+#[test]
+fn test_initramfs() {
+    println!("Testing initramfs ...");
+
+    axtask::init_scheduler(); // call this to use `axsync::Mutex`.
+    axfs::set_initramfs(make_initramfs().leak());
+    axfs::init_filesystems(AxDeviceContainer::from_one(RamDisk::default())); // not used as root
+
+    test_unpacked().expect("test unpacked failed");
+    test_common::test_all();
+}
@@ -0,0 +1,98 @@
+#![cfg(all(feature = "initramfs", not(feature = "myfs")))]
+
+use axdriver::AxDeviceContainer;
+use axdriver_block::ramdisk::RamDisk;
+use axfs::api as fs;
+use axio::Result;
+
+const TAR_BLOCK_SIZE: usize = 512;
+
+/// A path longer than the 100 bytes of the name field of a tar header.
+fn long_path() -> String {
+    format!("{}long.txt", "very-long-dir-name/".repeat(6))
+}
+
+/// Appends an entry in the POSIX tar format to `archive`.
+fn tar_entry(archive: &mut Vec<u8>, name: &str, typeflag: u8, link: &str, data: &[u8]) {
+    let mut header = [0u8; TAR_BLOCK_SIZE];
+    header[..name.len()].copy_from_slice(name.as_bytes());
+    header[100..108].copy_from_slice(b"0000644\0");
+    header[124..136].copy_from_slice(format!("{:011o}\0", data.len()).as_bytes());
+    header[156] = typeflag;
+    header[157..157 + link.len()].copy_from_slice(link.as_bytes());
+    header[257..265].copy_from_slice(b"ustar\x0000");
+    header[148..156].fill(b' ');
+    let checksum: u32 = header.iter().map(|&b| b as u32).sum();
+    header[148..156].copy_from_slice(format!("{:06o}\0 ", checksum).as_bytes());
+    archive.extend_from_slice(&header);
+    archive.extend_from_slice(data);
+    archive.resize(archive.len().next_multiple_of(TAR_BLOCK_SIZE), 0);
+}
+
+/// Returns the pax record of `key` and `value`, whose length includes the
+/// digits of the length itself.
+fn pax_record(key: &str, value: &str) -> String {
+    let body = format!(" {}={}\n", key, value);
+    let mut len = body.len() + 1;
+    while len.to_string().len() + body.len() != len {
+        len = len.to_string().len() + body.len();
+    }
+    format!("{}{}", len, body)
+}
+
+/// Makes an initramfs with a pax extended header and hard links.
+fn make_initramfs() -> Vec<u8> {
+    let mut archive = Vec::new();
+    tar_entry(&mut archive, "./", b'5', "", b"");
+    tar_entry(&mut archive, "short.txt", b'0', "", b"Rust is cool!\n");
+    // the path is given by the pax header, and the name field is truncated
+    let pax = pax_record("path", &long_path()) + &pax_record("mtime", "0.5");
+    tar_entry(
+        &mut archive,
+        "PaxHeaders/long.txt",
+        b'x',
+        "",
+        pax.as_bytes(),
+    );
+    tar_entry(&mut archive, "truncated", b'0', "", b"long path\n");
+    tar_entry(&mut archive, "dir/link.txt", b'1', "short.txt", b"");
+    // the link target is given by the pax header
+    let pax = pax_record("linkpath", "./short.txt");
+    tar_entry(
+        &mut archive,
+        "PaxHeaders/pax-link.txt",
+        b'x',
+        "",
+        pax.as_bytes(),
+    );
+    tar_entry(&mut archive, "pax-link.txt", b'1', "", b"");
+    archive.resize(archive.len() + 2 * TAR_BLOCK_SIZE, 0);
+    archive
+}
+
+fn test_pax_header() -> Result<()> {
+    assert_eq!(fs::read_to_string(&long_path())?, "long path\n");
+    assert!(fs::metadata("/truncated").is_err());
+    assert!(fs::metadata("/PaxHeaders").is_err());
+    println!("test_pax_header() OK!");
+    Ok(())
+}
+
+fn test_hard_links() -> Result<()> {
+    assert_eq!(fs::read_to_string("/dir/link.txt")?, "Rust is cool!\n");
+    assert_eq!(fs::read_to_string("/pax-link.txt")?, "Rust is cool!\n");
+    println!("test_hard_links() OK!");
+    Ok(())
+}
+
+#[test]
+fn test_initramfs_tar() {
+    println!("Testing tar initramfs ...");
+
+    axtask::init_scheduler(); // call this to use `axsync::Mutex`.
+    axfs::set_initramfs(make_initramfs().leak());
+    axfs::init_filesystems(AxDeviceContainer::from_one(RamDisk::default())); // not used as root
+
+    test_pax_header().expect("test pax header failed");
+    test_hard_links().expect("test hard links failed");
+}
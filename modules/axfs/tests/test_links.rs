@@ -0,0 +1,162 @@
+#![cfg(all(
+    feature = "lwext4_rs",
+    not(any(feature = "myfs", feature = "overlayfs", feature = "initramfs"))
+))]
+
+mod ext4_img;
+
+use axdriver::AxDeviceContainer;
+use axfs::api as fs;
+use axio::{Error, Read, Result, Seek, SeekFrom, Write};
+
+fn test_hard_link() -> Result<()> {
+    fs::write("/origin.txt", "Rust is cool!\n")?;
+    fs::hard_link("/origin.txt", "/very/link.txt")?;
+    assert_eq!(fs::metadata("/origin.txt")?.nlink(), 2);
+    assert_eq!(fs::metadata("/very/link.txt")?.nlink(), 2);
+
+    // both links refer to the same data, even after renaming
+    fs::write("/very/link.txt", "Hello, world!\n")?;
+    fs::rename("/origin.txt", "/renamed.txt")?;
+    assert_eq!(fs::read_to_string("/renamed.txt")?, "Hello, world!\n");
+
+    // error cases
+    assert_eq!(
+        fs::hard_link("/renamed.txt", "/short.txt").err(),
+        Some(Error::AlreadyExists)
+    );
+    assert_eq!(
+        fs::hard_link("/very", "/very2").err(),
+        Some(Error::PermissionDenied)
+    );
+    assert_eq!(
+        fs::hard_link("/renamed.txt", "/tmp/link.txt").err(),
+        Some(Error::InvalidInput)
+    );
+
+    // the file is deleted with its last link
+    fs::remove_file("/renamed.txt")?;
+    assert_eq!(fs::metadata("/very/link.txt")?.nlink(), 1);
+    assert_eq!(fs::read_to_string("/very/link.txt")?, "Hello, world!\n");
+    fs::remove_file("/very/link.txt")?;
+    assert_eq!(fs::metadata("/very/link.txt").err(), Some(Error::NotFound));
+
+    println!("test_hard_link() OK!");
+    Ok(())
+}
+
+fn test_cached_links() -> Result<()> {
+    fs::write("/cached.txt", "0123456789")?;
+    fs::hard_link("/cached.txt", "/very/cached.txt")?;
+
+    // writes through one link are seen through the other before they are
+    // written back, as both share the cached pages of the inode
+    let mut writer = fs::OpenOptions::new().write(true).open("/cached.txt")?;
+    let mut reader = fs::File::open("/very/cached.txt")?;
+    writer.seek(SeekFrom::Start(4))?;
+    writer.write_all(b"abcd")?;
+    let mut buf = String::new();
+    reader.read_to_string(&mut buf)?;
+    assert_eq!(buf, "0123abcd89");
+
+    // and the size grows for both
+    writer.write_all(b"XYZ")?;
+    assert_eq!(fs::metadata("/very/cached.txt")?.len(), 11);
+    drop(writer);
+
+    // the data stays reachable through the other link after a removal
+    fs::remove_file("/cached.txt")?;
+    reader.seek(SeekFrom::Start(0))?;
+    buf.clear();
+    reader.read_to_string(&mut buf)?;
+    assert_eq!(buf, "0123abcdXYZ");
+    drop(reader);
+    assert_eq!(fs::read_to_string("/very/cached.txt")?, "0123abcdXYZ");
+    fs::remove_file("/very/cached.txt")?;
+
+    println!("test_cached_links() OK!");
+    Ok(())
+}
+
+fn test_linked_locks() -> Result<()> {
+    use axfs::fops::{self, LockError, LockType::*, RecordLock};
+
+    fs::write("/locked.txt", "locked\n")?;
+    fs::hard_link("/locked.txt", "/very/locked.txt")?;
+    let mut opts = fops::OpenOptions::new();
+    opts.read(true);
+    opts.write(true);
+    let f1 = fops::File::open("/locked.txt", &opts)?;
+    let f2 = fops::File::open("/very/locked.txt", &opts)?;
+    let (l1, l2) = (f1.lock_handle(), f2.lock_handle());
+
+    // locks belong to the inode, not to the link it is opened by
+    l1.flock(Exclusive, false)?;
+    assert_eq!(l2.flock(Shared, false).err(), Some(Error::WouldBlock));
+    let lock = RecordLock {
+        ty: Exclusive,
+        start: 0,
+        end: u64::MAX,
+        owner: 1,
+    };
+    assert_eq!(l1.set(lock, false), Ok(()));
+    assert_eq!(
+        l2.set(RecordLock { owner: 2, ..lock }, false),
+        Err(LockError::Other(Error::WouldBlock))
+    );
+
+    l1.release(1);
+    drop((f1, f2));
+    fs::remove_file("/locked.txt")?;
+    fs::remove_file("/very/locked.txt")?;
+
+    println!("test_linked_locks() OK!");
+    Ok(())
+}
+
+fn test_linked_watches() -> Result<()> {
+    use axfs::fops::{EventMask, Watcher};
+
+    fs::write("/watched.txt", "old")?;
+    fs::hard_link("/watched.txt", "/very/watched.txt")?;
+    let watcher = Watcher::new();
+    let wd = watcher.add_watch("/very/watched.txt", EventMask::ALL_EVENTS)?;
+    let read_masks = || {
+        let mut masks = Vec::new();
+        watcher
+            .read_events(false, |e| {
+                assert_eq!((e.wd, e.name.as_str()), (wd, ""));
+                masks.push(e.mask);
+                true
+            })
+            .ok();
+        masks
+    };
+
+    // changes through the other link are reported to the watch of the file
+    fs::write("/watched.txt", "new")?;
+    assert_eq!(read_masks(), [EventMask::MODIFY, EventMask::CLOSE_WRITE]);
+
+    // removing a link changes the link count, and the last one the file
+    fs::remove_file("/watched.txt")?;
+    assert_eq!(read_masks(), [EventMask::ATTRIB]);
+    fs::remove_file("/very/watched.txt")?;
+    assert_eq!(read_masks(), [EventMask::DELETE_SELF, EventMask::IGNORED]);
+
+    println!("test_linked_watches() OK!");
+    Ok(())
+}
+
+#[test]
+fn test_links() {
+    println!("Testing hard links with ext4 ...");
+
+    let disk = ext4_img::make_disk().expect("failed to make disk image");
+    axtask::init_scheduler(); // call this to use `axsync::Mutex`.
+    axfs::init_filesystems(AxDeviceContainer::from_one(disk));
+
+    test_hard_link().expect("test hard link failed");
+    test_cached_links().expect("test cached links failed");
+    test_linked_locks().expect("test linked locks failed");
+    test_linked_watches().expect("test linked watches failed");
+}
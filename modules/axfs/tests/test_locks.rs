@@ -0,0 +1,137 @@
+#![cfg(all(feature = "myfs", feature = "multitask"))]
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use axdriver::AxDeviceContainer;
+use axdriver_block::ramdisk::RamDisk;
+use axfs::api as fs;
+use axfs::fops::{self, Disk, LockError, LockType::*, MyFileSystemIf, RecordLock};
+use axfs_ramfs::RamFileSystem;
+use axfs_vfs::VfsOps;
+use axio::Result;
+
+struct MyFileSystemIfImpl;
+
+#[crate_interface::impl_interface]
+impl MyFileSystemIf for MyFileSystemIfImpl {
+    fn new_myfs(_disk: Disk) -> Arc<dyn VfsOps> {
+        Arc::new(RamFileSystem::new())
+    }
+}
+
+fn lock(ty: fops::LockType, start: u64, end: u64, owner: u64) -> RecordLock {
+    RecordLock {
+        ty,
+        start,
+        end,
+        owner,
+    }
+}
+
+fn open(path: &str) -> Result<fops::File> {
+    let mut opts = fops::OpenOptions::new();
+    opts.read(true);
+    opts.write(true);
+    fops::File::open(path, &opts)
+}
+
+fn test_blocking_lock() -> Result<()> {
+    static LOCKED: AtomicBool = AtomicBool::new(false);
+
+    fs::write("/wait.txt", "locked\n")?;
+    let file = open("/wait.txt")?;
+    let handle = file.lock_handle();
+    handle.set(lock(Exclusive, 0, 10, 1), false).unwrap();
+    handle.set(lock(Exclusive, 20, 30, 2), false).unwrap();
+
+    // owner 2 blocks on the range locked by owner 1, like `F_SETLKW`
+    let waiter = handle.clone();
+    axtask::spawn(move || {
+        waiter.set(lock(Shared, 5, 6, 2), true).unwrap();
+        LOCKED.store(true, Ordering::Release);
+    });
+    for _ in 0..10 {
+        axtask::yield_now();
+    }
+    assert!(!LOCKED.load(Ordering::Acquire));
+
+    // owner 1 waiting for owner 2 would never end
+    assert_eq!(
+        handle.set(lock(Exclusive, 25, 26, 1), true),
+        Err(LockError::Deadlock)
+    );
+
+    // the waiter is woken up once owner 1 unlocks the range
+    handle.set(lock(Unlocked, 0, 10, 1), false).unwrap();
+    while !LOCKED.load(Ordering::Acquire) {
+        axtask::yield_now();
+    }
+    assert_eq!(
+        handle.test(&lock(Exclusive, 0, 10, 1)),
+        Some(lock(Shared, 5, 6, 2))
+    );
+
+    handle.release(2);
+    drop(file);
+    fs::remove_file("/wait.txt")?;
+
+    println!("test_blocking_lock() OK!");
+    Ok(())
+}
+
+fn test_deadlock_chain() -> Result<()> {
+    static LOCKED: AtomicBool = AtomicBool::new(false);
+
+    fs::write("/a.txt", "a\n")?;
+    fs::write("/b.txt", "b\n")?;
+    let (a, b) = (open("/a.txt")?, open("/b.txt")?);
+    let (la, lb) = (a.lock_handle(), b.lock_handle());
+    la.set(lock(Exclusive, 0, 1, 1), false).unwrap();
+    lb.set(lock(Exclusive, 0, 1, 2), false).unwrap();
+
+    // owner 2 waits for owner 1 on another file
+    let waiter = la.clone();
+    axtask::spawn(move || {
+        waiter.set(lock(Exclusive, 0, 1, 2), true).unwrap();
+        LOCKED.store(true, Ordering::Release);
+    });
+    for _ in 0..10 {
+        axtask::yield_now();
+    }
+    assert!(!LOCKED.load(Ordering::Acquire));
+
+    // a non-blocking request is not a deadlock
+    assert_eq!(
+        lb.set(lock(Shared, 0, 1, 1), false),
+        Err(LockError::Other(axio::Error::WouldBlock))
+    );
+    assert_eq!(
+        lb.set(lock(Shared, 0, 1, 1), true),
+        Err(LockError::Deadlock)
+    );
+
+    la.release(1);
+    while !LOCKED.load(Ordering::Acquire) {
+        axtask::yield_now();
+    }
+    la.release(2);
+    lb.release(2);
+    drop((a, b));
+    fs::remove_file("/a.txt")?;
+    fs::remove_file("/b.txt")?;
+
+    println!("test_deadlock_chain() OK!");
+    Ok(())
+}
+
+#[test]
+fn test_locks() {
+    println!("Testing blocking file locks ...");
+
+    axtask::init_scheduler(); // call this to use `axsync::Mutex`.
+    axfs::init_filesystems(AxDeviceContainer::from_one(RamDisk::default()));
+
+    test_blocking_lock().expect("test blocking lock failed");
+    test_deadlock_chain().expect("test deadlock chain failed");
+}
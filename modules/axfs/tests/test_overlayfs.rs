@@ -0,0 +1,93 @@
+#![cfg(all(
+    feature = "overlayfs",
+    not(any(feature = "myfs", feature = "initramfs", feature = "lwext4_rs"))
+))]
+
+mod test_common;
+
+use axdriver::AxDeviceContainer;
+use axdriver_block::ramdisk::RamDisk;
+use axfs::api as fs;
+use axio::{Error, Result};
+
+const IMG_PATH: &str = "resources/fat16.img";
+
+fn make_disk() -> std::io::Result<RamDisk> {
+    let path = std::env::current_dir()?.join(IMG_PATH);
+    println!("Loading disk image from {:?} ...", path);
+    let data = std::fs::read(path)?;
+    println!("size = {} bytes", data.len());
+    Ok(RamDisk::from(&data))
+}
+
+fn file_names(dir: &str) -> Result<Vec<String>> {
+    fs::read_dir(dir)?
+        .map(|e| e.map(|e| e.file_name()))
+        .collect()
+}
+
+fn test_overlay() -> Result<()> {
+    let mounts = fs::read_to_string("/proc/mounts")?;
+    assert!(mounts.starts_with("/dev/vda / overlay rw 0 0\n"));
+
+    // files in the image are copied up when written
+    fs::write("/short.txt", "Overlay is cool!\n")?;
+    assert_eq!(fs::read_to_string("/short.txt")?, "Overlay is cool!\n");
+    assert_eq!(fs::metadata("/long.txt")?.len(), 14000);
+
+    // removed files and directories are hidden
+    fs::remove_file("/very-long-dir-name/very-long-file-name.txt")?;
+    assert_eq!(file_names("/very-long-dir-name")?.len(), 2); // "." and ".."
+    fs::remove_dir("/very-long-dir-name")?;
+    assert_eq!(
+        fs::metadata("/very-long-dir-name").err(),
+        Some(Error::NotFound)
+    );
+    assert!(!file_names("/")?.contains(&"very-long-dir-name".into()));
+
+    // a directory created over a removed one does not show the old entries
+    fs::create_dir("/very-long-dir-name")?;
+    assert_eq!(
+        fs::metadata("/very-long-dir-name/very-long-file-name.txt").err(),
+        Some(Error::NotFound)
+    );
+    fs::write("/very-long-dir-name/new.txt", "new")?;
+    assert_eq!(file_names("/very-long-dir-name")?.len(), 3);
+
+    // both layers are listed, without the whiteouts
+    let names = file_names("/")?;
+    for name in ["short.txt", "long.txt", "very", "very-long-dir-name"] {
+        assert!(names.contains(&name.into()), "/{} not found", name);
+    }
+    assert!(names.iter().all(|n| !n.starts_with(".wh.")));
+    assert_eq!(
+        fs::write("/.wh.short.txt", "").err(),
+        Some(Error::InvalidInput)
+    );
+
+    // renaming files is supported, but not directories merged with the image
+    fs::rename("/long.txt", "/long2.txt")?;
+    assert_eq!(fs::metadata("/long.txt").err(), Some(Error::NotFound));
+    assert_eq!(fs::metadata("/long2.txt")?.len(), 14000);
+    assert_eq!(
+        fs::rename("/very", "/very2").err(),
+        Some(Error::Unsupported)
+    );
+    fs::rename("/very-long-dir-name", "/new-dir")?;
+    assert_eq!(fs::read_to_string("/new-dir/new.txt")?, "new");
+
+    println!("test_overlay() OK!");
+    Ok(())
+}
+
+#[test]
+fn test_overlayfs() {
+    println!("Testing overlayfs over fatfs with ramdisk ...");
+
+    let disk = make_disk().expect("failed to load disk image");
+    axtask::init_scheduler(); // call this to use `axsync::Mutex`.
+    axfs::init_filesystems(AxDeviceContainer::from_one(disk));
+
+    test_common::test_all();
+    test_overlay().expect("test overlay failed");
+}
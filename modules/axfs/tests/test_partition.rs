@@ -0,0 +1,166 @@
+#![cfg(feature = "myfs")]
+
+use std::sync::{Arc, OnceLock};
+
+use axdriver::AxDeviceContainer;
+use axdriver_block::ramdisk::RamDisk;
+use axfs::api::{self as fs, File, FileType};
+use axfs::fops::{Disk, MyFileSystemIf};
+use axfs_ramfs::RamFileSystem;
+use axfs_vfs::VfsOps;
+use axio::{Read, Result, Seek, SeekFrom, Write};
+
+const BLOCK_SIZE: usize = 512;
+const DISK_BLOCKS: usize = 256;
+const ROOT_MAGIC: &[u8] = b"root partition";
+
+/// The first block of the disk given to the root filesystem.
+static ROOT_FIRST_BLOCK: OnceLock<Vec<u8>> = OnceLock::new();
+
+struct MyFileSystemIfImpl;
+
+#[crate_interface::impl_interface]
+impl MyFileSystemIf for MyFileSystemIfImpl {
+    fn new_myfs(mut disk: Disk) -> Arc<dyn VfsOps> {
+        let mut block = vec![0; BLOCK_SIZE];
+        disk.read_data(&mut block).unwrap();
+        ROOT_FIRST_BLOCK.set(block).unwrap();
+        Arc::new(RamFileSystem::new())
+    }
+}
+
+fn put_u32(buf: &mut [u8], offset: usize, val: u32) {
+    buf[offset..offset + 4].copy_from_slice(&val.to_le_bytes());
+}
+
+fn put_mbr_entry(sector: &mut [u8], idx: usize, ty: u8, start: u32, len: u32) {
+    let e = 0x1be + idx * 16;
+    sector[e + 4] = ty;
+    put_u32(sector, e + 8, start);
+    put_u32(sector, e + 12, len);
+    sector[510] = 0x55;
+    sector[511] = 0xaa;
+}
+
+/// An MBR disk with primary partitions 1 (blocks 8..40) and 2 (blocks
+/// 40..72), and logical partition 5 (blocks 136..168) in the extended
+/// partition at blocks 128..256. Partition 2 starts with `ROOT_MAGIC`.
+fn make_mbr_disk() -> Vec<u8> {
+    let mut data = vec![0u8; DISK_BLOCKS * BLOCK_SIZE];
+    put_u32(&mut data, 0x1b8, 0x1234abcd);
+    put_mbr_entry(&mut data, 0, 0x0c, 8, 32);
+    put_mbr_entry(&mut data, 1, 0x83, 40, 32);
+    put_mbr_entry(&mut data, 2, 0x05, 128, 128);
+    let ebr = &mut data[128 * BLOCK_SIZE..129 * BLOCK_SIZE];
+    put_mbr_entry(ebr, 0, 0x83, 8, 32);
+    data[40 * BLOCK_SIZE..][..ROOT_MAGIC.len()].copy_from_slice(ROOT_MAGIC);
+    data
+}
+
+fn test_root_device() {
+    // selected by the partition number of the first device
+    let block = ROOT_FIRST_BLOCK.get().expect("root filesystem not created");
+    assert_eq!(&block[..ROOT_MAGIC.len()], ROOT_MAGIC);
+}
+
+fn test_partitions() -> Result<()> {
+    let dirents = fs::read_dir("/dev")?
+        .map(|e| e.unwrap().file_name())
+        .collect::<Vec<_>>();
+    for name in ["vda", "vda1", "vda2", "vda5"] {
+        assert!(dirents.contains(&name.into()), "/dev/{} not found", name);
+    }
+    assert!(!dirents.contains(&"vda3".into()));
+
+    for (path, blocks) in [
+        ("/dev/vda", DISK_BLOCKS),
+        ("/dev/vda1", 32),
+        ("/dev/vda5", 32),
+    ] {
+        let md = fs::metadata(path)?;
+        assert_eq!(md.file_type(), FileType::BlockDevice);
+        assert_eq!(md.len(), (blocks * BLOCK_SIZE) as u64);
+    }
+
+    // writes to a partition land at its offset on the whole device
+    let mut part = File::options().read(true).write(true).open("/dev/vda2")?;
+    part.seek(SeekFrom::Start(BLOCK_SIZE as u64 + 3))?;
+    part.write_all(b"partition")?;
+    let mut whole = File::open("/dev/vda")?;
+    whole.seek(SeekFrom::Start(41 * BLOCK_SIZE as u64 + 3))?;
+    let mut buf = [0u8; 9];
+    whole.read_exact(&mut buf)?;
+    assert_eq!(&buf, b"partition");
+
+    // partitions are bounded
+    part.seek(SeekFrom::Start(32 * BLOCK_SIZE as u64 - 4))?;
+    let mut buf = [0u8; 16];
+    assert_eq!(part.read(&mut buf)?, 4);
+    assert_eq!(part.read(&mut buf)?, 0);
+    Ok(())
+}
+
+fn test_block_queue() -> Result<()> {
+    assert_eq!(fs::io_scheduler("vda")?, "elevator");
+    assert!(fs::io_scheduler("vda1").is_err());
+    assert!(fs::set_io_scheduler("vda", "cfq").is_err());
+
+    // the free space between partition 2 and the extended partition
+    let offset = 72 * BLOCK_SIZE as u64 + 33;
+    for (scheduler, seed) in [("noop", 1), ("elevator", 7)] {
+        fs::set_io_scheduler("vda", scheduler)?;
+        assert_eq!(fs::io_scheduler("vda")?, scheduler);
+
+        let data = (0..40 * BLOCK_SIZE + 77)
+            .map(|i| (i * seed) as u8)
+            .collect::<Vec<_>>();
+        let mut dev = File::options().read(true).write(true).open("/dev/vda")?;
+        dev.seek(SeekFrom::Start(offset))?;
+        dev.write_all(&data)?;
+        // a scattered write of single blocks, merged on flush
+        for block in [100, 90, 101, 95, 91] {
+            dev.seek(SeekFrom::Start(block * BLOCK_SIZE as u64))?;
+            dev.write_all(&[seed as u8; BLOCK_SIZE])?;
+        }
+        dev.flush()?;
+
+        let mut buf = vec![0; data.len()];
+        let mut dev = File::open("/dev/vda")?;
+        dev.seek(SeekFrom::Start(offset))?;
+        dev.read_exact(&mut buf)?;
+        assert_eq!(
+            buf[..(90 - 72) * BLOCK_SIZE - 33],
+            data[..(90 - 72) * BLOCK_SIZE - 33]
+        );
+        for block in [90, 91, 95, 100, 101] {
+            let mut buf = [0; BLOCK_SIZE];
+            dev.seek(SeekFrom::Start(block * BLOCK_SIZE as u64))?;
+            dev.read_exact(&mut buf)?;
+            assert_eq!(buf, [seed as u8; BLOCK_SIZE]);
+        }
+    }
+
+    // the scheduler is a tunable in sysfs as well
+    let path = "/sys/block/vda/queue/scheduler";
+    assert_eq!(fs::read_to_string(path)?, "noop [elevator]\n");
+    fs::write(path, "noop\n")?;
+    assert_eq!(fs::io_scheduler("vda")?, "noop");
+    assert_eq!(fs::read_to_string(path)?, "[noop] elevator\n");
+    assert!(fs::write(path, "cfq").is_err());
+    assert!(fs::metadata("/sys/block/vda1").is_err());
+    Ok(())
+}
+
+#[test]
+fn test_partition() {
+    println!("Testing partitions ...");
+
+    axtask::init_scheduler(); // call this to use `axsync::Mutex`.
+    let disk = RamDisk::from(&make_mbr_disk());
+    axfs::set_root_device("2");
+    axfs::init_filesystems(AxDeviceContainer::from_one(disk));
+
+    test_root_device();
+    test_partitions().expect("test partitions failed");
+    test_block_queue().expect("test block queue failed");
+}
@@ -1,3 +1,5 @@
+use alloc::sync::Arc;
+use alloc::vec::Vec;
 use core::fmt;
 
 use axerrno::{AxError, AxResult, ax_err};
@@ -6,12 +8,12 @@ use axhal::paging::{MappingFlags, PageSize, PageTable, PagingError};
 use memory_addr::{MemoryAddr, PhysAddr, VirtAddr, VirtAddrRange, is_aligned};
 use memory_set::{MemoryArea, MemorySet};
 
-use crate::backend::Backend;
+use crate::backend::{Backend, FilePage, MappedFile, dealloc_frame};
 use crate::mapping_err_to_ax_err;
 use crate::page_iter_wrapper::{PAGE_SIZE_4K, PageIterWrapper};
 
 #[cfg(feature = "cow")]
-use crate::backend::{alloc_frame, dealloc_frame};
+use crate::backend::alloc_frame;
 #[cfg(feature = "cow")]
 use crate::frameinfo::frame_table;
 
@@ -224,6 +226,43 @@ impl AddrSpace {
         Ok(())
     }
 
+    /// Add a new file mapping, which maps `file` from `offset`.
+    ///
+    /// See [`Backend`] for more details about the mapping backends.
+    ///
+    /// The `flags` parameter indicates the mapping permissions and attributes.
+    /// If `shared` is `true`, the pages changed by writes are recorded, to be
+    /// written back after they are taken by [`AddrSpace::take_dirty_pages`].
+    /// Changes that are not taken before the pages are unmapped are lost.
+    ///
+    /// Returns an error if the address range is out of the address space, or
+    /// the range or `offset` is not aligned.
+    pub fn map_file(
+        &mut self,
+        start: VirtAddr,
+        size: usize,
+        flags: MappingFlags,
+        file: Arc<dyn MappedFile>,
+        offset: u64,
+        shared: bool,
+    ) -> AxResult {
+        self.validate_region(start, size, PageSize::Size4K)?;
+        if !is_aligned(offset as usize, PAGE_SIZE_4K) {
+            return ax_err!(InvalidInput, "offset not aligned");
+        }
+
+        let area = MemoryArea::new(
+            start,
+            size,
+            flags,
+            Backend::new_file(file, start, offset, shared),
+        );
+        self.areas
+            .map(area, &mut self.pt, false)
+            .map_err(mapping_err_to_ax_err)?;
+        Ok(())
+    }
+
     /// Ensures that the specified virtual memory region is fully mapped.
     ///
     /// This function walks through the given virtual address range and attempts to ensure
@@ -267,7 +306,12 @@ impl AddrSpace {
             }
 
             let backend = area.backend();
-            if let Backend::Alloc { populate, align } = *backend {
+            let lazy = match *backend {
+                Backend::Alloc { populate, align } => Some((populate, align)),
+                // file pages are read without the lock, see `map_file_page`
+                Backend::File { .. } | Backend::Linear { .. } => None,
+            };
+            if let Some((populate, align)) = lazy {
                 for addr in PageIterWrapper::new(
                     start.align_down(align),
                     end.align_up(align).min(area.end()),
@@ -300,7 +344,12 @@ impl AddrSpace {
                         // If the page is not mapped, try map it.
                         Err(PagingError::NotMapped) => {
                             if !populate {
-                                if !backend.handle_page_fault(addr, area.flags(), &mut self.pt) {
+                                if !backend.handle_page_fault(
+                                    addr,
+                                    area.flags(),
+                                    area.flags(),
+                                    &mut self.pt,
+                                ) {
                                     return Err(AxError::NoMemory);
                                 }
                             } else {
@@ -343,6 +392,7 @@ impl AddrSpace {
                     pa_va_offset: _,
                     align,
                 } => align,
+                Backend::File { .. } => PageSize::Size4K,
             };
 
             let unmap_start = start.max(area.start());
@@ -358,6 +408,92 @@ impl AddrSpace {
         Ok(())
     }
 
+    /// Moves the mapping in the `size` bytes from `start` to `new_start`, and
+    /// extends it to `new_size` bytes with the same backend. The mapping is
+    /// extended in place if `new_start` is `start`.
+    ///
+    /// The mapped pages are moved rather than copied, and the new range must
+    /// not overlap other mappings.
+    ///
+    /// Returns an error if the address ranges are out of the address space or
+    /// not aligned, if `new_size` is less than `size`, or if the range is not
+    /// in a single mapping.
+    pub fn remap(
+        &mut self,
+        start: VirtAddr,
+        size: usize,
+        new_start: VirtAddr,
+        new_size: usize,
+    ) -> AxResult {
+        self.validate_region(start, size, PageSize::Size4K)?;
+        self.validate_region(new_start, new_size, PageSize::Size4K)?;
+        if new_size < size {
+            return ax_err!(InvalidInput, "mapping shrunk by remap");
+        }
+
+        let (end, new_end) = (start + size, new_start + new_size);
+        let area = self
+            .areas
+            .find(start)
+            .filter(|area| area.end() >= end)
+            .ok_or(AxError::BadAddress)?;
+        let align = match *area.backend() {
+            Backend::Alloc { populate: _, align } => align,
+            _ => PageSize::Size4K,
+        };
+        if !start.is_aligned(align)
+            || !is_aligned(size, align.into())
+            || !new_start.is_aligned(align)
+            || !is_aligned(new_size, align.into())
+        {
+            return ax_err!(InvalidInput, "address not aligned");
+        }
+        if self.areas.iter().any(|area| {
+            let overlap = (new_start.max(area.start()), new_end.min(area.end()));
+            overlap.0 < overlap.1 && (overlap.0 < start || overlap.1 > end)
+        }) {
+            return ax_err!(AlreadyExists, "address range in use");
+        }
+        let flags = area.flags();
+        let backend = area
+            .backend()
+            .remapped(start, end, new_start)
+            .ok_or(AxError::Unsupported)?;
+
+        // Take the mapped pages out, so they are not freed with the mapping.
+        let mut pages = Vec::new();
+        for vaddr in
+            PageIterWrapper::new(start, end, align).expect("Failed to create page iterator")
+        {
+            let Ok((_, page_flags, _)) = self.pt.query(vaddr) else {
+                continue;
+            };
+            if let Ok((frame, page_size, tlb)) = self.pt.unmap(vaddr) {
+                tlb.flush();
+                pages.push((new_start + (vaddr - start), frame, page_flags, page_size));
+            }
+        }
+        self.areas
+            .unmap(start, size, &mut self.pt)
+            .map_err(mapping_err_to_ax_err)?;
+
+        let area = MemoryArea::new(new_start, new_size, flags, backend);
+        let result = self
+            .areas
+            .map(area, &mut self.pt, false)
+            .map_err(mapping_err_to_ax_err);
+        for (vaddr, frame, page_flags, page_size) in pages {
+            if result.is_ok()
+                && let Ok(tlb) = self.pt.map(vaddr, frame, page_size, page_flags)
+            {
+                tlb.flush();
+            } else {
+                dealloc_frame(frame, page_size);
+            }
+        }
+        result
+    }
+
     /// To remove user area mappings from address space.
     pub fn unmap_user_areas(&mut self) -> AxResult {
         self.areas.clear(&mut self.pt).unwrap();
@@ -515,6 +651,7 @@ impl AddrSpace {
                 // - cow
                 #[cfg(feature = "cow")]
                 if access_flags.contains(MappingFlags::WRITE)
+                    && !matches!(area.backend(), Backend::File { shared: true, .. })
                     && let Ok((paddr, _, page_size)) = self.pt.query(vaddr)
                 {
                     // 1. page fault caused by write
@@ -529,14 +666,106 @@ impl AddrSpace {
                     );
                 }
 
-                return area
-                    .backend()
-                    .handle_page_fault(vaddr, orig_flags, &mut self.pt);
+                return area.backend().handle_page_fault(
+                    vaddr,
+                    orig_flags,
+                    access_flags,
+                    &mut self.pt,
+                );
             }
         }
         false
     }
 
+    /// Returns the file and the file offset of the page to be read, if the
+    /// page fault at `vaddr` is in a page of a file mapping not mapped yet.
+    ///
+    /// The page is read by [`FilePage::read`] without holding the lock of the
+    /// address space, and then mapped by [`AddrSpace::map_file_page`].
+    pub fn file_page_to_read(
+        &self,
+        vaddr: VirtAddr,
+        access_flags: MappingFlags,
+    ) -> Option<(Arc<dyn MappedFile>, u64)> {
+        if !self.va_range.contains(vaddr) || self.pt.query(vaddr).is_ok() {
+            return None;
+        }
+        let area = self
+            .areas
+            .find(vaddr)
+            .filter(|area| area.flags().contains(access_flags))?;
+        match area.backend() {
+            Backend::File {
+                file,
+                start,
+                offset,
+                ..
+            } => Some((file.clone(), Backend::file_offset(vaddr, *start, *offset))),
+            _ => None,
+        }
+    }
+
+    /// Maps the file `page` read for the page fault at `vaddr`, and handles
+    /// the page fault.
+    ///
+    /// Returns `None` if the page no longer belongs to the mapping at `vaddr`,
+    /// as the mapping was changed while the page was read. The page fault
+    /// should be handled again then.
+    pub fn map_file_page(
+        &mut self,
+        vaddr: VirtAddr,
+        access_flags: MappingFlags,
+        page: FilePage,
+    ) -> Option<bool> {
+        let area = self
+            .areas
+            .find(vaddr)
+            .filter(|area| area.flags().contains(access_flags))?;
+        if !area
+            .backend()
+            .map_file_page(vaddr, area.flags(), page, &mut self.pt)?
+        {
+            return Some(false);
+        }
+        // a write also marks the page of a shared mapping dirty
+        Some(self.handle_page_fault(vaddr, access_flags))
+    }
+
+    /// Takes copies of the dirty pages of the shared file mappings in the
+    /// region, to be written back by [`FilePage::write_back`] without holding
+    /// the lock of the address space.
+    ///
+    /// The pages are clean after that, and mapped without write permission
+    /// to find out the next changes. Nothing is changed if the copies cannot
+    /// be allocated.
+    pub fn take_dirty_pages(&mut self, start: VirtAddr, size: usize) -> AxResult<Vec<FilePage>> {
+        self.validate_region(start, size, PageSize::Size4K)?;
+
+        let end = start + size;
+        let mut pages = Vec::new();
+        for area in self
+            .areas
+            .iter()
+            .skip_while(move |a| a.end() <= start)
+            .take_while(move |a| a.start() < end)
+        {
+            let (start, end) = (start.max(area.start()), end.min(area.end()));
+            area.backend()
+                .copy_dirty_pages(start, end, &self.pt, &mut pages)?;
+        }
+        for area in self
+            .areas
+            .iter()
+            .skip_while(move |a| a.end() <= start)
+            .take_while(move |a| a.start() < end)
+        {
+            let (start, end) = (start.max(area.start()), end.min(area.end()));
+            area.backend()
+                .clean_pages(start, end, area.flags(), &mut self.pt);
+        }
+        Ok(pages)
+    }
+
     /// Attempts to clone the current address space into a new one.
     ///
     /// This method creates a new empty address space with the same base and size,
@@ -571,6 +800,20 @@ impl AddrSpace {
                     // from mapping page table entries for the virtual addresses.
                     Backend::new_alloc(false, *align)
                 }
+                // The dirty pages are copied, but tracked separately from now on.
+                Backend::File {
+                    file,
+                    start,
+                    offset,
+                    shared,
+                    dirty,
+                } => Backend::File {
+                    file: file.clone(),
+                    start: *start,
+                    offset: *offset,
+                    shared: *shared,
+                    dirty: Arc::new(kspin::SpinNoIrq::new(dirty.lock().clone())),
+                },
                 other => other.clone(),
             };
 
@@ -583,6 +826,7 @@ impl AddrSpace {
 
             let align = match area.backend() {
                 Backend::Alloc { align, .. } => *align,
+                Backend::File { .. } => PageSize::Size4K,
                 // Linear-backed regions are usually allocated by the kernel and are shared
                 Backend::Linear { .. } => continue,
             };
@@ -602,11 +846,37 @@ impl AddrSpace {
                                 Ok((paddr, _, _)) => paddr,
                                 // If the page is not mapped, try map it.
                                 Err(PagingError::NotMapped) => {
-                                    if !area.backend().handle_page_fault(
-                                        vaddr,
-                                        area.flags(),
-                                        &mut new_aspace.pt,
-                                    ) {
+                                    let mapped = match area.backend() {
+                                        // The data is copied below instead of
+                                        // being read from the file.
+                                        Backend::File {
+                                            file,
+                                            start,
+                                            offset,
+                                            ..
+                                        } => FilePage::copy(
+                                            file.clone(),
+                                            Backend::file_offset(vaddr, *start, *offset),
+                                            paddr,
+                                        )
+                                        .ok()
+                                        .and_then(|page| {
+                                            area.backend().map_file_page(
+                                                vaddr,
+                                                area.flags(),
+                                                page,
+                                                &mut new_aspace.pt,
+                                            )
+                                        })
+                                        .unwrap_or(false),
+                                        backend => backend.handle_page_fault(
+                                            vaddr,
+                                            area.flags(),
+                                            area.flags(),
+                                            &mut new_aspace.pt,
+                                        ),
+                                    };
+                                    if !mapped {
                                         return Err(AxError::NoMemory);
                                     }
                                     match new_aspace.pt.query(vaddr) {
@@ -0,0 +1,333 @@
+use alloc::collections::BTreeSet;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::mem::ManuallyDrop;
+
+use axerrno::{AxError, AxResult};
+use axhal::mem::phys_to_virt;
+use axhal::paging::{MappingFlags, PageSize, PageTable};
+use kspin::SpinNoIrq;
+use memory_addr::{MemoryAddr, PhysAddr, VirtAddr};
+
+use super::{Backend, alloc_frame, dealloc_frame};
+use crate::page_iter_wrapper::{PAGE_SIZE_4K, PageIterWrapper};
+
+/// A file that can be mapped into memory by [`Backend::File`].
+pub trait MappedFile: Send + Sync {
+    /// Reads the data at `offset` into `buf`. Returns the number of bytes
+    /// read, which is less than `buf.len()` only at the end of the file.
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> AxResult<usize>;
+
+    /// Writes the data in `buf` back to the file at `offset`. Data beyond the
+    /// end of the file is discarded, as the file is not extended.
+    fn write_back(&self, offset: u64, buf: &[u8]) -> AxResult;
+}
+
+/// The file offsets of the pages changed since they were last written back.
+pub(crate) type DirtyPages = Arc<SpinNoIrq<BTreeSet<u64>>>;
+
+/// A physical frame owned by a [`FilePage`], which is freed on drop.
+struct Frame(PhysAddr);
+
+impl Drop for Frame {
+    fn drop(&mut self) {
+        dealloc_frame(self.0, PageSize::Size4K);
+    }
+}
+
+/// A page of a mapped file, held in a frame that is not mapped.
+///
+/// Reading and writing back the page may sleep in the file system, so it is
+/// done without holding the lock of the address space: the page is read by
+/// [`FilePage::read`] before it is mapped by [`AddrSpace::map_file_page`],
+/// and the dirty pages are copied out by [`AddrSpace::take_dirty_pages`]
+/// before they are written back by [`FilePage::write_back`].
+///
+/// [`AddrSpace::map_file_page`]: crate::AddrSpace::map_file_page
+/// [`AddrSpace::take_dirty_pages`]: crate::AddrSpace::take_dirty_pages
+pub struct FilePage {
+    file: Arc<dyn MappedFile>,
+    offset: u64,
+    frame: Frame,
+}
+
+impl FilePage {
+    fn alloc(file: Arc<dyn MappedFile>, offset: u64, zeroed: bool) -> AxResult<Self> {
+        let frame = alloc_frame(zeroed, PageSize::Size4K).ok_or(AxError::NoMemory)?;
+        Ok(Self {
+            file,
+            offset,
+            frame: Frame(frame),
+        })
+    }
+
+    /// Reads the page of `file` at `offset`, which must be page-aligned.
+    ///
+    /// The part beyond the end of the file is zeroed.
+    pub fn read(file: Arc<dyn MappedFile>, offset: u64) -> AxResult<Self> {
+        let mut page = Self::alloc(file, offset, true)?;
+        // SAFETY: the frame is owned by the page and not mapped.
+        let buf = unsafe {
+            core::slice::from_raw_parts_mut(phys_to_virt(page.frame.0).as_mut_ptr(), PAGE_SIZE_4K)
+        };
+        page.file.read_at(offset, buf)?;
+        Ok(page)
+    }
+
+    /// Copies the page of `file` at `offset` from the mapped `frame`.
+    pub(crate) fn copy(file: Arc<dyn MappedFile>, offset: u64, frame: PhysAddr) -> AxResult<Self> {
+        let page = Self::alloc(file, offset, false)?;
+        // SAFETY: both frames are allocated by `alloc_frame`.
+        unsafe {
+            core::ptr::copy_nonoverlapping(
+                phys_to_virt(frame).as_ptr(),
+                phys_to_virt(page.frame.0).as_mut_ptr(),
+                PAGE_SIZE_4K,
+            )
+        };
+        Ok(page)
+    }
+
+    /// Returns the file offset of the page.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Writes the page back to the file.
+    pub fn write_back(&self) -> AxResult {
+        // SAFETY: the frame is owned by the page and not mapped.
+        let data = unsafe {
+            core::slice::from_raw_parts(phys_to_virt(self.frame.0).as_ptr(), PAGE_SIZE_4K)
+        };
+        self.file.write_back(self.offset, data)
+    }
+}
+
+impl Backend {
+    /// Creates a new file mapping backend, which maps `file` from `offset` at
+    /// the virtual address `start`.
+    pub fn new_file(file: Arc<dyn MappedFile>, start: VirtAddr, offset: u64, shared: bool) -> Self {
+        Self::File {
+            file,
+            start,
+            offset,
+            shared,
+            dirty: DirtyPages::default(),
+        }
+    }
+
+    /// Returns the file offset of the page at `vaddr`, in a mapping of the
+    /// file from `offset` at `file_start`.
+    pub(crate) fn file_offset(vaddr: VirtAddr, file_start: VirtAddr, offset: u64) -> u64 {
+        offset + (vaddr.align_down_4k() - file_start) as u64
+    }
+
+    /// Returns the flags of the pages of a file mapping with `flags`.
+    ///
+    /// Pages of a shared mapping are mapped without write permission until
+    /// they are written, which marks them dirty.
+    pub(crate) fn file_page_flags(shared: bool, flags: MappingFlags) -> MappingFlags {
+        if shared {
+            flags - MappingFlags::WRITE
+        } else {
+            flags
+        }
+    }
+
+    pub(crate) fn map_file(start: VirtAddr, size: usize, flags: MappingFlags) -> bool {
+        debug!("map_file: [{:#x}, {:#x}) {:?}", start, start + size, flags);
+        // create mapping entries on demand later in `AddrSpace::map_file_page`.
+        true
+    }
+
+    pub(crate) fn unmap_file(
+        start: VirtAddr,
+        size: usize,
+        pt: &mut PageTable,
+        file_start: VirtAddr,
+        offset: u64,
+        dirty: &DirtyPages,
+    ) -> bool {
+        debug!("unmap_file: [{:#x}, {:#x})", start, start + size);
+        if let Some(iter) = PageIterWrapper::new(start, start + size, PageSize::Size4K) {
+            let mut dirty = dirty.lock();
+            for addr in iter {
+                if let Ok((frame, _page_size, tlb)) = pt.unmap(addr) {
+                    tlb.flush();
+                    if dirty.remove(&Self::file_offset(addr, file_start, offset)) {
+                        warn!("discarding the changes of the page at {:#x}", addr);
+                    }
+                    dealloc_frame(frame, PageSize::Size4K);
+                } else {
+                    // Deallocation is needn't if the page is not mapped.
+                }
+            }
+        }
+        true
+    }
+
+    /// Handles a page fault at `vaddr` in a file mapping.
+    ///
+    /// Pages are read from the file by [`AddrSpace::map_file_page`], so only
+    /// faults on pages that are already mapped are handled here. A write to a
+    /// clean page of a shared mapping marks it dirty and makes it writable.
+    ///
+    /// [`AddrSpace::map_file_page`]: crate::AddrSpace::map_file_page
+    pub(crate) fn handle_page_fault_file(
+        vaddr: VirtAddr,
+        orig_flags: MappingFlags,
+        access_flags: MappingFlags,
+        pt: &mut PageTable,
+        file_offset: u64,
+        shared: bool,
+        dirty: &DirtyPages,
+    ) -> bool {
+        let Ok((_, flags, _)) = pt.query(vaddr) else {
+            return false;
+        };
+        if flags.contains(access_flags) {
+            // mapped by another page fault in the meantime
+            return true;
+        }
+        if !shared || !access_flags.contains(MappingFlags::WRITE) {
+            return false;
+        }
+        dirty.lock().insert(file_offset);
+        pt.protect(vaddr, orig_flags)
+            .map(|(_, tlb)| tlb.flush())
+            .is_ok()
+    }
+
+    /// Maps `page` at `vaddr` in a file mapping, if it is the page of the
+    /// mapping there.
+    ///
+    /// Returns `None` if the page is not, or `Some(true)` if the page is
+    /// mapped, or another page was mapped there in the meantime.
+    pub(crate) fn map_file_page(
+        &self,
+        vaddr: VirtAddr,
+        orig_flags: MappingFlags,
+        page: FilePage,
+        pt: &mut PageTable,
+    ) -> Option<bool> {
+        let Self::File {
+            ref file,
+            start,
+            offset,
+            shared,
+            ..
+        } = *self
+        else {
+            return None;
+        };
+        if !Arc::ptr_eq(file, &page.file) || Self::file_offset(vaddr, start, offset) != page.offset
+        {
+            return None;
+        }
+        if pt.query(vaddr).is_ok() {
+            return Some(true);
+        }
+        let flags = Self::file_page_flags(shared, orig_flags);
+        let frame = page.frame.0;
+        match pt.map(vaddr.align_down_4k(), frame, PageSize::Size4K, flags) {
+            Ok(tlb) => {
+                tlb.flush();
+                // the frame is owned by the mapping now
+                let _ = ManuallyDrop::new(page.frame);
+                Some(true)
+            }
+            Err(_) => Some(false),
+        }
+    }
+
+    /// Returns the backend of the pages of a file mapping in `[start, end)`
+    /// moved to `new_start`, which takes the dirty pages among them, as the
+    /// mapping may be extended over file offsets still mapped here.
+    pub(crate) fn remap_file(
+        &self,
+        start: VirtAddr,
+        end: VirtAddr,
+        new_start: VirtAddr,
+    ) -> Option<Self> {
+        let Self::File {
+            ref file,
+            start: file_start,
+            offset,
+            shared,
+            ref dirty,
+        } = *self
+        else {
+            return None;
+        };
+        let range = Self::file_offset(start, file_start, offset)
+            ..Self::file_offset(end, file_start, offset);
+        let mut dirty = dirty.lock();
+        let mut moved = dirty.split_off(&range.start);
+        dirty.append(&mut moved.split_off(&range.end));
+        Some(Self::File {
+            file: file.clone(),
+            start: new_start,
+            offset: range.start,
+            shared,
+            dirty: Arc::new(SpinNoIrq::new(moved)),
+        })
+    }
+
+    /// Copies the dirty pages of a file mapping in `[start, end)` into `pages`.
+    pub(crate) fn copy_dirty_pages(
+        &self,
+        start: VirtAddr,
+        end: VirtAddr,
+        pt: &PageTable,
+        pages: &mut Vec<FilePage>,
+    ) -> AxResult {
+        let Self::File {
+            ref file,
+            start: file_start,
+            offset,
+            ref dirty,
+            ..
+        } = *self
+        else {
+            return Ok(());
+        };
+        let range = Self::file_offset(start, file_start, offset)
+            ..Self::file_offset(end, file_start, offset);
+        for &page_offset in dirty.lock().range(range) {
+            let vaddr = file_start + (page_offset - offset) as usize;
+            let (frame, ..) = pt.query(vaddr).map_err(|_| AxError::BadState)?;
+            pages.push(FilePage::copy(file.clone(), page_offset, frame)?);
+        }
+        Ok(())
+    }
+
+    /// Marks the pages of a file mapping in `[start, end)` clean, and maps
+    /// them without write permission again.
+    pub(crate) fn clean_pages(
+        &self,
+        start: VirtAddr,
+        end: VirtAddr,
+        flags: MappingFlags,
+        pt: &mut PageTable,
+    ) {
+        let Self::File {
+            start: file_start,
+            offset,
+            ref dirty,
+            ..
+        } = *self
+        else {
+            return;
+        };
+        let range = Self::file_offset(start, file_start, offset)
+            ..Self::file_offset(end, file_start, offset);
+        let mut dirty = dirty.lock();
+        for &page_offset in dirty.range(range.clone()) {
+            let vaddr = file_start + (page_offset - offset) as usize;
+            if let Ok((_, tlb)) = pt.protect(vaddr, flags - MappingFlags::WRITE) {
+                tlb.flush();
+            }
+        }
+        dirty.retain(|page_offset| !range.contains(page_offset));
+    }
+}
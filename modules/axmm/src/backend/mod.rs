@@ -1,22 +1,28 @@
 //! Memory mapping backends.
 
+use ::alloc::sync::Arc;
 use axhal::paging::{MappingFlags, PageSize, PageTable};
 use memory_addr::VirtAddr;
 use memory_set::MappingBackend;
 mod alloc;
+mod file;
 mod linear;
 
 #[allow(unused_imports)]
 pub(crate) use alloc::{alloc_frame, dealloc_frame};
+pub(crate) use file::DirtyPages;
+pub use file::{FilePage, MappedFile};
 
 /// A unified enum type for different memory mapping backends.
 ///
-/// Currently, two backends are implemented:
+/// Currently, three backends are implemented:
 ///
 /// - **Linear**: used for linear mappings. The target physical frames are
 ///   contiguous and their addresses should be known when creating the mapping.
 /// - **Allocation**: used in general, or for lazy mappings. The target physical
 ///   frames are obtained from the global allocator.
+/// - **File**: used for memory-mapped files. The target physical frames are
+///   obtained from the global allocator and filled with the file data.
 #[derive(Clone)]
 pub enum Backend {
     /// Linear mapping backend.
@@ -42,6 +48,30 @@ pub enum Backend {
         /// Alignment parameters for the starting address and memory range.
         align: PageSize,
     },
+    /// File mapping backend.
+    ///
+    /// The physical frames are allocated and filled with the data of `file`
+    /// on demand (by handling page faults). The page at the virtual address
+    /// `vaddr` holds the data at the file offset `offset + (vaddr - start)`.
+    /// `start` is kept when the mapping is split, so it may be out of the
+    /// mapping.
+    ///
+    /// If `shared` is `true`, the pages written since they were faulted in
+    /// or written back are recorded in `dirty`, and only they are written
+    /// back to the file. Otherwise, the changes are private to the mapping,
+    /// and the data of the file is copied when the pages are faulted in.
+    File {
+        /// The mapped file.
+        file: Arc<dyn MappedFile>,
+        /// The virtual address where the mapping was created.
+        start: VirtAddr,
+        /// The file offset mapped at `start`.
+        offset: u64,
+        /// Whether the changes are written back to the file.
+        shared: bool,
+        /// The dirty pages, which are shared when the mapping is split.
+        dirty: DirtyPages,
+    },
 }
 
 impl MappingBackend for Backend {
@@ -57,6 +87,7 @@ impl MappingBackend for Backend {
             Self::Alloc { populate, align } => {
                 Self::map_alloc(start, size, flags, pt, populate, align)
             }
+            Self::File { .. } => Self::map_file(start, size, flags),
         }
     }
 
@@ -67,6 +98,12 @@ impl MappingBackend for Backend {
                 align: _,
             } => Self::unmap_linear(start, size, pt, pa_va_offset),
             Self::Alloc { populate, align } => Self::unmap_alloc(start, size, pt, populate, align),
+            Self::File {
+                start: file_start,
+                offset,
+                ref dirty,
+                ..
+            } => Self::unmap_file(start, size, pt, file_start, offset, dirty),
         }
     }
 
@@ -77,6 +114,10 @@ impl MappingBackend for Backend {
         new_flags: Self::Flags,
         page_table: &mut Self::PageTable,
     ) -> bool {
+        let new_flags = match *self {
+            Self::File { shared, .. } => Self::file_page_flags(shared, new_flags),
+            _ => new_flags,
+        };
         page_table
             .protect_region(start, size, new_flags, true)
             .map(|tlb| tlb.ignore())
@@ -85,10 +126,30 @@ impl MappingBackend for Backend {
 }
 
 impl Backend {
+    /// Returns the backend of the pages in `[start, end)` moved to
+    /// `new_start`, or `None` if they cannot be moved.
+    ///
+    /// An allocation mapping is not populated any more, as its pages are
+    /// moved rather than allocated again, and the pages of a file mapping
+    /// take their dirty state with them.
+    pub(crate) fn remapped(
+        &self,
+        start: VirtAddr,
+        end: VirtAddr,
+        new_start: VirtAddr,
+    ) -> Option<Self> {
+        match *self {
+            Self::Linear { .. } => None,
+            Self::Alloc { populate: _, align } => Some(Self::new_alloc(false, align)),
+            Self::File { .. } => self.remap_file(start, end, new_start),
+        }
+    }
+
     pub(crate) fn handle_page_fault(
         &self,
         vaddr: VirtAddr,
         orig_flags: MappingFlags,
+        access_flags: MappingFlags,
         page_table: &mut PageTable,
     ) -> bool {
         match *self {
@@ -96,6 +157,21 @@ impl Backend {
             Self::Alloc { populate, align } => {
                 Self::handle_page_fault_alloc(vaddr, orig_flags, page_table, populate, align)
             }
+            Self::File {
+                start,
+                offset,
+                shared,
+                ref dirty,
+                ..
+            } => Self::handle_page_fault_file(
+                vaddr,
+                orig_flags,
+                access_flags,
+                page_table,
+                Self::file_offset(vaddr, start, offset),
+                shared,
+                dirty,
+            ),
         }
     }
 }
@@ -8,16 +8,23 @@
 //!
 //! - [`TcpSocket`]: A TCP socket that provides POSIX-like APIs.
 //! - [`UdpSocket`]: A UDP socket that provides POSIX-like APIs.
+//! - [`IcmpSocket`]: An ICMP socket for echo messages (`ping`) and raw ICMPv4
+//!   packets.
 //! - [`dns_query`]: Function for DNS query.
+//! - [`interfaces`], [`add_address`], [`add_route`], etc.: Functions to
+//!   configure the network interfaces and the routing table at runtime.
 //!
 //! # Cargo Features
 //!
 //! - `smoltcp`: Use [smoltcp] as the underlying network stack. This is enabled
 //!   by default.
+//! - `dhcp`: Configure the addresses, gateways and DNS servers of the NICs by
+//!   DHCPv4. The first NIC uses the values of `AX_IP` and `AX_GW` (if set)
+//!   without a lease.
 //!
 //! [smoltcp]: https://github.com/smoltcp-rs/smoltcp
 
-#![no_std]
+#![cfg_attr(not(test), no_std)]
 
 #[macro_use]
 extern crate log;
@@ -30,18 +37,35 @@ cfg_if::cfg_if! {
     }
 }
 
+pub use self::net_impl::IcmpSocket;
 pub use self::net_impl::TcpSocket;
 pub use self::net_impl::UdpSocket;
+pub use self::net_impl::{
+    RouteEntry, add_address, add_route, interface_addrs, interfaces, remove_address, remove_route,
+    routes,
+};
 pub use self::net_impl::{bench_receive, bench_transmit};
 pub use self::net_impl::{dns_query, poll_interfaces};
+pub use self::net_impl::{listen_backlog, set_listen_backlog};
 
+use alloc::vec::Vec;
 use axdriver::{AxDeviceContainer, prelude::*};
 
 /// Initializes the network subsystem by NIC devices.
+///
+/// Each NIC device becomes an interface named `eth0`, `eth1`, etc. The
+/// loopback interface is always created, so the network is usable even if
+/// there is no NIC device.
 pub fn init_network(mut net_devs: AxDeviceContainer<AxNetDevice>) {
     info!("Initialize network subsystem...");
 
-    let dev = net_devs.take_one().expect("No NIC device found!");
-    info!("  use NIC 0: {:?}", dev.device_name());
-    net_impl::init(dev);
+    let mut devs = Vec::new();
+    while let Some(dev) = net_devs.take_one() {
+        info!("  use NIC {}: {:?}", devs.len(), dev.device_name());
+        devs.push(dev);
+    }
+    if devs.is_empty() {
+        warn!("  no NIC device found, only the loopback interface is available");
+    }
+    net_impl::init(devs);
 }
@@ -1,10 +1,10 @@
-use core::net::{IpAddr, Ipv4Addr, SocketAddr};
-use smoltcp::wire::{IpAddress, IpEndpoint, Ipv4Address};
+use core::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use smoltcp::wire::{IpAddress, IpEndpoint, Ipv4Address, Ipv6Address};
 
 pub const fn from_core_ipaddr(ip: IpAddr) -> IpAddress {
     match ip {
         IpAddr::V4(ipv4) => IpAddress::Ipv4(Ipv4Address(ipv4.octets())),
-        _ => panic!("IPv6 not supported"),
+        IpAddr::V6(ipv6) => IpAddress::Ipv6(Ipv6Address(ipv6.octets())),
     }
 }
 
@@ -12,7 +12,10 @@ pub const fn into_core_ipaddr(ip: IpAddress) -> IpAddr {
     match ip {
         IpAddress::Ipv4(ipv4) => {
             IpAddr::V4(unsafe { core::mem::transmute::<[u8; 4], Ipv4Addr>(ipv4.0) })
-        } // _ => panic!("IPv6 not supported"),
+        }
+        IpAddress::Ipv6(ipv6) => {
+            IpAddr::V6(unsafe { core::mem::transmute::<[u8; 16], Ipv6Addr>(ipv6.0) })
+        }
     }
 }
 
@@ -27,8 +30,25 @@ pub const fn into_core_sockaddr(addr: IpEndpoint) -> SocketAddr {
     SocketAddr::new(into_core_ipaddr(addr.addr), addr.port)
 }
 
+/// Returns whether `ip` is `0.0.0.0` or `::`, which listens on both IPv4 and
+/// IPv6 addresses.
 pub fn is_unspecified(ip: IpAddress) -> bool {
-    ip.as_bytes() == [0, 0, 0, 0]
+    ip.is_unspecified()
+}
+
+pub fn is_loopback(ip: IpAddress) -> bool {
+    match ip {
+        IpAddress::Ipv4(ipv4) => ipv4.is_loopback(),
+        IpAddress::Ipv6(ipv6) => ipv6.is_loopback(),
+    }
+}
+
+/// Returns whether `ip` is an IPv6 link-local address.
+pub fn is_link_local(ip: IpAddress) -> bool {
+    match ip {
+        IpAddress::Ipv4(_) => false,
+        IpAddress::Ipv6(ipv6) => ipv6.is_link_local(),
+    }
 }
 
 pub const UNSPECIFIED_IP: IpAddress = IpAddress::v4(0, 0, 0, 0);
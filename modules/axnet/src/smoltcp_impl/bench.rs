@@ -1,12 +1,11 @@
-use super::{AxNetRxToken, AxNetTxToken, STANDARD_MTU};
-use super::{DeviceWrapper, InterfaceWrapper};
-use smoltcp::phy::{Device, RxToken, TxToken};
+use super::ethernet::EthernetDevice;
+use super::{InterfaceWrapper, STANDARD_MTU};
 
 const GB: usize = 1000 * MB;
 const MB: usize = 1000 * KB;
 const KB: usize = 1000;
 
-impl DeviceWrapper {
+impl EthernetDevice {
     pub fn bench_transmit_bandwidth(&mut self) {
         // 10 Gb
         const MAX_SEND_BYTES: usize = 10 * GB;
@@ -16,13 +15,13 @@ impl DeviceWrapper {
 
         // Send bytes
         while send_bytes < MAX_SEND_BYTES {
-            if let Some(tx_token) = self.transmit(InterfaceWrapper::current_time()) {
-                AxNetTxToken::consume(tx_token, STANDARD_MTU, |tx_buf| {
-                    tx_buf[0..12].fill(1);
-                    // ether type: IPv4
-                    tx_buf[12..14].copy_from_slice(&[0x08, 0x00]);
-                    tx_buf[14..STANDARD_MTU].fill(1);
-                });
+            let sent = self.transmit_with(STANDARD_MTU, |tx_buf| {
+                tx_buf[0..12].fill(1);
+                // ether type: IPv4
+                tx_buf[12..14].copy_from_slice(&[0x08, 0x00]);
+                tx_buf[14..STANDARD_MTU].fill(1);
+            });
+            if sent {
                 send_bytes += STANDARD_MTU;
             }
 
@@ -50,11 +49,9 @@ impl DeviceWrapper {
         let mut past_time = InterfaceWrapper::current_time();
         // Receive bytes
         while receive_bytes < MAX_RECEIVE_BYTES {
-            if let Some(rx_token) = self.receive(InterfaceWrapper::current_time()) {
-                AxNetRxToken::consume(rx_token.0, |rx_buf| {
-                    receive_bytes += rx_buf.len();
-                });
-            }
+            self.receive_with(|rx_buf| {
+                receive_bytes += rx_buf.len();
+            });
 
             let current_time = InterfaceWrapper::current_time();
             if (current_time - past_time).secs() == 1 {
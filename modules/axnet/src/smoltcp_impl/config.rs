@@ -0,0 +1,111 @@
+//! Runtime configuration of the network interfaces and the routing table.
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::net::IpAddr;
+
+use axerrno::{AxResult, ax_err};
+use smoltcp::wire::IpCidr;
+
+use super::IFACE;
+use super::addr::{from_core_ipaddr, into_core_ipaddr};
+use super::router::{Route, ipv6_network};
+
+/// An entry of the routing table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RouteEntry {
+    /// Network address of the destinations.
+    pub dest: IpAddr,
+    /// Prefix length of the destinations, which is 0 for the default route.
+    pub prefix_len: u8,
+    /// The gateway, or `None` if the destinations are on the link.
+    pub gateway: Option<IpAddr>,
+    /// Name of the interface to send the packets to.
+    pub iface: String,
+}
+
+fn to_cidr(addr: IpAddr, prefix_len: u8) -> AxResult<IpCidr> {
+    let max_prefix_len = match addr {
+        IpAddr::V4(_) => 32,
+        IpAddr::V6(_) => 128,
+    };
+    if prefix_len > max_prefix_len {
+        return ax_err!(InvalidInput, "invalid prefix length");
+    }
+    Ok(IpCidr::new(from_core_ipaddr(addr), prefix_len))
+}
+
+/// Returns the names of all network interfaces, with the loopback interface
+/// `lo` first.
+pub fn interfaces() -> Vec<String> {
+    let router = IFACE.router.lock();
+    router.devices().map(|dev| dev.name().to_string()).collect()
+}
+
+/// Returns the addresses of the interface `iface`, with their prefix lengths.
+pub fn interface_addrs(iface: &str) -> AxResult<Vec<(IpAddr, u8)>> {
+    let dev = IFACE.find_device(iface)?;
+    let router = IFACE.router.lock();
+    Ok(router
+        .addresses()
+        .iter()
+        .filter(|&&(d, _)| d == dev)
+        .map(|(_, cidr)| (into_core_ipaddr(cidr.address()), cidr.prefix_len()))
+        .collect())
+}
+
+/// Assigns an address to the interface `iface`, which also adds the route to
+/// its subnet through the interface.
+pub fn add_address(iface: &str, addr: IpAddr, prefix_len: u8) -> AxResult {
+    let dev = IFACE.find_device(iface)?;
+    IFACE.setup_ip_addr(dev, to_cidr(addr, prefix_len)?)
+}
+
+/// Removes an address from the interface `iface`, with the route added for
+/// it.
+pub fn remove_address(iface: &str, addr: IpAddr, prefix_len: u8) -> AxResult {
+    let dev = IFACE.find_device(iface)?;
+    IFACE.remove_ip_addr(dev, to_cidr(addr, prefix_len)?)
+}
+
+/// Returns all entries of the routing table.
+pub fn routes() -> Vec<RouteEntry> {
+    let router = IFACE.router.lock();
+    router
+        .routes()
+        .iter()
+        .map(|route| RouteEntry {
+            dest: into_core_ipaddr(route.cidr.address()),
+            prefix_len: route.cidr.prefix_len(),
+            gateway: route.via.map(into_core_ipaddr),
+            iface: router.device(route.dev).name().to_string(),
+        })
+        .collect()
+}
+
+fn to_route(entry: &RouteEntry) -> AxResult<Route> {
+    let cidr = to_cidr(entry.dest, entry.prefix_len)?;
+    let is_network = match cidr {
+        IpCidr::Ipv4(cidr) => cidr == cidr.network(),
+        IpCidr::Ipv6(cidr) => cidr == ipv6_network(cidr),
+    };
+    if !is_network {
+        return ax_err!(InvalidInput, "destination is not a network address");
+    }
+    Ok(Route {
+        cidr,
+        via: entry.gateway.map(from_core_ipaddr),
+        dev: IFACE.find_device(&entry.iface)?,
+    })
+}
+
+/// Adds an entry to the routing table. Packets are routed by the entry with
+/// the longest prefix that contains the destination.
+pub fn add_route(entry: &RouteEntry) -> AxResult {
+    IFACE.add_route(to_route(entry)?)
+}
+
+/// Removes an entry from the routing table.
+pub fn remove_route(entry: &RouteEntry) -> AxResult {
+    IFACE.remove_route(to_route(entry)?)
+}
@@ -0,0 +1,197 @@
+//! DHCPv4 clients, which configure the addresses of the Ethernet devices.
+//!
+//! smoltcp's DHCPv4 socket only works in an interface of the Ethernet medium,
+//! so each client polls an Ethernet interface of its own on the NIC. The
+//! [`EthernetDevice`] passes the DHCP replies and copies of ARP packets to
+//! it, and the interface gets the leased address as well, to renew the lease
+//! from the server by unicast.
+//!
+//! The static configuration is only a fallback: it is applied if no lease is
+//! got within [`DHCP_TIMEOUT`], or when the lease is lost, and replaced by the
+//! next lease.
+
+use alloc::{vec, vec::Vec};
+
+use smoltcp::iface::{Config, Interface, SocketHandle, SocketSet};
+use smoltcp::phy::{Device, DeviceCapabilities, Medium, RxToken, TxToken};
+use smoltcp::socket::dhcpv4::{self, Event};
+use smoltcp::time::{Duration, Instant};
+use smoltcp::wire::{EthernetFrame, HardwareAddress, IpAddress};
+
+use super::ethernet::EthernetDevice;
+use super::{IpConfig, RANDOM_SEED, STANDARD_MTU};
+
+/// How long a client waits for the first lease before using the fallback
+/// configuration.
+const DHCP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A DHCPv4 client of an Ethernet device.
+pub(crate) struct DhcpClient {
+    /// Index of the device in the router.
+    dev: usize,
+    iface: Interface,
+    sockets: SocketSet<'static>,
+    handle: SocketHandle,
+    /// The configuration used without a lease.
+    fallback: Option<IpConfig>,
+    /// Whether the fallback configuration is in use.
+    use_fallback: bool,
+    /// When the fallback configuration is used if no lease is got by then.
+    deadline: Instant,
+    lease: Option<IpConfig>,
+}
+
+impl DhcpClient {
+    /// Creates a client of the device `dev`, which is `eth` in the router,
+    /// started at `now`.
+    pub fn new(
+        dev: usize,
+        eth: &mut EthernetDevice,
+        fallback: Option<IpConfig>,
+        now: Instant,
+    ) -> Self {
+        eth.start_dhcp();
+        let mut config = Config::new(HardwareAddress::Ethernet(eth.ethernet_address()));
+        config.random_seed = RANDOM_SEED;
+        let iface = Interface::new(config, &mut DhcpDevice(eth), Instant::ZERO);
+        let mut sockets = SocketSet::new(vec![]);
+        let handle = sockets.add(dhcpv4::Socket::new());
+        Self {
+            dev,
+            iface,
+            sockets,
+            handle,
+            fallback,
+            use_fallback: false,
+            deadline: now + DHCP_TIMEOUT,
+            lease: None,
+        }
+    }
+
+    pub fn dev(&self) -> usize {
+        self.dev
+    }
+
+    /// Returns the configuration in use, from the lease if any, or the
+    /// fallback one after the first lease times out or the lease is lost.
+    pub fn config(&self) -> Option<&IpConfig> {
+        let fallback = self.fallback.as_ref().filter(|_| self.use_fallback);
+        self.lease.as_ref().or(fallback)
+    }
+
+    /// Polls the client on its device `eth`. Returns whether the
+    /// configuration is changed.
+    pub fn poll(&mut self, eth: &mut EthernetDevice, now: Instant) -> bool {
+        let timed_out = self.lease.is_none()
+            && !self.use_fallback
+            && self.fallback.is_some()
+            && now >= self.deadline;
+        self.iface
+            .poll(now, &mut DhcpDevice(eth), &mut self.sockets);
+        let socket = self.sockets.get_mut::<dhcpv4::Socket>(self.handle);
+        let lease = match socket.poll() {
+            None if timed_out => {
+                info!(
+                    "{}: no DHCP lease, using the static configuration",
+                    eth.name()
+                );
+                self.use_fallback = true;
+                return true;
+            }
+            None => return false,
+            Some(Event::Configured(config)) => {
+                info!(
+                    "{}: DHCP lease {} from {}, gateway {:?}, DNS servers {:?}",
+                    eth.name(),
+                    config.address,
+                    config.server.address,
+                    config.router,
+                    config.dns_servers
+                );
+                Some(IpConfig {
+                    address: config.address.into(),
+                    gateway: config.router.map(Into::into),
+                    dns_servers: config.dns_servers.iter().map(|&ip| ip.into()).collect(),
+                })
+            }
+            Some(Event::Deconfigured) => {
+                info!("{}: DHCP lease lost", eth.name());
+                None
+            }
+        };
+
+        self.iface.update_ip_addrs(|ip_addrs| {
+            ip_addrs.clear();
+            if let Some(lease) = &lease {
+                ip_addrs.push(lease.address).unwrap();
+            }
+        });
+        let routes = self.iface.routes_mut();
+        match lease.as_ref().and_then(|lease| lease.gateway) {
+            Some(IpAddress::Ipv4(gateway)) => {
+                routes.add_default_ipv4_route(gateway).unwrap();
+            }
+            _ => {
+                routes.remove_default_ipv4_route();
+            }
+        }
+        self.use_fallback = lease.is_none();
+        self.lease = lease;
+        true
+    }
+}
+
+/// The device of the DHCP interface, which is a view of the Ethernet device.
+struct DhcpDevice<'a>(&'a mut EthernetDevice);
+
+impl Device for DhcpDevice<'_> {
+    type RxToken<'a>
+        = FrameRxToken
+    where
+        Self: 'a;
+    type TxToken<'a>
+        = FrameTxToken<'a>
+    where
+        Self: 'a;
+
+    fn receive(&mut self, _timestamp: Instant) -> Option<(Self::RxToken<'_>, Self::TxToken<'_>)> {
+        let frame = self.0.take_dhcp_frame()?;
+        Some((FrameRxToken(frame), FrameTxToken(self.0)))
+    }
+
+    fn transmit(&mut self, _timestamp: Instant) -> Option<Self::TxToken<'_>> {
+        Some(FrameTxToken(self.0))
+    }
+
+    fn capabilities(&self) -> DeviceCapabilities {
+        let mut caps = DeviceCapabilities::default();
+        caps.max_transmission_unit = EthernetFrame::<&[u8]>::buffer_len(STANDARD_MTU);
+        caps.max_burst_size = None;
+        caps.medium = Medium::Ethernet;
+        caps
+    }
+}
+
+struct FrameRxToken(Vec<u8>);
+struct FrameTxToken<'a>(&'a mut EthernetDevice);
+
+impl RxToken for FrameRxToken {
+    fn consume<R, F>(mut self, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        f(&mut self.0)
+    }
+}
+
+impl TxToken for FrameTxToken<'_> {
+    fn consume<R, F>(self, len: usize, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        let mut frame = vec![0; len];
+        let ret = f(&mut frame);
+        self.0.transmit_with(len, |buf| buf.copy_from_slice(&frame));
+        ret
+    }
+}
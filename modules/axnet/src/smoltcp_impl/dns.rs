@@ -7,7 +7,7 @@ use smoltcp::socket::dns::{self, GetQueryResultError, StartQueryError};
 use smoltcp::wire::DnsQueryType;
 
 use super::addr::into_core_ipaddr;
-use super::{ETH0, SOCKET_SET, SocketSetWrapper};
+use super::{IFACE, SOCKET_SET, SocketSetWrapper};
 
 /// A DNS socket.
 struct DnsSocket {
@@ -35,7 +35,7 @@ impl DnsSocket {
     pub fn query(&self, name: &str, query_type: DnsQueryType) -> AxResult<Vec<IpAddr>> {
         // let local_addr = self.local_addr.unwrap_or_else(f);
         let handle = self.handle.ok_or_else(|| ax_err_type!(InvalidInput))?;
-        let iface = &ETH0.iface;
+        let iface = &IFACE.iface;
         let query_handle = SOCKET_SET
             .with_socket_mut::<dns::Socket, _, _>(handle, |socket| {
                 socket.start_query(iface.lock().context(), name, query_type)
@@ -84,7 +84,19 @@ impl Drop for DnsSocket {
 }
 
 /// Public function for DNS query.
+///
+/// It queries both the IPv4 (A) and IPv6 (AAAA) addresses, and returns the
+/// IPv4 addresses first. It fails only if both queries fail.
 pub fn dns_query(name: &str) -> AxResult<alloc::vec::Vec<IpAddr>> {
     let socket = DnsSocket::new();
-    socket.query(name, DnsQueryType::A)
+    let ipv4_addrs = socket.query(name, DnsQueryType::A);
+    let ipv6_addrs = socket.query(name, DnsQueryType::Aaaa);
+    match (ipv4_addrs, ipv6_addrs) {
+        (Ok(mut addrs), Ok(ipv6_addrs)) => {
+            addrs.extend(ipv6_addrs);
+            Ok(addrs)
+        }
+        (Ok(addrs), Err(_)) | (Err(_), Ok(addrs)) => Ok(addrs),
+        (Err(e), Err(_)) => Err(e),
+    }
 }
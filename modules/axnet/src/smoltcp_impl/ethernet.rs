@@ -0,0 +1,281 @@
+//! Ethernet devices, which resolve the link addresses of neighbors by their
+//! [`Neighbors`].
+
+use alloc::string::String;
+#[cfg(feature = "dhcp")]
+use alloc::{collections::VecDeque, vec::Vec};
+
+use axdriver::prelude::*;
+use axdriver_net::{DevError, NetBufPtr};
+use smoltcp::time::Instant;
+use smoltcp::wire::{
+    EthernetAddress, EthernetFrame, EthernetProtocol, IpAddress, IpCidr, Ipv6Address, Ipv6Cidr,
+};
+
+use super::neighbor::{Link, Neighbors};
+
+/// Maximum number of frames waiting for the DHCP client.
+#[cfg(feature = "dhcp")]
+const MAX_DHCP_FRAMES: usize = 16;
+
+/// A received frame of an IP packet, which is in the receive buffer of the
+/// NIC.
+pub(crate) struct RxFrame(NetBufPtr);
+
+impl RxFrame {
+    /// Returns the IP packet in the frame.
+    pub fn payload(&self) -> &[u8] {
+        &self.0.packet()[EthernetFrame::<&[u8]>::header_len()..]
+    }
+
+    pub fn payload_mut(&mut self) -> &mut [u8] {
+        &mut self.0.packet_mut()[EthernetFrame::<&[u8]>::header_len()..]
+    }
+}
+
+/// An Ethernet NIC.
+pub(crate) struct EthernetDevice {
+    nic: Nic,
+    neighbors: Neighbors,
+    /// Frames for the DHCP client of the device, if it is started.
+    #[cfg(feature = "dhcp")]
+    dhcp_frames: Option<VecDeque<Vec<u8>>>,
+}
+
+impl EthernetDevice {
+    pub fn new(name: String, inner: AxNetDevice) -> Self {
+        let ether_addr = EthernetAddress(inner.mac_address().0);
+        Self {
+            nic: Nic {
+                name,
+                inner,
+                ether_addr,
+            },
+            neighbors: Neighbors::new(ether_addr),
+            #[cfg(feature = "dhcp")]
+            dhcp_frames: None,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.nic.name
+    }
+
+    pub fn ethernet_address(&self) -> EthernetAddress {
+        self.nic.ether_addr
+    }
+
+    pub fn add_address(&mut self, cidr: IpCidr) {
+        self.neighbors.add_address(cidr);
+    }
+
+    pub fn remove_address(&mut self, cidr: IpCidr) {
+        self.neighbors.remove_address(cidr);
+    }
+
+    /// Returns whether a frame can be transmitted.
+    pub fn can_transmit(&mut self) -> bool {
+        self.nic.can_transmit()
+    }
+
+    /// Receives an IP packet, which stays in the receive buffer of the NIC
+    /// until it is recycled by [`EthernetDevice::recycle`]. ARP and NDP
+    /// packets are handled on the way.
+    pub fn receive(&mut self, now: Instant) -> Option<RxFrame> {
+        loop {
+            let rx_buf = match self.nic.inner.receive() {
+                Ok(buf) => buf,
+                Err(DevError::Again) => return None,
+                Err(err) => {
+                    warn!("{}: receive failed: {:?}", self.nic.name, err);
+                    return None;
+                }
+            };
+            trace!(
+                "{}: RECV {} bytes: {:02X?}",
+                self.nic.name,
+                rx_buf.packet_len(),
+                rx_buf.packet()
+            );
+            let frame = EthernetFrame::new_checked(rx_buf.packet()).ok();
+            let frame = frame.filter(|frame| {
+                let dst = frame.dst_addr();
+                dst == self.nic.ether_addr || dst.is_broadcast() || dst.is_multicast()
+            });
+            let ethertype = match frame {
+                #[cfg(feature = "dhcp")]
+                Some(frame) if self.deliver_to_dhcp(&frame) => EthernetProtocol::Unknown(0),
+                Some(frame) => frame.ethertype(),
+                None => EthernetProtocol::Unknown(0),
+            };
+
+            let payload = || &rx_buf.packet()[EthernetFrame::<&[u8]>::header_len()..];
+            let is_ip = match ethertype {
+                EthernetProtocol::Ipv4 => true,
+                EthernetProtocol::Ipv6 => {
+                    !self.neighbors.process_ndisc(&mut self.nic, payload(), now)
+                }
+                EthernetProtocol::Arp => {
+                    self.neighbors.process_arp(&mut self.nic, payload(), now);
+                    false
+                }
+                _ => false,
+            };
+            if is_ip {
+                return Some(RxFrame(rx_buf));
+            }
+            self.nic.recycle_rx_buffer(rx_buf);
+        }
+    }
+
+    /// Gives the buffer of a frame returned by [`EthernetDevice::receive`]
+    /// back to the NIC.
+    pub fn recycle(&mut self, frame: RxFrame) {
+        self.nic.recycle_rx_buffer(frame.0);
+    }
+
+    /// Starts passing frames to the DHCP client of the device.
+    #[cfg(feature = "dhcp")]
+    pub fn start_dhcp(&mut self) {
+        self.dhcp_frames = Some(VecDeque::new());
+    }
+
+    /// Takes a frame for the DHCP client.
+    #[cfg(feature = "dhcp")]
+    pub fn take_dhcp_frame(&mut self) -> Option<Vec<u8>> {
+        self.dhcp_frames.as_mut()?.pop_front()
+    }
+
+    /// Passes DHCP replies, and copies of ARP packets, to the DHCP client if
+    /// it is started. Returns whether the frame is taken by the client.
+    #[cfg(feature = "dhcp")]
+    fn deliver_to_dhcp(&mut self, frame: &EthernetFrame<&[u8]>) -> bool {
+        use smoltcp::wire::{
+            DHCP_CLIENT_PORT, DHCP_SERVER_PORT, IpProtocol, Ipv4Packet, UdpPacket,
+        };
+
+        let Some(frames) = self.dhcp_frames.as_mut() else {
+            return false;
+        };
+        let is_dhcp_reply = || {
+            let packet = Ipv4Packet::new_checked(frame.payload()).ok()?;
+            if packet.next_header() != IpProtocol::Udp {
+                return None;
+            }
+            let udp = UdpPacket::new_checked(packet.payload()).ok()?;
+            Some(udp.src_port() == DHCP_SERVER_PORT && udp.dst_port() == DHCP_CLIENT_PORT)
+        };
+        let taken = match frame.ethertype() {
+            EthernetProtocol::Ipv4 if is_dhcp_reply() == Some(true) => true,
+            EthernetProtocol::Arp => false,
+            _ => return false,
+        };
+        if frames.len() >= MAX_DHCP_FRAMES {
+            frames.pop_front();
+        }
+        frames.push_back(frame.as_ref().to_vec());
+        taken
+    }
+
+    /// Sends an IP packet to `next_hop` on the link, which waits for the
+    /// link address of `next_hop` to be resolved if unknown.
+    pub fn send(&mut self, next_hop: IpAddress, packet: &[u8], now: Instant) {
+        self.neighbors.send(&mut self.nic, next_hop, packet, now);
+    }
+
+    /// Transmits a frame of `len` bytes filled by `f`. Returns whether it is
+    /// transmitted.
+    pub(crate) fn transmit_with(&mut self, len: usize, f: impl FnOnce(&mut [u8])) -> bool {
+        self.nic.transmit_with(len, f)
+    }
+
+    /// Receives a frame and passes it to `f`. Returns whether a frame is
+    /// received.
+    pub(crate) fn receive_with(&mut self, f: impl FnOnce(&[u8])) -> bool {
+        match self.nic.inner.receive() {
+            Ok(rx_buf) => {
+                f(rx_buf.packet());
+                self.nic.recycle_rx_buffer(rx_buf);
+                true
+            }
+            Err(_) => false,
+        }
+    }
+}
+
+/// The NIC of an Ethernet device, which is its link.
+struct Nic {
+    name: String,
+    inner: AxNetDevice,
+    ether_addr: EthernetAddress,
+}
+
+impl Nic {
+    fn can_transmit(&mut self) -> bool {
+        if let Err(e) = self.inner.recycle_tx_buffers() {
+            warn!("{}: recycle_tx_buffers failed: {:?}", self.name, e);
+            return false;
+        }
+        self.inner.can_transmit()
+    }
+
+    fn transmit_with(&mut self, len: usize, f: impl FnOnce(&mut [u8])) -> bool {
+        if !self.can_transmit() {
+            return false;
+        }
+        let mut tx_buf = match self.inner.alloc_tx_buffer(len) {
+            Ok(buf) => buf,
+            Err(e) => {
+                warn!("{}: alloc_tx_buffer failed: {:?}", self.name, e);
+                return false;
+            }
+        };
+        f(tx_buf.packet_mut());
+        trace!(
+            "{}: SEND {} bytes: {:02X?}",
+            self.name,
+            len,
+            tx_buf.packet()
+        );
+        if let Err(e) = self.inner.transmit(tx_buf) {
+            warn!("{}: transmit failed: {:?}", self.name, e);
+            return false;
+        }
+        true
+    }
+
+    /// Gives a receive buffer back to the NIC. The buffer is dropped if the
+    /// NIC fails to take it.
+    fn recycle_rx_buffer(&mut self, rx_buf: NetBufPtr) {
+        if let Err(e) = self.inner.recycle_rx_buffer(rx_buf) {
+            warn!("{}: recycle_rx_buffer failed: {:?}", self.name, e);
+        }
+    }
+}
+
+impl Link for Nic {
+    fn send_frame(&mut self, dst: EthernetAddress, ethertype: EthernetProtocol, payload: &[u8]) {
+        let len = EthernetFrame::<&[u8]>::buffer_len(payload.len());
+        let src = self.ether_addr;
+        let sent = self.transmit_with(len, |buf| {
+            let mut frame = EthernetFrame::new_unchecked(buf);
+            frame.set_src_addr(src);
+            frame.set_dst_addr(dst);
+            frame.set_ethertype(ethertype);
+            frame.payload_mut().copy_from_slice(payload);
+        });
+        if !sent {
+            debug!("{}: dropping a frame to {}", self.name, dst);
+        }
+    }
+}
+
+/// Returns the link-local address of the device with the link address
+/// `ether_addr`, in the modified EUI-64 format (RFC 4291).
+pub(crate) fn link_local_address(ether_addr: EthernetAddress) -> Ipv6Cidr {
+    let m = ether_addr.0;
+    let mut addr = [0; 16];
+    addr[..2].copy_from_slice(&[0xfe, 0x80]);
+    addr[8..].copy_from_slice(&[m[0] ^ 0x02, m[1], m[2], 0xff, 0xfe, m[3], m[4], m[5]]);
+    Ipv6Cidr::new(Ipv6Address(addr), 64)
+}
@@ -0,0 +1,383 @@
+use alloc::{vec, vec::Vec};
+use core::net::IpAddr;
+use core::sync::atomic::{AtomicBool, AtomicU16, Ordering};
+use core::time::Duration;
+
+use axerrno::{AxError, AxResult, ax_err, ax_err_type};
+use axhal::time::{TimeValue, monotonic_time};
+use axio::PollState;
+use spin::RwLock;
+
+use smoltcp::iface::SocketHandle;
+use smoltcp::phy::ChecksumCapabilities;
+use smoltcp::socket::{icmp, raw};
+use smoltcp::wire::{Icmpv4Packet, IpAddress, IpProtocol, Ipv4Packet, Ipv4Repr};
+
+use super::addr::{from_core_ipaddr, into_core_ipaddr};
+use super::{IFACE, SOCKET_SET, SocketSetWrapper};
+
+const ICMPV4_ECHO_REQUEST: u8 = 8;
+const ICMPV4_ECHO_REPLY: u8 = 0;
+const ICMPV6_ECHO_REQUEST: u8 = 128;
+const ICMPV6_ECHO_REPLY: u8 = 129;
+
+/// Length of the header of ICMP echo messages.
+const ECHO_HEADER_LEN: usize = 8;
+/// Maximum length of the IPv4 header.
+const MAX_IPV4_HEADER_LEN: usize = 60;
+const IPV4_HOP_LIMIT: u8 = 64;
+
+enum Kind {
+    /// Sends and receives ICMP messages with the identifier of the socket.
+    Datagram { ident: u16 },
+    /// Sends ICMPv4 messages, and receives all ICMPv4 packets with their
+    /// IPv4 headers.
+    Raw,
+}
+
+/// An ICMP socket that provides POSIX-like APIs.
+///
+/// - [`new`] creates a socket like the ping socket (`SOCK_DGRAM` and
+///   `IPPROTO_ICMP`) of Linux, which sends echo requests of both IPv4 and
+///   IPv6, and receives the echo replies to it.
+/// - [`new_raw`] creates a socket like the raw socket (`SOCK_RAW` and
+///   `IPPROTO_ICMP`) of Linux, which sends ICMPv4 messages, and receives all
+///   ICMPv4 packets with their IPv4 headers.
+///
+/// [`send_echo_request`] and [`recv_echo_reply`] are for `ping`-like usage on
+/// both kinds of sockets.
+///
+/// [`new`]: IcmpSocket::new
+/// [`new_raw`]: IcmpSocket::new_raw
+/// [`send_echo_request`]: IcmpSocket::send_echo_request
+/// [`recv_echo_reply`]: IcmpSocket::recv_echo_reply
+pub struct IcmpSocket {
+    handle: SocketHandle,
+    kind: Kind,
+    nonblock: AtomicBool,
+    read_timeout: RwLock<Option<Duration>>,
+}
+
+impl IcmpSocket {
+    /// Creates a new datagram ICMP socket, with an identifier of echo
+    /// messages generated automatically.
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        let ident = get_ephemeral_ident();
+        let mut socket = SocketSetWrapper::new_icmp_socket();
+        socket.bind(icmp::Endpoint::Ident(ident)).unwrap();
+        let handle = SOCKET_SET.add(socket);
+        debug!("ICMP socket {}: bound on identifier {}", handle, ident);
+        Self {
+            handle,
+            kind: Kind::Datagram { ident },
+            nonblock: AtomicBool::new(false),
+            read_timeout: RwLock::new(None),
+        }
+    }
+
+    /// Creates a new raw ICMPv4 socket.
+    pub fn new_raw() -> Self {
+        let socket = SocketSetWrapper::new_raw_socket(IpProtocol::Icmp);
+        let handle = SOCKET_SET.add(socket);
+        Self {
+            handle,
+            kind: Kind::Raw,
+            nonblock: AtomicBool::new(false),
+            read_timeout: RwLock::new(None),
+        }
+    }
+
+    /// Returns the identifier of echo messages of a datagram socket, or
+    /// `None` for a raw socket.
+    pub fn ident(&self) -> Option<u16> {
+        match self.kind {
+            Kind::Datagram { ident } => Some(ident),
+            Kind::Raw => None,
+        }
+    }
+
+    /// Returns whether this socket is in nonblocking mode.
+    #[inline]
+    pub fn is_nonblocking(&self) -> bool {
+        self.nonblock.load(Ordering::Acquire)
+    }
+
+    /// Moves this ICMP socket into or out of nonblocking mode.
+    ///
+    /// This will result in `send_to`, `recv_from`, `send_echo_request` and
+    /// `recv_echo_reply` operations becoming nonblocking, i.e., immediately
+    /// returning from their calls. If the IO operation is successful, `Ok` is
+    /// returned and no further action is required. If the IO operation could
+    /// not be completed and needs to be retried, an error with kind
+    /// [`Err(WouldBlock)`](AxError::WouldBlock) is returned.
+    #[inline]
+    pub fn set_nonblocking(&self, nonblocking: bool) {
+        self.nonblock.store(nonblocking, Ordering::Release);
+    }
+
+    /// Sets the timeout of `recv_from` and `recv_echo_reply` operations, after
+    /// which an error with kind [`Err(TimedOut)`](AxError::TimedOut) is
+    /// returned. `None` means blocking indefinitely, which is the default.
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) {
+        *self.read_timeout.write() = timeout;
+    }
+
+    /// Sends an ICMP message (from the ICMP header) to the given address. On
+    /// success, returns the number of bytes written.
+    ///
+    /// A datagram socket only sends echo requests, whose identifier is
+    /// replaced by the one of the socket. The checksum is always filled in.
+    pub fn send_to(&self, buf: &[u8], addr: IpAddr) -> AxResult<usize> {
+        if addr.is_unspecified() {
+            return ax_err!(InvalidInput, "socket send_to() failed: invalid address");
+        }
+        let dst = from_core_ipaddr(addr);
+        let mut message = buf.to_vec();
+        match self.kind {
+            Kind::Datagram { ident } => {
+                let request = match dst {
+                    IpAddress::Ipv4(_) => ICMPV4_ECHO_REQUEST,
+                    IpAddress::Ipv6(_) => ICMPV6_ECHO_REQUEST,
+                };
+                if message.len() < ECHO_HEADER_LEN || message[0] != request {
+                    return ax_err!(InvalidInput, "socket send_to() failed: not an echo request");
+                }
+                message[4..6].copy_from_slice(&ident.to_be_bytes());
+                self.send_impl(|socket: &mut icmp::Socket| socket.send_slice(&message, dst))?;
+            }
+            Kind::Raw => {
+                let packet = ipv4_packet(&mut message, dst)?;
+                self.send_impl(|socket: &mut raw::Socket| socket.send_slice(&packet))?;
+            }
+        }
+        Ok(buf.len())
+    }
+
+    /// Receives an ICMP message on the socket. On success, returns the number
+    /// of bytes read and the origin.
+    ///
+    /// The message starts from the ICMP header on a datagram socket, or the
+    /// IPv4 header on a raw socket.
+    pub fn recv_from(&self, buf: &mut [u8]) -> AxResult<(usize, IpAddr)> {
+        self.recv_from_until(buf, self.read_deadline())
+    }
+
+    /// Sends an echo request with `data` to the given address, like `ping`.
+    pub fn send_echo_request(&self, addr: IpAddr, seq_no: u16, data: &[u8]) -> AxResult {
+        let (request, ident) = match (addr, &self.kind) {
+            (IpAddr::V4(_), _) => (ICMPV4_ECHO_REQUEST, self.ident().unwrap_or(0)),
+            (IpAddr::V6(_), Kind::Datagram { ident }) => (ICMPV6_ECHO_REQUEST, *ident),
+            (IpAddr::V6(_), Kind::Raw) => {
+                return ax_err!(InvalidInput, "socket send_echo_request() failed: not IPv4");
+            }
+        };
+        let mut message = vec![request, 0, 0, 0];
+        message.extend_from_slice(&ident.to_be_bytes());
+        message.extend_from_slice(&seq_no.to_be_bytes());
+        message.extend_from_slice(data);
+        self.send_to(&message, addr)?;
+        Ok(())
+    }
+
+    /// Receives an echo reply, and copies its data into `buf`. On success,
+    /// returns the length of the data, the origin, and the sequence number.
+    ///
+    /// Other messages received on the socket are discarded.
+    pub fn recv_echo_reply(&self, buf: &mut [u8]) -> AxResult<(usize, IpAddr, u16)> {
+        let deadline = self.read_deadline();
+        let mut packet = vec![0; MAX_IPV4_HEADER_LEN + ECHO_HEADER_LEN + buf.len()];
+        loop {
+            let (len, addr) = self.recv_from_until(&mut packet, deadline)?;
+            let message = match self.kind {
+                Kind::Datagram { .. } => &packet[..len],
+                Kind::Raw => match Ipv4Packet::new_checked(&packet[..len]) {
+                    Ok(packet) => packet.payload(),
+                    Err(_) => continue,
+                },
+            };
+            let reply = match addr {
+                IpAddr::V4(_) => ICMPV4_ECHO_REPLY,
+                IpAddr::V6(_) => ICMPV6_ECHO_REPLY,
+            };
+            if message.len() < ECHO_HEADER_LEN || message[0] != reply {
+                continue;
+            }
+            let ident = u16::from_be_bytes([message[4], message[5]]);
+            if self.ident().is_some_and(|i| i != ident) {
+                continue;
+            }
+            let seq_no = u16::from_be_bytes([message[6], message[7]]);
+            let data = &message[ECHO_HEADER_LEN..];
+            let len = data.len().min(buf.len());
+            buf[..len].copy_from_slice(&data[..len]);
+            return Ok((len, addr, seq_no));
+        }
+    }
+
+    /// Whether the socket is readable or writable.
+    pub fn poll(&self) -> AxResult<PollState> {
+        let (readable, writable) = match self.kind {
+            Kind::Datagram { .. } => SOCKET_SET
+                .with_socket::<icmp::Socket, _, _>(self.handle, |socket| {
+                    (socket.can_recv(), socket.can_send())
+                }),
+            Kind::Raw => SOCKET_SET.with_socket::<raw::Socket, _, _>(self.handle, |socket| {
+                (socket.can_recv(), socket.can_send())
+            }),
+        };
+        Ok(PollState { readable, writable })
+    }
+}
+
+/// Private methods
+impl IcmpSocket {
+    fn read_deadline(&self) -> Option<TimeValue> {
+        self.read_timeout.read().map(|t| monotonic_time() + t)
+    }
+
+    fn recv_from_until(
+        &self,
+        buf: &mut [u8],
+        deadline: Option<TimeValue>,
+    ) -> AxResult<(usize, IpAddr)> {
+        match self.kind {
+            Kind::Datagram { .. } => self.recv_impl(deadline, |socket: &mut icmp::Socket| {
+                let (len, addr) = socket
+                    .recv_slice(buf)
+                    .map_err(|_| ax_err_type!(BadState, "socket recv_from() failed"))?;
+                Ok((len, into_core_ipaddr(addr)))
+            }),
+            Kind::Raw => self.recv_impl(deadline, |socket: &mut raw::Socket| {
+                let len = socket
+                    .recv_slice(buf)
+                    .map_err(|_| ax_err_type!(BadState, "socket recv_from() failed"))?;
+                let packet = Ipv4Packet::new_checked(&buf[..len])
+                    .map_err(|_| ax_err_type!(BadState, "socket recv_from() failed"))?;
+                Ok((len, into_core_ipaddr(packet.src_addr().into())))
+            }),
+        }
+    }
+
+    fn send_impl<S, E>(&self, mut op: impl FnMut(&mut S) -> Result<(), E>) -> AxResult
+    where
+        S: smoltcp::socket::AnySocket<'static> + ReadySocket,
+    {
+        self.block_on(|| {
+            SOCKET_SET.with_socket_mut::<S, _, _>(self.handle, |socket| {
+                if socket.can_send() {
+                    op(socket).map_err(|_| ax_err_type!(ConnectionRefused, "socket send() failed"))
+                } else {
+                    // tx buffer is full
+                    Err(AxError::WouldBlock)
+                }
+            })
+        })
+    }
+
+    fn recv_impl<S, T>(
+        &self,
+        deadline: Option<TimeValue>,
+        mut op: impl FnMut(&mut S) -> AxResult<T>,
+    ) -> AxResult<T>
+    where
+        S: smoltcp::socket::AnySocket<'static> + ReadySocket,
+    {
+        self.block_on(|| {
+            SOCKET_SET.with_socket_mut::<S, _, _>(self.handle, |socket| {
+                if socket.can_recv() {
+                    // data available
+                    op(socket)
+                } else if deadline.is_some_and(|d| monotonic_time() >= d) {
+                    Err(AxError::TimedOut)
+                } else {
+                    // no more data
+                    Err(AxError::WouldBlock)
+                }
+            })
+        })
+    }
+
+    fn block_on<F, T>(&self, mut f: F) -> AxResult<T>
+    where
+        F: FnMut() -> AxResult<T>,
+    {
+        if self.is_nonblocking() {
+            f()
+        } else {
+            loop {
+                SOCKET_SET.poll_interfaces();
+                match f() {
+                    Ok(t) => return Ok(t),
+                    Err(AxError::WouldBlock) => axtask::yield_now(),
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+    }
+}
+
+impl Drop for IcmpSocket {
+    fn drop(&mut self) {
+        SOCKET_SET.remove(self.handle);
+    }
+}
+
+/// Readiness of ICMP and raw sockets of smoltcp.
+trait ReadySocket {
+    fn can_send(&self) -> bool;
+    fn can_recv(&self) -> bool;
+}
+
+impl ReadySocket for icmp::Socket<'_> {
+    fn can_send(&self) -> bool {
+        self.can_send()
+    }
+
+    fn can_recv(&self) -> bool {
+        self.can_recv()
+    }
+}
+
+impl ReadySocket for raw::Socket<'_> {
+    fn can_send(&self) -> bool {
+        self.can_send()
+    }
+
+    fn can_recv(&self) -> bool {
+        self.can_recv()
+    }
+}
+
+/// Builds an IPv4 packet of the ICMP message to `dst`, with the checksum of
+/// the message filled in.
+fn ipv4_packet(message: &mut [u8], dst: IpAddress) -> AxResult<Vec<u8>> {
+    let IpAddress::Ipv4(dst_addr) = dst else {
+        return ax_err!(InvalidInput, "socket send_to() failed: not IPv4");
+    };
+    if message.len() < 4 {
+        return ax_err!(InvalidInput, "socket send_to() failed: message too short");
+    }
+    let Some(IpAddress::Ipv4(src_addr)) = IFACE.route_source_address(dst) else {
+        return ax_err!(ConnectionRefused, "socket send_to() failed: no route");
+    };
+    Icmpv4Packet::new_unchecked(&mut *message).fill_checksum();
+
+    let repr = Ipv4Repr {
+        src_addr,
+        dst_addr,
+        next_header: IpProtocol::Icmp,
+        payload_len: message.len(),
+        hop_limit: IPV4_HOP_LIMIT,
+    };
+    let mut buf = vec![0; repr.buffer_len() + message.len()];
+    let mut packet = Ipv4Packet::new_unchecked(&mut buf[..]);
+    repr.emit(&mut packet, &ChecksumCapabilities::default());
+    packet.payload_mut().copy_from_slice(message);
+    Ok(buf)
+}
+
+fn get_ephemeral_ident() -> u16 {
+    static CURR: AtomicU16 = AtomicU16::new(0x4158);
+    CURR.fetch_add(1, Ordering::Relaxed)
+}
@@ -7,25 +7,31 @@ use smoltcp::iface::{SocketHandle, SocketSet};
 use smoltcp::socket::tcp::{self, State};
 use smoltcp::wire::{IpAddress, IpEndpoint, IpListenEndpoint};
 
-use super::{LISTEN_QUEUE_SIZE, SOCKET_SET, SocketSetWrapper};
+use super::{SOCKET_SET, SocketSetWrapper, listen_backlog};
 
 const PORT_NUM: usize = 65536;
 
 struct ListenTableEntry {
     listen_endpoint: IpListenEndpoint,
+    /// The device that the socket is bound to, if any.
+    device: Option<usize>,
     syn_queue: VecDeque<SocketHandle>,
 }
 
 impl ListenTableEntry {
-    pub fn new(listen_endpoint: IpListenEndpoint) -> Self {
+    pub fn new(listen_endpoint: IpListenEndpoint, device: Option<usize>) -> Self {
         Self {
             listen_endpoint,
-            syn_queue: VecDeque::with_capacity(LISTEN_QUEUE_SIZE),
+            device,
+            syn_queue: VecDeque::new(),
         }
     }
 
     #[inline]
-    fn can_accept(&self, dst: IpAddress) -> bool {
+    fn can_accept(&self, dst: IpAddress, dev: usize) -> bool {
+        if self.device.is_some_and(|device| device != dev) {
+            return false;
+        }
         match self.listen_endpoint.addr {
             Some(addr) => addr == dst,
             None => true,
@@ -61,12 +67,12 @@ impl ListenTable {
         self.tcp[port as usize].lock().is_none()
     }
 
-    pub fn listen(&self, listen_endpoint: IpListenEndpoint) -> AxResult {
+    pub fn listen(&self, listen_endpoint: IpListenEndpoint, device: Option<usize>) -> AxResult {
         let port = listen_endpoint.port;
         assert_ne!(port, 0);
         let mut entry = self.tcp[port as usize].lock();
         if entry.is_none() {
-            *entry = Some(Box::new(ListenTableEntry::new(listen_endpoint)));
+            *entry = Some(Box::new(ListenTableEntry::new(listen_endpoint, device)));
             Ok(())
         } else {
             ax_err!(AddrInUse, "socket listen() failed")
@@ -114,14 +120,15 @@ impl ListenTable {
         &self,
         src: IpEndpoint,
         dst: IpEndpoint,
+        dev: usize,
         sockets: &mut SocketSet<'_>,
     ) {
         if let Some(entry) = self.tcp[dst.port as usize].lock().deref_mut() {
-            if !entry.can_accept(dst.addr) {
-                // not listening on this address
+            if !entry.can_accept(dst.addr, dev) {
+                // not listening on this address or device
                 return;
             }
-            if entry.syn_queue.len() >= LISTEN_QUEUE_SIZE {
+            if entry.syn_queue.len() >= listen_backlog() {
                 // SYN queue is full, drop the packet
                 warn!("SYN queue overflow!");
                 return;
@@ -0,0 +1,31 @@
+//! The loopback device, which delivers every packet sent to it back to the
+//! stack.
+
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+
+/// The loopback device `lo`.
+///
+/// Like `Loopback` of smoltcp, it queues the packets sent to it, but a
+/// received packet is moved out of the queue rather than copied.
+pub(crate) struct LoopbackDevice {
+    queue: VecDeque<Vec<u8>>,
+}
+
+impl LoopbackDevice {
+    pub fn new() -> Self {
+        Self {
+            queue: VecDeque::new(),
+        }
+    }
+
+    /// Receives a packet that is sent before.
+    pub fn receive(&mut self) -> Option<Vec<u8>> {
+        self.queue.pop_front()
+    }
+
+    /// Sends a packet, which is received later.
+    pub fn send(&mut self, packet: &[u8]) {
+        self.queue.push_back(packet.to_vec());
+    }
+}
@@ -1,28 +1,48 @@
 mod addr;
 mod bench;
+mod config;
+#[cfg(feature = "dhcp")]
+mod dhcp;
 mod dns;
+mod ethernet;
+mod icmp;
 mod listen_table;
+mod loopback;
+mod neighbor;
+mod router;
 mod tcp;
 mod udp;
 
-use alloc::vec;
-use core::cell::RefCell;
+#[cfg(test)]
+mod tests;
+
+use alloc::{format, vec, vec::Vec};
 use core::ops::DerefMut;
+use core::sync::atomic::{AtomicUsize, Ordering};
 
 use axdriver::prelude::*;
-use axdriver_net::{DevError, NetBufPtr};
+use axerrno::{AxResult, ax_err, ax_err_type};
 use axhal::time::{NANOS_PER_MICROS, wall_time_nanos};
 use axsync::Mutex;
 use lazyinit::LazyInit;
 use smoltcp::iface::{Config, Interface, SocketHandle, SocketSet};
-use smoltcp::phy::{Device, DeviceCapabilities, Medium, RxToken, TxToken};
 use smoltcp::socket::{self, AnySocket};
 use smoltcp::time::Instant;
-use smoltcp::wire::{EthernetAddress, HardwareAddress, IpAddress, IpCidr};
+use smoltcp::wire::{HardwareAddress, IpAddress, IpCidr, IpProtocol, IpVersion};
 
+use self::addr::{is_link_local, is_loopback};
+#[cfg(feature = "dhcp")]
+use self::dhcp::DhcpClient;
+use self::ethernet::{EthernetDevice, link_local_address};
 use self::listen_table::ListenTable;
+use self::router::{LOOPBACK, NetDevice, Route, Router};
 
+pub use self::config::{
+    RouteEntry, add_address, add_route, interface_addrs, interfaces, remove_address, remove_route,
+    routes,
+};
 pub use self::dns::dns_query;
+pub use self::icmp::IcmpSocket;
 pub use self::tcp::TcpSocket;
 pub use self::udp::UdpSocket;
 
@@ -37,8 +57,15 @@ macro_rules! env_or_default {
 
 const IP: &str = env_or_default!("AX_IP");
 const GATEWAY: &str = env_or_default!("AX_GW");
+const IP6: &str = env_or_default!("AX_IP6");
+const GATEWAY6: &str = env_or_default!("AX_GW6");
 const DNS_SEVER: &str = "8.8.8.8";
 const IP_PREFIX: u8 = 24;
+const IP6_PREFIX: u8 = 64;
+
+const LOOPBACK_IP: IpAddress = IpAddress::v4(127, 0, 0, 1);
+const LOOPBACK_PREFIX: u8 = 8;
+const LOOPBACK_IP6: IpAddress = IpAddress::v6(0, 0, 0, 0, 0, 0, 0, 1);
 
 const STANDARD_MTU: usize = 1500;
 
@@ -48,23 +75,34 @@ const TCP_RX_BUF_LEN: usize = 64 * 1024;
 const TCP_TX_BUF_LEN: usize = 64 * 1024;
 const UDP_RX_BUF_LEN: usize = 64 * 1024;
 const UDP_TX_BUF_LEN: usize = 64 * 1024;
-const LISTEN_QUEUE_SIZE: usize = 512;
+const ICMP_RX_BUF_LEN: usize = 16 * 1024;
+const ICMP_TX_BUF_LEN: usize = 16 * 1024;
+
+/// Maximum number of pending connections of a listening TCP socket.
+static LISTEN_BACKLOG: AtomicUsize = AtomicUsize::new(512);
 
 static LISTEN_TABLE: LazyInit<ListenTable> = LazyInit::new();
 static SOCKET_SET: LazyInit<SocketSetWrapper> = LazyInit::new();
-static ETH0: LazyInit<InterfaceWrapper> = LazyInit::new();
+static IFACE: LazyInit<InterfaceWrapper> = LazyInit::new();
+/// DNS servers for new DNS sockets, which may be configured by DHCP.
+static DNS_SERVERS: Mutex<Vec<IpAddress>> = Mutex::new(Vec::new());
 
 struct SocketSetWrapper<'a>(Mutex<SocketSet<'a>>);
 
-struct DeviceWrapper {
-    inner: RefCell<AxNetDevice>, // use `RefCell` is enough since it's wrapped in `Mutex` in `InterfaceWrapper`.
-}
-
+/// The network interface, with all the network devices behind its router.
 struct InterfaceWrapper {
-    name: &'static str,
-    ether_addr: EthernetAddress,
-    dev: Mutex<DeviceWrapper>,
+    router: Mutex<Router>,
     iface: Mutex<Interface>,
+    #[cfg(feature = "dhcp")]
+    dhcp: Mutex<Vec<DhcpClient>>,
+}
+
+/// Configuration of a device, which is static or from DHCP.
+#[derive(Debug, Clone)]
+struct IpConfig {
+    address: IpCidr,
+    gateway: Option<IpAddress>,
+    dns_servers: Vec<IpAddress>,
 }
 
 impl<'a> SocketSetWrapper<'a> {
@@ -90,9 +128,33 @@ impl<'a> SocketSetWrapper<'a> {
         socket::udp::Socket::new(udp_rx_buffer, udp_tx_buffer)
     }
 
+    pub fn new_icmp_socket() -> socket::icmp::Socket<'a> {
+        let icmp_rx_buffer = socket::icmp::PacketBuffer::new(
+            vec![socket::icmp::PacketMetadata::EMPTY; 8],
+            vec![0; ICMP_RX_BUF_LEN],
+        );
+        let icmp_tx_buffer = socket::icmp::PacketBuffer::new(
+            vec![socket::icmp::PacketMetadata::EMPTY; 8],
+            vec![0; ICMP_TX_BUF_LEN],
+        );
+        socket::icmp::Socket::new(icmp_rx_buffer, icmp_tx_buffer)
+    }
+
+    /// Creates a raw IPv4 socket of the given protocol.
+    pub fn new_raw_socket(protocol: IpProtocol) -> socket::raw::Socket<'a> {
+        let raw_rx_buffer = socket::raw::PacketBuffer::new(
+            vec![socket::raw::PacketMetadata::EMPTY; 8],
+            vec![0; ICMP_RX_BUF_LEN],
+        );
+        let raw_tx_buffer = socket::raw::PacketBuffer::new(
+            vec![socket::raw::PacketMetadata::EMPTY; 8],
+            vec![0; ICMP_TX_BUF_LEN],
+        );
+        socket::raw::Socket::new(IpVersion::Ipv4, protocol, raw_rx_buffer, raw_tx_buffer)
+    }
+
     pub fn new_dns_socket() -> socket::dns::Socket<'a> {
-        let server_addr = DNS_SEVER.parse().expect("invalid DNS server address");
-        socket::dns::Socket::new(&[server_addr], vec![])
+        socket::dns::Socket::new(&DNS_SERVERS.lock(), vec![])
     }
 
     pub fn add<T: AnySocket<'a>>(&self, socket: T) -> SocketHandle {
@@ -120,7 +182,9 @@ impl<'a> SocketSetWrapper<'a> {
     }
 
     pub fn poll_interfaces(&self) {
-        ETH0.poll(&self.0);
+        IFACE.poll(&self.0);
+        #[cfg(feature = "dhcp")]
+        IFACE.poll_dhcp();
     }
 
     pub fn remove(&self, handle: SocketHandle) {
@@ -130,17 +194,29 @@ impl<'a> SocketSetWrapper<'a> {
 }
 
 impl InterfaceWrapper {
-    fn new(name: &'static str, dev: AxNetDevice, ether_addr: EthernetAddress) -> Self {
-        let mut config = Config::new(HardwareAddress::Ethernet(ether_addr));
+    fn new() -> Self {
+        let mut config = Config::new(HardwareAddress::Ip);
         config.random_seed = RANDOM_SEED;
 
-        let mut dev = DeviceWrapper::new(dev);
-        let iface = Mutex::new(Interface::new(config, &mut dev, Self::current_time()));
+        let mut router = Router::new();
+        let mut iface = Interface::new(config, &mut router, Self::current_time());
+        // accept packets to any address in 127.0.0.0/8, as they are routed
+        // to the loopback address.
+        iface.set_any_ip(true);
+        iface.routes_mut().update(|routes| {
+            let route = smoltcp::iface::Route {
+                cidr: IpCidr::new(LOOPBACK_IP, LOOPBACK_PREFIX),
+                via_router: LOOPBACK_IP,
+                preferred_until: None,
+                expires_at: None,
+            };
+            routes.push(route).unwrap();
+        });
         Self {
-            name,
-            ether_addr,
-            dev: Mutex::new(dev),
-            iface,
+            router: Mutex::new(router),
+            iface: Mutex::new(iface),
+            #[cfg(feature = "dhcp")]
+            dhcp: Mutex::new(Vec::new()),
         }
     }
 
@@ -148,151 +224,198 @@ impl InterfaceWrapper {
         Instant::from_micros_const((wall_time_nanos() / NANOS_PER_MICROS) as i64)
     }
 
-    pub fn name(&self) -> &str {
-        self.name
+    pub fn add_device(&self, dev: NetDevice) -> usize {
+        self.router.lock().add_device(dev)
     }
 
-    pub fn ethernet_address(&self) -> EthernetAddress {
-        self.ether_addr
+    /// Returns the index of the device named `name`.
+    pub fn find_device(&self, name: &str) -> AxResult<usize> {
+        self.router
+            .lock()
+            .find_device(name)
+            .ok_or_else(|| ax_err_type!(NotFound, "no such network interface"))
     }
 
-    pub fn setup_ip_addr(&self, ip: IpAddress, prefix_len: u8) {
-        let mut iface = self.iface.lock();
-        iface.update_ip_addrs(|ip_addrs| {
-            ip_addrs.push(IpCidr::new(ip, prefix_len)).unwrap();
-        });
+    /// Returns the address of the device `dev` to send packets to `dst`
+    /// from.
+    pub fn source_address(&self, dev: usize, dst: Option<IpAddress>) -> AxResult<IpAddress> {
+        self.router
+            .lock()
+            .source_address(dev, dst)
+            .ok_or_else(|| ax_err_type!(InvalidInput, "no address on the network interface"))
     }
 
-    pub fn setup_gateway(&self, gateway: IpAddress) {
-        let mut iface = self.iface.lock();
-        match gateway {
-            IpAddress::Ipv4(v4) => iface.routes_mut().add_default_ipv4_route(v4).unwrap(),
-        };
+    /// Returns the address to send packets to `dst` from, if no device is
+    /// specified.
+    pub fn route_source_address(&self, dst: IpAddress) -> Option<IpAddress> {
+        self.router.lock().route_source_address(dst)
     }
 
-    pub fn poll(&self, sockets: &Mutex<SocketSet>) {
-        let mut dev = self.dev.lock();
-        let mut iface = self.iface.lock();
-        let mut sockets = sockets.lock();
-        let timestamp = Self::current_time();
-        iface.poll(timestamp, dev.deref_mut(), &mut sockets);
+    /// Assigns the address `cidr` to the device `dev`.
+    pub fn setup_ip_addr(&self, dev: usize, cidr: IpCidr) -> AxResult {
+        let mut router = self.router.lock();
+        if router
+            .addresses()
+            .iter()
+            .any(|(_, c)| c.address() == cidr.address())
+        {
+            return ax_err!(AlreadyExists, "address already assigned");
+        }
+        let mut res = Ok(());
+        self.iface.lock().update_ip_addrs(|ip_addrs| {
+            res = ip_addrs.push(cidr);
+            // smoltcp chooses the first address of the version as the source
+            // of outgoing packets, so keep the link-local and loopback
+            // addresses behind the others.
+            ip_addrs.sort_by_key(|cidr| match cidr.address() {
+                addr if is_loopback(addr) => 2,
+                addr if is_link_local(addr) => 1,
+                _ => 0,
+            });
+        });
+        if res.is_err() {
+            return ax_err!(NoMemory, "too many addresses");
+        }
+        router.add_address(dev, cidr);
+        Ok(())
     }
-}
 
-impl DeviceWrapper {
-    fn new(inner: AxNetDevice) -> Self {
-        Self {
-            inner: RefCell::new(inner),
+    pub fn remove_ip_addr(&self, dev: usize, cidr: IpCidr) -> AxResult {
+        let mut router = self.router.lock();
+        if !router.addresses().contains(&(dev, cidr)) {
+            return ax_err!(NotFound, "address not assigned");
         }
+        router.remove_address(dev, cidr);
+        self.iface.lock().update_ip_addrs(|ip_addrs| {
+            ip_addrs.retain(|&c| c != cidr);
+        });
+        Ok(())
     }
-}
-
-impl Device for DeviceWrapper {
-    type RxToken<'a>
-        = AxNetRxToken<'a>
-    where
-        Self: 'a;
-    type TxToken<'a>
-        = AxNetTxToken<'a>
-    where
-        Self: 'a;
 
-    fn receive(&mut self, _timestamp: Instant) -> Option<(Self::RxToken<'_>, Self::TxToken<'_>)> {
-        let mut dev = self.inner.borrow_mut();
-        if let Err(e) = dev.recycle_tx_buffers() {
-            warn!("recycle_tx_buffers failed: {:?}", e);
-            return None;
+    pub fn add_route(&self, route: Route) -> AxResult {
+        let mut router = self.router.lock();
+        if router.routes().contains(&route) {
+            return ax_err!(AlreadyExists, "route already exists");
         }
+        router.add_route(route);
+        Ok(())
+    }
 
-        if !dev.can_transmit() {
-            return None;
+    pub fn remove_route(&self, route: Route) -> AxResult {
+        if self.router.lock().remove_route(route) {
+            Ok(())
+        } else {
+            ax_err!(NotFound, "no such route")
         }
-        let rx_buf = match dev.receive() {
-            Ok(buf) => buf,
-            Err(err) => {
-                if !matches!(err, DevError::Again) {
-                    warn!("receive failed: {:?}", err);
-                }
-                return None;
-            }
+    }
+
+    fn default_route(dev: usize, gateway: IpAddress) -> Route {
+        let cidr = match gateway {
+            IpAddress::Ipv4(_) => IpCidr::new(IpAddress::v4(0, 0, 0, 0), 0),
+            IpAddress::Ipv6(_) => IpCidr::new(IpAddress::v6(0, 0, 0, 0, 0, 0, 0, 0), 0),
         };
-        Some((AxNetRxToken(&self.inner, rx_buf), AxNetTxToken(&self.inner)))
+        Route {
+            cidr,
+            via: Some(gateway),
+            dev,
+        }
     }
 
-    fn transmit(&mut self, _timestamp: Instant) -> Option<Self::TxToken<'_>> {
-        let mut dev = self.inner.borrow_mut();
-        if let Err(e) = dev.recycle_tx_buffers() {
-            warn!("recycle_tx_buffers failed: {:?}", e);
-            return None;
+    /// Applies `config` to the device `dev`.
+    fn configure(&self, dev: usize, config: &IpConfig) {
+        if let Err(e) = self.setup_ip_addr(dev, config.address) {
+            warn!("failed to assign address {}: {:?}", config.address, e);
         }
-        if dev.can_transmit() {
-            Some(AxNetTxToken(&self.inner))
-        } else {
-            None
+        if let Some(gateway) = config.gateway {
+            self.add_route(Self::default_route(dev, gateway)).ok();
+        }
+        if !config.dns_servers.is_empty() {
+            *DNS_SERVERS.lock() = config.dns_servers.clone();
         }
     }
 
-    fn capabilities(&self) -> DeviceCapabilities {
-        let mut caps = DeviceCapabilities::default();
-        caps.max_transmission_unit = 1514;
-        caps.max_burst_size = None;
-        caps.medium = Medium::Ethernet;
-        caps
+    /// Reverts `config` applied to the device `dev`. The DNS servers are
+    /// kept until others are configured.
+    #[cfg(feature = "dhcp")]
+    fn unconfigure(&self, dev: usize, config: &IpConfig) {
+        self.remove_ip_addr(dev, config.address).ok();
+        if let Some(gateway) = config.gateway {
+            self.remove_route(Self::default_route(dev, gateway)).ok();
+        }
     }
-}
 
-struct AxNetRxToken<'a>(&'a RefCell<AxNetDevice>, NetBufPtr);
-struct AxNetTxToken<'a>(&'a RefCell<AxNetDevice>);
-
-impl RxToken for AxNetRxToken<'_> {
-    fn preprocess(&self, sockets: &mut SocketSet<'_>) {
-        snoop_tcp_packet(self.1.packet(), sockets).ok();
+    /// Starts the DHCP client of the Ethernet device `dev`, which applies
+    /// `fallback` if no lease is got in time, or the lease is lost.
+    #[cfg(feature = "dhcp")]
+    fn start_dhcp(&self, dev: usize, fallback: Option<IpConfig>) {
+        let mut router = self.router.lock();
+        let eth = router.ethernet_mut(dev).expect("not an Ethernet device");
+        let client = DhcpClient::new(dev, eth, fallback, Self::current_time());
+        self.dhcp.lock().push(client);
     }
 
-    fn consume<R, F>(self, f: F) -> R
-    where
-        F: FnOnce(&mut [u8]) -> R,
-    {
-        let mut rx_buf = self.1;
-        trace!(
-            "RECV {} bytes: {:02X?}",
-            rx_buf.packet_len(),
-            rx_buf.packet()
-        );
-        let result = f(rx_buf.packet_mut());
-        self.0.borrow_mut().recycle_rx_buffer(rx_buf).unwrap();
-        result
+    /// Polls the DHCP clients, and applies the configurations they change.
+    #[cfg(feature = "dhcp")]
+    fn poll_dhcp(&self) {
+        let timestamp = Self::current_time();
+        for client in self.dhcp.lock().iter_mut() {
+            let dev = client.dev();
+            let old = client.config().cloned();
+            let changed = {
+                let mut router = self.router.lock();
+                let eth = router.ethernet_mut(dev).unwrap();
+                client.poll(eth, timestamp)
+            };
+            if changed {
+                if let Some(old) = &old {
+                    self.unconfigure(dev, old);
+                }
+                if let Some(new) = client.config() {
+                    self.configure(dev, new);
+                }
+            }
+        }
     }
-}
 
-impl TxToken for AxNetTxToken<'_> {
-    fn consume<R, F>(self, len: usize, f: F) -> R
-    where
-        F: FnOnce(&mut [u8]) -> R,
-    {
-        let mut dev = self.0.borrow_mut();
-        let mut tx_buf = dev.alloc_tx_buffer(len).unwrap();
-        let ret = f(tx_buf.packet_mut());
-        trace!("SEND {} bytes: {:02X?}", len, tx_buf.packet());
-        dev.transmit(tx_buf).unwrap();
-        ret
+    pub fn poll(&self, sockets: &Mutex<SocketSet>) {
+        let mut router = self.router.lock();
+        let mut iface = self.iface.lock();
+        let mut sockets = sockets.lock();
+        let timestamp = Self::current_time();
+        iface.poll(timestamp, router.deref_mut(), &mut sockets);
     }
 }
 
-fn snoop_tcp_packet(buf: &[u8], sockets: &mut SocketSet<'_>) -> Result<(), smoltcp::wire::Error> {
-    use smoltcp::wire::{EthernetFrame, IpProtocol, Ipv4Packet, TcpPacket};
-
-    let ether_frame = EthernetFrame::new_checked(buf)?;
-    let ipv4_packet = Ipv4Packet::new_checked(ether_frame.payload())?;
+/// Snoops an incoming packet from the device `dev`.
+fn snoop_tcp_packet(
+    buf: &[u8],
+    dev: usize,
+    sockets: &mut SocketSet<'_>,
+) -> Result<(), smoltcp::wire::Error> {
+    use smoltcp::wire::{Ipv4Packet, Ipv6Packet, TcpPacket};
+
+    let (src_ip, dst_ip, protocol, payload): (IpAddress, IpAddress, _, _) =
+        match IpVersion::of_packet(buf)? {
+            IpVersion::Ipv4 => {
+                let packet = Ipv4Packet::new_checked(buf)?;
+                let (src, dst) = (packet.src_addr().into(), packet.dst_addr().into());
+                (src, dst, packet.next_header(), packet.payload())
+            }
+            IpVersion::Ipv6 => {
+                let packet = Ipv6Packet::new_checked(buf)?;
+                let (src, dst) = (packet.src_addr().into(), packet.dst_addr().into());
+                (src, dst, packet.next_header(), packet.payload())
+            }
+        };
 
-    if ipv4_packet.next_header() == IpProtocol::Tcp {
-        let tcp_packet = TcpPacket::new_checked(ipv4_packet.payload())?;
-        let src_addr = (ipv4_packet.src_addr(), tcp_packet.src_port()).into();
-        let dst_addr = (ipv4_packet.dst_addr(), tcp_packet.dst_port()).into();
+    if protocol == IpProtocol::Tcp {
+        let tcp_packet = TcpPacket::new_checked(payload)?;
+        let src_addr = (src_ip, tcp_packet.src_port()).into();
+        let dst_addr = (dst_ip, tcp_packet.dst_port()).into();
         let is_first = tcp_packet.syn() && !tcp_packet.ack();
         if is_first {
             // create a socket for the first incoming TCP packet, as the later accept() returns.
-            LISTEN_TABLE.incoming_tcp_packet(src_addr, dst_addr, sockets);
+            LISTEN_TABLE.incoming_tcp_packet(src_addr, dst_addr, dev, sockets);
         }
     }
     Ok(())
@@ -300,37 +423,133 @@ fn snoop_tcp_packet(buf: &[u8], sockets: &mut SocketSet<'_>) -> Result<(), smolt
 
 /// Poll the network stack.
 ///
-/// It may receive packets from the NIC and process them, and transmit queued
-/// packets to the NIC.
+/// It may receive packets from the NICs and the loopback device and process
+/// them, and transmit queued packets to them.
 pub fn poll_interfaces() {
     SOCKET_SET.poll_interfaces();
 }
 
+/// Returns the maximum number of pending connections of a listening TCP
+/// socket, like `somaxconn` of Linux.
+pub fn listen_backlog() -> usize {
+    LISTEN_BACKLOG.load(Ordering::Relaxed)
+}
+
+/// Sets the maximum number of pending connections of a listening TCP socket,
+/// at least 1. Sockets that are already listening are affected as well.
+pub fn set_listen_backlog(backlog: usize) {
+    LISTEN_BACKLOG.store(backlog.max(1), Ordering::Relaxed);
+}
+
 /// Benchmark raw socket transmit bandwidth.
 pub fn bench_transmit() {
-    ETH0.dev.lock().bench_transmit_bandwidth();
+    match IFACE.router.lock().first_ethernet_mut() {
+        Some(dev) => dev.bench_transmit_bandwidth(),
+        None => warn!("no NIC device to benchmark"),
+    }
 }
 
 /// Benchmark raw socket receive bandwidth.
 pub fn bench_receive() {
-    ETH0.dev.lock().bench_receive_bandwidth();
+    match IFACE.router.lock().first_ethernet_mut() {
+        Some(dev) => dev.bench_receive_bandwidth(),
+        None => warn!("no NIC device to benchmark"),
+    }
 }
 
-pub(crate) fn init(net_dev: AxNetDevice) {
-    let ether_addr = EthernetAddress(net_dev.mac_address().0);
-    let eth0 = InterfaceWrapper::new("eth0", net_dev, ether_addr);
-
+/// Returns the static IPv4 configuration of the NIC, from the environment
+/// variables `AX_IP` and `AX_GW`. They may be unset if DHCP is enabled.
+fn static_config() -> Option<IpConfig> {
+    if cfg!(feature = "dhcp") && IP.is_empty() {
+        return None;
+    }
     let ip = IP.parse().expect("invalid IP address");
     let gateway = GATEWAY.parse().expect("invalid gateway IP address");
-    eth0.setup_ip_addr(ip, IP_PREFIX);
-    eth0.setup_gateway(gateway);
+    Some(IpConfig {
+        address: IpCidr::new(ip, IP_PREFIX),
+        gateway: Some(gateway),
+        dns_servers: vec![DNS_SEVER.parse().expect("invalid DNS server address")],
+    })
+}
 
-    ETH0.init_once(eth0);
+/// Returns the static IPv6 configuration of the NIC, from the environment
+/// variables `AX_IP6` and `AX_GW6`, if `AX_IP6` is set.
+fn static_config6() -> Option<IpConfig> {
+    if IP6.is_empty() {
+        return None;
+    }
+    let ip = IP6.parse().expect("invalid IPv6 address");
+    let gateway =
+        (!GATEWAY6.is_empty()).then(|| GATEWAY6.parse().expect("invalid gateway IPv6 address"));
+    Some(IpConfig {
+        address: IpCidr::new(ip, IP6_PREFIX),
+        gateway,
+        dns_servers: Vec::new(),
+    })
+}
+
+pub(crate) fn init(net_devs: Vec<AxNetDevice>) {
+    let iface = InterfaceWrapper::new();
+    *DNS_SERVERS.lock() = vec![DNS_SEVER.parse().expect("invalid DNS server address")];
+
+    iface
+        .setup_ip_addr(LOOPBACK, IpCidr::new(LOOPBACK_IP, LOOPBACK_PREFIX))
+        .unwrap();
+    info!("created net interface \"lo\":");
+    info!("  ip:       {}/{}", LOOPBACK_IP, LOOPBACK_PREFIX);
+    iface
+        .setup_ip_addr(LOOPBACK, IpCidr::new(LOOPBACK_IP6, 128))
+        .unwrap();
+    info!("  ip6:      {}/128", LOOPBACK_IP6);
+
+    for (i, net_dev) in net_devs.into_iter().enumerate() {
+        let eth = EthernetDevice::new(format!("eth{}", i), net_dev);
+        let ether_addr = eth.ethernet_address();
+        let dev = iface.add_device(NetDevice::Ethernet(eth));
+        info!(
+            "created net interface {:?}:",
+            iface.router.lock().device(dev).name()
+        );
+        info!("  ether:    {}", ether_addr);
+
+        // SLAAC of the link-local address
+        let link_local = link_local_address(ether_addr).into();
+        if let Err(e) = iface.setup_ip_addr(dev, link_local) {
+            warn!("failed to assign address {}: {:?}", link_local, e);
+        }
+        info!("  ip6:      {}", link_local);
+        if i == 0 {
+            if let Some(config) = static_config6() {
+                iface.configure(dev, &config);
+                info!("  ip6:      {}", config.address);
+                if let Some(gateway) = config.gateway {
+                    info!("  gateway6: {}", gateway);
+                }
+            }
+        }
+
+        // the static configuration is for the first NIC only, which is the
+        // fallback of DHCP if enabled
+        let config = if i == 0 { static_config() } else { None };
+        #[cfg(not(feature = "dhcp"))]
+        if let Some(config) = &config {
+            iface.configure(dev, config);
+            info!("  ip:       {}", config.address);
+            if let Some(gateway) = config.gateway {
+                info!("  gateway:  {}", gateway);
+            }
+        }
+        #[cfg(feature = "dhcp")]
+        {
+            if let Some(config) = &config {
+                info!("  fallback: {}", config.address);
+            }
+            iface.start_dhcp(dev, config);
+            info!("  dhcp:     enabled");
+        }
+    }
+
+    IFACE.init_once(iface);
     SOCKET_SET.init_once(SocketSetWrapper::new());
     LISTEN_TABLE.init_once(ListenTable::new());
-
-    info!("created net interface {:?}:", ETH0.name());
-    info!("  ether:    {}", ETH0.ethernet_address());
-    info!("  ip:       {}/{}", ip, IP_PREFIX);
-    info!("  gateway:  {}", gateway);
 }
@@ -0,0 +1,399 @@
+//! Resolution of the link addresses of neighbors, by ARP for IPv4, and by
+//! NDP (Neighbor Discovery Protocol) for IPv6.
+//!
+//! smoltcp resolves neighbors only in an interface of the Ethernet medium,
+//! but all devices are behind one interface of the IP medium (see the
+//! [`router`] module). So each Ethernet device resolves its neighbors by its
+//! own [`Neighbors`], when the router sends a packet to a next hop on it.
+//!
+//! [`router`]: super::router
+
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::{vec, vec::Vec};
+
+use smoltcp::phy::ChecksumCapabilities;
+use smoltcp::time::{Duration, Instant};
+use smoltcp::wire::{
+    ArpOperation, ArpPacket, ArpRepr, EthernetAddress, EthernetProtocol, Icmpv6Packet, Icmpv6Repr,
+    IpAddress, IpCidr, IpProtocol, Ipv4Address, Ipv4Cidr, Ipv6Address, Ipv6Cidr, Ipv6Packet,
+    Ipv6Repr, NdiscNeighborFlags, NdiscRepr,
+};
+
+/// How long a resolved neighbor is valid.
+pub(crate) const NEIGHBOR_TTL: Duration = Duration::from_secs(60);
+/// Interval of ARP requests or neighbor solicitations for a neighbor that is not resolved yet.
+pub(crate) const REQUEST_INTERVAL: Duration = Duration::from_secs(1);
+/// How long a packet waits for its neighbor to be resolved.
+pub(crate) const PENDING_TIMEOUT: Duration = Duration::from_secs(3);
+/// Maximum number of packets waiting for their neighbors.
+pub(crate) const MAX_PENDING: usize = 16;
+
+/// The link of an Ethernet device, which the frames are sent to.
+pub(crate) trait Link {
+    /// Sends a frame of `payload` to `dst`, which is dropped if it cannot be
+    /// sent.
+    fn send_frame(&mut self, dst: EthernetAddress, ethertype: EthernetProtocol, payload: &[u8]);
+}
+
+struct Neighbor {
+    ether_addr: EthernetAddress,
+    expires_at: Instant,
+}
+
+/// A packet waiting for the link address of its next hop.
+struct Pending {
+    next_hop: IpAddress,
+    packet: Vec<u8>,
+    since: Instant,
+}
+
+/// The neighbors of an Ethernet device.
+pub(crate) struct Neighbors {
+    ether_addr: EthernetAddress,
+    /// IPv4 addresses of the device, which are answered in ARP.
+    addrs: Vec<Ipv4Cidr>,
+    /// IPv6 addresses of the device, which are answered in NDP.
+    addrs6: Vec<Ipv6Cidr>,
+    neighbors: BTreeMap<IpAddress, Neighbor>,
+    /// Last time a request is sent for each neighbor being resolved.
+    requests: BTreeMap<IpAddress, Instant>,
+    pending: VecDeque<Pending>,
+}
+
+impl Neighbors {
+    /// Creates the neighbors of the device with the link address
+    /// `ether_addr`.
+    pub fn new(ether_addr: EthernetAddress) -> Self {
+        Self {
+            ether_addr,
+            addrs: Vec::new(),
+            addrs6: Vec::new(),
+            neighbors: BTreeMap::new(),
+            requests: BTreeMap::new(),
+            pending: VecDeque::new(),
+        }
+    }
+
+    pub fn add_address(&mut self, cidr: IpCidr) {
+        match cidr {
+            IpCidr::Ipv4(cidr) => self.addrs.push(cidr),
+            IpCidr::Ipv6(cidr) => self.addrs6.push(cidr),
+        }
+    }
+
+    pub fn remove_address(&mut self, cidr: IpCidr) {
+        match cidr {
+            IpCidr::Ipv4(cidr) => self.addrs.retain(|&c| c != cidr),
+            IpCidr::Ipv6(cidr) => self.addrs6.retain(|&c| c != cidr),
+        }
+    }
+
+    /// Sends an IP packet to `next_hop` on the link, which waits for the
+    /// link address of `next_hop` to be resolved if unknown.
+    pub fn send(&mut self, link: &mut impl Link, next_hop: IpAddress, packet: &[u8], now: Instant) {
+        if let Some(dst) = self.link_address(next_hop, now) {
+            link.send_frame(dst, ethertype_of(next_hop), packet);
+            return;
+        }
+
+        while self
+            .pending
+            .front()
+            .is_some_and(|p| now - p.since > PENDING_TIMEOUT)
+            || self.pending.len() >= MAX_PENDING
+        {
+            let p = self.pending.pop_front().unwrap();
+            debug!("neighbor {} unreachable", p.next_hop);
+        }
+        self.pending.push_back(Pending {
+            next_hop,
+            packet: packet.to_vec(),
+            since: now,
+        });
+        let requested = self.requests.get(&next_hop);
+        if requested.is_none_or(|&at| now - at >= REQUEST_INTERVAL) {
+            self.requests.insert(next_hop, now);
+            match next_hop {
+                IpAddress::Ipv4(ip) => self.send_arp_request(link, ip),
+                IpAddress::Ipv6(ip) => self.send_neighbor_solicit(link, ip),
+            }
+        }
+    }
+
+    /// Returns the link address of `ip`, if it is a broadcast or multicast
+    /// address, or a resolved neighbor.
+    fn link_address(&mut self, ip: IpAddress, now: Instant) -> Option<EthernetAddress> {
+        match ip {
+            IpAddress::Ipv4(ip) => {
+                if ip.is_broadcast() || self.addrs.iter().any(|cidr| cidr.broadcast() == Some(ip)) {
+                    return Some(EthernetAddress::BROADCAST);
+                }
+                if ip.is_multicast() {
+                    let b = ip.as_bytes();
+                    return Some(EthernetAddress([0x01, 0x00, 0x5e, b[1] & 0x7f, b[2], b[3]]));
+                }
+            }
+            IpAddress::Ipv6(ip) => {
+                if ip.is_multicast() {
+                    return Some(ipv6_multicast_ether(ip));
+                }
+            }
+        }
+        match self.neighbors.get(&ip) {
+            Some(n) if n.expires_at > now => Some(n.ether_addr),
+            Some(_) => {
+                self.neighbors.remove(&ip);
+                None
+            }
+            None => None,
+        }
+    }
+
+    /// Handles the ARP packet `payload`.
+    pub fn process_arp(&mut self, link: &mut impl Link, payload: &[u8], now: Instant) {
+        let Ok(repr) = ArpPacket::new_checked(payload).and_then(|p| ArpRepr::parse(&p)) else {
+            return;
+        };
+        let ArpRepr::EthernetIpv4 {
+            operation,
+            source_hardware_addr,
+            source_protocol_addr,
+            target_protocol_addr,
+            ..
+        } = repr
+        else {
+            return;
+        };
+        if !source_hardware_addr.is_unicast() || !source_protocol_addr.is_unicast() {
+            return;
+        }
+        let for_us = self
+            .addrs
+            .iter()
+            .any(|cidr| cidr.address() == target_protocol_addr);
+        self.update_neighbor(
+            source_protocol_addr.into(),
+            source_hardware_addr,
+            for_us,
+            now,
+        );
+        if for_us && operation == ArpOperation::Request {
+            let reply = ArpRepr::EthernetIpv4 {
+                operation: ArpOperation::Reply,
+                source_hardware_addr: self.ether_addr,
+                source_protocol_addr: target_protocol_addr,
+                target_hardware_addr: source_hardware_addr,
+                target_protocol_addr: source_protocol_addr,
+            };
+            send_arp(link, source_hardware_addr, &reply);
+        }
+        self.flush_pending(link, source_protocol_addr.into(), source_hardware_addr);
+    }
+
+    /// Handles an NDP packet in the IPv6 packet `payload`. Returns whether it
+    /// is an NDP packet, which is not passed to the interface.
+    pub fn process_ndisc(&mut self, link: &mut impl Link, payload: &[u8], now: Instant) -> bool {
+        let Ok(packet) = Ipv6Packet::new_checked(payload) else {
+            return false;
+        };
+        if packet.next_header() != IpProtocol::Icmpv6 {
+            return false;
+        }
+        let Ok(icmp) = Icmpv6Packet::new_checked(packet.payload()) else {
+            return false;
+        };
+        let (src, dst) = (packet.src_addr(), packet.dst_addr());
+        let caps = ChecksumCapabilities::default();
+        let Ok(Icmpv6Repr::Ndisc(repr)) = Icmpv6Repr::parse(&src.into(), &dst.into(), &icmp, &caps)
+        else {
+            return false;
+        };
+        // NDP packets from other links are invalid (RFC 4861)
+        if packet.hop_limit() != 255 {
+            return true;
+        }
+        match repr {
+            NdiscRepr::NeighborSolicit {
+                target_addr,
+                lladdr,
+            } => {
+                if !self.addrs6.iter().any(|cidr| cidr.address() == target_addr) {
+                    return true;
+                }
+                let lladdr = lladdr.and_then(|addr| ether_address(addr.as_bytes()));
+                if let Some(lladdr) = lladdr.filter(|_| src.is_unicast()) {
+                    self.update_neighbor(src.into(), lladdr, true, now);
+                    self.flush_pending(link, src.into(), lladdr);
+                }
+                // solicitations for duplicate address detection are answered
+                // to all nodes
+                let (dst, dst_lladdr, flags) = match lladdr {
+                    Some(lladdr) if src.is_unicast() => (
+                        src,
+                        lladdr,
+                        NdiscNeighborFlags::SOLICITED | NdiscNeighborFlags::OVERRIDE,
+                    ),
+                    _ => (
+                        Ipv6Address::LINK_LOCAL_ALL_NODES,
+                        ipv6_multicast_ether(Ipv6Address::LINK_LOCAL_ALL_NODES),
+                        NdiscNeighborFlags::OVERRIDE,
+                    ),
+                };
+                let advert = NdiscRepr::NeighborAdvert {
+                    flags,
+                    target_addr,
+                    lladdr: Some(self.ether_addr.into()),
+                };
+                let packet = ndisc_packet(target_addr, dst, 255, advert);
+                link.send_frame(dst_lladdr, EthernetProtocol::Ipv6, &packet);
+            }
+            NdiscRepr::NeighborAdvert {
+                target_addr,
+                lladdr,
+                ..
+            } => {
+                if let Some(lladdr) = lladdr.and_then(|addr| ether_address(addr.as_bytes())) {
+                    self.update_neighbor(target_addr.into(), lladdr, false, now);
+                    self.flush_pending(link, target_addr.into(), lladdr);
+                }
+            }
+            // router discovery and redirects are not supported
+            _ => {}
+        }
+        true
+    }
+
+    /// Updates the link address of the neighbor `ip`, if it is requested by
+    /// the neighbor or us.
+    fn update_neighbor(
+        &mut self,
+        ip: IpAddress,
+        ether_addr: EthernetAddress,
+        force: bool,
+        now: Instant,
+    ) {
+        if force || self.neighbors.contains_key(&ip) || self.requests.contains_key(&ip) {
+            self.neighbors.insert(
+                ip,
+                Neighbor {
+                    ether_addr,
+                    expires_at: now + NEIGHBOR_TTL,
+                },
+            );
+        }
+    }
+
+    /// Sends the packets waiting for `ip`, which is resolved as `ether_addr`.
+    fn flush_pending(&mut self, link: &mut impl Link, ip: IpAddress, ether_addr: EthernetAddress) {
+        if self.requests.remove(&ip).is_none() {
+            return;
+        }
+        let (ready, pending) = self.pending.drain(..).partition(|p| p.next_hop == ip);
+        self.pending = pending;
+        for p in ready.iter() {
+            link.send_frame(ether_addr, ethertype_of(ip), &p.packet);
+        }
+    }
+
+    fn send_arp_request(&self, link: &mut impl Link, ip: Ipv4Address) {
+        // use the address in the same subnet as the source, if any
+        let Some(src) = self
+            .addrs
+            .iter()
+            .find(|cidr| cidr.contains_addr(&ip))
+            .or(self.addrs.first())
+        else {
+            return;
+        };
+        let request = ArpRepr::EthernetIpv4 {
+            operation: ArpOperation::Request,
+            source_hardware_addr: self.ether_addr,
+            source_protocol_addr: src.address(),
+            target_hardware_addr: EthernetAddress::BROADCAST,
+            target_protocol_addr: ip,
+        };
+        send_arp(link, EthernetAddress::BROADCAST, &request);
+    }
+
+    fn send_neighbor_solicit(&self, link: &mut impl Link, ip: Ipv6Address) {
+        // use the address in the same subnet as the source, or the
+        // link-local address
+        let Some(src) = self
+            .addrs6
+            .iter()
+            .find(|cidr| cidr.contains_addr(&ip))
+            .or(self
+                .addrs6
+                .iter()
+                .find(|cidr| cidr.address().is_link_local()))
+        else {
+            return;
+        };
+        let solicit = NdiscRepr::NeighborSolicit {
+            target_addr: ip,
+            lladdr: Some(self.ether_addr.into()),
+        };
+        let dst = solicited_node(ip);
+        let packet = ndisc_packet(src.address(), dst, 255, solicit);
+        link.send_frame(ipv6_multicast_ether(dst), EthernetProtocol::Ipv6, &packet);
+    }
+}
+
+/// Returns the IPv6 packet of the NDP message `repr`.
+pub(crate) fn ndisc_packet(
+    src: Ipv6Address,
+    dst: Ipv6Address,
+    hop_limit: u8,
+    repr: NdiscRepr<'_>,
+) -> Vec<u8> {
+    let repr = Icmpv6Repr::Ndisc(repr);
+    let ip_repr = Ipv6Repr {
+        src_addr: src,
+        dst_addr: dst,
+        next_header: IpProtocol::Icmpv6,
+        payload_len: repr.buffer_len(),
+        hop_limit,
+    };
+    let mut buf = vec![0; ip_repr.buffer_len() + repr.buffer_len()];
+    let mut packet = Ipv6Packet::new_unchecked(&mut buf[..]);
+    ip_repr.emit(&mut packet);
+    repr.emit(
+        &src.into(),
+        &dst.into(),
+        &mut Icmpv6Packet::new_unchecked(packet.payload_mut()),
+        &ChecksumCapabilities::default(),
+    );
+    buf
+}
+
+fn send_arp(link: &mut impl Link, dst: EthernetAddress, repr: &ArpRepr) {
+    let mut buf = [0; 28];
+    repr.emit(&mut ArpPacket::new_unchecked(&mut buf[..repr.buffer_len()]));
+    link.send_frame(dst, EthernetProtocol::Arp, &buf[..repr.buffer_len()]);
+}
+
+fn ethertype_of(ip: IpAddress) -> EthernetProtocol {
+    match ip {
+        IpAddress::Ipv4(_) => EthernetProtocol::Ipv4,
+        IpAddress::Ipv6(_) => EthernetProtocol::Ipv6,
+    }
+}
+
+/// Returns the link address of the IPv6 multicast address `ip` (RFC 2464).
+fn ipv6_multicast_ether(ip: Ipv6Address) -> EthernetAddress {
+    let b = ip.as_bytes();
+    EthernetAddress([0x33, 0x33, b[12], b[13], b[14], b[15]])
+}
+
+/// Returns the solicited-node multicast address of `ip` (RFC 4291).
+pub(crate) fn solicited_node(ip: Ipv6Address) -> Ipv6Address {
+    let b = ip.as_bytes();
+    let mut addr = [0; 16];
+    addr[..2].copy_from_slice(&[0xff, 0x02]);
+    addr[11..].copy_from_slice(&[0x01, 0xff, b[13], b[14], b[15]]);
+    Ipv6Address(addr)
+}
+
+fn ether_address(bytes: &[u8]) -> Option<EthernetAddress> {
+    (bytes.len() == 6).then(|| EthernetAddress::from_bytes(bytes))
+}
@@ -0,0 +1,463 @@
+//! Routing of IP packets among the network devices.
+//!
+//! smoltcp sockets are polled by a single [`Interface`] over a single device,
+//! so a socket set can not be shared by two interfaces. Sockets still need to
+//! reach the loopback device and every NIC, choosing the device by the
+//! longest prefix match, or by the device a socket is bound to. So all
+//! devices are put behind one interface of the IP medium. The [`Router`] is
+//! the device of that interface: it sends each outgoing packet to a device
+//! chosen by the routing table, and gathers incoming packets from all
+//! devices.
+//!
+//! Packets with a loopback source or destination never leave or enter
+//! through a device other than the loopback device, as they are martian
+//! (RFC 1122).
+//!
+//! [`Interface`]: smoltcp::iface::Interface
+
+use alloc::{vec, vec::Vec};
+use core::cell::{Ref, RefCell};
+
+use smoltcp::iface::SocketSet;
+use smoltcp::phy::{Device, DeviceCapabilities, Medium, RxToken, TxToken};
+use smoltcp::time::Instant;
+use smoltcp::wire::{IpAddress, IpCidr, IpVersion, Ipv4Packet, Ipv6Address, Ipv6Cidr, Ipv6Packet};
+
+use super::addr::{is_link_local, is_loopback};
+use super::ethernet::{EthernetDevice, RxFrame};
+use super::loopback::LoopbackDevice;
+use super::{STANDARD_MTU, snoop_tcp_packet};
+
+/// Index of the loopback device, which is always the first device.
+pub(crate) const LOOPBACK: usize = 0;
+
+/// A network device under the router.
+pub(crate) enum NetDevice {
+    Loopback(LoopbackDevice),
+    Ethernet(EthernetDevice),
+}
+
+impl NetDevice {
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Loopback(_) => "lo",
+            Self::Ethernet(dev) => dev.name(),
+        }
+    }
+
+    fn add_address(&mut self, cidr: IpCidr) {
+        match self {
+            Self::Loopback(_) => {}
+            Self::Ethernet(dev) => dev.add_address(cidr),
+        }
+    }
+
+    fn remove_address(&mut self, cidr: IpCidr) {
+        match self {
+            Self::Loopback(_) => {}
+            Self::Ethernet(dev) => dev.remove_address(cidr),
+        }
+    }
+
+    fn can_send(&mut self) -> bool {
+        match self {
+            Self::Loopback(_) => true,
+            Self::Ethernet(dev) => dev.can_transmit(),
+        }
+    }
+
+    fn receive(&mut self, now: Instant) -> Option<RxPacket> {
+        match self {
+            Self::Loopback(dev) => dev.receive().map(RxPacket::Loopback),
+            Self::Ethernet(dev) => dev.receive(now).map(RxPacket::Ethernet),
+        }
+    }
+
+    /// Gives the buffer of a received packet back to the device.
+    fn recycle(&mut self, packet: RxPacket) {
+        match (self, packet) {
+            (Self::Ethernet(dev), RxPacket::Ethernet(frame)) => dev.recycle(frame),
+            (_, RxPacket::Loopback(_)) => {}
+            _ => unreachable!("packet from another device"),
+        }
+    }
+
+    fn send(&mut self, next_hop: IpAddress, packet: &[u8], now: Instant) {
+        match self {
+            Self::Loopback(dev) => dev.send(packet),
+            Self::Ethernet(dev) => dev.send(next_hop, packet, now),
+        }
+    }
+}
+
+/// A packet received from a device, which is in the buffer of the device.
+pub(crate) enum RxPacket {
+    Loopback(Vec<u8>),
+    Ethernet(RxFrame),
+}
+
+impl RxPacket {
+    fn as_slice(&self) -> &[u8] {
+        match self {
+            Self::Loopback(packet) => packet,
+            Self::Ethernet(frame) => frame.payload(),
+        }
+    }
+
+    fn as_mut_slice(&mut self) -> &mut [u8] {
+        match self {
+            Self::Loopback(packet) => packet,
+            Self::Ethernet(frame) => frame.payload_mut(),
+        }
+    }
+}
+
+/// An entry of the routing table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Route {
+    /// Destinations of the route.
+    pub cidr: IpCidr,
+    /// The gateway, or `None` if the destinations are on the link.
+    pub via: Option<IpAddress>,
+    /// Index of the device to send the packets to.
+    pub dev: usize,
+}
+
+/// The device of the interface, which routes packets among the devices.
+pub(crate) struct Router {
+    /// Use `RefCell` is enough since it's wrapped in `Mutex`. The packets
+    /// are sent while a received packet is processed, so the devices are
+    /// borrowed only to receive, recycle or send a packet.
+    devices: Vec<RefCell<NetDevice>>,
+    /// Addresses of the devices, with the indices of the devices.
+    addrs: Vec<(usize, IpCidr)>,
+    routes: Vec<Route>,
+    /// The buffer of outgoing packets, which is reused for every packet.
+    tx_buf: RefCell<Vec<u8>>,
+    now: Instant,
+}
+
+impl Router {
+    /// Creates a router with only the loopback device.
+    pub fn new() -> Self {
+        Self {
+            devices: vec![RefCell::new(NetDevice::Loopback(LoopbackDevice::new()))],
+            addrs: Vec::new(),
+            routes: Vec::new(),
+            tx_buf: RefCell::new(Vec::with_capacity(STANDARD_MTU)),
+            now: Instant::ZERO,
+        }
+    }
+
+    /// Adds a device, and returns its index.
+    pub fn add_device(&mut self, dev: NetDevice) -> usize {
+        self.devices.push(RefCell::new(dev));
+        self.devices.len() - 1
+    }
+
+    pub fn device(&self, dev: usize) -> Ref<'_, NetDevice> {
+        self.devices[dev].borrow()
+    }
+
+    pub fn devices(&self) -> impl Iterator<Item = Ref<'_, NetDevice>> {
+        self.devices.iter().map(RefCell::borrow)
+    }
+
+    /// Returns the index of the device named `name`.
+    pub fn find_device(&self, name: &str) -> Option<usize> {
+        self.devices().position(|dev| dev.name() == name)
+    }
+
+    /// Returns the device `dev` if it is an Ethernet device.
+    #[cfg_attr(not(feature = "dhcp"), allow(dead_code))]
+    pub fn ethernet_mut(&mut self, dev: usize) -> Option<&mut EthernetDevice> {
+        match self.devices[dev].get_mut() {
+            NetDevice::Ethernet(dev) => Some(dev),
+            _ => None,
+        }
+    }
+
+    /// Returns the first Ethernet device, if any.
+    pub fn first_ethernet_mut(&mut self) -> Option<&mut EthernetDevice> {
+        self.devices.iter_mut().find_map(|dev| match dev.get_mut() {
+            NetDevice::Ethernet(dev) => Some(dev),
+            _ => None,
+        })
+    }
+
+    /// Assigns `cidr` to the device `dev`, which adds the route to the
+    /// address itself by the loopback device, and the route to its subnet by
+    /// `dev`.
+    pub fn add_address(&mut self, dev: usize, cidr: IpCidr) {
+        self.devices[dev].get_mut().add_address(cidr);
+        self.addrs.push((dev, cidr));
+        for route in Self::address_routes(dev, cidr) {
+            self.add_route(route);
+        }
+    }
+
+    /// Removes `cidr` from the device `dev`, with the routes added for it.
+    pub fn remove_address(&mut self, dev: usize, cidr: IpCidr) {
+        self.devices[dev].get_mut().remove_address(cidr);
+        self.addrs.retain(|&a| a != (dev, cidr));
+        for route in Self::address_routes(dev, cidr) {
+            self.remove_route(route);
+        }
+    }
+
+    pub fn addresses(&self) -> &[(usize, IpCidr)] {
+        &self.addrs
+    }
+
+    /// Returns the address of the device `dev` to send packets to `dst`
+    /// from, which is in the same subnet as `dst` if possible. Link-local
+    /// IPv6 addresses are preferred only for link-local destinations, and
+    /// IPv4 addresses are preferred if `dst` is unknown.
+    pub fn source_address(&self, dev: usize, dst: Option<IpAddress>) -> Option<IpAddress> {
+        let addrs = self.addrs.iter().filter_map(|&(d, cidr)| {
+            let same_version = dst.is_none_or(|dst| dst.version() == cidr.address().version());
+            (d == dev && same_version).then_some(cidr)
+        });
+        let dst_is_link_local = dst.is_some_and(is_link_local);
+        let rank = |cidr: &IpCidr| match dst {
+            Some(dst) if cidr.contains_addr(&dst) => 0,
+            _ if is_link_local(cidr.address()) != dst_is_link_local => 3,
+            _ if matches!(cidr, IpCidr::Ipv4(_)) => 1,
+            _ => 2,
+        };
+        addrs.min_by_key(rank).map(|cidr| cidr.address())
+    }
+
+    /// Returns the address to send packets to `dst` from, which is chosen
+    /// from the device of the route to `dst`.
+    pub fn route_source_address(&self, dst: IpAddress) -> Option<IpAddress> {
+        if self.addrs.iter().any(|(_, cidr)| cidr.address() == dst) {
+            return Some(dst);
+        }
+        let route = self.lookup_by(dst, |_| true)?;
+        self.source_address(route.dev, Some(dst))
+    }
+
+    fn address_routes(dev: usize, cidr: IpCidr) -> [Route; 2] {
+        let (host, subnet) = match cidr {
+            IpCidr::Ipv4(cidr) => (
+                IpCidr::new(cidr.address().into(), 32),
+                cidr.network().into(),
+            ),
+            IpCidr::Ipv6(cidr) => (
+                IpCidr::new(cidr.address().into(), 128),
+                ipv6_network(cidr).into(),
+            ),
+        };
+        [
+            Route {
+                cidr: host,
+                via: None,
+                dev: LOOPBACK,
+            },
+            Route {
+                cidr: subnet,
+                via: None,
+                dev,
+            },
+        ]
+    }
+
+    pub fn add_route(&mut self, route: Route) {
+        self.routes.push(route);
+    }
+
+    /// Removes a route that is equal to `route`. Returns whether it exists.
+    pub fn remove_route(&mut self, route: Route) -> bool {
+        match self.routes.iter().position(|r| *r == route) {
+            Some(i) => {
+                self.routes.remove(i);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn routes(&self) -> &[Route] {
+        &self.routes
+    }
+
+    /// Returns the route to `dst` with the longest prefix, among the routes
+    /// that satisfy `f`.
+    fn lookup_by(&self, dst: IpAddress, f: impl Fn(&Route) -> bool) -> Option<&Route> {
+        self.routes
+            .iter()
+            .filter(|route| route.cidr.contains_addr(&dst) && f(route))
+            .max_by_key(|route| route.cidr.prefix_len())
+    }
+
+    /// Returns the route of a packet from `src` to `dst`.
+    ///
+    /// Packets from an address of a device prefer the routes through that
+    /// device, so that sockets bound to the device send packets through it,
+    /// unless they are sent to a local address.
+    pub fn lookup(&self, src: IpAddress, dst: IpAddress) -> Option<&Route> {
+        let route = self.lookup_by(dst, |_| true)?;
+        let src_dev = self
+            .addrs
+            .iter()
+            .find(|(_, cidr)| cidr.address() == src)
+            .map(|&(dev, _)| dev);
+        match src_dev {
+            Some(dev) if dev != LOOPBACK && route.dev != LOOPBACK && route.dev != dev => self
+                .lookup_by(dst, |route| route.dev == dev)
+                .or(Some(route)),
+            _ => Some(route),
+        }
+    }
+
+    /// Receives a packet from the device `dev`. Martian packets are dropped.
+    fn receive_from(dev: usize, device: &mut NetDevice, now: Instant) -> Option<RxPacket> {
+        loop {
+            let packet = device.receive(now)?;
+            let addrs = packet_addrs(packet.as_slice());
+            if dev == LOOPBACK || !addrs.is_some_and(|(src, dst)| is_martian(src, dst)) {
+                return Some(packet);
+            }
+            debug!("router: dropping a martian packet on {}", device.name());
+            device.recycle(packet);
+        }
+    }
+
+    fn send(&self, packet: &[u8]) {
+        let Some((src, dst)) = packet_addrs(packet) else {
+            warn!("router: dropping a malformed packet");
+            return;
+        };
+        let Some(&route) = self.lookup(src, dst) else {
+            debug!("router: no route to {}", dst);
+            return;
+        };
+        if route.dev != LOOPBACK && is_martian(src, dst) {
+            debug!("router: dropping a martian packet from {} to {}", src, dst);
+            return;
+        }
+        let next_hop = route.via.unwrap_or(dst);
+        let mut dev = self.devices[route.dev].borrow_mut();
+        if !dev.can_send() {
+            debug!(
+                "router: {} is busy, dropping a packet to {}",
+                dev.name(),
+                dst
+            );
+            return;
+        }
+        trace!("router: {} via {} on {}", dst, next_hop, dev.name());
+        dev.send(next_hop, packet, self.now);
+    }
+}
+
+impl Device for Router {
+    type RxToken<'a>
+        = RouterRxToken<'a>
+    where
+        Self: 'a;
+    type TxToken<'a>
+        = RouterTxToken<'a>
+    where
+        Self: 'a;
+
+    fn receive(&mut self, timestamp: Instant) -> Option<(Self::RxToken<'_>, Self::TxToken<'_>)> {
+        self.now = timestamp;
+        let (dev, packet) = self
+            .devices
+            .iter_mut()
+            .enumerate()
+            .find_map(|(i, dev)| Some((i, Self::receive_from(i, dev.get_mut(), timestamp)?)))?;
+        let this = &*self;
+        let rx_token = RouterRxToken {
+            device: &this.devices[dev],
+            dev,
+            packet,
+        };
+        Some((rx_token, RouterTxToken(this)))
+    }
+
+    /// Packets are checked against the device they are routed to when they
+    /// are sent, which is unknown yet.
+    fn transmit(&mut self, timestamp: Instant) -> Option<Self::TxToken<'_>> {
+        self.now = timestamp;
+        Some(RouterTxToken(self))
+    }
+
+    fn capabilities(&self) -> DeviceCapabilities {
+        let mut caps = DeviceCapabilities::default();
+        caps.max_transmission_unit = STANDARD_MTU;
+        caps.max_burst_size = None;
+        caps.medium = Medium::Ip;
+        caps
+    }
+}
+
+/// Returns the source and destination addresses of the IP packet.
+fn packet_addrs(packet: &[u8]) -> Option<(IpAddress, IpAddress)> {
+    match IpVersion::of_packet(packet).ok()? {
+        IpVersion::Ipv4 => {
+            let packet = Ipv4Packet::new_checked(packet).ok()?;
+            Some((packet.src_addr().into(), packet.dst_addr().into()))
+        }
+        IpVersion::Ipv6 => {
+            let packet = Ipv6Packet::new_checked(packet).ok()?;
+            Some((packet.src_addr().into(), packet.dst_addr().into()))
+        }
+    }
+}
+
+/// Returns whether a packet from `src` to `dst` is martian if it is not on
+/// the loopback device.
+fn is_martian(src: IpAddress, dst: IpAddress) -> bool {
+    is_loopback(src) || is_loopback(dst)
+}
+
+/// Returns the subnet of `cidr`, with the host part of the address cleared.
+pub(crate) fn ipv6_network(cidr: Ipv6Cidr) -> Ipv6Cidr {
+    let mut addr = cidr.address().0;
+    for (i, b) in addr.iter_mut().enumerate() {
+        let bits = (cidr.prefix_len() as usize).saturating_sub(i * 8).min(8);
+        *b &= !(0xffu8.checked_shr(bits as u32).unwrap_or(0));
+    }
+    Ipv6Cidr::new(Ipv6Address(addr), cidr.prefix_len())
+}
+
+/// A received packet, with the device it comes from and its index.
+pub(crate) struct RouterRxToken<'a> {
+    device: &'a RefCell<NetDevice>,
+    dev: usize,
+    packet: RxPacket,
+}
+pub(crate) struct RouterTxToken<'a>(&'a Router);
+
+impl RxToken for RouterRxToken<'_> {
+    fn preprocess(&self, sockets: &mut SocketSet<'_>) {
+        snoop_tcp_packet(self.packet.as_slice(), self.dev, sockets).ok();
+    }
+
+    fn consume<R, F>(mut self, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        // the device is not borrowed by `f`, which may send a reply to it
+        let ret = f(self.packet.as_mut_slice());
+        self.device.borrow_mut().recycle(self.packet);
+        ret
+    }
+}
+
+impl TxToken for RouterTxToken<'_> {
+    fn consume<R, F>(self, len: usize, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        let mut buf = self.0.tx_buf.borrow_mut();
+        buf.clear();
+        buf.resize(len, 0);
+        let ret = f(&mut buf);
+        self.0.send(&buf);
+        ret
+    }
+}
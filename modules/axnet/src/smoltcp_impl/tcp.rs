@@ -11,7 +11,7 @@ use smoltcp::socket::tcp::{self, ConnectError, State};
 use smoltcp::wire::{IpEndpoint, IpListenEndpoint};
 
 use super::addr::{UNSPECIFIED_ENDPOINT, from_core_sockaddr, into_core_sockaddr, is_unspecified};
-use super::{ETH0, LISTEN_TABLE, SOCKET_SET, SocketSetWrapper};
+use super::{IFACE, LISTEN_TABLE, SOCKET_SET, SocketSetWrapper};
 
 // State transitions:
 // CLOSED -(connect)-> BUSY -> CONNECTING -> CONNECTED -(shutdown)-> BUSY -> CLOSED
@@ -40,6 +40,8 @@ pub struct TcpSocket {
     handle: UnsafeCell<Option<SocketHandle>>,
     local_addr: UnsafeCell<IpEndpoint>,
     peer_addr: UnsafeCell<IpEndpoint>,
+    /// The device that the socket is bound to, if any.
+    device: UnsafeCell<Option<usize>>,
     nonblock: AtomicBool,
 }
 
@@ -53,6 +55,7 @@ impl TcpSocket {
             handle: UnsafeCell::new(None),
             local_addr: UnsafeCell::new(UNSPECIFIED_ENDPOINT),
             peer_addr: UnsafeCell::new(UNSPECIFIED_ENDPOINT),
+            device: UnsafeCell::new(None),
             nonblock: AtomicBool::new(false),
         }
     }
@@ -68,6 +71,7 @@ impl TcpSocket {
             handle: UnsafeCell::new(Some(handle)),
             local_addr: UnsafeCell::new(local_addr),
             peer_addr: UnsafeCell::new(peer_addr),
+            device: UnsafeCell::new(None),
             nonblock: AtomicBool::new(false),
         }
     }
@@ -126,8 +130,15 @@ impl TcpSocket {
 
             // TODO: check remote addr unreachable
             let remote_endpoint = from_core_sockaddr(remote_addr);
-            let bound_endpoint = self.bound_endpoint()?;
-            let iface = &ETH0.iface;
+            let mut bound_endpoint = self.bound_endpoint()?;
+            if bound_endpoint.addr.is_none() {
+                // SAFETY: no other threads can read or write `self.device`.
+                bound_endpoint.addr = match unsafe { self.device.get().read() } {
+                    Some(dev) => Some(IFACE.source_address(dev, Some(remote_endpoint.addr))?),
+                    None => IFACE.route_source_address(remote_endpoint.addr),
+                };
+            }
+            let iface = &IFACE.iface;
             let (local_endpoint, remote_endpoint) = SOCKET_SET
                 .with_socket_mut::<tcp::Socket, _, _>(handle, |socket| {
                     socket
@@ -199,6 +210,31 @@ impl TcpSocket {
         .unwrap_or_else(|_| ax_err!(InvalidInput, "socket bind() failed: already bound"))
     }
 
+    /// Binds the socket to the network interface named `iface`, or unbinds it
+    /// if `iface` is `None`, like `SO_BINDTODEVICE` of Linux.
+    ///
+    /// The socket only sends packets through the interface, and only accepts
+    /// connections from it. It must be called before [`connect`] and
+    /// [`listen`].
+    ///
+    /// [`connect`]: Self::connect
+    /// [`listen`]: Self::listen
+    pub fn bind_device(&self, iface: Option<&str>) -> AxResult {
+        self.update_state(STATE_CLOSED, STATE_CLOSED, || {
+            let device = iface.map(|name| IFACE.find_device(name)).transpose()?;
+            // SAFETY: no other threads can read or write `self.device` as we
+            // have changed the state to `BUSY`.
+            unsafe { self.device.get().write(device) };
+            Ok(())
+        })
+        .unwrap_or_else(|_| {
+            ax_err!(
+                InvalidInput,
+                "socket bind_device() failed: already connected"
+            )
+        })
+    }
+
     /// Starts listening on the bound address and port.
     ///
     /// It's must be called after [`bind`](Self::bind) and before
@@ -209,7 +245,8 @@ impl TcpSocket {
             unsafe {
                 (*self.local_addr.get()).port = bound_endpoint.port;
             }
-            LISTEN_TABLE.listen(bound_endpoint)?;
+            let device = unsafe { self.device.get().read() };
+            LISTEN_TABLE.listen(bound_endpoint, device)?;
             debug!("TCP socket listening on {}", bound_endpoint);
             Ok(())
         })
@@ -0,0 +1,273 @@
+use smoltcp::phy::ChecksumCapabilities;
+use smoltcp::time::{Duration, Instant};
+use smoltcp::wire::{
+    ArpOperation, ArpPacket, ArpRepr, EthernetAddress, EthernetProtocol, Icmpv6Packet, Icmpv6Repr,
+    IpCidr, IpProtocol, Ipv4Address, Ipv6Address, Ipv6Packet, NdiscNeighborFlags, NdiscRepr,
+};
+
+use super::ethernet::link_local_address;
+use super::neighbor::{
+    Link, MAX_PENDING, NEIGHBOR_TTL, Neighbors, PENDING_TIMEOUT, REQUEST_INTERVAL, ndisc_packet,
+    solicited_node,
+};
+
+const OUR_MAC: EthernetAddress = EthernetAddress([0x52, 0x54, 0x00, 0x12, 0x34, 0x56]);
+const PEER_MAC: EthernetAddress = EthernetAddress([0x52, 0x54, 0x00, 0x12, 0x34, 0x57]);
+const OUR_IP: Ipv4Address = Ipv4Address([10, 0, 2, 15]);
+const PEER_IP: Ipv4Address = Ipv4Address([10, 0, 2, 2]);
+const OTHER_IP: Ipv4Address = Ipv4Address([10, 0, 2, 3]);
+
+type Frame = (EthernetAddress, EthernetProtocol, Vec<u8>);
+
+/// A link that keeps the frames sent to it.
+#[derive(Default)]
+struct TestLink(Vec<Frame>);
+
+impl Link for TestLink {
+    fn send_frame(&mut self, dst: EthernetAddress, ethertype: EthernetProtocol, payload: &[u8]) {
+        self.0.push((dst, ethertype, payload.to_vec()));
+    }
+}
+
+impl TestLink {
+    /// Takes the frames sent so far.
+    fn take(&mut self) -> Vec<Frame> {
+        core::mem::take(&mut self.0)
+    }
+}
+
+fn our_ip6() -> Ipv6Address {
+    link_local_address(OUR_MAC).address()
+}
+
+fn peer_ip6() -> Ipv6Address {
+    link_local_address(PEER_MAC).address()
+}
+
+fn new_neighbors() -> (Neighbors, TestLink) {
+    let mut neighbors = Neighbors::new(OUR_MAC);
+    neighbors.add_address(IpCidr::new(OUR_IP.into(), 24));
+    neighbors.add_address(link_local_address(OUR_MAC).into());
+    (neighbors, TestLink::default())
+}
+
+fn arp_packet(
+    operation: ArpOperation,
+    src: (EthernetAddress, Ipv4Address),
+    dst: Ipv4Address,
+) -> Vec<u8> {
+    let repr = ArpRepr::EthernetIpv4 {
+        operation,
+        source_hardware_addr: src.0,
+        source_protocol_addr: src.1,
+        target_hardware_addr: OUR_MAC,
+        target_protocol_addr: dst,
+    };
+    let mut buf = vec![0; repr.buffer_len()];
+    repr.emit(&mut ArpPacket::new_unchecked(&mut buf[..]));
+    buf
+}
+
+fn parse_arp(payload: &[u8]) -> ArpRepr {
+    ArpRepr::parse(&ArpPacket::new_checked(payload).unwrap()).unwrap()
+}
+
+fn ip_frame(dst: EthernetAddress, payload: &[u8]) -> Frame {
+    (dst, EthernetProtocol::Ipv4, payload.to_vec())
+}
+
+#[test]
+fn test_arp_resolution() {
+    let (mut neighbors, mut link) = new_neighbors();
+    let now = Instant::ZERO;
+
+    // one request is sent for the packets waiting for the same neighbor
+    neighbors.send(&mut link, PEER_IP.into(), b"first", now);
+    neighbors.send(&mut link, PEER_IP.into(), b"second", now);
+    let frames = link.take();
+    assert_eq!(frames.len(), 1);
+    assert_eq!(
+        (frames[0].0, frames[0].1),
+        (EthernetAddress::BROADCAST, EthernetProtocol::Arp)
+    );
+    assert_eq!(
+        parse_arp(&frames[0].2),
+        ArpRepr::EthernetIpv4 {
+            operation: ArpOperation::Request,
+            source_hardware_addr: OUR_MAC,
+            source_protocol_addr: OUR_IP,
+            target_hardware_addr: EthernetAddress::BROADCAST,
+            target_protocol_addr: PEER_IP,
+        }
+    );
+
+    // they are sent in order when the reply comes, and later packets at once
+    let reply = arp_packet(ArpOperation::Reply, (PEER_MAC, PEER_IP), OUR_IP);
+    neighbors.process_arp(&mut link, &reply, now);
+    neighbors.send(&mut link, PEER_IP.into(), b"third", now);
+    assert_eq!(
+        link.take(),
+        [
+            ip_frame(PEER_MAC, b"first"),
+            ip_frame(PEER_MAC, b"second"),
+            ip_frame(PEER_MAC, b"third"),
+        ]
+    );
+
+    // announcements of neighbors that are not requested are ignored
+    let announce = arp_packet(ArpOperation::Reply, (PEER_MAC, OTHER_IP), OTHER_IP);
+    neighbors.process_arp(&mut link, &announce, now);
+    neighbors.send(&mut link, OTHER_IP.into(), b"other", now);
+    let frames = link.take();
+    assert_eq!(frames.len(), 1);
+    assert_eq!(frames[0].1, EthernetProtocol::Arp);
+
+    // requests for our address are answered, and the sender is resolved
+    let (mut neighbors, mut link) = new_neighbors();
+    let request = arp_packet(ArpOperation::Request, (PEER_MAC, PEER_IP), OUR_IP);
+    neighbors.process_arp(&mut link, &request, now);
+    neighbors.send(&mut link, PEER_IP.into(), b"packet", now);
+    let frames = link.take();
+    assert_eq!(frames.len(), 2);
+    assert_eq!(
+        (frames[0].0, frames[0].1),
+        (PEER_MAC, EthernetProtocol::Arp)
+    );
+    assert_eq!(
+        parse_arp(&frames[0].2),
+        ArpRepr::EthernetIpv4 {
+            operation: ArpOperation::Reply,
+            source_hardware_addr: OUR_MAC,
+            source_protocol_addr: OUR_IP,
+            target_hardware_addr: PEER_MAC,
+            target_protocol_addr: PEER_IP,
+        }
+    );
+    assert_eq!(frames[1], ip_frame(PEER_MAC, b"packet"));
+}
+
+#[test]
+fn test_neighbor_timeout() {
+    let (mut neighbors, mut link) = new_neighbors();
+    let start = Instant::ZERO;
+
+    // requests are not repeated within the interval
+    neighbors.send(&mut link, PEER_IP.into(), b"lost", start);
+    neighbors.send(
+        &mut link,
+        PEER_IP.into(),
+        b"lost",
+        start + Duration::from_millis(500),
+    );
+    assert_eq!(link.take().len(), 1);
+
+    // packets are dropped if the neighbor is not resolved in time
+    let now = start + PENDING_TIMEOUT + REQUEST_INTERVAL;
+    neighbors.send(&mut link, PEER_IP.into(), b"sent", now);
+    let frames = link.take();
+    assert_eq!(frames.len(), 1);
+    assert_eq!(frames[0].1, EthernetProtocol::Arp);
+    let reply = arp_packet(ArpOperation::Reply, (PEER_MAC, PEER_IP), OUR_IP);
+    neighbors.process_arp(&mut link, &reply, now);
+    assert_eq!(link.take(), [ip_frame(PEER_MAC, b"sent")]);
+
+    // resolved neighbors expire
+    neighbors.send(
+        &mut link,
+        PEER_IP.into(),
+        b"sent",
+        now + NEIGHBOR_TTL - REQUEST_INTERVAL,
+    );
+    assert_eq!(link.take(), [ip_frame(PEER_MAC, b"sent")]);
+    neighbors.send(&mut link, PEER_IP.into(), b"waiting", now + NEIGHBOR_TTL);
+    let frames = link.take();
+    assert_eq!(frames.len(), 1);
+    assert_eq!(frames[0].1, EthernetProtocol::Arp);
+}
+
+#[test]
+fn test_max_pending() {
+    let (mut neighbors, mut link) = new_neighbors();
+    let now = Instant::ZERO;
+
+    // the oldest packet is dropped when too many are waiting
+    for i in 0..=MAX_PENDING {
+        neighbors.send(&mut link, PEER_IP.into(), &[i as u8], now);
+    }
+    link.take();
+    let reply = arp_packet(ArpOperation::Reply, (PEER_MAC, PEER_IP), OUR_IP);
+    neighbors.process_arp(&mut link, &reply, now);
+    let sent: Vec<_> = link
+        .take()
+        .iter()
+        .map(|frame| frame.2[0] as usize)
+        .collect();
+    assert_eq!(sent, (1..=MAX_PENDING).collect::<Vec<_>>());
+}
+
+#[test]
+fn test_ndisc_hop_limit() {
+    let (mut neighbors, mut link) = new_neighbors();
+    let now = Instant::ZERO;
+
+    neighbors.send(&mut link, peer_ip6().into(), b"packet", now);
+    let frames = link.take();
+    assert_eq!(frames.len(), 1);
+    assert_eq!(frames[0].1, EthernetProtocol::Ipv6);
+    let packet = Ipv6Packet::new_checked(&frames[0].2[..]).unwrap();
+    assert_eq!(packet.dst_addr(), solicited_node(peer_ip6()));
+    assert_eq!(packet.hop_limit(), 255);
+
+    // NDP packets from other links are taken, but ignored
+    let advert = |hop_limit| {
+        let repr = NdiscRepr::NeighborAdvert {
+            flags: NdiscNeighborFlags::SOLICITED | NdiscNeighborFlags::OVERRIDE,
+            target_addr: peer_ip6(),
+            lladdr: Some(PEER_MAC.into()),
+        };
+        ndisc_packet(peer_ip6(), our_ip6(), hop_limit, repr)
+    };
+    assert!(neighbors.process_ndisc(&mut link, &advert(64), now));
+    assert!(link.take().is_empty());
+    assert!(neighbors.process_ndisc(&mut link, &advert(255), now));
+    assert_eq!(
+        link.take(),
+        [(PEER_MAC, EthernetProtocol::Ipv6, b"packet".to_vec())]
+    );
+
+    let solicit = |hop_limit| {
+        let repr = NdiscRepr::NeighborSolicit {
+            target_addr: our_ip6(),
+            lladdr: Some(PEER_MAC.into()),
+        };
+        ndisc_packet(peer_ip6(), solicited_node(our_ip6()), hop_limit, repr)
+    };
+    assert!(neighbors.process_ndisc(&mut link, &solicit(64), now));
+    assert!(link.take().is_empty());
+    assert!(neighbors.process_ndisc(&mut link, &solicit(255), now));
+    let frames = link.take();
+    assert_eq!(frames.len(), 1);
+    assert_eq!(
+        (frames[0].0, frames[0].1),
+        (PEER_MAC, EthernetProtocol::Ipv6)
+    );
+    let packet = Ipv6Packet::new_checked(&frames[0].2[..]).unwrap();
+    assert_eq!(packet.dst_addr(), peer_ip6());
+    assert_eq!(packet.hop_limit(), 255);
+    let icmp = Icmpv6Packet::new_checked(packet.payload()).unwrap();
+    let repr = Icmpv6Repr::parse(
+        &our_ip6().into(),
+        &peer_ip6().into(),
+        &icmp,
+        &ChecksumCapabilities::default(),
+    );
+    assert!(matches!(
+        repr,
+        Ok(Icmpv6Repr::Ndisc(NdiscRepr::NeighborAdvert { target_addr, .. })) if target_addr == our_ip6()
+    ));
+
+    // other IPv6 packets are passed to the interface
+    let mut packet = advert(255);
+    Ipv6Packet::new_unchecked(&mut packet[..]).set_next_header(IpProtocol::Udp);
+    assert!(!neighbors.process_ndisc(&mut link, &packet, now));
+}
@@ -11,13 +11,15 @@ use smoltcp::socket::udp::{self, BindError, SendError};
 use smoltcp::wire::{IpEndpoint, IpListenEndpoint};
 
 use super::addr::{UNSPECIFIED_ENDPOINT, from_core_sockaddr, into_core_sockaddr, is_unspecified};
-use super::{SOCKET_SET, SocketSetWrapper};
+use super::{IFACE, SOCKET_SET, SocketSetWrapper};
 
 /// A UDP socket that provides POSIX-like APIs.
 pub struct UdpSocket {
     handle: SocketHandle,
     local_addr: RwLock<Option<IpEndpoint>>,
     peer_addr: RwLock<Option<IpEndpoint>>,
+    /// The device that the socket is bound to, if any.
+    device: RwLock<Option<usize>>,
     nonblock: AtomicBool,
 }
 
@@ -31,6 +33,7 @@ impl UdpSocket {
             handle,
             local_addr: RwLock::new(None),
             peer_addr: RwLock::new(None),
+            device: RwLock::new(None),
             nonblock: AtomicBool::new(false),
         }
     }
@@ -83,7 +86,12 @@ impl UdpSocket {
             return ax_err!(InvalidInput, "socket bind() failed: already bound");
         }
 
-        let local_endpoint = from_core_sockaddr(local_addr);
+        let mut local_endpoint = from_core_sockaddr(local_addr);
+        if let Some(dev) = *self.device.read() {
+            if is_unspecified(local_endpoint.addr) {
+                local_endpoint.addr = IFACE.source_address(dev, None)?;
+            }
+        }
         let endpoint = IpListenEndpoint {
             addr: (!is_unspecified(local_endpoint.addr)).then_some(local_endpoint.addr),
             port: local_endpoint.port,
@@ -100,6 +108,20 @@ impl UdpSocket {
         Ok(())
     }
 
+    /// Binds the socket to the network interface named `iface`, or unbinds it
+    /// if `iface` is `None`, like `SO_BINDTODEVICE` of Linux.
+    ///
+    /// The socket is bound to an address of the interface instead of the
+    /// unspecified address, so it must be called before [`bind`](Self::bind).
+    pub fn bind_device(&self, iface: Option<&str>) -> AxResult {
+        let local_addr = self.local_addr.read();
+        if local_addr.is_some() {
+            return ax_err!(InvalidInput, "socket bind_device() failed: already bound");
+        }
+        *self.device.write() = iface.map(|name| IFACE.find_device(name)).transpose()?;
+        Ok(())
+    }
+
     /// Sends data on the socket to the given address. On success, returns the
     /// number of bytes written.
     pub fn send_to(&self, buf: &[u8], remote_addr: SocketAddr) -> AxResult<usize> {
@@ -0,0 +1,80 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use axdriver::AxDeviceContainer;
+use axio::Result;
+use axnet::{TcpSocket, UdpSocket};
+
+const LOCALHOST: IpAddr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+const LOCALHOST6: IpAddr = IpAddr::V6(Ipv6Addr::LOCALHOST);
+
+fn test_interfaces() {
+    // only the loopback interface without NICs
+    assert_eq!(axnet::interfaces(), ["lo"]);
+    let mut addrs = axnet::interface_addrs("lo").unwrap();
+    addrs.sort();
+    assert_eq!(addrs, [(LOCALHOST, 8), (LOCALHOST6, 128)]);
+    assert!(axnet::routes().iter().all(|route| route.iface == "lo"));
+
+    println!("test_interfaces() OK!");
+}
+
+fn test_udp(ip: IpAddr) -> Result<()> {
+    let server = UdpSocket::new();
+    server.bind(SocketAddr::new(ip, 5555))?;
+    let client = UdpSocket::new();
+    client.bind(SocketAddr::new(ip, 0))?;
+    let client_addr = client.local_addr()?;
+
+    let mut buf = [0; 64];
+    client.send_to(b"ping", SocketAddr::new(ip, 5555))?;
+    let (n, from) = server.recv_from(&mut buf)?;
+    assert_eq!((&buf[..n], from), (&b"ping"[..], client_addr));
+
+    server.send_to(b"pong", from)?;
+    let (n, from) = client.recv_from(&mut buf)?;
+    assert_eq!((&buf[..n], from), (&b"pong"[..], SocketAddr::new(ip, 5555)));
+
+    println!("test_udp({}) OK!", ip);
+    Ok(())
+}
+
+fn test_tcp(ip: IpAddr) -> Result<()> {
+    let listener = TcpSocket::new();
+    listener.bind(SocketAddr::new(ip, 8080))?;
+    listener.listen()?;
+
+    // the handshake is done while the client polls the interface
+    let client = TcpSocket::new();
+    client.connect(SocketAddr::new(ip, 8080))?;
+    let server = listener.accept()?;
+    assert_eq!(server.peer_addr()?, client.local_addr()?);
+
+    let mut buf = [0; 64];
+    client.send(b"hello")?;
+    let n = server.recv(&mut buf)?;
+    assert_eq!(&buf[..n], b"hello");
+    server.send(b"world")?;
+    let n = client.recv(&mut buf)?;
+    assert_eq!(&buf[..n], b"world");
+
+    // the peer sees the end of the stream after a shutdown
+    client.shutdown()?;
+    assert_eq!(server.recv(&mut buf)?, 0);
+
+    println!("test_tcp({}) OK!", ip);
+    Ok(())
+}
+
+#[test]
+fn test_loopback() {
+    println!("Testing the loopback interface ...");
+
+    axtask::init_scheduler(); // call this to use `axsync::Mutex`.
+    axnet::init_network(AxDeviceContainer::default());
+
+    test_interfaces();
+    test_udp(LOCALHOST).expect("test UDP over IPv4 failed");
+    test_udp(LOCALHOST6).expect("test UDP over IPv6 failed");
+    test_tcp(LOCALHOST).expect("test TCP over IPv4 failed");
+    test_tcp(LOCALHOST6).expect("test TCP over IPv6 failed");
+}
@@ -168,6 +168,9 @@ pub extern "C" fn rust_main(cpu_id: usize, dtb: usize) -> ! {
         axdisplay::init_display(all_devices.display);
     }
 
+    #[cfg(feature = "fs")]
+    register_tunables();
+
     #[cfg(feature = "smp")]
     self::mp::start_secondary_cpus(cpu_id);
 
@@ -194,6 +197,11 @@ pub extern "C" fn rust_main(cpu_id: usize, dtb: usize) -> ! {
 
     unsafe { main() };
 
+    #[cfg(feature = "fs")]
+    if let Err(e) = axfs::api::sync() {
+        warn!("failed to sync filesystems: {:?}", e);
+    }
+
     #[cfg(feature = "multitask")]
     axtask::exit(0);
     #[cfg(not(feature = "multitask"))]
@@ -203,6 +211,36 @@ pub extern "C" fn rust_main(cpu_id: usize, dtb: usize) -> ! {
     }
 }
 
+/// Registers the tunables of kernel modules, which appear in `/sys`.
+#[cfg(feature = "fs")]
+fn register_tunables() {
+    use axfs::api::{Tunable, register_tunable};
+
+    let register = |path, tunable| {
+        if let Err(e) = register_tunable(path, tunable) {
+            warn!("failed to register tunable {}: {:?}", path, e);
+        }
+    };
+    register(
+        "kernel/log_level",
+        Tunable::choice(
+            &["off", "error", "warn", "info", "debug", "trace"],
+            axlog::max_level,
+            axlog::set_max_level,
+        ),
+    );
+    #[cfg(feature = "multitask")]
+    register(
+        "kernel/sched/timeslice",
+        Tunable::integer(axtask::time_slice, axtask::set_time_slice, 1..=1000),
+    );
+    #[cfg(feature = "net")]
+    register(
+        "kernel/net/somaxconn",
+        Tunable::integer(axnet::listen_backlog, axnet::set_listen_backlog, 1..=65535),
+    );
+}
+
 #[cfg(feature = "alloc")]
 fn init_allocator() {
     use axhal::mem::{MemRegionFlags, memory_regions, phys_to_virt};
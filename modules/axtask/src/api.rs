@@ -10,7 +10,7 @@ use kernel_guard::NoPreemptIrqSave;
 pub(crate) use crate::run_queue::{current_run_queue, select_run_queue};
 
 #[doc(cfg(feature = "multitask"))]
-pub use crate::task::{CurrentTask, TaskId, TaskInner};
+pub use crate::task::{CurrentTask, TaskId, TaskInner, all_tasks, get_task};
 #[doc(cfg(feature = "multitask"))]
 pub use crate::task_ext::{TaskExtMut, TaskExtRef};
 #[doc(cfg(feature = "multitask"))]
@@ -29,7 +29,9 @@ pub type AxCpuMask = cpumask::CpuMask<{ axconfig::SMP }>;
 
 cfg_if::cfg_if! {
     if #[cfg(feature = "sched_rr")] {
-        const MAX_TIME_SLICE: usize = 5;
+        // Time slices are accounted by run queues instead, so that they can
+        // be changed at runtime by `set_time_slice`.
+        const MAX_TIME_SLICE: usize = isize::MAX as usize;
         pub(crate) type AxTask = scheduler::RRTask<TaskInner, MAX_TIME_SLICE>;
         pub(crate) type Scheduler = scheduler::RRScheduler<TaskInner, MAX_TIME_SLICE>;
     } else if #[cfg(feature = "sched_cfs")] {
@@ -184,6 +186,21 @@ pub fn set_current_affinity(cpumask: AxCpuMask) -> bool {
     }
 }
 
+/// Returns the time slice of tasks in timer ticks, after which the running
+/// task is preempted if other tasks are ready.
+///
+/// Only the round-robin scheduler (`sched_rr`) preempts tasks by time slices.
+pub fn time_slice() -> usize {
+    crate::run_queue::TIME_SLICE.load(core::sync::atomic::Ordering::Relaxed)
+}
+
+/// Sets the time slice of tasks to `ticks` timer ticks, at least 1.
+///
+/// See [`time_slice`] for details.
+pub fn set_time_slice(ticks: usize) {
+    crate::run_queue::TIME_SLICE.store(ticks.max(1), core::sync::atomic::Ordering::Relaxed);
+}
+
 /// Current task gives up the CPU time voluntarily, and switches to another
 /// ready task.
 pub fn yield_now() {
@@ -1,6 +1,7 @@
 use alloc::collections::VecDeque;
 use alloc::sync::Arc;
 use core::mem::MaybeUninit;
+use core::sync::atomic::AtomicUsize;
 
 #[cfg(feature = "smp")]
 use alloc::sync::Weak;
@@ -29,6 +30,10 @@ macro_rules! percpu_static {
     };
 }
 
+/// Number of timer ticks a task runs before it is preempted, if other tasks
+/// are ready. See [`crate::set_time_slice`].
+pub(crate) static TIME_SLICE: AtomicUsize = AtomicUsize::new(5);
+
 percpu_static! {
     RUN_QUEUE: LazyInit<AxRunQueue> = LazyInit::new(),
     EXITED_TASKS: VecDeque<AxTaskRef> = VecDeque::new(),
@@ -98,7 +103,7 @@ pub(crate) fn current_run_queue<G: BaseGuard>() -> CurrentRunQueueRef<'static, G
 #[allow(clippy::modulo_one)]
 #[inline]
 fn select_run_queue_index(cpumask: AxCpuMask) -> usize {
-    use core::sync::atomic::{AtomicUsize, Ordering};
+    use core::sync::atomic::Ordering;
     static RUN_QUEUE_INDEX: AtomicUsize = AtomicUsize::new(0);
 
     assert!(!cpumask.is_empty(), "No available CPU for task execution");
@@ -186,6 +191,11 @@ pub(crate) struct AxRunQueue {
     /// Since irq and preempt are preserved by the kernel guard hold by `AxRunQueueRef`,
     /// we just use a simple raw spin lock here.
     scheduler: SpinRaw<Scheduler>,
+    /// Number of timer ticks the current task has run since it was last
+    /// picked to run, which are accounted here instead of by the scheduler so that
+    /// [`TIME_SLICE`] can be changed at runtime.
+    #[cfg(feature = "sched_rr")]
+    slice_ticks: usize,
 }
 
 /// A reference to the run queue with specific guard.
@@ -277,6 +287,13 @@ impl<G: BaseGuard> CurrentRunQueueRef<'_, G> {
             #[cfg(feature = "preempt")]
             curr.set_preempt_pending(true);
         }
+        #[cfg(feature = "sched_rr")]
+        if !curr.is_idle() {
+            self.inner.slice_ticks += 1;
+            if self.inner.slice_expired() {
+                curr.set_preempt_pending(true);
+            }
+        }
     }
 
     /// Yield the current task and reschedule.
@@ -342,8 +359,14 @@ impl<G: BaseGuard> CurrentRunQueueRef<'_, G> {
             can_preempt
         );
         if can_preempt {
+            // A task that has used up its time slice goes to the back of the
+            // queue with a new one, otherwise it keeps the rest of it.
+            #[cfg(feature = "sched_rr")]
+            let keep_slice = !self.inner.slice_expired();
+            #[cfg(not(feature = "sched_rr"))]
+            let keep_slice = true;
             self.inner
-                .put_task_with_state(curr.clone(), TaskState::Running, true);
+                .put_task_with_state(curr.clone(), TaskState::Running, keep_slice);
             self.inner.resched();
         } else {
             curr.set_preempt_pending(true);
@@ -455,9 +478,17 @@ impl AxRunQueue {
         Self {
             cpu_id,
             scheduler: SpinRaw::new(scheduler),
+            #[cfg(feature = "sched_rr")]
+            slice_ticks: 0,
         }
     }
 
+    /// Whether the current task has used up its time slice.
+    #[cfg(feature = "sched_rr")]
+    fn slice_expired(&self) -> bool {
+        self.slice_ticks >= TIME_SLICE.load(core::sync::atomic::Ordering::Relaxed)
+    }
+
     /// Puts target task into current run queue with `Ready` state
     /// if its state matches `current_state` (except idle task).
     ///
@@ -536,6 +567,11 @@ impl AxRunQueue {
         #[cfg(feature = "preempt")]
         next_task.set_preempt_pending(false);
         next_task.set_state(TaskState::Running);
+        // a new time slice starts, even if the same task is picked again
+        #[cfg(feature = "sched_rr")]
+        {
+            self.slice_ticks = 0;
+        }
         if prev_task.ptr_eq(&next_task) {
             return;
         }
@@ -128,3 +128,62 @@ fn test_task_join() {
         assert_eq!(tasks[i].join(), Some(i as _));
     }
 }
+
+#[test]
+fn test_task_list() {
+    let _lock = SERIAL.lock();
+    INIT.call_once(axtask::init_scheduler);
+
+    let task = axtask::spawn_raw(axtask::yield_now, "listed".into(), 0x1000);
+    let id = task.id().as_u64();
+    let tasks = axtask::all_tasks();
+    assert!(
+        tasks
+            .iter()
+            .any(|t| t.id() == task.id() && t.name() == "listed")
+    );
+    assert!(
+        tasks
+            .windows(2)
+            .all(|w| w[0].id().as_u64() < w[1].id().as_u64())
+    );
+    assert_eq!(axtask::get_task(id).unwrap().name(), "listed");
+    assert_eq!(task.join(), Some(0));
+}
+
+#[test]
+fn test_time_slice() {
+    let _lock = SERIAL.lock();
+    INIT.call_once(axtask::init_scheduler);
+
+    let old = axtask::time_slice();
+    axtask::set_time_slice(0);
+    assert_eq!(axtask::time_slice(), 1);
+
+    #[cfg(all(feature = "sched_rr", feature = "irq"))]
+    {
+        use core::sync::atomic::AtomicBool;
+        static RAN: AtomicBool = AtomicBool::new(false);
+
+        // a timer tick with IRQs disabled, which preempts the current task
+        // when IRQs are enabled again, if it has used up its time slice
+        let tick = || {
+            let guard = kernel_guard::NoPreemptIrqSave::new();
+            axtask::on_timer_tick();
+            drop(guard);
+        };
+
+        axtask::set_time_slice(3);
+        axtask::yield_now(); // start with a full time slice
+        let task = axtask::spawn(|| RAN.store(true, Ordering::Release));
+        for _ in 0..2 {
+            tick();
+            assert!(!RAN.load(Ordering::Acquire), "preempted too early");
+        }
+        tick();
+        assert!(RAN.load(Ordering::Acquire), "not preempted");
+        assert_eq!(task.join(), Some(0));
+    }
+
+    axtask::set_time_slice(old);
+}
@@ -51,5 +51,10 @@ endef
 define unit_test
   $(call run_cmd,cargo test,-p axfs $(1) $(verbose) -- --nocapture)
   $(call run_cmd,cargo test,-p axfs $(1) --features "myfs" $(verbose) -- --nocapture)
+  $(call run_cmd,cargo test,-p axfs $(1) --features "myfs multitask" $(verbose) -- --nocapture)
+  $(call run_cmd,cargo test,-p axfs $(1) --features "overlayfs" $(verbose) -- --nocapture)
+  $(call run_cmd,cargo test,-p axfs $(1) --features "initramfs" $(verbose) -- --nocapture)
+  $(call run_cmd,cargo test,-p axfs $(1) --features "lwext4_rs" $(verbose) -- --nocapture)
+  $(call run_cmd,cargo test,-p axtask $(1) --features "sched_rr irq" $(verbose) -- --nocapture)
   $(call run_cmd,cargo test,--workspace --exclude axfs $(1) $(verbose) -- --nocapture)
 endef
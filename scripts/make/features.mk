@@ -13,7 +13,7 @@
 
 ifeq ($(APP_TYPE),c)
   ax_feat_prefix := axfeat/
-  lib_features := fp_simd irq alloc multitask fs net fd pipe select epoll
+  lib_features := fp_simd irq alloc multitask fs net fd pipe select epoll mmap
 else
   ifeq ($(NO_AXSTD),y)
     ax_feat_prefix := axfeat/
@@ -3,6 +3,7 @@
 #include <dirent.h>
 #include <errno.h>
 #include <fcntl.h>
+#include <stdint.h>
 #include <stdio.h>
 #include <stdlib.h>
 #include <string.h>
@@ -46,21 +47,40 @@ int dirfd(DIR *d)
     return d->fd;
 }
 
-// TODO
-DIR *opendir(const char *__name)
+DIR *opendir(const char *name)
 {
-    unimplemented();
-    return NULL;
+    int fd;
+    DIR *dir;
+
+    if ((fd = open(name, O_RDONLY | O_DIRECTORY | O_CLOEXEC)) < 0) {
+        return 0;
+    }
+    if (!(dir = calloc(1, sizeof(*dir)))) {
+        close(fd);
+        return 0;
+    }
+    dir->fd = fd;
+    return dir;
 }
 
-// TODO
-struct dirent *readdir(DIR *__dirp)
+struct dirent *readdir(DIR *dir)
 {
-    unimplemented();
-    return NULL;
+    struct dirent *de;
+
+    if (dir->buf_pos >= dir->buf_end) {
+        int len = getdents64(dir->fd, dir->buf, sizeof(dir->buf));
+        if (len <= 0) {
+            return 0;
+        }
+        dir->buf_end = len;
+        dir->buf_pos = 0;
+    }
+    de = (void *)(dir->buf + dir->buf_pos);
+    dir->buf_pos += de->d_reclen;
+    dir->tell = de->d_off;
+    return de;
 }
 
-// TODO
 int readdir_r(DIR *restrict dir, struct dirent *restrict buf, struct dirent **restrict result)
 {
     struct dirent *de;
@@ -85,7 +105,6 @@ int readdir_r(DIR *restrict dir, struct dirent *restrict buf, struct dirent **re
     return 0;
 }
 
-// TODO
 void rewinddir(DIR *dir)
 {
     // LOCK(dir->lock);
@@ -95,4 +114,67 @@ void rewinddir(DIR *dir)
     // UNLOCK(dir->lock);
 }
 
+void seekdir(DIR *dir, long off)
+{
+    // LOCK(dir->lock);
+    dir->tell = lseek(dir->fd, off, SEEK_SET);
+    dir->buf_pos = dir->buf_end = 0;
+    // UNLOCK(dir->lock);
+}
+
+long telldir(DIR *dir)
+{
+    return dir->tell;
+}
+
+int alphasort(const struct dirent **a, const struct dirent **b)
+{
+    return strcoll((*a)->d_name, (*b)->d_name);
+}
+
+int scandir(const char *path, struct dirent ***res, int (*sel)(const struct dirent *),
+            int (*cmp)(const struct dirent **, const struct dirent **))
+{
+    DIR *d = opendir(path);
+    struct dirent *de, **names = 0, **tmp;
+    size_t cnt = 0, len = 0;
+    int old_errno = errno;
+
+    if (!d)
+        return -1;
+
+    while ((errno = 0), (de = readdir(d))) {
+        if (sel && !sel(de))
+            continue;
+        if (cnt >= len) {
+            len = 2 * len + 1;
+            if (len > SIZE_MAX / sizeof(*names))
+                break;
+            tmp = realloc(names, len * sizeof(*names));
+            if (!tmp)
+                break;
+            names = tmp;
+        }
+        names[cnt] = malloc(de->d_reclen);
+        if (!names[cnt])
+            break;
+        memcpy(names[cnt++], de, de->d_reclen);
+    }
+
+    closedir(d);
+
+    if (errno) {
+        if (names)
+            while (cnt-- > 0) free(names[cnt]);
+        free(names);
+        return -1;
+    }
+    errno = old_errno;
+
+    if (cmp)
+        qsort(names, cnt, sizeof(*names), (int (*)(const void *, const void *))cmp);
+    *res = names;
+    return cnt;
+}
+
 #endif // AX_CONFIG_FS
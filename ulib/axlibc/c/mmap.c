@@ -1,7 +1,11 @@
+#include <errno.h>
+#include <stdarg.h>
 #include <stddef.h>
 #include <stdio.h>
 #include <sys/mman.h>
 
+#ifndef AX_CONFIG_MMAP
+
 // TODO:
 void *mmap(void *addr, size_t len, int prot, int flags, int fildes, off_t off)
 {
@@ -16,24 +20,56 @@ int munmap(void *addr, size_t length)
     return 0;
 }
 
-// TODO:
-void *mremap(void *old_address, size_t old_size, size_t new_size, int flags,
-             ... /* void *new_address */)
+// TODO
+int mprotect(void *addr, size_t len, int prot)
 {
     unimplemented();
-    return NULL;
+    return 0;
 }
 
 // TODO
-int mprotect(void *addr, size_t len, int prot)
+int msync(void *addr, size_t len, int flags)
 {
     unimplemented();
     return 0;
 }
 
-// TODO
+#endif // AX_CONFIG_MMAP
+
+#ifdef AX_CONFIG_MMAP
+
+// TODO: remove this function in future work
+void *ax_mremap(void *old_address, size_t old_size, size_t new_size, int flags,
+                void *new_address);
+
+void *mremap(void *old_address, size_t old_size, size_t new_size, int flags,
+             ... /* void *new_address */)
+{
+    void *new_address = NULL;
+
+    if (flags & MREMAP_FIXED) {
+        va_list ap;
+        va_start(ap, flags);
+        new_address = va_arg(ap, void *);
+        va_end(ap);
+    }
+
+    return ax_mremap(old_address, old_size, new_size, flags, new_address);
+}
+
+#else
+
+void *mremap(void *old_address, size_t old_size, size_t new_size, int flags,
+             ... /* void *new_address */)
+{
+    errno = ENOMEM;
+    return MAP_FAILED;
+}
+
+#endif // AX_CONFIG_MMAP
+
+// The advice is only a hint, so it is ignored.
 int madvise(void *addr, size_t len, int advice)
 {
-    unimplemented();
     return 0;
 }
@@ -28,7 +28,13 @@ DIR *opendir(const char *);
 struct dirent *readdir(DIR *);
 int readdir_r(DIR *__restrict, struct dirent *__restrict, struct dirent **__restrict);
 void rewinddir(DIR *);
+void seekdir(DIR *, long);
+long telldir(DIR *);
 int dirfd(DIR *);
+int alphasort(const struct dirent **, const struct dirent **);
+int scandir(const char *, struct dirent ***, int (*)(const struct dirent *),
+            int (*)(const struct dirent **, const struct dirent **));
+ssize_t getdents64(int, void *, size_t);
 
 #define DT_UNKNOWN 0
 #define DT_FIFO    1
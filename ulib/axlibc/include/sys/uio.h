@@ -8,6 +8,7 @@ struct iovec {
     size_t iov_len; /* Length of data.  */
 };
 
+ssize_t readv(int, const struct iovec *, int);
 ssize_t writev(int, const struct iovec *, int);
 
 #endif
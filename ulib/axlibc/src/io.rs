@@ -1,6 +1,6 @@
 use core::ffi::{c_int, c_void};
 
-use arceos_posix_api::{sys_read, sys_write, sys_writev};
+use arceos_posix_api::{sys_read, sys_readv, sys_write, sys_writev};
 
 use crate::{ctypes, utils::e};
 
@@ -12,6 +12,16 @@ pub unsafe extern "C" fn read(fd: c_int, buf: *mut c_void, count: usize) -> ctyp
     e(sys_read(fd, buf, count) as _) as _
 }
 
+/// Read into a vector.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn readv(
+    fd: c_int,
+    iov: *const ctypes::iovec,
+    iocnt: c_int,
+) -> ctypes::ssize_t {
+    e(sys_readv(fd, iov, iocnt) as _) as _
+}
+
 /// Write data to the file indicated by `fd`.
 ///
 /// Return the written size if success.
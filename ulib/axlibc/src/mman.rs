@@ -0,0 +1,73 @@
+use core::ffi::{c_int, c_void};
+
+use arceos_posix_api::{sys_mmap, sys_mprotect, sys_mremap, sys_msync, sys_munmap};
+
+use crate::{ctypes, utils::e};
+
+/// Map `len` bytes of the file `fd` from `off`, or anonymous memory if
+/// `MAP_ANONYMOUS` is in `flags`, with the protection `prot`.
+///
+/// Return the start address of the mapping, or `MAP_FAILED` on error.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn mmap(
+    addr: *mut c_void,
+    len: usize,
+    prot: c_int,
+    flags: c_int,
+    fd: c_int,
+    off: ctypes::off_t,
+) -> *mut c_void {
+    map_result(sys_mmap(addr, len, prot, flags, fd, off))
+}
+
+/// Resize the mapping of `old_size` bytes at `old_addr` to `new_size` bytes,
+/// moving it to `new_addr` if `MREMAP_FIXED` is in `flags`.
+///
+/// Return the new start address of the mapping, or `MAP_FAILED` on error.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ax_mremap(
+    old_addr: *mut c_void,
+    old_size: usize,
+    new_size: usize,
+    flags: c_int,
+    new_addr: *mut c_void,
+) -> *mut c_void {
+    map_result(sys_mremap(old_addr, old_size, new_size, flags, new_addr))
+}
+
+/// Converts the result of a mapping call to `MAP_FAILED` on error.
+fn map_result(ret: *mut c_void) -> *mut c_void {
+    // errors are returned as -errno, which are in the last page of addresses
+    let errno = (ret as isize).wrapping_neg();
+    if (1..4096).contains(&errno) {
+        crate::errno::set_errno(errno as _);
+        return usize::MAX as *mut c_void; // MAP_FAILED
+    }
+    ret
+}
+
+/// Remove the mappings in the `len` bytes from `addr`.
+///
+/// Return 0 if success.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn munmap(addr: *mut c_void, len: usize) -> c_int {
+    e(sys_munmap(addr, len))
+}
+
+/// Change the protection of the mappings in the `len` bytes from `addr` to
+/// `prot`.
+///
+/// Return 0 if success.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn mprotect(addr: *mut c_void, len: usize, prot: c_int) -> c_int {
+    e(sys_mprotect(addr, len, prot))
+}
+
+/// Write back the changes to shared file mappings in the `len` bytes from
+/// `addr`.
+///
+/// Return 0 if success.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn msync(addr: *mut c_void, len: usize, flags: c_int) -> c_int {
+    e(sys_msync(addr, len, flags))
+}
@@ -1,5 +1,5 @@
 use crate::io::{Result, SeekFrom, prelude::*};
-use core::fmt;
+use core::{fmt, time::Duration};
 
 use arceos_api::fs as api;
 
@@ -110,6 +110,24 @@ impl Metadata {
     pub const fn blocks(&self) -> u64 {
         self.0.blocks()
     }
+
+    /// Returns the last access time of the file, since the Unix epoch.
+    ///
+    /// It is zero if the filesystem does not keep it, and so are the other
+    /// timestamps.
+    pub const fn accessed(&self) -> Duration {
+        self.0.times().accessed
+    }
+
+    /// Returns the last modification time of the file, since the Unix epoch.
+    pub const fn modified(&self) -> Duration {
+        self.0.times().modified
+    }
+
+    /// Returns the creation time of the file, since the Unix epoch.
+    pub const fn created(&self) -> Duration {
+        self.0.times().created
+    }
 }
 
 impl fmt::Debug for Metadata {
@@ -119,6 +137,7 @@ impl fmt::Debug for Metadata {
             .field("is_dir", &self.is_dir())
             .field("is_file", &self.is_file())
             .field("permissions", &self.permissions())
+            .field("modified", &self.modified())
             .finish_non_exhaustive()
     }
 }
@@ -11,6 +11,9 @@ use alloc::{string::String, vec::Vec};
 pub use self::dir::{DirBuilder, DirEntry, ReadDir};
 pub use self::file::{File, FileType, Metadata, OpenOptions, Permissions};
 
+/// Statistics of a mounted filesystem, e.g., its capacity and free space.
+pub type FsStat = arceos_api::fs::AxFsStat;
+
 /// Read the entire contents of a file into a bytes vector.
 #[cfg(feature = "alloc")]
 pub fn read(path: &str) -> io::Result<Vec<u8>> {
@@ -75,3 +78,9 @@ pub fn remove_file(path: &str) -> io::Result<()> {
 pub fn rename(old: &str, new: &str) -> io::Result<()> {
     arceos_api::fs::ax_rename(old, new)
 }
+
+/// Returns the statistics of the mounted filesystem that `path` is in, like
+/// `statvfs` of POSIX.
+pub fn statfs(path: &str) -> io::Result<FsStat> {
+    arceos_api::fs::ax_statfs(path)
+}
@@ -30,7 +30,10 @@
 //! - Upperlayer stacks
 //!     - `fs`: Enable file system support.
 //!     - `myfs`: Allow users to define their custom filesystems to override the default.
+//!     - `overlayfs`: Keep the root filesystem read-only, with changes kept in memory.
+//!     - `initramfs`: Use a ramfs unpacked from a cpio or tar archive as the root filesystem.
 //!     - `net`: Enable networking support.
+//!     - `dhcp`: Configure the NIC by DHCP.
 //!     - `dns`: Enable DNS lookup support.
 //!     - `display`: Enable graphics support.
 //! - Device drivers
@@ -0,0 +1,46 @@
+use super::IpAddr;
+use crate::io;
+use crate::time::Duration;
+
+use arceos_api::net::{self as api, AxIcmpSocketHandle};
+
+/// An ICMP socket for sending echo requests and receiving echo replies, like
+/// the ping socket of Linux.
+///
+/// Both IPv4 and IPv6 are supported.
+pub struct IcmpSocket(AxIcmpSocketHandle);
+
+impl IcmpSocket {
+    /// Creates a new ICMP socket, with an identifier of echo messages
+    /// generated automatically.
+    pub fn new() -> io::Result<IcmpSocket> {
+        Ok(IcmpSocket(api::ax_icmp_socket()))
+    }
+
+    /// Moves this socket into or out of nonblocking mode.
+    pub fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+        api::ax_icmp_set_nonblocking(&self.0, nonblocking)
+    }
+
+    /// Sets the timeout of [`recv_echo_reply`], after which an error with kind
+    /// [`TimedOut`](io::Error::TimedOut) is returned. `None` means blocking
+    /// indefinitely, which is the default.
+    ///
+    /// [`recv_echo_reply`]: IcmpSocket::recv_echo_reply
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        api::ax_icmp_set_read_timeout(&self.0, timeout)
+    }
+
+    /// Sends an echo request with the given sequence number and data to
+    /// `addr`.
+    pub fn send_echo_request(&self, addr: IpAddr, seq_no: u16, data: &[u8]) -> io::Result<()> {
+        api::ax_icmp_send_echo_request(&self.0, addr, seq_no, data)
+    }
+
+    /// Receives an echo reply to this socket, and copies its data into `buf`.
+    /// On success, returns the length of the data, the origin and the sequence
+    /// number.
+    pub fn recv_echo_reply(&self, buf: &mut [u8]) -> io::Result<(usize, IpAddr, u16)> {
+        api::ax_icmp_recv_echo_reply(&self.0, buf)
+    }
+}